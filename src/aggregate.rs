@@ -0,0 +1,334 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+
+use anyhow::Context;
+use clap_derive::Args;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use flume::{Receiver, Sender};
+use ratatui::{prelude::*, widgets::*};
+use serde::Deserialize;
+
+use crate::colors::theme;
+use crate::domain;
+use crate::schema::{LogEntry, K_SCHEMA_VERSION};
+use crate::widget::DiscoveryWidget;
+
+/// How often the view is redrawn with the lines received meanwhile.
+const K_REDRAW: Duration = Duration::from_millis(100);
+
+#[derive(Args, Debug)]
+pub struct AggregateOpts {
+    #[arg(long, default_value = "0.0.0.0:9000")]
+    /// Address to accept the probes' streams on, as sent by --stream-to
+    listen: SocketAddr,
+}
+
+/// A line of a probe's stream.
+#[derive(Debug, Deserialize)]
+struct Streamed {
+    schema_version: u32,
+    #[serde(flatten)]
+    entry: LogEntry,
+}
+
+/// What the connections of the probes report.
+#[derive(Debug)]
+enum Message {
+    Connected(SocketAddr),
+    Entry(SocketAddr, LogEntry),
+    Disconnected(SocketAddr),
+}
+
+#[derive(Debug, Default)]
+struct Probe {
+    connections: usize,
+    events: u64,
+}
+
+/// The last a probe reported of an instance.
+#[derive(Debug)]
+struct Sighting {
+    removed: bool,
+    /// Why it was removed, when known
+    reason: Option<String>,
+}
+
+/// Merged view of the events streamed by several probes with `--stream-to`,
+/// telling which probe sees which instance.
+///
+/// Probes are named by the host they run on, or by their address for
+/// streams that don't say.
+#[derive(Debug)]
+pub struct Aggregator {
+    listen: SocketAddr,
+    messages: Receiver<Message>,
+    /// Probe names by connection, learned from the first entry received
+    peers: HashMap<SocketAddr, String>,
+    probes: BTreeMap<String, Probe>,
+    /// Service type and instance fullname, and their sightings by probe
+    instances: BTreeMap<(String, String), BTreeMap<String, Sighting>>,
+    scroll: usize,
+}
+
+impl Aggregator {
+    /// Accept the probes' streams in the background.
+    pub fn listen(opts: &AggregateOpts) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(opts.listen)
+            .with_context(|| format!("Failed to listen on {}", opts.listen))?;
+        let (tx, rx) = flume::unbounded();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let tx = tx.clone();
+                        std::thread::spawn(move || receive(stream, tx));
+                    }
+                    Err(e) => tracing::warn!("Failed to accept a probe: {e}"),
+                }
+            }
+        });
+        Ok(Self {
+            listen: opts.listen,
+            messages: rx,
+            peers: HashMap::new(),
+            probes: BTreeMap::new(),
+            instances: BTreeMap::new(),
+            scroll: 0,
+        })
+    }
+
+    fn connect(&mut self, peer: SocketAddr, name: String) {
+        self.probes.entry(name.clone()).or_default().connections += 1;
+        self.peers.insert(peer, name);
+    }
+
+    fn disconnect(&mut self, peer: SocketAddr) {
+        let Some(name) = self.peers.remove(&peer) else {
+            return;
+        };
+        if let Some(probe) = self.probes.get_mut(&name) {
+            probe.connections = probe.connections.saturating_sub(1);
+            if probe.connections == 0 && probe.events == 0 {
+                self.probes.remove(&name);
+            }
+        }
+    }
+
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::Connected(peer) => {
+                tracing::info!("Probe connected from {peer}");
+                self.connect(peer, peer.ip().to_string());
+            }
+            Message::Entry(peer, entry) => {
+                let name = entry.probe.unwrap_or_else(|| peer.ip().to_string());
+                if self.peers.get(&peer) != Some(&name) {
+                    self.disconnect(peer);
+                    self.connect(peer, name.clone());
+                }
+                if let Some(probe) = self.probes.get_mut(&name) {
+                    probe.events += 1;
+                }
+                if domain::is_type_enumeration(&entry.service_type) {
+                    return;
+                }
+                let removed = entry.event == "removed";
+                self.instances
+                    .entry((entry.service_type, entry.name))
+                    .or_default()
+                    .insert(
+                        name,
+                        Sighting {
+                            removed,
+                            reason: entry.reason,
+                        },
+                    );
+            }
+            Message::Disconnected(peer) => {
+                tracing::info!("Probe disconnected from {peer}");
+                self.disconnect(peer);
+            }
+        }
+    }
+
+    /// Show the merged view on `terminal` until quit.
+    pub fn run(mut self, mut terminal: Terminal<impl Backend>) -> anyhow::Result<()> {
+        loop {
+            let messages: Vec<_> = self.messages.drain().collect();
+            for message in messages {
+                self.handle(message);
+            }
+            terminal.draw(|frame| self.render(frame.size(), frame.buffer_mut(), true))?;
+            if !event::poll(K_REDRAW)? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    _ => self.process_key_event(&key),
+                }
+            }
+        }
+    }
+
+    /// The probes along with whether they are connected.
+    fn probes_line(&self) -> Line<'static> {
+        let mut spans = vec![Span::from("Probes: ").bold()];
+        if self.probes.is_empty() {
+            spans.push(Span::from("none yet, start them with --stream-to").dark_gray());
+        }
+        for (i, (name, probe)) in self.probes.iter().enumerate() {
+            if i > 0 {
+                spans.push(Span::from(", "));
+            }
+            if probe.connections > 0 {
+                spans.push(Span::from(format!("● {name}")).fg(theme().success));
+                spans.push(Span::from(format!(" ({} events)", probe.events)));
+            } else {
+                spans.push(Span::from(format!("○ {name} (disconnected)")).dark_gray());
+            }
+        }
+        Line::from(spans)
+    }
+}
+
+/// Forward the lines streamed by the probe at the other end of `stream`.
+fn receive(stream: TcpStream, tx: Sender<Message>) {
+    let Ok(peer) = stream.peer_addr() else {
+        return;
+    };
+    let _ = tx.send(Message::Connected(peer));
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::debug!("Stopped reading from {peer}: {e}");
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Streamed>(&line) {
+            Ok(line) if line.schema_version > K_SCHEMA_VERSION => {
+                tracing::debug!(
+                    "Skipped a line of schema version {} from {peer}",
+                    line.schema_version
+                );
+            }
+            Ok(line) => {
+                if tx.send(Message::Entry(peer, line.entry)).is_err() {
+                    return;
+                }
+            }
+            Err(e) => tracing::debug!("Skipped a bad line from {peer}: {e}"),
+        }
+    }
+    let _ = tx.send(Message::Disconnected(peer));
+}
+
+impl DiscoveryWidget for Aggregator {
+    fn title(&self) -> String {
+        format!(
+            "Aggregating on {}, {} instances from {} probes",
+            self.listen,
+            self.instances.len(),
+            self.probes.len()
+        )
+    }
+
+    fn controls(&self) -> String {
+        "Use ↓↑ to scroll, q or Esc to quit".to_string()
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        match key_event.code {
+            KeyCode::Down => {
+                self.scroll = (self.scroll + 1).min(self.instances.len().saturating_sub(1))
+            }
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(theme().selected_fg).bold())
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .padding(Padding::horizontal(1))
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        let inner_area = block.inner(area);
+        block.render(area, buf);
+        let [probes_area, table_area, controls_area] = Layout::vertical([
+            Constraint::Length(2),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(inner_area);
+
+        Paragraph::new(self.probes_line())
+            .wrap(Wrap { trim: true })
+            .render(probes_area, buf);
+
+        let header = Row::new(["Service type", "Instance", "Seen by"])
+            .bold()
+            .light_cyan();
+        let rows =
+            self.instances
+                .iter()
+                .skip(self.scroll)
+                .map(|((service_type, fullname), sightings)| {
+                    let instance = fullname
+                        .strip_suffix(service_type.as_str())
+                        .map(|instance| instance.trim_end_matches('.'))
+                        .filter(|instance| !instance.is_empty())
+                        .unwrap_or(fullname);
+                    let mut seen_by = Vec::new();
+                    for (i, (probe, sighting)) in sightings.iter().enumerate() {
+                        if i > 0 {
+                            seen_by.push(Span::from(", "));
+                        }
+                        seen_by.push(if sighting.removed {
+                            let reason = sighting.reason.as_deref().unwrap_or("removed");
+                            Span::from(format!("✗ {probe} ({reason})")).dark_gray()
+                        } else {
+                            Span::from(format!("✓ {probe}"))
+                        });
+                    }
+                    let style = if sightings.values().all(|sighting| sighting.removed) {
+                        Style::new().dark_gray()
+                    } else {
+                        Style::new()
+                    };
+                    Row::new([
+                        Cell::new(service_type.clone()),
+                        Cell::new(instance.to_string()),
+                        Cell::new(Line::from(seen_by)),
+                    ])
+                    .style(style)
+                });
+        let widths = [
+            Constraint::Percentage(25),
+            Constraint::Percentage(35),
+            Constraint::Percentage(40),
+        ];
+        Widget::render(
+            Table::new(rows, widths).header(header).column_spacing(2),
+            table_area,
+            buf,
+        );
+
+        Paragraph::new(self.controls())
+            .dark_gray()
+            .render(controls_area, buf);
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use crossterm::event::{KeyCode, KeyEvent};
+use parking_lot::Mutex;
+use ratatui::{prelude::*, widgets::*};
+
+use crate::colors::theme;
+use crate::widget::DiscoveryWidget;
+
+const K_AUDIT_FILE: &str = "audit.tsv";
+
+/// Something done to an instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Queried once with `w`
+    Probed,
+    /// Connected to with `i`
+    Opened,
+    /// Marked as nothing to worry about with `m`
+    Ignored,
+    /// Free text added with `n`
+    Noted(String),
+}
+
+impl Action {
+    fn parse(action: &str, note: &str) -> Option<Self> {
+        match action {
+            "probed" => Some(Action::Probed),
+            "opened" => Some(Action::Opened),
+            "ignored" => Some(Action::Ignored),
+            "noted" => Some(Action::Noted(note.to_string())),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Probed => "probed",
+            Action::Opened => "opened",
+            Action::Ignored => "ignored",
+            Action::Noted(_) => "noted",
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Noted(note) => write!(f, "noted \"{note}\""),
+            action => f.write_str(action.name()),
+        }
+    }
+}
+
+/// An [`Action`] and when it was taken.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub at: SystemTime,
+    pub action: Action,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            humantime::format_rfc3339_seconds(self.at),
+            self.action
+        )
+    }
+}
+
+/// Tabs and line breaks would break the lines of the file.
+fn flatten(text: &str) -> String {
+    text.replace(['\t', '\n', '\r'], " ")
+}
+
+/// A line of the file, about the instance `fullname`.
+fn format_line(fullname: &str, at: SystemTime, action: &Action) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\n",
+        at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        flatten(fullname),
+        action.name(),
+        match action {
+            Action::Noted(note) => flatten(note),
+            _ => String::new(),
+        }
+    )
+}
+
+/// The instance and the entry of a line of the file, `None` if corrupt.
+fn parse_line(line: &str) -> Option<(&str, Entry)> {
+    let mut fields = line.splitn(4, '\t');
+    let (secs, fullname, action) = (fields.next()?, fields.next()?, fields.next()?);
+    let at = UNIX_EPOCH.checked_add(Duration::from_secs(secs.parse().ok()?))?;
+    let action = Action::parse(action, fields.next().unwrap_or_default())?;
+    Some((fullname, Entry { at, action }))
+}
+
+/// Actions taken on the instances by fullname, so that an audit can tell
+/// what has been checked already.
+///
+/// Stored as tab-separated lines of the time, the instance, the action and
+/// the note, appended as they are recorded, so that they survive restarts.
+/// Cloning is cheap and yields a handle to the same trail.
+#[derive(Debug, Clone, Default)]
+pub struct Audit {
+    path: Option<PathBuf>,
+    entries: Arc<Mutex<HashMap<String, Vec<Entry>>>>,
+}
+
+impl Audit {
+    /// Load the trail stored under `$XDG_DATA_HOME/discovery-rs` or the
+    /// platform equivalent, starting afresh if there is none yet.
+    pub fn load() -> Self {
+        let path = dirs::data_dir().map(|dir| dir.join("discovery-rs").join(K_AUDIT_FILE));
+        let mut entries: HashMap<String, Vec<Entry>> = HashMap::new();
+        let contents = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .unwrap_or_default();
+        for line in contents.lines().filter(|line| !line.is_empty()) {
+            let Some((fullname, entry)) = parse_line(line) else {
+                tracing::warn!("Skipping the audit entry \"{line}\"");
+                continue;
+            };
+            entries.entry(fullname.to_string()).or_default().push(entry);
+        }
+        Self {
+            path,
+            entries: Arc::new(Mutex::new(entries)),
+        }
+    }
+
+    /// Record `action` as taken on the instance `fullname` now, and store it.
+    pub fn record(&self, fullname: &str, action: Action) -> anyhow::Result<()> {
+        let at = SystemTime::now();
+        let line = format_line(fullname, at, &action);
+        self.entries
+            .lock()
+            .entry(fullname.to_string())
+            .or_default()
+            .push(Entry { at, action });
+        let Some(path) = self.path.as_ref() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Actions taken on the instance `fullname`, oldest first.
+    pub fn entries(&self, fullname: &str) -> Vec<Entry> {
+        self.entries
+            .lock()
+            .get(fullname)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Modal prompt for a note on an instance.
+///
+/// Like [`crate::destination::ExportPicker`], the owner takes the
+/// [`NotePrompt::answer`] once the prompt is done.
+#[derive(Debug)]
+pub struct NotePrompt {
+    fullname: String,
+    input: String,
+    /// Whether to record the note, once the prompt is done
+    answer: Option<bool>,
+}
+
+impl NotePrompt {
+    pub fn new(fullname: String) -> Self {
+        Self {
+            fullname,
+            input: String::new(),
+            answer: None,
+        }
+    }
+
+    /// The instance and the note on it.
+    ///
+    /// Returns `Err(self)` while still typing.
+    pub fn answer(self) -> Result<Option<(String, String)>, Self> {
+        match self.answer {
+            Some(true) => Ok(Some((self.fullname, self.input.trim().to_string()))),
+            Some(false) => Ok(None),
+            None => Err(self),
+        }
+    }
+}
+
+impl DiscoveryWidget for NotePrompt {
+    fn title(&self) -> String {
+        "Note".to_string()
+    }
+
+    fn controls(&self) -> String {
+        "Type the note, ↵ to record it, Esc to cancel".to_string()
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        match key_event.code {
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Enter if !self.input.trim().is_empty() => self.answer = Some(true),
+            KeyCode::Esc => self.answer = Some(false),
+            _ => {}
+        }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(theme().selected_fg).bold())
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .padding(Padding::horizontal(1))
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        Paragraph::new(vec![
+            Line::from(self.fullname.clone()).dark_gray(),
+            Line::from(vec![
+                Span::styled("Note: ", Style::new().bold().light_cyan()),
+                Span::raw(format!("{}▏", self.input)).fg(theme().selected_fg),
+            ]),
+        ])
+        .block(block)
+        .wrap(Wrap { trim: false })
+        .render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(line: &str) -> Option<(&str, u64, Action)> {
+        let (fullname, entry) = parse_line(line)?;
+        let secs = entry.at.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        Some((fullname, secs, entry.action))
+    }
+
+    #[test]
+    fn lines() {
+        let fullname = "Office._ipp._tcp.local.";
+        assert_eq!(
+            parsed("1718000000\tOffice._ipp._tcp.local.\tprobed\t"),
+            Some((fullname, 1718000000, Action::Probed))
+        );
+        assert_eq!(
+            parsed("1718000060\tOffice._ipp._tcp.local.\topened\t"),
+            Some((fullname, 1718000060, Action::Opened))
+        );
+        assert_eq!(
+            parsed("1718000120\tOffice._ipp._tcp.local.\tignored\t"),
+            Some((fullname, 1718000120, Action::Ignored))
+        );
+        assert_eq!(
+            parsed("1718000180\tOffice._ipp._tcp.local.\tnoted\tThe 2nd floor printer, tabs: none"),
+            Some((
+                fullname,
+                1718000180,
+                Action::Noted("The 2nd floor printer, tabs: none".to_string())
+            ))
+        );
+        // Without the trailing tab of lines written by hand
+        assert_eq!(
+            parsed("1718000000\tLiving Room._airplay._tcp.local.\tprobed"),
+            Some((
+                "Living Room._airplay._tcp.local.",
+                1718000000,
+                Action::Probed
+            ))
+        );
+    }
+
+    #[test]
+    fn corrupt_lines() {
+        for line in [
+            "",
+            "garbage",
+            "1718000000\tOffice._ipp._tcp.local.",
+            "yesterday\tOffice._ipp._tcp.local.\tprobed\t",
+            "-1718000000\tOffice._ipp._tcp.local.\tprobed\t",
+            "1718000000\tOffice._ipp._tcp.local.\tdeleted\t",
+            "1718000000\tOffice._ipp._tcp.local.\tPROBED\t",
+            "1718000000 Office._ipp._tcp.local. probed",
+            "99999999999999999999\tOffice._ipp._tcp.local.\tprobed\t",
+        ] {
+            assert_eq!(parsed(line), None, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn lines_are_read_back() {
+        let at = UNIX_EPOCH + Duration::from_secs(1718000180);
+        let action = Action::Noted("line\nbreaks\tand tabs".to_string());
+        let line = format_line("Tab\tbed._ipp._tcp.local.", at, &action);
+        assert_eq!(
+            line,
+            "1718000180\tTab bed._ipp._tcp.local.\tnoted\tline breaks and tabs\n"
+        );
+        assert_eq!(
+            parsed(line.trim_end_matches('\n')),
+            Some((
+                "Tab bed._ipp._tcp.local.",
+                1718000180,
+                Action::Noted("line breaks and tabs".to_string())
+            ))
+        );
+        let line = format_line("Office._ipp._tcp.local.", at, &Action::Opened);
+        assert_eq!(line, "1718000180\tOffice._ipp._tcp.local.\topened\t\n");
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::{BufRead, BufReader};
+use std::net::IpAddr;
+use std::process::{Child, Command, Stdio};
+
+use anyhow::Context;
+use flume::{Receiver, Sender};
+use mdns_sd::{IfKind, ServiceEvent, ServiceInfo};
+use parking_lot::Mutex;
+
+use crate::backend::Backend;
+use crate::domain;
+use crate::K_SERVICE_TYPE_ENUMERATION;
+
+/// Backend using the system Avahi daemon.
+///
+/// Every browse runs `avahi-browse --parsable` and translates its output
+/// into [`ServiceEvent`]s, so no mDNS sockets are opened by this process.
+/// The command line tool is used rather than the daemon's D-Bus API so that
+/// the build doesn't need a D-Bus client.
+pub struct Avahi {
+    interfaces: Vec<IfKind>,
+    /// Drop everything seen over IPv4
+    ipv6_only: bool,
+    /// Running `avahi-browse` processes and the types they browse
+    children: Mutex<Vec<(String, Child)>>,
+}
+
+impl Avahi {
+    pub fn new(interfaces: Vec<IfKind>, ipv6_only: bool) -> anyhow::Result<Self> {
+        // Fail early with a helpful message rather than on the first browse
+        Command::new("avahi-browse")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .context("Failed to run avahi-browse, is avahi-utils installed?")?;
+        Ok(Self {
+            interfaces,
+            ipv6_only,
+            children: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Enumerate the browse domains with `avahi-browse --browse-domains`.
+    ///
+    /// Avahi doesn't distinguish the legacy browse domains, so every found
+    /// domain is reported under `query`.
+    fn browse_domains(&self, query: &str) -> anyhow::Result<Receiver<ServiceEvent>> {
+        let mut child = Command::new("avahi-browse")
+            .args(["--parsable", "--no-fail", "--browse-domains"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Failed to run avahi-browse")?;
+        let stdout = child.stdout.take().context("No avahi-browse output")?;
+        self.children.lock().push((query.to_string(), child));
+
+        let (tx, rx) = flume::unbounded();
+        let query = query.to_string();
+        std::thread::spawn(move || {
+            let _span = tracing::span!(tracing::Level::TRACE, "Avahi domain reader").entered();
+            let _ = tx.send(ServiceEvent::SearchStarted(query.clone()));
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                tracing::trace!("avahi-browse: {line}");
+                // +;eth0;IPv4;example.com
+                let fields: Vec<_> = line.split(';').collect();
+                let event = match fields.as_slice() {
+                    ["+", _, _, domain, ..] => {
+                        ServiceEvent::ServiceFound(query.clone(), unescape(domain))
+                    }
+                    ["-", _, _, domain, ..] => {
+                        ServiceEvent::ServiceRemoved(query.clone(), unescape(domain))
+                    }
+                    _ => continue,
+                };
+                if tx.send(event).is_err() {
+                    break;
+                }
+            }
+            let _ = tx.send(ServiceEvent::SearchStopped(query));
+        });
+        Ok(rx)
+    }
+}
+
+impl Backend for Avahi {
+    fn browse(&self, service_type: &str) -> anyhow::Result<Receiver<ServiceEvent>> {
+        if domain::is_browse_domain_query(service_type) {
+            return self.browse_domains(service_type);
+        }
+        let mut command = Command::new("avahi-browse");
+        command.args(["--parsable", "--no-db-lookup", "--no-fail"]);
+        let (ty, domain) = split_service_type(service_type);
+        let enumeration = ty == split_service_type(K_SERVICE_TYPE_ENUMERATION).0;
+        if enumeration {
+            command.args(["--all", "--domain", domain]);
+        } else {
+            command.args(["--resolve", "--domain", domain, ty]);
+        }
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Failed to run avahi-browse")?;
+        let stdout = child.stdout.take().context("No avahi-browse output")?;
+        self.children.lock().push((service_type.to_string(), child));
+
+        let (tx, rx) = flume::unbounded();
+        let mut translator = Translator {
+            query: service_type.to_string(),
+            enumeration,
+            interfaces: self.interfaces.clone(),
+            ipv6_only: self.ipv6_only,
+            tx,
+            sources: HashMap::new(),
+            resolved: HashMap::new(),
+        };
+        std::thread::spawn(move || {
+            let _span = tracing::span!(tracing::Level::TRACE, "Avahi reader").entered();
+            let _ = translator
+                .tx
+                .send(ServiceEvent::SearchStarted(translator.query.clone()));
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                tracing::trace!("avahi-browse: {line}");
+                if let Some(record) = Record::parse(&line) {
+                    if translator.translate(record).is_err() {
+                        // Nobody is listening anymore
+                        break;
+                    }
+                }
+            }
+            let _ = translator
+                .tx
+                .send(ServiceEvent::SearchStopped(translator.query));
+        });
+        Ok(rx)
+    }
+
+    fn stop_browse(&self, service_type: &str) -> anyhow::Result<()> {
+        self.children.lock().retain_mut(|(browsed, child)| {
+            if browsed != service_type {
+                return true;
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+            false
+        });
+        Ok(())
+    }
+
+    fn shutdown(&self) -> anyhow::Result<()> {
+        for (_, mut child) in self.children.lock().drain(..) {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        Ok(())
+    }
+}
+
+/// Split `_http._tcp.local.` into `_http._tcp` and `local`.
+fn split_service_type(service_type: &str) -> (&str, &str) {
+    let service_type = service_type.trim_end_matches('.');
+    // The protocol label separates the type from the domain
+    for protocol in ["._tcp.", "._udp."] {
+        if let Some(index) = service_type.find(protocol) {
+            let split = index + protocol.len() - 1;
+            return (&service_type[..split], &service_type[split + 1..]);
+        }
+    }
+    (service_type, "local")
+}
+
+/// One line of `avahi-browse --parsable` output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub kind: RecordKind,
+    pub interface: String,
+    pub protocol: String,
+    pub name: String,
+    pub service_type: String,
+    pub domain: String,
+    pub resolved: Option<Resolved>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    Added,
+    Removed,
+    Resolved,
+}
+
+/// Resolution details of a [`RecordKind::Resolved`] record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolved {
+    pub hostname: String,
+    pub address: IpAddr,
+    pub port: u16,
+    pub txt: Vec<String>,
+}
+
+impl Record {
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<_> = line.split(';').collect();
+        let kind = match *fields.first()? {
+            "+" => RecordKind::Added,
+            "-" => RecordKind::Removed,
+            "=" => RecordKind::Resolved,
+            _ => return None,
+        };
+        if fields.len() < 6 {
+            return None;
+        }
+        let resolved = if kind == RecordKind::Resolved {
+            if fields.len() < 10 {
+                return None;
+            }
+            Some(Resolved {
+                hostname: unescape(fields[6]),
+                // IPv6 link-local addresses may carry a zone
+                address: fields[7].split('%').next()?.parse().ok()?,
+                port: fields[8].parse().ok()?,
+                txt: parse_txt(&fields[9..].join(";")),
+            })
+        } else {
+            None
+        };
+        Some(Self {
+            kind,
+            interface: fields[1].to_string(),
+            protocol: fields[2].to_string(),
+            name: unescape(fields[3]),
+            service_type: fields[4].to_string(),
+            domain: fields[5].to_string(),
+            resolved,
+        })
+    }
+
+    /// `_http._tcp.local.`
+    pub fn ty_domain(&self) -> String {
+        format!("{}.{}.", self.service_type, self.domain)
+    }
+
+    /// `My Printer._http._tcp.local.`
+    pub fn fullname(&self) -> String {
+        format!("{}.{}", self.name, self.ty_domain())
+    }
+
+    fn source(&self) -> (String, String, String) {
+        (
+            self.fullname(),
+            self.interface.clone(),
+            self.protocol.clone(),
+        )
+    }
+
+    fn matches(&self, interfaces: &[IfKind]) -> bool {
+        interfaces.iter().any(|interface| match interface {
+            IfKind::IPv4 => self.protocol == "IPv4",
+            IfKind::IPv6 => self.protocol == "IPv6",
+            IfKind::Name(name) => self.interface == *name,
+            // avahi-browse doesn't report the interface addresses
+            _ => true,
+        })
+    }
+}
+
+/// Undo avahi's `\DDD` decimal escaping of special characters.
+fn unescape(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'\\' {
+            let digits = bytes
+                .get(index + 1..index + 4)
+                .filter(|digits| digits.iter().all(u8::is_ascii_digit));
+            if let Some(byte) = digits
+                .and_then(|digits| std::str::from_utf8(digits).ok())
+                .and_then(|digits| digits.parse::<u8>().ok())
+            {
+                unescaped.push(byte);
+                index += 4;
+                continue;
+            }
+            if let Some(escaped) = bytes.get(index + 1) {
+                unescaped.push(*escaped);
+                index += 2;
+                continue;
+            }
+        }
+        unescaped.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+/// Split `"a=b" "c=d"` into its quoted strings.
+fn parse_txt(txt: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = txt.chars();
+    while let Some(c) = chars.next() {
+        match (current.as_mut(), c) {
+            (None, '"') => current = Some(String::new()),
+            (Some(_), '"') => entries.extend(current.take()),
+            (Some(entry), '\\') => entry.extend(chars.next()),
+            (Some(entry), c) => entry.push(c),
+            (None, _) => {}
+        }
+    }
+    entries
+}
+
+/// Per-browse state turning [`Record`]s into [`ServiceEvent`]s.
+///
+/// Avahi reports every instance once per interface and protocol, while the
+/// rest of the app expects one event per instance, so the sources are
+/// tracked and merged here.
+struct Translator {
+    query: String,
+    enumeration: bool,
+    interfaces: Vec<IfKind>,
+    ipv6_only: bool,
+    tx: Sender<ServiceEvent>,
+    /// Interface/protocol pairs an instance (or a type when enumerating) is seen on
+    sources: HashMap<String, HashSet<(String, String, String)>>,
+    /// Accumulated addresses of resolved instances
+    resolved: HashMap<String, BTreeSet<IpAddr>>,
+}
+
+impl Translator {
+    fn translate(&mut self, record: Record) -> anyhow::Result<()> {
+        if !record.matches(&self.interfaces) || (self.ipv6_only && record.protocol != "IPv6") {
+            return Ok(());
+        }
+        // When enumerating, instances stand in for their types
+        let (ty_domain, key) = if self.enumeration {
+            (self.query.clone(), record.ty_domain())
+        } else {
+            (record.ty_domain(), record.fullname())
+        };
+        let source = record.source();
+
+        match record.kind {
+            RecordKind::Added => {
+                let sources = self.sources.entry(key.clone()).or_default();
+                let first = sources.is_empty();
+                sources.insert(source);
+                if first {
+                    self.tx.send(ServiceEvent::ServiceFound(ty_domain, key))?;
+                }
+            }
+            RecordKind::Removed => {
+                if let Some(sources) = self.sources.get_mut(&key) {
+                    sources.remove(&source);
+                    if sources.is_empty() {
+                        self.sources.remove(&key);
+                        self.resolved.remove(&key);
+                        self.tx.send(ServiceEvent::ServiceRemoved(ty_domain, key))?;
+                    }
+                }
+            }
+            RecordKind::Resolved => {
+                let Some(resolved) = record.resolved.as_ref() else {
+                    return Ok(());
+                };
+                let addresses = self.resolved.entry(key).or_default();
+                addresses.insert(resolved.address);
+                let addresses: Vec<_> = addresses.iter().copied().collect();
+                let properties: Vec<_> = resolved
+                    .txt
+                    .iter()
+                    .map(|entry| match entry.split_once('=') {
+                        Some((key, value)) => (key.to_string(), value.to_string()),
+                        None => (entry.clone(), String::new()),
+                    })
+                    .collect();
+                match ServiceInfo::new(
+                    &record.ty_domain(),
+                    &record.name,
+                    &format!("{}.", resolved.hostname.trim_end_matches('.')),
+                    addresses.as_slice(),
+                    resolved.port,
+                    properties.as_slice(),
+                ) {
+                    Ok(info) => self.tx.send(ServiceEvent::ServiceResolved(info))?,
+                    Err(e) => tracing::warn!("Skipping {}: {e}", record.fullname()),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_added_and_removed_lines() {
+        let record = Record::parse("+;eth0;IPv4;Office\\032Printer;_ipp._tcp;local").unwrap();
+        assert_eq!(record.kind, RecordKind::Added);
+        assert_eq!(record.interface, "eth0");
+        assert_eq!(record.protocol, "IPv4");
+        assert_eq!(record.name, "Office Printer");
+        assert_eq!(record.fullname(), "Office Printer._ipp._tcp.local.");
+        assert_eq!(record.resolved, None);
+
+        let record = Record::parse("-;wlan0;IPv6;Office\\032Printer;_ipp._tcp;local").unwrap();
+        assert_eq!(record.kind, RecordKind::Removed);
+        assert_eq!(record.ty_domain(), "_ipp._tcp.local.");
+    }
+
+    #[test]
+    fn parses_resolved_lines() {
+        let record = Record::parse(
+            "=;eth0;IPv4;Office\\032Printer;_ipp._tcp;local;printer.local;192.168.2.10;631;\
+             \"txtvers=1\" \"rp=ipp/print\" \"note=2nd floor; east\"",
+        )
+        .unwrap();
+        assert_eq!(record.kind, RecordKind::Resolved);
+        assert_eq!(
+            record.resolved,
+            Some(Resolved {
+                hostname: "printer.local".to_string(),
+                address: "192.168.2.10".parse().unwrap(),
+                port: 631,
+                txt: vec![
+                    "txtvers=1".to_string(),
+                    "rp=ipp/print".to_string(),
+                    "note=2nd floor; east".to_string(),
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn drops_the_zone_of_link_local_addresses() {
+        let record =
+            Record::parse("=;eth0;IPv6;nas;_smb._tcp;local;nas.local;fe80::1%eth0;445;").unwrap();
+        let resolved = record.resolved.unwrap();
+        assert_eq!(resolved.address, "fe80::1".parse::<IpAddr>().unwrap());
+        assert!(resolved.txt.is_empty());
+    }
+
+    #[test]
+    fn rejects_other_lines() {
+        for line in [
+            "",
+            "Failed to resolve service 'x' of type '_ipp._tcp' in domain 'local': Timeout reached",
+            "+;eth0;IPv4;Printer;_ipp._tcp",
+            "=;eth0;IPv4;Printer;_ipp._tcp;local;printer.local;192.168.2.10",
+            "=;eth0;IPv4;Printer;_ipp._tcp;local;printer.local;not-an-address;631;",
+            "=;eth0;IPv4;Printer;_ipp._tcp;local;printer.local;192.168.2.10;port;",
+        ] {
+            assert_eq!(Record::parse(line), None, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn unescapes_decimal_and_literal_escapes() {
+        assert_eq!(unescape("Office\\032Printer"), "Office Printer");
+        assert_eq!(
+            unescape("Living\\032Room\\032\\040Sonos\\041"),
+            "Living Room (Sonos)"
+        );
+        assert_eq!(unescape("a\\.b\\\\c"), "a.b\\c");
+        // UTF-8 is escaped byte by byte
+        assert_eq!(unescape("caf\\195\\169"), "café");
+        assert_eq!(unescape("trailing\\"), "trailing\\");
+        assert_eq!(unescape("\\999"), "999");
+    }
+
+    #[test]
+    fn splits_service_types_from_their_domain() {
+        assert_eq!(
+            split_service_type("_http._tcp.local."),
+            ("_http._tcp", "local")
+        );
+        assert_eq!(
+            split_service_type("_ipp._tcp.example.com"),
+            ("_ipp._tcp", "example.com")
+        );
+        assert_eq!(split_service_type("_http"), ("_http", "local"));
+    }
+
+    #[test]
+    fn merges_the_sources_of_an_instance() {
+        let (tx, rx) = flume::unbounded();
+        let mut translator = Translator {
+            query: "_ipp._tcp.local.".to_string(),
+            enumeration: false,
+            interfaces: vec![IfKind::All],
+            ipv6_only: false,
+            tx,
+            sources: HashMap::new(),
+            resolved: HashMap::new(),
+        };
+        for line in [
+            "+;eth0;IPv4;Printer;_ipp._tcp;local",
+            "+;eth0;IPv6;Printer;_ipp._tcp;local",
+            "=;eth0;IPv4;Printer;_ipp._tcp;local;printer.local;192.168.2.10;631;",
+            "=;eth0;IPv6;Printer;_ipp._tcp;local;printer.local;fd00::10;631;",
+            "-;eth0;IPv4;Printer;_ipp._tcp;local",
+        ] {
+            translator.translate(Record::parse(line).unwrap()).unwrap();
+        }
+        let events: Vec<_> = rx.try_iter().collect();
+        assert_eq!(events.len(), 3, "{events:?}");
+        assert!(matches!(
+            &events[0],
+            ServiceEvent::ServiceFound(ty, name)
+                if ty == "_ipp._tcp.local." && name == "Printer._ipp._tcp.local."
+        ));
+        let ServiceEvent::ServiceResolved(info) = &events[2] else {
+            panic!("{:?}", events[2]);
+        };
+        assert_eq!(info.get_addresses().len(), 2);
+        assert_eq!(info.get_port(), 631);
+
+        // Gone once it is gone from every source
+        translator
+            .translate(Record::parse("-;eth0;IPv6;Printer;_ipp._tcp;local").unwrap())
+            .unwrap();
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(ServiceEvent::ServiceRemoved(_, name)) if name == "Printer._ipp._tcp.local."
+        ));
+    }
+}
@@ -0,0 +1,128 @@
+use std::net::IpAddr;
+
+use clap_derive::ValueEnum;
+use flume::Receiver;
+use mdns_sd::{IfKind, ServiceDaemon, ServiceEvent};
+use serde::Deserialize;
+
+use crate::avahi::Avahi;
+
+/// Available mDNS implementations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackendKind {
+    /// Built-in responder, talks to the network directly
+    #[default]
+    MdnsSd,
+    /// System Avahi daemon, queried through `avahi-browse`
+    Avahi,
+}
+
+impl BackendKind {
+    /// Whether unicast DNS-SD domains other than `local.` can be browsed.
+    pub fn wide_area(&self) -> bool {
+        matches!(self, BackendKind::Avahi)
+    }
+}
+
+/// Source of mDNS events.
+///
+/// Abstracts over the mDNS implementation, so that the system responder can
+/// be used on hosts where running another one would conflict with it.
+pub trait Backend: Send {
+    /// Browse for `service_type`, e.g. `_http._tcp.local.`
+    fn browse(&self, service_type: &str) -> anyhow::Result<Receiver<ServiceEvent>>;
+    /// Stop browsing for `service_type`.
+    fn stop_browse(&self, service_type: &str) -> anyhow::Result<()>;
+    /// Stop all the browsing.
+    fn shutdown(&self) -> anyhow::Result<()>;
+}
+
+impl Backend for ServiceDaemon {
+    fn browse(&self, service_type: &str) -> anyhow::Result<Receiver<ServiceEvent>> {
+        Ok(ServiceDaemon::browse(self, service_type)?)
+    }
+
+    fn stop_browse(&self, service_type: &str) -> anyhow::Result<()> {
+        Ok(ServiceDaemon::stop_browse(self, service_type)?)
+    }
+
+    fn shutdown(&self) -> anyhow::Result<()> {
+        ServiceDaemon::shutdown(self)?;
+        Ok(())
+    }
+}
+
+/// Name of the local interface holding `addr`.
+fn interface_with(addr: IpAddr) -> anyhow::Result<String> {
+    if_addrs::get_if_addrs()?
+        .into_iter()
+        .find(|interface| interface.ip() == addr)
+        .map(|interface| interface.name)
+        .ok_or_else(|| {
+            anyhow::anyhow!("No local interface has the address {addr}, see --diagnose-container")
+        })
+}
+
+/// Start a DNS Push client subscribing to a wide-area domain through
+/// `server`, `host` or `host:port`.
+#[cfg(feature = "dns-push")]
+pub fn start_push(server: &str) -> anyhow::Result<Box<dyn Backend>> {
+    Ok(Box::new(crate::push::Push::connect(server)?))
+}
+
+#[cfg(not(feature = "dns-push"))]
+pub fn start_push(_server: &str) -> anyhow::Result<Box<dyn Backend>> {
+    anyhow::bail!("This build doesn't include the dns-push feature")
+}
+
+/// Start an mdns-sd daemon on `interfaces` only, and over IPv6 only if
+/// `ipv6_only`.
+pub fn daemon(interfaces: Vec<IfKind>, ipv6_only: bool) -> anyhow::Result<ServiceDaemon> {
+    for interface in &interfaces {
+        if let IfKind::Addr(addr) = interface {
+            interface_with(*addr)?;
+        }
+    }
+    let mdns = ServiceDaemon::new()?;
+    // Every interface is enabled by default, selecting some means
+    // disabling the others first
+    if !interfaces
+        .iter()
+        .any(|interface| matches!(interface, IfKind::All))
+    {
+        mdns.disable_interface(IfKind::All)?;
+    }
+    mdns.enable_interface(interfaces)?;
+    // Selections apply in order, so this wins over the interfaces
+    if ipv6_only {
+        mdns.disable_interface(IfKind::IPv4)?;
+    }
+    Ok(mdns)
+}
+
+/// Start the `kind` backend, discovering on `interfaces` only, and over IPv6
+/// only if `ipv6_only`.
+///
+/// An interface can be given by one of its addresses, e.g. in minimal
+/// containers where names aren't known in advance.
+pub fn start(
+    kind: BackendKind,
+    interfaces: Vec<IfKind>,
+    ipv6_only: bool,
+) -> anyhow::Result<Box<dyn Backend>> {
+    match kind {
+        BackendKind::MdnsSd => Ok(Box::new(daemon(interfaces, ipv6_only)?)),
+        BackendKind::Avahi => {
+            // avahi-browse only reports interface names
+            let interfaces = interfaces
+                .into_iter()
+                .map(|interface| match interface {
+                    IfKind::Addr(addr) => interface_with(addr).map(IfKind::Name),
+                    interface => Ok(interface),
+                })
+                .collect::<anyhow::Result<_>>()?;
+            Ok(Box::new(Avahi::new(interfaces, ipv6_only)?))
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+/// Persisted inventory of the hosts known to a profile.
+///
+/// Hosts that are missing from the baseline are highlighted as anomalies,
+/// making it easy to spot unexpected devices on a known network. The
+/// baseline is stored as a plain list of hostnames, one per line.
+#[derive(Debug, Default, Clone)]
+pub struct Baseline {
+    path: PathBuf,
+    hosts: BTreeSet<String>,
+}
+
+impl Baseline {
+    /// `$XDG_DATA_HOME/discovery-rs/baselines/<profile>.txt` or the platform equivalent.
+    pub fn default_path(profile: &str) -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| {
+            dir.join("discovery-rs")
+                .join("baselines")
+                .join(format!("{profile}.txt"))
+        })
+    }
+
+    /// Load the baseline stored at `path`, if it has been recorded yet.
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            hosts: contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string)
+                .collect(),
+        }))
+    }
+
+    /// Record `hosts` as the new baseline stored at `path`.
+    pub fn record(path: &Path, hosts: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        let baseline = Self {
+            path: path.to_path_buf(),
+            hosts: hosts.into_iter().collect(),
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let contents = baseline
+            .hosts
+            .iter()
+            .fold(String::new(), |acc, host| acc + host.as_str() + "\n");
+        std::fs::write(path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(baseline)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn contains(&self, host: &str) -> bool {
+        self.hosts.contains(host)
+    }
+}
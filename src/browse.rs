@@ -0,0 +1,158 @@
+use std::io::Write;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use clap_derive::Args;
+use flume::{RecvTimeoutError, Sender};
+
+use crate::backend::BackendKind;
+use crate::config::Profile;
+use crate::domain;
+use crate::error::Error;
+use crate::eventlog::{Event, EventKind};
+use crate::events::{EventStats, QueueSettings};
+use crate::model::Model;
+use crate::schema;
+use crate::script::Scripts;
+use crate::units;
+use crate::zone::Zones;
+use crate::WorkerCommand;
+
+/// Events printed late are dropped beyond this many.
+const K_BACKLOG: usize = 10_000;
+/// How often the deadline is checked while nothing happens
+const K_POLL: Duration = Duration::from_millis(200);
+
+#[derive(Args, Debug)]
+pub struct BrowseOpts {
+    #[arg(long, action)]
+    /// Print NDJSON lines, like --stream-to sends, rather than text
+    ndjson: bool,
+    #[arg(long, value_parser = units::duration)]
+    /// How long to browse for, e.g. 30s or 5m, default: until interrupted
+    duration: Option<Duration>,
+}
+
+/// The text line of `event`, with the target and addresses of a resolved
+/// instance.
+fn line(event: &Event, model: &Model) -> String {
+    let line = event.line();
+    if !matches!(event.kind, EventKind::Resolved) {
+        return line;
+    }
+    let instances = model.instances();
+    let Some(info) = instances
+        .get(domain::base_type(&event.service_type))
+        .and_then(|list| {
+            list.items()
+                .find(|info| info.info.get_fullname() == event.name)
+        })
+    else {
+        return line;
+    };
+    let zones = Zones::local();
+    let addresses: Vec<_> = info
+        .addresses()
+        .into_iter()
+        .map(|addr| zones.display(addr))
+        .collect();
+    format!(
+        "{line} -> {}:{} {}",
+        info.info.get_hostname(),
+        info.info.get_port(),
+        addresses.join(" ")
+    )
+}
+
+/// The mDNS worker the TUI browses with, running without it.
+pub struct Headless {
+    pub model: Model,
+    worker: Sender<WorkerCommand>,
+    handle: JoinHandle<anyhow::Result<()>>,
+}
+
+impl Headless {
+    /// Browse according to `profile` like the TUI does, with the same
+    /// worker.
+    pub fn start(
+        profile: &Profile,
+        backend: BackendKind,
+        queue_settings: QueueSettings,
+    ) -> Result<Self, Error> {
+        let model = Model::new(
+            profile.ignore_filter().map_err(Error::Config)?,
+            None,
+            Scripts::default(),
+        );
+        let (worker, handle) = crate::spawn_worker(
+            profile,
+            domain::K_LOCAL_DOMAIN,
+            backend,
+            None,
+            queue_settings,
+            Arc::new(EventStats::default()),
+            model.clone(),
+        )
+        .map_err(Error::daemon)?;
+        Ok(Self {
+            model,
+            worker,
+            handle,
+        })
+    }
+
+    /// Stop browsing, and wait for the worker to be done.
+    pub fn stop(self) -> Result<(), Error> {
+        let _ = self.worker.send(WorkerCommand::Stop);
+        self.handle
+            .join()
+            .expect("The worker being joined has panicked")
+            .map_err(Error::daemon)
+    }
+}
+
+/// Browse like the TUI does, printing every service found, resolved,
+/// updated and removed to the standard output as it happens, e.g. in
+/// scripts or over SSH.
+pub fn run(
+    opts: &BrowseOpts,
+    profile: &Profile,
+    backend: BackendKind,
+    queue_settings: QueueSettings,
+) -> Result<(), Error> {
+    let headless = Headless::start(profile, backend, queue_settings)?;
+    // Subscribed right away, nothing is resolved before the worker is up
+    let events = headless.model.event_log.subscribe(K_BACKLOG);
+
+    let deadline = opts.duration.map(|duration| Instant::now() + duration);
+    let mut stdout = std::io::stdout().lock();
+    let printed = loop {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            break Ok(());
+        }
+        let event = match events.recv_timeout(K_POLL) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break Ok(()),
+        };
+        let text = if opts.ndjson {
+            match schema::to_json_line(&event.entry()) {
+                Ok(text) => text,
+                Err(e) => break Err(anyhow::anyhow!("Failed to serialize an event: {e}")),
+            }
+        } else {
+            line(&event, &headless.model)
+        };
+        // The reader went away, e.g. `head` had enough
+        if writeln!(stdout, "{text}")
+            .and_then(|()| stdout.flush())
+            .is_err()
+        {
+            break Ok(());
+        }
+    };
+
+    headless.stop()?;
+    printed.map_err(Error::Other)
+}
@@ -0,0 +1,723 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use flume::Receiver;
+
+use crate::domain;
+
+const K_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long the device gets to answer every step
+const K_ANSWER_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the pattern is shown before it is taken down again
+const K_SHOWN: Duration = Duration::from_secs(15);
+/// Size of the pattern, in whole 8×8 blocks
+const K_WIDTH: usize = 640;
+const K_HEIGHT: usize = 360;
+
+/// Devices a test pattern can be shown on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Device {
+    /// Chromecasts and TVs with Google Cast built in
+    Cast,
+    /// Apple TVs and AirPlay receivers accepting photos without pairing
+    AirPlay,
+}
+
+impl Device {
+    /// The device advertising `service_type`, if it can show a pattern.
+    pub fn of(service_type: &str) -> Option<Self> {
+        let service_type = domain::base_type(service_type);
+        if service_type.starts_with("_googlecast._tcp.") {
+            Some(Device::Cast)
+        } else if service_type.starts_with("_airplay._tcp.") {
+            Some(Device::AirPlay)
+        } else {
+            None
+        }
+    }
+}
+
+/// Show a test pattern on the `device` at `target` for a few seconds, in the
+/// background, to tell which of the devices on the shelf it is.
+///
+/// Reports once the pattern is shown, or why it isn't.
+pub fn show(device: Device, target: SocketAddr) -> Receiver<anyhow::Result<()>> {
+    let (tx, rx) = flume::bounded(1);
+    std::thread::spawn(move || {
+        let _span = tracing::span!(tracing::Level::TRACE, "Test pattern").entered();
+        let image = test_pattern();
+        let result = match device {
+            Device::Cast => cast(target, image, |shown| {
+                let _ = tx.send(shown);
+            }),
+            Device::AirPlay => airplay(target, &image, |shown| {
+                let _ = tx.send(shown);
+            }),
+        };
+        // Only reported when failing before the pattern is shown, the
+        // channel is full otherwise
+        if let Err(e) = result {
+            tracing::debug!("Test pattern on {target}: {e:#}");
+            let _ = tx.try_send(Err(e));
+        }
+    });
+    rx
+}
+
+/// Color bars, the 75% ones over reversed blue ones, as a baseline JPEG.
+///
+/// Every 8×8 block is of a single color, so only the DC coefficients are
+/// coded and the encoder stays tiny.
+fn test_pattern() -> Vec<u8> {
+    const K_BARS: [[u8; 3]; 7] = [
+        [191, 191, 191],
+        [191, 191, 0],
+        [0, 191, 191],
+        [0, 191, 0],
+        [191, 0, 191],
+        [191, 0, 0],
+        [0, 0, 191],
+    ];
+    const K_REVERSED: [[u8; 3]; 7] = [
+        [0, 0, 191],
+        [19, 19, 19],
+        [191, 0, 191],
+        [19, 19, 19],
+        [0, 191, 191],
+        [19, 19, 19],
+        [191, 191, 191],
+    ];
+    let (columns, rows) = (K_WIDTH / 8, K_HEIGHT / 8);
+
+    let mut jpeg = vec![0xff, 0xd8];
+    let mut segment = |marker: u8, data: &[u8]| {
+        jpeg.extend_from_slice(&[0xff, marker]);
+        jpeg.extend_from_slice(&(data.len() as u16 + 2).to_be_bytes());
+        jpeg.extend_from_slice(data);
+    };
+    segment(0xe0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+    // A single quantization table of ones, nothing to quantize
+    let mut quantization = vec![0];
+    quantization.extend_from_slice(&[1; 64]);
+    segment(0xdb, &quantization);
+    let mut frame = vec![8];
+    frame.extend_from_slice(&(K_HEIGHT as u16).to_be_bytes());
+    frame.extend_from_slice(&(K_WIDTH as u16).to_be_bytes());
+    frame.extend_from_slice(&[3, 1, 0x11, 0, 2, 0x11, 0, 3, 0x11, 0]);
+    segment(0xc0, &frame);
+    // DC: the 12 categories on 4 bits each. AC: the end of block only.
+    let mut huffman = vec![0x00, 0, 0, 0, 12];
+    huffman.extend_from_slice(&[0; 12]);
+    huffman.extend(0..12);
+    huffman.extend_from_slice(&[0x10, 1]);
+    huffman.extend_from_slice(&[0; 15]);
+    huffman.push(0);
+    segment(0xc4, &huffman);
+    segment(0xda, &[3, 1, 0x00, 2, 0x00, 3, 0x00, 0, 63, 0]);
+
+    let mut bits = Bits::default();
+    let mut previous = [0i32; 3];
+    for row in 0..rows {
+        for column in 0..columns {
+            let bars = if row < rows * 2 / 3 {
+                &K_BARS
+            } else {
+                &K_REVERSED
+            };
+            let [r, g, b] = bars[column * bars.len() / columns].map(f32::from);
+            let components = [
+                0.299 * r + 0.587 * g + 0.114 * b,
+                -0.1687 * r - 0.3313 * g + 0.5 * b + 128.,
+                0.5 * r - 0.4187 * g - 0.0813 * b + 128.,
+            ];
+            for (component, value) in components.into_iter().enumerate() {
+                // The DC coefficient of a flat block is 8 times its level
+                let dc = (8. * (value - 128.)).round() as i32;
+                let diff = dc - previous[component];
+                previous[component] = dc;
+                let category = 32 - diff.unsigned_abs().leading_zeros();
+                bits.push(category, 4);
+                let extra = if diff < 0 {
+                    diff + (1 << category) - 1
+                } else {
+                    diff
+                };
+                bits.push(extra as u32, category);
+                // End of block
+                bits.push(0, 1);
+            }
+        }
+    }
+    jpeg.extend(bits.finish());
+    jpeg.extend_from_slice(&[0xff, 0xd9]);
+    jpeg
+}
+
+/// Entropy coded bits, with the `0xff` bytes stuffed.
+#[derive(Default)]
+struct Bits {
+    bytes: Vec<u8>,
+    current: u32,
+    len: u32,
+}
+
+impl Bits {
+    fn push(&mut self, value: u32, len: u32) {
+        for bit in (0..len).rev() {
+            self.current = (self.current << 1) | ((value >> bit) & 1);
+            self.len += 1;
+            if self.len == 8 {
+                self.flush();
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        let byte = self.current as u8;
+        self.bytes.push(byte);
+        if byte == 0xff {
+            self.bytes.push(0);
+        }
+        self.current = 0;
+        self.len = 0;
+    }
+
+    /// The bytes, the last one padded with ones.
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.push(u32::MAX, 8 - self.len);
+        }
+        self.bytes
+    }
+}
+
+/// Show `image` with AirPlay's photo API, then take it down.
+fn airplay(
+    target: SocketAddr,
+    image: &[u8],
+    shown: impl FnOnce(anyhow::Result<()>),
+) -> anyhow::Result<()> {
+    let mut stream = TcpStream::connect_timeout(&target, K_CONNECT_TIMEOUT)
+        .with_context(|| format!("Failed to connect to {target}"))?;
+    stream.set_read_timeout(Some(K_ANSWER_TIMEOUT))?;
+    let session = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let session = format!(
+        "{:08x}-0000-4000-8000-{:012x}",
+        session as u32,
+        session >> 32
+    );
+    let mut request = |head: &str, body: &[u8]| -> anyhow::Result<u16> {
+        let head = format!(
+            "{head}\r\nHost: {target}\r\nUser-Agent: MediaControl/1.0\r\nX-Apple-Session-ID: {session}\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body)?;
+        // Only the status matters, the answers have no body to speak of
+        let mut response = Vec::new();
+        let mut buf = [0; 1024];
+        while !response.windows(4).any(|end| end == b"\r\n\r\n") {
+            match stream.read(&mut buf)? {
+                0 => break,
+                read => response.extend_from_slice(&buf[..read]),
+            }
+        }
+        let response = String::from_utf8_lossy(&response);
+        response
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .with_context(|| format!("Unexpected answer from {target}"))
+    };
+
+    match request(
+        "PUT /photo HTTP/1.1\r\nContent-Type: image/jpeg\r\nX-Apple-Transition: Dissolve",
+        image,
+    )? {
+        200 => shown(Ok(())),
+        status @ (401 | 403 | 470) => anyhow::bail!(
+            "{target} asks for a password or pairing (HTTP {status}), which isn't supported"
+        ),
+        status => anyhow::bail!("{target} refused the pattern: HTTP {status}"),
+    }
+    std::thread::sleep(K_SHOWN);
+    request("POST /stop HTTP/1.1", &[])?;
+    Ok(())
+}
+
+#[cfg(feature = "cast")]
+use castv2::cast;
+
+/// The Cast protocol, left out of builds without the `cast` feature along
+/// with its TLS client.
+#[cfg(feature = "cast")]
+mod castv2 {
+    use std::io::{ErrorKind, Read, Write};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use anyhow::Context;
+    use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+    use rustls::crypto::{ring, CryptoProvider};
+    use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+    use rustls::{
+        ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme, StreamOwned,
+    };
+    use serde_json::{json, Value};
+
+    use super::{K_ANSWER_TIMEOUT, K_CONNECT_TIMEOUT, K_SHOWN};
+
+    /// Google's Default Media Receiver, which shows images too
+    const K_MEDIA_RECEIVER: &str = "CC1AD845";
+    const K_SENDER: &str = "sender-0";
+    const K_RECEIVER: &str = "receiver-0";
+    const K_CONNECTION: &str = "urn:x-cast:com.google.cast.tp.connection";
+    const K_HEARTBEAT: &str = "urn:x-cast:com.google.cast.tp.heartbeat";
+    const K_RECEIVER_NAMESPACE: &str = "urn:x-cast:com.google.cast.receiver";
+    const K_MEDIA: &str = "urn:x-cast:com.google.cast.media";
+    /// How long the pattern is served for the device to fetch it
+    const K_SERVED: Duration = Duration::from_secs(60);
+    const K_POLL: Duration = Duration::from_millis(100);
+    /// Longest message accepted from the device, receiver status included
+    const K_MAX_FRAME: usize = 64 * 1024;
+
+    /// Cast devices present certificates of their own, signed by Google
+    /// for the device rather than for a name, which aren't checked.
+    #[derive(Debug)]
+    struct Unchecked(Arc<CryptoProvider>);
+
+    impl ServerCertVerifier for Unchecked {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: UnixTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls12_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls13_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.0.signature_verification_algorithms.supported_schemes()
+        }
+    }
+
+    /// The local address `target` is reached from.
+    fn local_addr(target: SocketAddr) -> anyhow::Result<IpAddr> {
+        let unspecified = match target {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0))?;
+        socket
+            .connect(target)
+            .with_context(|| format!("No route to {target}"))?;
+        Ok(socket.local_addr()?.ip())
+    }
+
+    /// Serve `image` over HTTP on `local` for a minute, whatever the path, for
+    /// the device to fetch it. Returns its URL.
+    fn serve(image: Arc<Vec<u8>>, local: IpAddr) -> anyhow::Result<String> {
+        let listener =
+            TcpListener::bind(SocketAddr::new(local, 0)).context("Failed to serve the pattern")?;
+        listener.set_nonblocking(true)?;
+        let url = format!("http://{}/test-pattern.jpg", listener.local_addr()?);
+        std::thread::spawn(move || {
+            let deadline = Instant::now() + K_SERVED;
+            while Instant::now() < deadline {
+                let mut stream = match listener.accept() {
+                    Ok((stream, _)) => stream,
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                        std::thread::sleep(K_POLL);
+                        continue;
+                    }
+                    Err(e) => {
+                        tracing::warn!("Failed to serve the test pattern: {e}");
+                        return;
+                    }
+                };
+                let _ = stream.set_nonblocking(false);
+                let _ = stream.set_read_timeout(Some(K_CONNECT_TIMEOUT));
+                // The request doesn't matter, only that it is over
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.windows(4).any(|end| end == b"\r\n\r\n") {
+                    match stream.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => request.extend_from_slice(&buf[..read]),
+                    }
+                }
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    image.len()
+                );
+                let _ = stream
+                    .write_all(head.as_bytes())
+                    .and_then(|()| stream.write_all(&image));
+            }
+        });
+        Ok(url)
+    }
+
+    fn varint(out: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn read_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = *data.get(*pos)?;
+            *pos += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// A `CastMessage` protobuf carrying `payload` as a string, framed.
+    fn message(destination: &str, namespace: &str, payload: &Value) -> Vec<u8> {
+        let mut message = Vec::new();
+        // protocol_version: CASTV2_1_0
+        message.extend_from_slice(&[0x08, 0]);
+        for (field, value) in [(2, K_SENDER), (3, destination), (4, namespace)] {
+            message.push(field << 3 | 2);
+            varint(&mut message, value.len() as u64);
+            message.extend_from_slice(value.as_bytes());
+        }
+        // payload_type: STRING
+        message.extend_from_slice(&[5 << 3, 0]);
+        let payload = payload.to_string();
+        message.push(6 << 3 | 2);
+        varint(&mut message, payload.len() as u64);
+        message.extend_from_slice(payload.as_bytes());
+        let mut framed = (message.len() as u32).to_be_bytes().to_vec();
+        framed.extend(message);
+        framed
+    }
+
+    /// The namespace and the string payload of a `CastMessage`.
+    fn parse(message: &[u8]) -> Option<(String, String)> {
+        let (mut namespace, mut payload) = (None, None);
+        let mut pos = 0;
+        while pos < message.len() {
+            let key = read_varint(message, &mut pos)?;
+            match key & 7 {
+                0 => {
+                    read_varint(message, &mut pos)?;
+                }
+                2 => {
+                    let len = usize::try_from(read_varint(message, &mut pos)?).ok()?;
+                    let end = pos.checked_add(len)?;
+                    let value = message.get(pos..end)?;
+                    pos = end;
+                    let value = String::from_utf8_lossy(value).into_owned();
+                    match key >> 3 {
+                        4 => namespace = Some(value),
+                        6 => payload = Some(value),
+                        _ => {}
+                    }
+                }
+                _ => return None,
+            }
+        }
+        Some((namespace?, payload?))
+    }
+
+    /// Take the next length-prefixed message off `buf`, once it is whole.
+    fn frame(buf: &mut Vec<u8>) -> anyhow::Result<Option<Vec<u8>>> {
+        let Some(prefix) = buf.get(..4) else {
+            return Ok(None);
+        };
+        let len = u32::from_be_bytes(prefix.try_into()?) as usize;
+        if len > K_MAX_FRAME {
+            anyhow::bail!("The device sent a message of {len} bytes, over {K_MAX_FRAME}");
+        }
+        if buf.len() < 4 + len {
+            return Ok(None);
+        }
+        Ok(Some(buf.drain(..4 + len).skip(4).collect()))
+    }
+
+    struct Connection {
+        stream: StreamOwned<ClientConnection, TcpStream>,
+        buf: Vec<u8>,
+    }
+
+    impl Connection {
+        fn send(
+            &mut self,
+            destination: &str,
+            namespace: &str,
+            payload: Value,
+        ) -> anyhow::Result<()> {
+            self.stream
+                .write_all(&message(destination, namespace, &payload))?;
+            Ok(self.stream.flush()?)
+        }
+
+        /// The next message but heartbeats, which are answered, if any comes
+        /// before `deadline`.
+        fn next(&mut self, deadline: Instant) -> anyhow::Result<Option<(String, Value)>> {
+            let mut chunk = [0; 4096];
+            while Instant::now() < deadline {
+                if let Some(message) = frame(&mut self.buf)? {
+                    let Some((namespace, payload)) = parse(&message) else {
+                        continue;
+                    };
+                    let payload: Value = serde_json::from_str(&payload).unwrap_or_default();
+                    if namespace == K_HEARTBEAT && payload["type"] == "PING" {
+                        self.send(K_RECEIVER, K_HEARTBEAT, json!({ "type": "PONG" }))?;
+                        continue;
+                    }
+                    return Ok(Some((namespace, payload)));
+                }
+                match self.stream.read(&mut chunk) {
+                    Ok(0) => anyhow::bail!("The device closed the connection"),
+                    Ok(read) => self.buf.extend_from_slice(&chunk[..read]),
+                    Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            Ok(None)
+        }
+
+        /// The next answer to the request `id`.
+        fn answer(&mut self, id: u64) -> anyhow::Result<Value> {
+            let deadline = Instant::now() + K_ANSWER_TIMEOUT;
+            while let Some((_, payload)) = self.next(deadline)? {
+                if payload["requestId"] == id {
+                    return Ok(payload);
+                }
+            }
+            anyhow::bail!("The device didn't answer")
+        }
+    }
+
+    /// Launch the Default Media Receiver on the Cast device at `target`,
+    /// load the served `image` in it, then stop it.
+    pub fn cast(
+        target: SocketAddr,
+        image: Vec<u8>,
+        shown: impl FnOnce(anyhow::Result<()>),
+    ) -> anyhow::Result<()> {
+        let url = serve(Arc::new(image), local_addr(target)?)?;
+        let tcp = TcpStream::connect_timeout(&target, K_CONNECT_TIMEOUT)
+            .with_context(|| format!("Failed to connect to {target}"))?;
+        tcp.set_read_timeout(Some(K_POLL))?;
+        let provider = Arc::new(ring::default_provider());
+        let config = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(Unchecked(provider)))
+            .with_no_client_auth();
+        let name = ServerName::from(target.ip());
+        let mut connection = Connection {
+            stream: StreamOwned::new(ClientConnection::new(Arc::new(config), name)?, tcp),
+            buf: Vec::new(),
+        };
+
+        connection.send(K_RECEIVER, K_CONNECTION, json!({ "type": "CONNECT" }))?;
+        connection.send(
+            K_RECEIVER,
+            K_RECEIVER_NAMESPACE,
+            json!({ "type": "LAUNCH", "appId": K_MEDIA_RECEIVER, "requestId": 1 }),
+        )?;
+        let status = connection.answer(1)?;
+        if status["type"] != "RECEIVER_STATUS" {
+            anyhow::bail!(
+                "The device didn't launch the media receiver: {}",
+                status["type"]
+            );
+        }
+        let Some(app) = status["status"]["applications"]
+            .as_array()
+            .and_then(|apps| apps.iter().find(|app| app["appId"] == K_MEDIA_RECEIVER))
+        else {
+            anyhow::bail!("The device didn't launch the media receiver");
+        };
+        let transport = app["transportId"].as_str().unwrap_or_default().to_string();
+        let session = app["sessionId"].clone();
+
+        connection.send(&transport, K_CONNECTION, json!({ "type": "CONNECT" }))?;
+        connection.send(
+            &transport,
+            K_MEDIA,
+            json!({
+                "type": "LOAD",
+                "requestId": 2,
+                "autoplay": true,
+                "media": {
+                    "contentId": url,
+                    "contentType": "image/jpeg",
+                    "streamType": "NONE",
+                    "metadata": { "metadataType": 4, "title": "discovery-rs" },
+                },
+            }),
+        )?;
+        let loaded = connection.answer(2)?;
+        if loaded["type"] != "MEDIA_STATUS" {
+            anyhow::bail!("The device didn't load the pattern: {}", loaded["type"]);
+        }
+        shown(Ok(()));
+
+        // Heartbeats keep the connection up meanwhile
+        let deadline = Instant::now() + K_SHOWN;
+        while connection.next(deadline)?.is_some() {}
+        connection.send(
+            K_RECEIVER,
+            K_RECEIVER_NAMESPACE,
+            json!({ "type": "STOP", "sessionId": session, "requestId": 3 }),
+        )?;
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// A heartbeat PING from a Chromecast, as framed on the wire.
+        #[rustfmt::skip]
+        const K_PING: [u8; 88] = [
+            0x00, 0x00, 0x00, 0x54,
+            // protocol_version: CASTV2_1_0
+            0x08, 0x00,
+            // source_id: receiver-0
+            0x12, 0x0a, b'r', b'e', b'c', b'e', b'i', b'v', b'e', b'r', b'-', b'0',
+            // destination_id: sender-0
+            0x1a, 0x08, b's', b'e', b'n', b'd', b'e', b'r', b'-', b'0',
+            // namespace: urn:x-cast:com.google.cast.tp.heartbeat
+            0x22, 0x27, b'u', b'r', b'n', b':', b'x', b'-', b'c', b'a', b's', b't', b':',
+            b'c', b'o', b'm', b'.', b'g', b'o', b'o', b'g', b'l', b'e', b'.', b'c', b'a',
+            b's', b't', b'.', b't', b'p', b'.', b'h', b'e', b'a', b'r', b't', b'b', b'e',
+            b'a', b't',
+            // payload_type: STRING
+            0x28, 0x00,
+            // payload_utf8: {"type":"PING"}
+            0x32, 0x0f, b'{', b'"', b't', b'y', b'p', b'e', b'"', b':', b'"', b'P', b'I',
+            b'N', b'G', b'"', b'}',
+        ];
+
+        #[test]
+        fn device_messages() {
+            let mut buf = K_PING.to_vec();
+            let message = frame(&mut buf).unwrap().unwrap();
+            assert!(buf.is_empty());
+            assert_eq!(
+                parse(&message),
+                Some((K_HEARTBEAT.to_string(), r#"{"type":"PING"}"#.to_string()))
+            );
+        }
+
+        #[test]
+        fn round_trip() {
+            let mut buf = message(K_RECEIVER, K_HEARTBEAT, &json!({ "type": "PONG" }));
+            buf.extend_from_slice(&K_PING);
+            let sent = frame(&mut buf).unwrap().unwrap();
+            assert_eq!(
+                parse(&sent),
+                Some((K_HEARTBEAT.to_string(), r#"{"type":"PONG"}"#.to_string()))
+            );
+            // The next message is left whole
+            assert_eq!(buf, K_PING);
+        }
+
+        #[test]
+        fn varints() {
+            let mut pos = 0;
+            assert_eq!(read_varint(&[0x05], &mut pos), Some(5));
+            assert_eq!(pos, 1);
+            let mut pos = 0;
+            assert_eq!(read_varint(&[0xac, 0x02, 0x08], &mut pos), Some(300));
+            assert_eq!(pos, 2);
+            let max = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+            let mut pos = 0;
+            assert_eq!(read_varint(&max, &mut pos), Some(u64::MAX));
+            assert_eq!(pos, 10);
+            let mut encoded = Vec::new();
+            varint(&mut encoded, 300);
+            assert_eq!(encoded, [0xac, 0x02]);
+            // Unterminated, and longer than 10 bytes
+            assert_eq!(read_varint(&[0x80], &mut 0), None);
+            assert_eq!(read_varint(&[0x80; 11], &mut 0), None);
+        }
+
+        #[test]
+        fn truncated_messages() {
+            let mut buf = K_PING[..50].to_vec();
+            assert_eq!(frame(&mut buf).unwrap(), None);
+            assert_eq!(buf.len(), 50);
+            assert_eq!(frame(&mut vec![0x00, 0x00]).unwrap(), None);
+            assert_eq!(parse(&K_PING[4..50]), None);
+        }
+
+        #[test]
+        fn oversized_lengths() {
+            let mut buf = vec![0x00, 0x01, 0x00, 0x01, 0x08, 0x00];
+            assert!(frame(&mut buf).is_err());
+            // A string field claiming close to u64::MAX bytes
+            let mut message = vec![0x22];
+            message
+                .extend_from_slice(&[0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+            message.extend_from_slice(b"urn");
+            assert_eq!(parse(&message), None);
+        }
+
+        #[test]
+        fn other_messages() {
+            assert_eq!(parse(b"GET / HTTP/1.1\r\n"), None);
+            assert_eq!(parse(br#"{"type":"PING"}"#), None);
+            // A CastMessage without a payload
+            assert_eq!(parse(&K_PING[4..69]), None);
+        }
+    }
+}
+
+#[cfg(not(feature = "cast"))]
+fn cast(
+    _target: SocketAddr,
+    _image: Vec<u8>,
+    _shown: impl FnOnce(anyhow::Result<()>),
+) -> anyhow::Result<()> {
+    anyhow::bail!("This build doesn't include the cast feature")
+}
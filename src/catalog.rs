@@ -0,0 +1,528 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use clap_derive::Args;
+use crossterm::event::{KeyCode, KeyEvent};
+use mdns_sd::{IfKind, ServiceInfo};
+use parking_lot::Mutex;
+use ratatui::{prelude::*, widgets::*};
+use serde::Deserialize;
+
+use crate::backend::{self, BackendKind};
+use crate::census;
+use crate::clock::SystemClock;
+use crate::colors::theme;
+use crate::domain;
+use crate::error::Error;
+use crate::model::Model;
+use crate::session::{self, Session};
+use crate::units;
+use crate::widget::DiscoveryWidget;
+use crate::K_SERVICE_TYPE_ENUMERATION;
+
+#[derive(Args, Debug)]
+pub struct CheckOpts {
+    #[arg(long)]
+    /// YAML catalog of the services every host should advertise
+    catalog: PathBuf,
+    #[arg(long, default_value_t = 4)]
+    /// How many types to browse at once
+    concurrency: usize,
+    #[arg(long, value_parser = units::duration, default_value = "5s")]
+    /// How long to enumerate the types, then to browse each type for, e.g. 5s or 1m
+    timeout: Duration,
+}
+
+/// A service a host of the catalog should advertise.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Expected {
+    #[serde(rename = "type")]
+    service_type: String,
+    /// Any port if left out
+    #[serde(default)]
+    port: Option<u16>,
+    /// TXT values the service should carry, other keys may come along
+    #[serde(default)]
+    txt: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CatalogFile {
+    hosts: BTreeMap<String, Vec<Expected>>,
+}
+
+/// The services each named host should advertise, e.g. to validate the
+/// provisioning of a fleet.
+///
+/// ```yaml
+/// hosts:
+///   printer-1:
+///     - type: _ipp._tcp
+///       port: 631
+///       txt:
+///         pdl: application/pdf
+///   nas.local.:
+///     - type: _smb._tcp
+/// ```
+///
+/// Hostnames without a domain are looked up in `local.`, like types.
+#[derive(Debug, Clone)]
+pub struct Catalog {
+    pub path: PathBuf,
+    /// Expected services by normalized hostname, with qualified types
+    hosts: BTreeMap<String, Vec<Expected>>,
+}
+
+/// `hostname` fully qualified and lowercase, e.g. `printer-1.local.` for
+/// `Printer-1`.
+fn qualify_host(hostname: &str) -> String {
+    if hostname.trim_end_matches('.').contains('.') {
+        domain::normalize(hostname)
+    } else {
+        domain::normalize(&format!("{hostname}.{}", domain::K_LOCAL_DOMAIN))
+    }
+}
+
+/// A live instance, as far as the catalog is concerned.
+#[derive(Debug)]
+pub struct Live {
+    service_type: String,
+    hostname: String,
+    port: u16,
+    txt: BTreeMap<String, String>,
+}
+
+impl Live {
+    pub fn new(service_type: &str, info: &ServiceInfo) -> Self {
+        Self {
+            service_type: domain::normalize(domain::base_type(service_type)),
+            hostname: domain::normalize(info.get_hostname()),
+            port: info.get_port(),
+            txt: info
+                .get_properties()
+                .iter()
+                .map(|property| (property.key().to_string(), property.val_str().to_string()))
+                .collect(),
+        }
+    }
+
+    /// What differs from `expected`, if anything.
+    fn mismatch(&self, expected: &Expected) -> Option<String> {
+        let mut differences = Vec::new();
+        if let Some(port) = expected.port.filter(|port| *port != self.port) {
+            differences.push(format!("port {}, expected {port}", self.port));
+        }
+        for (key, value) in expected.txt.iter() {
+            match self.txt.get(key) {
+                Some(live) if live == value => {}
+                Some(live) => differences.push(format!("{key}={live}, expected {value}")),
+                None => differences.push(format!("no {key}, expected {value}")),
+            }
+        }
+        (!differences.is_empty()).then(|| differences.join(", "))
+    }
+}
+
+/// How a host of the catalog compares with the live network.
+#[derive(Debug)]
+pub struct Compliance {
+    pub hostname: String,
+    /// Types expected but not advertised
+    pub missing: Vec<String>,
+    /// Types advertised but not expected
+    pub extra: Vec<String>,
+    /// Types advertised, but not as expected, and how
+    pub misconfigured: Vec<(String, String)>,
+}
+
+impl Compliance {
+    pub fn passed(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.misconfigured.is_empty()
+    }
+}
+
+impl Catalog {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(path, &contents)
+    }
+
+    /// The catalog in the YAML `contents` of the file at `path`.
+    fn parse(path: &Path, contents: &str) -> anyhow::Result<Self> {
+        let file: CatalogFile = serde_yaml::from_str(contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        let hosts = file
+            .hosts
+            .into_iter()
+            .map(|(hostname, mut services)| {
+                for service in services.iter_mut() {
+                    service.service_type =
+                        domain::normalize(&domain::qualify(&service.service_type));
+                }
+                (qualify_host(&hostname), services)
+            })
+            .collect();
+        Ok(Self {
+            path: path.to_path_buf(),
+            hosts,
+        })
+    }
+
+    /// Every type the catalog lists.
+    fn types(&self) -> BTreeSet<String> {
+        self.hosts
+            .values()
+            .flatten()
+            .map(|service| service.service_type.clone())
+            .collect()
+    }
+
+    /// Compare every host of the catalog with the `live` instances.
+    ///
+    /// An expected service is fine as long as one instance of its type on
+    /// the host matches, and misconfigured when only others do. Hosts left
+    /// out of the catalog aren't checked.
+    pub fn check(&self, live: &[Live]) -> Vec<Compliance> {
+        self.hosts
+            .iter()
+            .map(|(hostname, expected)| {
+                let on_host: Vec<_> = live
+                    .iter()
+                    .filter(|live| live.hostname == *hostname)
+                    .collect();
+                let mut compliance = Compliance {
+                    hostname: hostname.clone(),
+                    missing: Vec::new(),
+                    extra: Vec::new(),
+                    misconfigured: Vec::new(),
+                };
+                for service in expected.iter() {
+                    let instances: Vec<_> = on_host
+                        .iter()
+                        .filter(|live| live.service_type == service.service_type)
+                        .collect();
+                    // Only when every instance differs, telling how the first does
+                    let mismatches: Option<Vec<_>> = instances
+                        .iter()
+                        .map(|live| live.mismatch(service))
+                        .collect();
+                    match mismatches {
+                        _ if instances.is_empty() => {
+                            compliance.missing.push(service.service_type.clone())
+                        }
+                        Some(mut mismatches) => compliance
+                            .misconfigured
+                            .push((service.service_type.clone(), mismatches.swap_remove(0))),
+                        None => {}
+                    }
+                }
+                let expected_types: BTreeSet<_> = expected
+                    .iter()
+                    .map(|service| &service.service_type)
+                    .collect();
+                let extra: BTreeSet<_> = on_host
+                    .iter()
+                    .map(|live| &live.service_type)
+                    .filter(|service_type| !expected_types.contains(service_type))
+                    .collect();
+                compliance.extra = extra.into_iter().cloned().collect();
+                compliance
+            })
+            .collect()
+    }
+}
+
+/// Lines telling how `compliance` went, each host then what is wrong with it.
+fn report(compliance: &[Compliance]) -> Vec<(bool, String)> {
+    let mut lines = Vec::new();
+    for host in compliance {
+        let passed = host.passed();
+        lines.push((
+            passed,
+            format!("{} {}", if passed { "PASS" } else { "FAIL" }, host.hostname),
+        ));
+        lines.extend(
+            host.missing
+                .iter()
+                .map(|service_type| (false, format!("  missing {service_type}"))),
+        );
+        lines.extend(
+            host.extra
+                .iter()
+                .map(|service_type| (false, format!("  extra   {service_type}"))),
+        );
+        lines.extend(
+            host.misconfigured
+                .iter()
+                .map(|(service_type, how)| (false, format!("  wrong   {service_type}: {how}"))),
+        );
+    }
+    lines
+}
+
+/// Compliance of the live results with a [`Catalog`].
+///
+/// Like the host card, a snapshot taken when opened.
+#[derive(Debug)]
+pub struct CatalogView {
+    path: PathBuf,
+    failing: usize,
+    hosts: usize,
+    lines: Vec<Line<'static>>,
+    scroll: usize,
+}
+
+impl CatalogView {
+    pub fn check(catalog: &Catalog, model: &Model) -> Self {
+        let live: Vec<_> = model
+            .instances()
+            .iter()
+            .flat_map(|(service_type, list)| {
+                list.items()
+                    .map(|info| Live::new(service_type, &info.info))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let compliance = catalog.check(&live);
+        let lines = report(&compliance)
+            .into_iter()
+            .map(|(passed, line)| {
+                if line.starts_with(' ') {
+                    Line::from(line).red()
+                } else if passed {
+                    Line::from(line).green().bold()
+                } else {
+                    Line::from(line).red().bold()
+                }
+            })
+            .collect();
+        Self {
+            path: catalog.path.clone(),
+            failing: compliance.iter().filter(|host| !host.passed()).count(),
+            hosts: compliance.len(),
+            lines,
+            scroll: 0,
+        }
+    }
+}
+
+impl DiscoveryWidget for CatalogView {
+    fn title(&self) -> String {
+        format!(
+            "{} vs. live: {} of {} hosts failing",
+            self.path.display(),
+            self.failing,
+            self.hosts
+        )
+    }
+
+    fn controls(&self) -> String {
+        "Use ↓↑ to scroll, Esc to close".to_string()
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        match key_event.code {
+            KeyCode::Down => {
+                self.scroll = (self.scroll + 1).min(self.lines.len().saturating_sub(1))
+            }
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(theme().selected_fg).bold())
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .padding(Padding::horizontal(1))
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        Paragraph::new(
+            self.lines
+                .iter()
+                .skip(self.scroll)
+                .cloned()
+                .collect::<Vec<_>>(),
+        )
+        .block(block)
+        .render(area, buf);
+    }
+}
+
+/// Check the network against the catalog of the options without the TUI,
+/// print a pass/fail report and fail unless every host passes.
+///
+/// Every type found is browsed, along with the ones of the catalog, so
+/// that the services hosts shouldn't advertise show up too.
+pub fn run(
+    opts: &CheckOpts,
+    kind: BackendKind,
+    interfaces: Vec<IfKind>,
+    ipv6_only: bool,
+    summary: Option<&Path>,
+) -> Result<(), Error> {
+    let catalog = Catalog::load(&opts.catalog).map_err(Error::Config)?;
+    let backend = Mutex::new(backend::start(kind, interfaces, ipv6_only).map_err(Error::daemon)?);
+    let session = Session::new(Arc::new(SystemClock));
+
+    let mut types = catalog.types();
+    match census::enumerate(&backend, K_SERVICE_TYPE_ENUMERATION, opts.timeout, &session) {
+        Ok(found) => types.extend(
+            found
+                .iter()
+                .map(|service_type| domain::normalize(service_type)),
+        ),
+        Err(e) => tracing::warn!("Enumerating the service types failed: {e:#}"),
+    }
+    let types: Vec<_> = types.into_iter().collect();
+    let results = census::browse_all(&backend, &types, opts.concurrency, opts.timeout, &session);
+    backend.lock().shutdown().map_err(Error::daemon)?;
+
+    let mut live = Vec::new();
+    for (service_type, result) in types.iter().zip(results) {
+        match result {
+            Ok(instances) => {
+                live.extend(instances.iter().map(|info| Live::new(service_type, info)))
+            }
+            Err(e) => tracing::warn!("Browsing {service_type} failed: {e:#}"),
+        }
+    }
+    let compliance = catalog.check(&live);
+    let mut stdout = std::io::stdout();
+    for (_, line) in report(&compliance) {
+        writeln!(stdout, "{line}")
+            .context("Failed to write the report")
+            .map_err(Error::Other)?;
+    }
+    session::report(&session, summary, std::io::stderr()).map_err(Error::Export)?;
+
+    let failing = compliance.iter().filter(|host| !host.passed()).count();
+    if failing > 0 {
+        return Err(Error::Other(anyhow::anyhow!(
+            "{failing} of {} hosts don't match {}",
+            compliance.len(),
+            opts.catalog.display()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const K_CATALOG: &str = "\
+hosts:
+  Printer-1:
+    - type: _ipp._tcp
+      port: 631
+      txt:
+        pdl: application/pdf
+  nas.local.:
+    - type: _smb._tcp
+    - type: _device-info._tcp.local.
+";
+
+    fn catalog(contents: &str) -> anyhow::Result<Catalog> {
+        Catalog::parse(Path::new("catalog.yaml"), contents)
+    }
+
+    fn live(service_type: &str, hostname: &str, port: u16, txt: &[(&str, &str)]) -> Live {
+        Live {
+            service_type: service_type.to_string(),
+            hostname: hostname.to_string(),
+            port,
+            txt: txt
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn catalogs() {
+        let catalog = catalog(K_CATALOG).unwrap();
+        let hosts: Vec<_> = catalog.hosts.keys().map(String::as_str).collect();
+        assert_eq!(hosts, ["nas.local.", "printer-1.local."]);
+        let printer = &catalog.hosts["printer-1.local."];
+        assert_eq!(printer[0].service_type, "_ipp._tcp.local.");
+        assert_eq!(printer[0].port, Some(631));
+        assert_eq!(printer[0].txt["pdl"], "application/pdf");
+        let types: Vec<_> = catalog.types().into_iter().collect();
+        assert_eq!(
+            types,
+            [
+                "_device-info._tcp.local.",
+                "_ipp._tcp.local.",
+                "_smb._tcp.local."
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_catalogs() {
+        let error = |contents| format!("{:#}", catalog(contents).unwrap_err());
+        for contents in [
+            // Not a mapping of hosts
+            "- printer-1",
+            "hosts: printer-1",
+            // A misspelled field
+            "hosts:\n  printer-1:\n    - type: _ipp._tcp\n      prot: 631\n",
+            "host:\n  printer-1: []\n",
+            // Without a type, or with a port out of range
+            "hosts:\n  printer-1:\n    - port: 631\n",
+            "hosts:\n  printer-1:\n    - type: _ipp._tcp\n      port: 65536\n",
+            "hosts:\n  printer-1:\n    - type: _ipp._tcp\n      txt: [pdl]\n",
+        ] {
+            assert!(
+                error(contents).starts_with("Failed to parse catalog.yaml: "),
+                "{contents}"
+            );
+        }
+    }
+
+    #[test]
+    fn compliance() {
+        let catalog = catalog(K_CATALOG).unwrap();
+        let compliance = catalog.check(&[
+            live(
+                "_ipp._tcp.local.",
+                "printer-1.local.",
+                631,
+                &[("pdl", "application/postscript")],
+            ),
+            live("_http._tcp.local.", "printer-1.local.", 80, &[]),
+            live("_smb._tcp.local.", "nas.local.", 445, &[]),
+            live("_device-info._tcp.local.", "nas.local.", 0, &[]),
+            live("_ssh._tcp.local.", "laptop.local.", 22, &[]),
+        ]);
+        assert_eq!(compliance.len(), 2);
+        assert!(compliance[0].passed());
+        let printer = &compliance[1];
+        assert_eq!(printer.hostname, "printer-1.local.");
+        assert!(printer.missing.is_empty());
+        assert_eq!(printer.extra, ["_http._tcp.local."]);
+        assert_eq!(
+            printer.misconfigured,
+            [(
+                "_ipp._tcp.local.".to_string(),
+                "pdl=application/postscript, expected application/pdf".to_string()
+            )]
+        );
+        let missing = catalog.check(&[]);
+        assert_eq!(
+            missing[0].missing,
+            ["_smb._tcp.local.", "_device-info._tcp.local."]
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use clap_derive::Args;
+use flume::RecvTimeoutError;
+use mdns_sd::{IfKind, ServiceEvent, ServiceInfo};
+use parking_lot::Mutex;
+
+use crate::backend::{self, Backend, BackendKind};
+use crate::clock::SystemClock;
+use crate::domain;
+use crate::error::Error;
+use crate::filter::Filter;
+use crate::label::{self, Labeler};
+use crate::schema::{self, Census, Instance, TypeCensus};
+use crate::session::{self, Session};
+use crate::units;
+
+#[derive(Args, Debug)]
+pub struct CensusOpts {
+    #[arg(long)]
+    /// File listing the service types to enumerate, one per line
+    types: PathBuf,
+    #[arg(long, default_value_t = 4)]
+    /// How many types to browse at once
+    concurrency: usize,
+    #[arg(long, value_parser = units::duration, default_value = "5s")]
+    /// How long to browse each type for, e.g. 5s or 1m
+    timeout: Duration,
+    #[arg(long)]
+    /// Only keep the instances matching this search, e.g. 'type == "_ssh._tcp" && txt.os != "macOS"'
+    filter: Option<String>,
+    #[arg(long)]
+    /// File to write the census to, default: standard output
+    output: Option<PathBuf>,
+}
+
+/// Read the service types listed in `path`.
+///
+/// Blank lines and `#` comments are skipped, and types given without a
+/// domain, e.g. `_http._tcp`, are looked up in `local.`
+fn read_types(path: &Path) -> anyhow::Result<Vec<String>> {
+    let types = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(types
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(domain::qualify)
+        .collect())
+}
+
+/// Whether `info` satisfies `filter`, searched like in the Instances pane:
+/// by hostname and labels, or by its fields.
+fn matches(filter: &Filter, labelers: &[Labeler], info: &ServiceInfo) -> bool {
+    let text = label::labels(labelers, info)
+        .iter()
+        .fold(info.get_hostname().to_string(), |text, label| {
+            format!("{text} label:{}", label.name)
+        });
+    filter.matches(&text, Some(info))
+}
+
+/// Collect the instances of `service_type` resolved within `timeout`.
+pub fn browse(
+    backend: &Mutex<Box<dyn Backend>>,
+    service_type: &str,
+    timeout: Duration,
+    session: &Session,
+) -> anyhow::Result<Vec<ServiceInfo>> {
+    let events = backend.lock().browse(service_type)?;
+    let deadline = Instant::now() + timeout;
+    let mut instances = BTreeMap::new();
+    loop {
+        let event = events.recv_deadline(deadline);
+        if let Ok(
+            ServiceEvent::ServiceFound(..)
+            | ServiceEvent::ServiceResolved(_)
+            | ServiceEvent::ServiceRemoved(..),
+        ) = &event
+        {
+            session.event();
+        }
+        match event {
+            Ok(ServiceEvent::ServiceResolved(info)) => {
+                session.resolved(&info);
+                instances.insert(info.get_fullname().to_string(), info);
+            }
+            Ok(ServiceEvent::ServiceRemoved(_, fullname)) => {
+                instances.remove(&fullname);
+            }
+            Ok(_) => {}
+            Err(RecvTimeoutError::Timeout) => break,
+            Err(RecvTimeoutError::Disconnected) => anyhow::bail!("Browsing has stopped early"),
+        }
+    }
+    backend.lock().stop_browse(service_type)?;
+    if !instances.is_empty() {
+        session.service_type(service_type);
+    }
+    Ok(instances.into_values().collect())
+}
+
+/// Collect the service types found within `timeout`, for an enumeration
+/// `query`.
+pub fn enumerate(
+    backend: &Mutex<Box<dyn Backend>>,
+    query: &str,
+    timeout: Duration,
+    session: &Session,
+) -> anyhow::Result<Vec<String>> {
+    let events = backend.lock().browse(query)?;
+    let deadline = Instant::now() + timeout;
+    let mut types = BTreeSet::new();
+    loop {
+        match events.recv_deadline(deadline) {
+            Ok(ServiceEvent::ServiceFound(_, service_type)) => {
+                session.event();
+                types.insert(service_type);
+            }
+            Ok(ServiceEvent::ServiceRemoved(_, service_type)) => {
+                session.event();
+                types.remove(&service_type);
+            }
+            Ok(_) => {}
+            Err(RecvTimeoutError::Timeout) => break,
+            Err(RecvTimeoutError::Disconnected) => anyhow::bail!("Browsing has stopped early"),
+        }
+    }
+    backend.lock().stop_browse(query)?;
+    Ok(types.into_iter().collect())
+}
+
+/// [`browse`] every one of `types`, `concurrency` of them at once, in the
+/// order of `types`.
+pub fn browse_all(
+    backend: &Mutex<Box<dyn Backend>>,
+    types: &[String],
+    concurrency: usize,
+    timeout: Duration,
+    session: &Session,
+) -> Vec<anyhow::Result<Vec<ServiceInfo>>> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<anyhow::Result<Vec<ServiceInfo>>>>> =
+        Mutex::new(std::iter::repeat_with(|| None).take(types.len()).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.clamp(1, types.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(service_type) = types.get(index) else {
+                    break;
+                };
+                tracing::debug!("Browsing {service_type}");
+                results.lock()[index] = Some(browse(backend, service_type, timeout, session));
+            });
+        }
+    });
+    results.into_inner().into_iter().flatten().collect()
+}
+
+/// Run a census of the types listed in the options without the TUI and
+/// write it as a single JSON document.
+///
+/// Meant for scheduled jobs: a type failing to browse is reported in the
+/// document rather than failing the whole run. The session summary goes to
+/// the standard error, or as JSON to `summary`, as the standard output may
+/// carry the census.
+pub fn run(
+    opts: &CensusOpts,
+    kind: BackendKind,
+    interfaces: Vec<IfKind>,
+    ipv6_only: bool,
+    labelers: &[Labeler],
+    summary: Option<&Path>,
+) -> Result<(), Error> {
+    let types = read_types(&opts.types).map_err(Error::Config)?;
+    let filter = opts
+        .filter
+        .as_deref()
+        .map(Filter::parse)
+        .transpose()
+        .map_err(Error::Config)?;
+    let backend = Mutex::new(backend::start(kind, interfaces, ipv6_only).map_err(Error::daemon)?);
+    let started = SystemTime::now();
+    let clock = Instant::now();
+    let session = Session::new(Arc::new(SystemClock));
+
+    let results = browse_all(&backend, &types, opts.concurrency, opts.timeout, &session);
+    backend.lock().shutdown().map_err(Error::daemon)?;
+    let types = types
+        .into_iter()
+        .zip(results)
+        .map(|(service_type, result)| {
+            let (instances, error) = match result {
+                Ok(instances) => (
+                    instances
+                        .iter()
+                        .filter(|info| {
+                            filter
+                                .as_ref()
+                                .map_or(true, |filter| matches(filter, labelers, info))
+                        })
+                        .map(Instance::from)
+                        .collect(),
+                    None,
+                ),
+                Err(e) => {
+                    tracing::warn!("Census of {service_type} failed: {e:#}");
+                    (Vec::new(), Some(format!("{e:#}")))
+                }
+            };
+            TypeCensus {
+                service_type,
+                instances,
+                error,
+            }
+        })
+        .collect();
+
+    let census = Census {
+        started: started
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        duration_ms: clock.elapsed().as_millis() as u64,
+        types,
+    };
+    let json = schema::to_json(&census).map_err(|e| Error::Export(e.into()))?;
+    match opts.output.as_ref() {
+        Some(path) => std::fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write {}", path.display())),
+        None => writeln!(std::io::stdout(), "{json}").context("Failed to write the census"),
+    }
+    .map_err(Error::Export)?;
+    session::report(&session, summary, std::io::stderr()).map_err(Error::Export)
+}
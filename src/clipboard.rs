@@ -0,0 +1,118 @@
+use std::io::Write;
+
+use base64::Engine;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::colors::theme;
+use crate::widget::DiscoveryWidget;
+
+/// Copy `text` to the clipboard through the terminal, with an OSC 52 escape.
+///
+/// This works over SSH too, as long as the terminal supports it, which most
+/// do, sometimes behind a setting.
+pub fn copy(text: &str) -> anyhow::Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{encoded}\x07")?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Popup choosing which field of an instance to copy, e.g. one of its
+/// addresses or its hostname.
+///
+/// Like [`crate::query::QueryPicker`], the owner takes the
+/// [`CopyPicker::answer`] once the picker is done.
+#[derive(Debug)]
+pub struct CopyPicker {
+    /// What each field is, and its text as copied
+    fields: Vec<(String, String)>,
+    selected: usize,
+    answer: Option<Option<(String, String)>>,
+}
+
+impl CopyPicker {
+    pub fn new(fields: Vec<(String, String)>, selected: usize) -> Self {
+        Self {
+            selected: selected.min(fields.len().saturating_sub(1)),
+            fields,
+            answer: None,
+        }
+    }
+
+    /// What the field picked is, and its text.
+    ///
+    /// Returns `Err(self)` while still picking.
+    pub fn answer(self) -> Result<Option<(String, String)>, Self> {
+        match self.answer {
+            Some(answer) => Ok(answer),
+            None => Err(self),
+        }
+    }
+}
+
+impl DiscoveryWidget for CopyPicker {
+    fn title(&self) -> String {
+        "Copy".to_string()
+    }
+
+    fn controls(&self) -> String {
+        "Use ↓↑ to choose, ↵ to copy, Esc to cancel".to_string()
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        match key_event.code {
+            KeyCode::Down if !self.fields.is_empty() => {
+                self.selected = (self.selected + 1) % self.fields.len()
+            }
+            KeyCode::Up if !self.fields.is_empty() => {
+                self.selected = (self.selected + self.fields.len() - 1) % self.fields.len()
+            }
+            KeyCode::Enter => self.answer = Some(self.fields.get(self.selected).cloned()),
+            KeyCode::Esc => self.answer = Some(None),
+            _ => {}
+        }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(theme().selected_fg).bold())
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .padding(Padding::horizontal(1))
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        let inner_area = block.inner(area);
+        block.render(area, buf);
+
+        let width = self
+            .fields
+            .iter()
+            .map(|(field, _)| field.chars().count())
+            .max()
+            .unwrap_or_default();
+        let items: Vec<_> = self
+            .fields
+            .iter()
+            .map(|(field, text)| {
+                // Multiline texts, e.g. the record, are folded onto one line
+                let folded: Vec<_> = text.lines().map(str::trim).collect();
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{field:<width$}  "), Style::new().light_cyan()),
+                    Span::raw(folded.join(" ")),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .highlight_style(Style::new().bold().reversed().fg(theme().selected_fg));
+        StatefulWidget::render(
+            list,
+            inner_area,
+            buf,
+            &mut ListState::default().with_selected(Some(self.selected)),
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use std::fmt::Debug;
+use std::time::{Instant, SystemTime};
+
+/// Source of the current time.
+///
+/// The model reads the time through a clock rather than directly, for TTL
+/// inference, eviction, sightings, first-seen times and event timestamps, so
+/// that it can run on simulated time, e.g. to test TTL expiries without
+/// waiting for them. Waiting itself, e.g. for the refresh schedule or toasts,
+/// stays on real time.
+pub trait Clock: Debug + Send + Sync {
+    /// Monotonic time, for durations such as TTLs
+    fn now(&self) -> Instant;
+    /// Wall-clock time, for timestamps
+    fn system_time(&self) -> SystemTime;
+}
+
+/// The real time.
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Simulated time, which only moves when advanced, e.g. to test expiries.
+#[cfg(test)]
+#[derive(Debug)]
+pub struct ManualClock {
+    /// The real time when created, which the simulated time starts from
+    start: (Instant, SystemTime),
+    elapsed: parking_lot::Mutex<std::time::Duration>,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            start: (Instant::now(), SystemTime::now()),
+            elapsed: Default::default(),
+        }
+    }
+
+    pub fn advance(&self, by: std::time::Duration) {
+        *self.elapsed.lock() += by;
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.start.0 + *self.elapsed.lock()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.start.1 + *self.elapsed.lock()
+    }
+}
@@ -1,9 +1,63 @@
+use parking_lot::RwLock;
 use ratatui::style::palette::tailwind;
 use ratatui::style::Color;
 
-pub const NORMAL_ROW_COLOR: Color = tailwind::SLATE.c950;
-pub const ALT_ROW_COLOR: Color = tailwind::SLATE.c900;
-pub const TEXT_COLOR: Color = tailwind::SLATE.c200;
-pub const SELECTED_STYLE_FG: Color = tailwind::BLUE.c300;
-pub const HEADER_BG: Color = tailwind::BLUE.c950;
-pub const SEARCH_STYLE_BORDER: Color = tailwind::YELLOW.c300;
+/// Palette used by all the widgets.
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    pub normal_row: Color,
+    pub alt_row: Color,
+    pub text: Color,
+    pub selected_fg: Color,
+    pub header_bg: Color,
+    pub search_border: Color,
+    pub warning: Color,
+    pub success: Color,
+    pub error: Color,
+}
+
+impl Theme {
+    pub const DEFAULT: Theme = Theme {
+        normal_row: tailwind::SLATE.c950,
+        alt_row: tailwind::SLATE.c900,
+        text: tailwind::SLATE.c200,
+        selected_fg: tailwind::BLUE.c300,
+        header_bg: tailwind::BLUE.c950,
+        search_border: tailwind::YELLOW.c300,
+        warning: tailwind::RED.c400,
+        success: tailwind::GREEN.c400,
+        error: tailwind::RED.c500,
+    };
+
+    pub const LIGHT: Theme = Theme {
+        normal_row: tailwind::SLATE.c50,
+        alt_row: tailwind::SLATE.c200,
+        text: tailwind::SLATE.c900,
+        selected_fg: tailwind::BLUE.c700,
+        header_bg: tailwind::BLUE.c100,
+        search_border: tailwind::AMBER.c600,
+        warning: tailwind::RED.c600,
+        success: tailwind::GREEN.c700,
+        error: tailwind::RED.c700,
+    };
+
+    /// Look up a built-in theme by its name.
+    pub fn named(name: &str) -> Option<Theme> {
+        match name {
+            "default" => Some(Self::DEFAULT),
+            "light" => Some(Self::LIGHT),
+            _ => None,
+        }
+    }
+}
+
+static THEME: RwLock<Theme> = parking_lot::const_rwlock(Theme::DEFAULT);
+
+/// Currently active [`Theme`].
+pub fn theme() -> Theme {
+    *THEME.read()
+}
+
+pub fn set_theme(theme: Theme) {
+    *THEME.write() = theme;
+}
@@ -0,0 +1,228 @@
+use anyhow::Context;
+use serde::Deserialize;
+
+use crate::filter::Field;
+use crate::info::Info;
+
+/// Config column computed from every resolved instance, shown in the
+/// Instances pane and exported along.
+///
+/// ```toml
+/// [[computed_columns]]
+/// name = "fw"
+/// value = "txt.fw"
+///
+/// [[computed_columns]]
+/// name = "addrs"
+/// value = "addresses | count"
+/// ```
+///
+/// `value` is a field, one of `hostname`, `name`, `type`, `txt.<key>`,
+/// `addresses` or `port`, piped through any of `count`, `first`, `lower` and
+/// `upper`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ComputedColumn {
+    pub name: String,
+    pub value: String,
+}
+
+/// What a computed column starts from.
+#[derive(Debug, Clone)]
+enum Source {
+    Field(Field),
+    Addresses,
+    Port,
+}
+
+/// A step of the pipe, applied to the values so far.
+#[derive(Debug, Clone, Copy)]
+enum Pipe {
+    Count,
+    First,
+    Lower,
+    Upper,
+}
+
+/// A compiled [`ComputedColumn`].
+#[derive(Debug, Clone)]
+pub struct Computed {
+    name: String,
+    source: Source,
+    pipes: Vec<Pipe>,
+}
+
+impl ComputedColumn {
+    pub fn compile(&self) -> anyhow::Result<Computed> {
+        let mut steps = self.value.split('|').map(str::trim);
+        let source = match steps.next().unwrap_or_default() {
+            "addresses" => Source::Addresses,
+            "port" => Source::Port,
+            field => Source::Field(
+                Field::parse(field).with_context(|| format!("Unknown field \"{field}\""))?,
+            ),
+        };
+        let pipes = steps
+            .map(|pipe| match pipe {
+                "count" => Ok(Pipe::Count),
+                "first" => Ok(Pipe::First),
+                "lower" => Ok(Pipe::Lower),
+                "upper" => Ok(Pipe::Upper),
+                pipe => anyhow::bail!("Unknown pipe \"{pipe}\""),
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Computed {
+            name: self.name.clone(),
+            source,
+            pipes,
+        })
+    }
+}
+
+impl Computed {
+    /// The column's value for `info`, `None` when there is nothing to show,
+    /// e.g. a missing TXT key.
+    fn value(&self, info: &Info) -> Option<String> {
+        let mut values: Vec<String> = match &self.source {
+            Source::Field(field) => field
+                .value(&info.info)
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            Source::Addresses => info
+                .addresses()
+                .into_iter()
+                .map(|addr| addr.to_string())
+                .collect(),
+            Source::Port => vec![info.info.get_port().to_string()],
+        };
+        for pipe in self.pipes.iter() {
+            values = match pipe {
+                Pipe::Count => vec![values.len().to_string()],
+                Pipe::First => values.into_iter().take(1).collect(),
+                Pipe::Lower => values.iter().map(|value| value.to_lowercase()).collect(),
+                Pipe::Upper => values.iter().map(|value| value.to_uppercase()).collect(),
+            };
+        }
+        (!values.is_empty()).then(|| values.join(" "))
+    }
+}
+
+/// Compile all the `columns`.
+pub fn compile(columns: &[ComputedColumn]) -> anyhow::Result<Vec<Computed>> {
+    columns
+        .iter()
+        .map(|column| {
+            column
+                .compile()
+                .with_context(|| format!("Invalid computed column \"{}\"", column.name))
+        })
+        .collect()
+}
+
+/// Names and values of the `columns` for `info`, leaving out the empty ones.
+pub fn values(columns: &[Computed], info: &Info) -> Vec<(String, String)> {
+    columns
+        .iter()
+        .filter_map(|column| Some((column.name.clone(), column.value(info)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use mdns_sd::ServiceInfo;
+
+    use super::*;
+
+    fn column(name: &str, value: &str) -> ComputedColumn {
+        ComputedColumn {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn printer() -> Info {
+        let info = ServiceInfo::new(
+            "_ipp._tcp.local.",
+            "Office Printer",
+            "Printer.local.",
+            "192.168.2.10,fe80::1",
+            631,
+            &[("fw", "2.4.1-Beta"), ("rp", "ipp/print")][..],
+        )
+        .unwrap();
+        Info::new(info, Instant::now())
+    }
+
+    #[test]
+    fn columns() {
+        let columns = compile(&[
+            column("fw", "txt.fw"),
+            column("FW", "txt.fw | upper"),
+            column("host", "hostname|lower"),
+            column("addrs", "addresses | count"),
+            column("addr", "addresses | first"),
+            column("all", "addresses"),
+            column("port", "port"),
+            column("type", "type"),
+            column("name", "name"),
+            // Left out, as there is no such key
+            column("pdl", "txt.pdl | upper"),
+        ])
+        .unwrap();
+        let values: Vec<_> = values(&columns, &printer())
+            .into_iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                "fw=2.4.1-Beta",
+                "FW=2.4.1-BETA",
+                "host=printer.local.",
+                "addrs=2",
+                "addr=192.168.2.10",
+                "all=192.168.2.10 fe80::1",
+                "port=631",
+                "type=_ipp._tcp.local.",
+                "name=Office Printer",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_values_still_count() {
+        let columns = compile(&[column("pdls", "txt.pdl | count")]).unwrap();
+        assert_eq!(
+            values(&columns, &printer()),
+            [("pdls".to_string(), "0".to_string())]
+        );
+    }
+
+    #[test]
+    fn invalid_columns() {
+        let error = |value| format!("{:#}", compile(&[column("fw", value)]).unwrap_err());
+        assert_eq!(
+            error("firmware"),
+            "Invalid computed column \"fw\": Unknown field \"firmware\""
+        );
+        assert_eq!(
+            error(""),
+            "Invalid computed column \"fw\": Unknown field \"\""
+        );
+        assert_eq!(
+            error("| upper"),
+            "Invalid computed column \"fw\": Unknown field \"\""
+        );
+        assert_eq!(
+            error("txt.fw | reverse"),
+            "Invalid computed column \"fw\": Unknown pipe \"reverse\""
+        );
+        assert_eq!(
+            error("txt.fw |"),
+            "Invalid computed column \"fw\": Unknown pipe \"\""
+        );
+    }
+}
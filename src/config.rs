@@ -0,0 +1,336 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use flume::Receiver;
+use mdns_sd::IfKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Deserialize;
+
+use crate::backend::BackendKind;
+use crate::baseline::Baseline;
+use crate::colors::Theme;
+use crate::computed::{self, ComputedColumn};
+use crate::domain;
+use crate::events::Backpressure;
+use crate::filter::Filter;
+use crate::info::AgeThresholds;
+use crate::keymap::KeyAction;
+use crate::label::{self, LabelRule};
+use crate::service::Column;
+use crate::txt::TxtAlert;
+use crate::units;
+
+/// Requerying any more often would flood the network.
+const K_MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Contents of the `config.toml` file.
+///
+/// ```toml
+/// default_profile = "home"
+/// event_queue_size = 1024
+/// backpressure = "drop-oldest"
+/// backend = "avahi"
+/// update_check = true
+/// remember_queries = false
+/// remember_ui_state = false
+/// pause_unfocused = true
+/// refresh_rate = 30
+/// max_instances = 10000
+/// stale_grace = "30s"
+/// query_templates = ["_{service}._tcp.local.", "_{service}._sub._{parent}._tcp.local."]
+/// csv_columns = ["type", "hostname", "addresses", "port", "txt.model"]
+///
+/// [profiles.office]
+/// queries = ["_ipp._tcp.local.", "_http._tcp.local."]
+/// interfaces = ["eth0"]
+/// ignore = ["^Brother"]
+/// theme = "light"
+/// baseline = "/srv/inventory/office.txt"
+/// columns = ["instances", "label"]
+///
+/// [profiles.office.refresh]
+/// "_ipp._tcp" = "5m"
+/// "_googlecast._tcp" = "30s"
+///
+/// [profiles.office.age]
+/// new = "5m"
+/// expiring = "1m"
+///
+/// [[labels]]
+/// label = "media"
+/// color = "green"
+/// match = "txt.md=Chromecast"
+///
+/// [[txt_alerts]]
+/// key = "sf"
+/// type = "_hap._tcp"
+///
+/// [[computed_columns]]
+/// name = "fw"
+/// value = "txt.fw"
+///
+/// [dns_push]
+/// "example.com." = "push.example.com"
+///
+/// [keys]
+/// pick-query = "C-o"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub default_profile: Option<String>,
+    /// Capacity of the queue between mDNS events and the UI model
+    pub event_queue_size: Option<usize>,
+    /// What to do with new mDNS events when the queue is full
+    pub backpressure: Option<Backpressure>,
+    /// mDNS implementation to discover with
+    pub backend: Option<BackendKind>,
+    /// Look for a newer release on startup, off unless enabled
+    pub update_check: Option<bool>,
+    /// Keep the recently used queries across restarts, default: true
+    pub remember_queries: Option<bool>,
+    /// Restore the selected service, the searches, the order and the zoom
+    /// of the previous run on startup, default: true
+    pub remember_ui_state: Option<bool>,
+    /// Pause the refresh queries and reverse lookups while the terminal is
+    /// unfocused, default: false
+    pub pause_unfocused: Option<bool>,
+    /// Frames drawn per second at most, default: 24
+    pub refresh_rate: Option<u8>,
+    /// Most instances kept at once, the least recently resolved are evicted beyond it
+    pub max_instances: Option<usize>,
+    /// How long instances are kept past their TTL, marked stale, before
+    /// they expire, default: `"0s"`
+    pub stale_grace: Option<String>,
+    /// Queries with `{variables}` offered by the query picker
+    pub query_templates: Vec<String>,
+    /// Rules labeling the instances they match
+    pub labels: Vec<LabelRule>,
+    /// TXT keys whose changes raise an alert
+    pub txt_alerts: Vec<TxtAlert>,
+    /// Values computed from every instance, shown next to it and exported
+    pub computed_columns: Vec<ComputedColumn>,
+    /// Columns of the CSV exports, default: type, name, hostname, addresses, port and txt
+    pub csv_columns: Vec<String>,
+    /// DNS Push servers of wide-area domains, `host` or `host:port`, by domain
+    pub dns_push: BTreeMap<String, String>,
+    /// Keys of the global actions, broken bindings fall back to the defaults
+    pub keys: BTreeMap<KeyAction, String>,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Named bundle of browsing settings.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Service types to browse, default: _services._dns-sd._udp.local.
+    pub queries: Vec<String>,
+    /// Interfaces to browse on (`all`, `ipv4`, `ipv6`, a name or an address), default: all
+    pub interfaces: Vec<String>,
+    /// Search expressions for entries that should never be shown
+    pub ignore: Vec<String>,
+    /// Built-in theme name, default: `default`
+    pub theme: Option<String>,
+    /// Where to keep the host baseline, default: ~/.local/share/discovery-rs/baselines/<profile>.txt
+    pub baseline: Option<PathBuf>,
+    /// Extra columns of the Services pane, default: none
+    pub columns: Vec<Column>,
+    /// How often to query service types again, e.g. `"_ipp._tcp" = "5m"`
+    pub refresh: BTreeMap<String, String>,
+    /// When instances stop being shown as new, and start being shown as expiring
+    pub age: Ages,
+    /// Browse and query over IPv6 only, whatever the interfaces
+    pub ipv6_only: bool,
+    /// Pace the queries as RFC 6762 has it and cap their rate, default: false
+    pub polite: bool,
+    /// Group the Apple continuity services under a single row, default: false
+    pub group_apple: bool,
+    /// Probe for WS-Discovery endpoints too, e.g. ONVIF cameras, default: false
+    pub ws_discovery: bool,
+}
+
+/// Thresholds of the instances' styling by age, durations like `"5m"`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Ages {
+    /// How long instances are shown bright once resolved, default: `"1m"`
+    pub new: Option<String>,
+    /// How long before their records run out instances are shown dimmed,
+    /// default: `"30s"`
+    pub expiring: Option<String>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/discovery-rs/config.toml` or the platform equivalent.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("discovery-rs").join("config.toml"))
+    }
+
+    /// Load the configuration from `path`, or from [`Config::default_path`].
+    ///
+    /// An explicitly given file must exist, while a missing default one simply
+    /// results in the default configuration.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match Self::default_path().filter(|path| path.exists()) {
+                Some(path) => path,
+                None => return Ok(Self::default()),
+            },
+        };
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config: Config = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        for (name, profile) in config.profiles.iter() {
+            profile
+                .validate()
+                .with_context(|| format!("Invalid profile \"{name}\""))?;
+        }
+        label::compile(&config.labels)?;
+        computed::compile(&config.computed_columns)?;
+        config
+            .stale_grace()
+            .with_context(|| format!("Invalid config {}", path.display()))?;
+        Ok(config)
+    }
+
+    /// The stale grace, if set.
+    pub fn stale_grace(&self) -> anyhow::Result<Option<Duration>> {
+        self.stale_grace
+            .as_deref()
+            .map(|grace| {
+                units::duration(grace).map_err(|e| anyhow::anyhow!("Invalid stale_grace: {e}"))
+            })
+            .transpose()
+    }
+}
+
+/// Watch `path` for changes.
+///
+/// The parent directory is watched rather than the file itself, so that
+/// editors replacing the file on save are handled too. Returns the watcher,
+/// which must be kept alive, and a channel signalling every change.
+pub fn watch(path: &Path) -> anyhow::Result<(RecommendedWatcher, Receiver<()>)> {
+    let (tx, rx) = flume::unbounded();
+    let file_name = path.file_name().map(|name| name.to_os_string());
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        if let Ok(event) = event {
+            let modified = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_));
+            if modified
+                && event
+                    .paths
+                    .iter()
+                    .any(|changed| changed.file_name() == file_name.as_deref())
+            {
+                let _ = tx.send(());
+            }
+        }
+    })?;
+    let directory = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    watcher.watch(directory, RecursiveMode::NonRecursive)?;
+    Ok((watcher, rx))
+}
+
+impl Profile {
+    /// Check the settings that are parsed when the profile is applied.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.ignore_filter()?;
+        self.theme()?;
+        self.refresh_intervals()?;
+        self.age_thresholds()?;
+        Ok(())
+    }
+
+    /// The age thresholds, the defaults where not set.
+    pub fn age_thresholds(&self) -> anyhow::Result<AgeThresholds> {
+        let threshold = |name: &str, value: Option<&str>, default: Duration| {
+            value.map_or(Ok(default), |value| {
+                units::duration(value).map_err(|e| anyhow::anyhow!("Invalid age.{name}: {e}"))
+            })
+        };
+        let defaults = AgeThresholds::default();
+        Ok(AgeThresholds {
+            new: threshold("new", self.age.new.as_deref(), defaults.new)?,
+            expiring: threshold("expiring", self.age.expiring.as_deref(), defaults.expiring)?,
+        })
+    }
+
+    /// Fully qualified service types to query again, and how often.
+    pub fn refresh_intervals(&self) -> anyhow::Result<Vec<(String, Duration)>> {
+        self.refresh
+            .iter()
+            .map(|(service_type, interval)| {
+                let interval = units::duration(interval).map_err(|e| {
+                    anyhow::anyhow!("Invalid refresh interval of {service_type}: {e}")
+                })?;
+                if interval < K_MIN_REFRESH_INTERVAL {
+                    anyhow::bail!(
+                        "The refresh interval of {service_type} must be at least {K_MIN_REFRESH_INTERVAL:?}"
+                    );
+                }
+                Ok((domain::qualify(service_type), interval))
+            })
+            .collect()
+    }
+
+    pub fn baseline_path(&self, name: &str) -> Option<PathBuf> {
+        self.baseline
+            .clone()
+            .or_else(|| Baseline::default_path(name))
+    }
+
+    pub fn if_kinds(&self) -> Vec<IfKind> {
+        if self.interfaces.is_empty() {
+            vec![IfKind::All]
+        } else {
+            self.interfaces
+                .iter()
+                .map(|interface| parse_interface(interface))
+                .collect()
+        }
+    }
+
+    /// All the ignore expressions combined into a single [`Filter`].
+    pub fn ignore_filter(&self) -> anyhow::Result<Option<Filter>> {
+        let mut filters = self
+            .ignore
+            .iter()
+            .map(|ignore| Filter::parse(ignore))
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter();
+        Ok(filters.next().map(|first| {
+            filters.fold(first, |acc, filter| {
+                Filter::Or(Box::new(acc), Box::new(filter))
+            })
+        }))
+    }
+
+    pub fn theme(&self) -> anyhow::Result<Theme> {
+        match self.theme.as_deref() {
+            Some(name) => {
+                Theme::named(name).ok_or_else(|| anyhow::anyhow!("Unknown theme \"{name}\""))
+            }
+            None => Ok(Theme::DEFAULT),
+        }
+    }
+}
+
+/// Parse an interface specification into an [`IfKind`].
+pub fn parse_interface(interface: &str) -> IfKind {
+    match interface.to_lowercase().as_str() {
+        "all" => IfKind::All,
+        "ipv4" => IfKind::IPv4,
+        "ipv6" => IfKind::IPv6,
+        _ => match interface.parse::<IpAddr>() {
+            Ok(addr) => IfKind::Addr(addr),
+            Err(_) => IfKind::Name(interface.to_string()),
+        },
+    }
+}
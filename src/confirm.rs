@@ -0,0 +1,118 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::colors::theme;
+use crate::widget::DiscoveryWidget;
+
+const K_DIALOG_WIDTH: u16 = 56;
+
+/// Modal Yes/No question guarding a destructive `action`.
+///
+/// While open the dialog takes all the keyboard input. Once answered, the
+/// owner takes the [`Confirm::answer`] and runs the action if it was accepted.
+/// Defaults to "No", so that a stray ↵ never destroys anything.
+#[derive(Debug)]
+pub struct Confirm<A> {
+    message: String,
+    action: A,
+    yes: bool,
+    answer: Option<bool>,
+}
+
+impl<A: std::fmt::Debug> Confirm<A> {
+    pub fn new(message: impl Into<String>, action: A) -> Self {
+        Self {
+            message: message.into(),
+            action,
+            yes: false,
+            answer: None,
+        }
+    }
+
+    /// The action to run, once the user has accepted it.
+    ///
+    /// Returns `Err(self)` while the question is still open.
+    pub fn answer(self) -> Result<Option<A>, Self> {
+        match self.answer {
+            Some(true) => Ok(Some(self.action)),
+            Some(false) => Ok(None),
+            None => Err(self),
+        }
+    }
+}
+
+impl<A: std::fmt::Debug> DiscoveryWidget for Confirm<A> {
+    fn title(&self) -> String {
+        "Are you sure?".to_string()
+    }
+
+    fn controls(&self) -> String {
+        "Use ←→ to choose, ↵ to confirm, y/n to answer directly, Esc to cancel".to_string()
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        match key_event.code {
+            KeyCode::Left | KeyCode::Right | KeyCode::Tab | KeyCode::BackTab => {
+                self.yes = !self.yes
+            }
+            KeyCode::Enter => self.answer = Some(self.yes),
+            KeyCode::Char('y') | KeyCode::Char('Y') => self.answer = Some(true),
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => self.answer = Some(false),
+            _ => {}
+        }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool) {
+        let width = K_DIALOG_WIDTH.min(area.width);
+        let lines = textwrap::wrap(&self.message, (width as usize).saturating_sub(4));
+        let height = (lines.len() as u16 + 5).min(area.height);
+        let dialog_area = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(theme().warning).bold())
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .padding(Padding::horizontal(1))
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        let inner_area = block.inner(dialog_area);
+        Clear.render(dialog_area, buf);
+        block.render(dialog_area, buf);
+
+        let [message_area, buttons_area] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(2)]).areas(inner_area);
+        Paragraph::new(
+            lines
+                .into_iter()
+                .map(|line| Line::from(line.into_owned()))
+                .collect::<Vec<_>>(),
+        )
+        .render(message_area, buf);
+
+        let button = |label: &'static str, focused: bool| {
+            let style = if focused {
+                Style::new().bold().reversed().fg(theme().selected_fg)
+            } else {
+                Style::new()
+            };
+            Span::styled(format!(" {label} "), style)
+        };
+        Paragraph::new(vec![
+            Line::default(),
+            Line::from(vec![
+                button("Yes", self.yes),
+                Span::raw("   "),
+                button("No", !self.yes),
+            ]),
+        ])
+        .centered()
+        .render(buttons_area, buf);
+    }
+}
@@ -0,0 +1,229 @@
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+
+use if_addrs::{IfAddr, Interface};
+
+use crate::error::Error;
+
+const K_MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+/// `IFF_MULTICAST` in the interface flags
+const K_IFF_MULTICAST: u32 = 0x1000;
+
+/// Container runtime the process runs in, if any.
+fn runtime() -> Option<&'static str> {
+    if std::path::Path::new("/.dockerenv").exists() {
+        return Some("Docker");
+    }
+    if std::path::Path::new("/run/.containerenv").exists() {
+        return Some("Podman");
+    }
+    if std::env::var_os("KUBERNETES_SERVICE_HOST").is_some() {
+        return Some("Kubernetes");
+    }
+    let cgroup = std::fs::read_to_string("/proc/1/cgroup").unwrap_or_default();
+    [
+        ("kubepods", "Kubernetes"),
+        ("docker", "Docker"),
+        ("libpod", "Podman"),
+        ("containerd", "containerd"),
+        ("lxc", "LXC"),
+    ]
+    .iter()
+    .find(|(marker, _)| cgroup.contains(marker))
+    .map(|(_, runtime)| *runtime)
+}
+
+/// Whether `interface` can do multicast, `None` when unknown, e.g. off Linux.
+fn multicast(interface: &Interface) -> Option<bool> {
+    let flags = std::fs::read_to_string(format!("/sys/class/net/{}/flags", interface.name)).ok()?;
+    let flags = u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16).ok()?;
+    Some(flags & K_IFF_MULTICAST != 0)
+}
+
+/// Docker and Podman bridges, e.g. `docker0` or `br-3f1a2b4c5d6e`, routed to
+/// in `table`, the contents of `/proc/net/route`.
+///
+/// Containers only see them with host networking, as they live on the host.
+fn host_bridges(table: &str) -> Vec<String> {
+    let mut bridges: Vec<String> = Vec::new();
+    // After the header, e.g. `docker0	000011AC	00000000	0001	...`
+    for line in table.lines().skip(1) {
+        let mut fields = line.split_whitespace();
+        let (Some(interface), Some(destination)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        if u32::from_str_radix(destination, 16).is_err() {
+            continue;
+        }
+        let bridge = interface == "docker0"
+            || ["br-", "podman", "cni-podman"]
+                .iter()
+                .any(|prefix| interface.starts_with(prefix));
+        if bridge && !bridges.iter().any(|known| known == interface) {
+            bridges.push(interface.to_string());
+        }
+    }
+    bridges
+}
+
+/// Whether `addr` is on a default Docker or Podman bridge network.
+fn bridged(addr: &IpAddr) -> bool {
+    match addr {
+        // 172.16.0.0/12, and Podman's 10.88.0.0/16
+        IpAddr::V4(v4) => {
+            let [a, b, _, _] = v4.octets();
+            (a == 172 && (16..32).contains(&b)) || (a == 10 && b == 88)
+        }
+        IpAddr::V6(_) => false,
+    }
+}
+
+/// Check whether mDNS can work from here, and tell how to fix it otherwise.
+///
+/// Meant for containers, where browsing usually fails silently: without host
+/// networking multicast never leaves the container's own network. Prints
+/// every finding and fails with [`Error::Daemon`] when any is a problem.
+pub fn diagnose() -> Result<(), Error> {
+    let mut problems = Vec::new();
+    let runtime = runtime();
+    match runtime {
+        Some(runtime) => println!("Running in a container: {runtime}"),
+        None => println!("Not running in a container, as far as can be told"),
+    }
+
+    let interfaces: Vec<_> = if_addrs::get_if_addrs()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|interface| !interface.is_loopback())
+        .collect();
+    if interfaces.is_empty() {
+        problems.push(
+            "No network interface besides loopback, the container may have no network at all"
+                .to_string(),
+        );
+    }
+    for interface in &interfaces {
+        let name = if interface.name.is_empty() {
+            "(unnamed)"
+        } else {
+            &interface.name
+        };
+        let multicast = match multicast(interface) {
+            Some(true) => "multicast",
+            Some(false) => {
+                problems.push(format!("{name} doesn't support multicast"));
+                "no multicast"
+            }
+            None => "multicast unknown",
+        };
+        println!("Interface {name}: {} ({multicast})", interface.ip());
+    }
+
+    for interface in &interfaces {
+        let IfAddr::V4(v4) = &interface.addr else {
+            continue;
+        };
+        let joined = UdpSocket::bind("0.0.0.0:0")
+            .and_then(|socket| socket.join_multicast_v4(&K_MDNS_GROUP, &v4.ip));
+        if let Err(e) = joined {
+            problems.push(format!(
+                "Failed to join the mDNS group on {}: {e}",
+                interface.ip()
+            ));
+        }
+    }
+
+    let bridges = std::fs::read_to_string("/proc/net/route")
+        .map(|table| host_bridges(&table))
+        .unwrap_or_default();
+    if runtime.is_some() && !bridges.is_empty() {
+        println!(
+            "Host networking, as the host's bridges are in sight: {}",
+            bridges.join(", ")
+        );
+    }
+    let bridge = runtime.is_some()
+        && bridges.is_empty()
+        && interfaces.iter().any(|interface| interface.ip().is_ipv4())
+        && interfaces
+            .iter()
+            .all(|interface| interface.ip().is_ipv6() || bridged(&interface.ip()));
+    if bridge {
+        problems.push(
+            "The only networks look like a container bridge, which mDNS multicast doesn't cross"
+                .to_string(),
+        );
+    }
+
+    if problems.is_empty() {
+        println!("\nNo problems found");
+        return Ok(());
+    }
+    println!("\nProblems:");
+    for problem in &problems {
+        println!("  • {problem}");
+    }
+    let example = interfaces
+        .first()
+        .map_or("192.168.1.10".to_string(), |interface| {
+            interface.ip().to_string()
+        });
+    println!("\nTo fix them:");
+    println!("  • Docker, Podman: run with --network host");
+    println!("  • Kubernetes: set hostNetwork: true in the pod spec");
+    println!("  • Docker Compose: set network_mode: host on the service");
+    println!("  • When interface names are unknown or unstable, pick the interface by address,");
+    println!("    e.g. --interface {example}");
+    Err(Error::Daemon(anyhow::anyhow!(
+        "{} problem(s) would keep mDNS from working",
+        problems.len()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `/proc/net/route` of a Docker host, as seen with host networking.
+    const K_HOST_ROUTES: &str = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eno1\t00000000\t0101A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0
+docker0\t000011AC\t00000000\t0001\t0\t0\t0\t0000FFFF\t0\t0\t0
+br-3f1a2b4c5d6e\t000012AC\t00000000\t0001\t0\t0\t0\t0000FFFF\t0\t0\t0
+br-3f1a2b4c5d6e\t000112AC\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0
+eno1\t0001A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0
+";
+
+    /// `/proc/net/route` of a container on the default bridge network.
+    const K_BRIDGED_ROUTES: &str = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eth0\t00000000\t010011AC\t0003\t0\t0\t0\t00000000\t0\t0\t0
+eth0\t000011AC\t00000000\t0001\t0\t0\t0\t0000FFFF\t0\t0\t0
+";
+
+    #[test]
+    fn host_networking() {
+        assert_eq!(host_bridges(K_HOST_ROUTES), ["docker0", "br-3f1a2b4c5d6e"]);
+    }
+
+    #[test]
+    fn bridge_networking() {
+        assert!(host_bridges(K_BRIDGED_ROUTES).is_empty());
+        // Only the header, without any route
+        assert!(host_bridges(K_BRIDGED_ROUTES.lines().next().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn garbled_route_tables() {
+        assert!(host_bridges("").is_empty());
+        assert!(host_bridges("docker0\t000011AC\t00000000").is_empty());
+        assert!(host_bridges("Iface\tDestination\ndocker0\tgarbage\nbr-\n").is_empty());
+    }
+
+    #[test]
+    fn bridge_addresses() {
+        assert!(bridged(&IpAddr::V4(Ipv4Addr::new(172, 17, 0, 2))));
+        assert!(bridged(&IpAddr::V4(Ipv4Addr::new(10, 88, 0, 5))));
+        assert!(!bridged(&IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))));
+        assert!(!bridged(&IpAddr::V4(Ipv4Addr::new(172, 32, 0, 1))));
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::colors::theme;
+use crate::info::Info;
+use crate::list::{ListEntry, ListWidget};
+use crate::widget::DiscoveryWidget;
+
+/// Service types Apple devices keep in touch with each other over, for
+/// Handoff, AirDrop, device pairing, Bonjour Sleep Proxy and the like,
+/// rather than for anyone to connect to.
+const K_CONTINUITY_TYPES: [&str; 8] = [
+    "_companion-link._tcp.",
+    "_rdlink._tcp.",
+    "_sleep-proxy._udp.",
+    "_remotepairing._tcp.",
+    "_apple-mobdev2._tcp.",
+    "_apple-pairable._tcp.",
+    "_airdrop._tcp.",
+    "_asquic._udp.",
+];
+
+/// The Services pane row the continuity types are grouped under.
+pub const K_GROUP_TYPE: &str = "Apple continuity";
+
+/// Whether `service_type` is one of the continuity types, in any domain.
+pub fn is_continuity(service_type: &str) -> bool {
+    K_CONTINUITY_TYPES
+        .iter()
+        .any(|continuity| service_type.starts_with(continuity))
+}
+
+/// `service_type` without its protocol and domain, e.g. `companion-link`
+/// for `_companion-link._tcp.local.`
+fn short_type(service_type: &str) -> &str {
+    service_type
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches('_')
+}
+
+/// The continuity instances of a host.
+#[derive(Debug)]
+struct Group {
+    hostname: String,
+    /// Shown in place of the hostname, as the scripts named it
+    name: Option<String>,
+    device_model: Option<String>,
+    /// Service type and instance name of every instance
+    instances: Vec<(String, String)>,
+}
+
+/// The continuity instances among `instances`, grouped by host.
+fn groups(instances: &HashMap<String, ListWidget<Info>>) -> Vec<Group> {
+    let mut groups: BTreeMap<String, Group> = BTreeMap::new();
+    for (service_type, list) in instances
+        .iter()
+        .filter(|(service_type, _)| is_continuity(service_type))
+    {
+        for info in list.items() {
+            let group = groups.entry(info.id()).or_insert_with(|| Group {
+                hostname: info.id(),
+                name: None,
+                device_model: None,
+                instances: Vec::new(),
+            });
+            group.name = group.name.take().or_else(|| info.name.clone());
+            group.device_model = group
+                .device_model
+                .take()
+                .or_else(|| info.device_model.clone());
+            let fullname = info.info.get_fullname();
+            let name = fullname
+                .strip_suffix(info.info.get_type())
+                .map_or(fullname, |name| name.trim_end_matches('.'));
+            group
+                .instances
+                .push((service_type.clone(), name.to_string()));
+        }
+    }
+    let mut groups: Vec<_> = groups.into_values().collect();
+    for group in groups.iter_mut() {
+        group.instances.sort();
+    }
+    groups
+}
+
+/// Number of hosts advertising any of the continuity types.
+pub fn hosts(instances: &HashMap<String, ListWidget<Info>>) -> usize {
+    groups(instances).len()
+}
+
+/// The Instances pane of the [`K_GROUP_TYPE`] row: a row per host with the
+/// continuity services it advertises, expanded into its instances on demand.
+#[derive(Debug, Default)]
+pub struct Continuity {
+    groups: Vec<Group>,
+    /// Hostnames of the expanded hosts
+    expanded: BTreeSet<String>,
+    /// Hostname of the selected row, and the index of the instance when on
+    /// one of an expanded host
+    selected: Option<(String, Option<usize>)>,
+}
+
+impl Continuity {
+    /// Group the continuity instances among `instances` again, keeping the
+    /// selection and the expanded hosts.
+    pub fn refresh(&mut self, instances: &HashMap<String, ListWidget<Info>>) {
+        self.groups = groups(instances);
+        let groups = &self.groups;
+        self.expanded
+            .retain(|hostname| groups.iter().any(|group| group.hostname == *hostname));
+    }
+
+    /// The rows shown, by index of the group and of its instance.
+    fn rows(&self) -> Vec<(usize, Option<usize>)> {
+        let mut rows = Vec::new();
+        for (index, group) in self.groups.iter().enumerate() {
+            rows.push((index, None));
+            if self.expanded.contains(&group.hostname) {
+                rows.extend((0..group.instances.len()).map(|instance| (index, Some(instance))));
+            }
+        }
+        rows
+    }
+
+    /// Index of the selected row among `rows`, the first row by default.
+    fn position(&self, rows: &[(usize, Option<usize>)]) -> Option<usize> {
+        if rows.is_empty() {
+            return None;
+        }
+        let position = self.selected.as_ref().and_then(|(hostname, instance)| {
+            rows.iter().position(|(group, row_instance)| {
+                self.groups[*group].hostname == *hostname && row_instance == instance
+            })
+        });
+        Some(position.unwrap_or(0))
+    }
+
+    fn select(&mut self, rows: &[(usize, Option<usize>)], position: usize) {
+        self.selected = rows
+            .get(position)
+            .map(|(group, instance)| (self.groups[*group].hostname.clone(), *instance));
+    }
+
+    fn select_delta(&mut self, delta: isize) {
+        let rows = self.rows();
+        if let Some(position) = self.position(&rows) {
+            let position = (position as isize + delta).rem_euclid(rows.len() as isize);
+            self.select(&rows, position as usize);
+        }
+    }
+
+    /// Expand the selected host, or collapse it, from any of its rows.
+    fn toggle(&mut self) {
+        let rows = self.rows();
+        let Some((group, _)) = self.position(&rows).map(|position| rows[position]) else {
+            return;
+        };
+        let hostname = self.groups[group].hostname.clone();
+        if !self.expanded.remove(&hostname) {
+            self.expanded.insert(hostname.clone());
+        }
+        self.selected = Some((hostname, None));
+    }
+
+    fn row(&self, group: &Group, instance: Option<usize>) -> Line<'static> {
+        match instance {
+            None => {
+                let marker = if self.expanded.contains(&group.hostname) {
+                    "▾ "
+                } else {
+                    "▸ "
+                };
+                let mut types: Vec<_> = group
+                    .instances
+                    .iter()
+                    .map(|(service_type, _)| short_type(service_type))
+                    .collect();
+                types.dedup();
+                let mut spans = vec![
+                    Span::raw(marker).dark_gray(),
+                    Span::styled(
+                        group.name.clone().unwrap_or_else(|| group.hostname.clone()),
+                        theme().text,
+                    ),
+                ];
+                if let Some(model) = group.device_model.as_ref() {
+                    spans.push(format!(" ({model})").dark_gray());
+                }
+                spans.push(format!("  {}", types.join(", ")).light_cyan());
+                Line::from(spans)
+            }
+            Some(instance) => {
+                let (service_type, name) = &group.instances[instance];
+                Line::from(vec![
+                    Span::raw(format!("    {:<16} ", short_type(service_type))).dark_gray(),
+                    Span::styled(name.clone(), theme().text),
+                ])
+            }
+        }
+    }
+}
+
+impl DiscoveryWidget for Continuity {
+    fn title(&self) -> String {
+        format!("{K_GROUP_TYPE} ({} hosts)", self.groups.len())
+    }
+
+    fn controls(&self) -> String {
+        "Use ↓↑ to select next/prev, g/G to go top/bottom, ↵ to expand/collapse a host".to_string()
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        match key_event.code {
+            KeyCode::Down => self.select_delta(1),
+            KeyCode::Up => self.select_delta(-1),
+            KeyCode::Char('g') => {
+                let rows = self.rows();
+                self.select(&rows, 0);
+            }
+            KeyCode::Char('G') => {
+                let rows = self.rows();
+                self.select(&rows, rows.len().saturating_sub(1));
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => self.toggle(),
+            _ => {}
+        }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, selected: bool) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(if selected {
+                Style::new().fg(theme().selected_fg)
+            } else {
+                Style::default()
+            })
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        let inner_area = block.inner(area);
+        block.render(area, buf);
+
+        let rows = self.rows();
+        if rows.is_empty() {
+            Paragraph::new("No instance of the grouped services resolved yet")
+                .dark_gray()
+                .block(Block::new().padding(Padding::uniform(1)))
+                .wrap(Wrap { trim: true })
+                .render(inner_area, buf);
+            return;
+        }
+        let items: Vec<_> = rows
+            .iter()
+            .enumerate()
+            .map(|(index, (group, instance))| {
+                ListItem::new(self.row(&self.groups[*group], *instance)).bg(if index % 2 == 0 {
+                    theme().normal_row
+                } else {
+                    theme().alt_row
+                })
+            })
+            .collect();
+        let mut state = ListState::default().with_selected(self.position(&rows));
+        StatefulWidget::render(
+            List::new(items)
+                .block(Block::new().bg(theme().normal_row))
+                .highlight_style(
+                    Style::default()
+                        .add_modifier(Modifier::BOLD)
+                        .add_modifier(Modifier::REVERSED)
+                        .fg(theme().selected_fg),
+                )
+                .highlight_symbol(">")
+                .highlight_spacing(HighlightSpacing::Always),
+            inner_area,
+            buf,
+            &mut state,
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use flume::Receiver;
+
+/// A command read from the control pipe, one per line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlCommand {
+    /// `filter <expression>`, search the focused pane, an empty expression
+    /// clears the search
+    Filter(String),
+    /// `query <type>`, restart browsing with a single query
+    Query(String),
+    /// `export <path>`, write a snapshot of everything resolved, in the
+    /// format of `--output` or of the path's extension
+    Export(PathBuf),
+    /// `quit`
+    Quit,
+}
+
+impl ControlCommand {
+    /// Parse a single line, `Ok(None)` for blank lines and `#` comments.
+    pub fn parse(line: &str) -> anyhow::Result<Option<Self>> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+        let (command, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let argument = argument.trim();
+        Ok(Some(match command {
+            "filter" => ControlCommand::Filter(argument.to_string()),
+            "query" if !argument.is_empty() => ControlCommand::Query(argument.to_string()),
+            "export" if !argument.is_empty() => ControlCommand::Export(PathBuf::from(argument)),
+            "query" | "export" => anyhow::bail!("\"{command}\" needs an argument"),
+            "quit" => ControlCommand::Quit,
+            _ => anyhow::bail!("Unknown command \"{command}\""),
+        }))
+    }
+}
+
+/// Read commands from `path` on a background thread.
+///
+/// `-` reads the standard input until it's closed. Anything else is expected
+/// to be a named pipe, created with `mkfifo`, which is reopened whenever its
+/// writer goes away so that commands can be sent one `echo` at a time.
+pub fn listen(path: &Path) -> anyhow::Result<Receiver<anyhow::Result<ControlCommand>>> {
+    let (tx, rx) = flume::unbounded();
+    if path == Path::new("-") {
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let command = line
+                    .context("Failed to read a command")
+                    .and_then(|line| ControlCommand::parse(&line));
+                if let Some(command) = command.transpose() {
+                    if tx.send(command).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        return Ok(rx);
+    }
+
+    if !path.exists() {
+        anyhow::bail!("Control pipe {} doesn't exist", path.display());
+    }
+    let path = path.to_path_buf();
+    std::thread::spawn(move || loop {
+        // Blocks until there's a writer
+        let pipe = match File::open(&path) {
+            Ok(pipe) => pipe,
+            Err(e) => {
+                let _ = tx.send(Err(anyhow::anyhow!(
+                    "Failed to open {}: {e}",
+                    path.display()
+                )));
+                return;
+            }
+        };
+        for line in BufReader::new(pipe).lines() {
+            let command = line
+                .context("Failed to read a command")
+                .and_then(|line| ControlCommand::parse(&line));
+            if let Some(command) = command.transpose() {
+                if tx.send(command).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Option<ControlCommand> {
+        ControlCommand::parse(line).unwrap()
+    }
+
+    fn error(line: &str) -> String {
+        ControlCommand::parse(line).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(
+            parse("filter port:631 && txt:rp=ipp/print"),
+            Some(ControlCommand::Filter(
+                "port:631 && txt:rp=ipp/print".to_string()
+            ))
+        );
+        assert_eq!(
+            parse("  query   _ipp._tcp  "),
+            Some(ControlCommand::Query("_ipp._tcp".to_string()))
+        );
+        assert_eq!(
+            parse("export\t/tmp/lan snapshot.json"),
+            Some(ControlCommand::Export(PathBuf::from(
+                "/tmp/lan snapshot.json"
+            )))
+        );
+        assert_eq!(parse("quit"), Some(ControlCommand::Quit));
+    }
+
+    #[test]
+    fn an_empty_filter_clears_the_search() {
+        assert_eq!(parse("filter"), Some(ControlCommand::Filter(String::new())));
+        assert_eq!(
+            parse("filter   "),
+            Some(ControlCommand::Filter(String::new()))
+        );
+    }
+
+    #[test]
+    fn blank_lines_and_comments_are_skipped() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("   \t"), None);
+        assert_eq!(parse("# query _ssh._tcp"), None);
+        assert_eq!(parse("  # indented"), None);
+    }
+
+    #[test]
+    fn rejected_lines() {
+        assert_eq!(error("query"), "\"query\" needs an argument");
+        assert_eq!(error("export  "), "\"export\" needs an argument");
+        assert_eq!(error("Quit"), "Unknown command \"Quit\"");
+        assert_eq!(error("reload now"), "Unknown command \"reload\"");
+    }
+}
@@ -0,0 +1,225 @@
+use std::path::PathBuf;
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::colors::theme;
+use crate::widget::DiscoveryWidget;
+
+/// `path` with a leading `~` standing for the home directory.
+fn expand(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => dirs::home_dir()
+            .map(|home| home.join(rest.trim_start_matches('/')))
+            .unwrap_or_else(|| PathBuf::from(path)),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Longest prefix `a` and `b` share.
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let len = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(a.len().min(b.len()), |((i, _), _)| i);
+    &a[..len]
+}
+
+/// Complete the last component of the path `input` from the entries of its
+/// directory.
+///
+/// Returns the input completed as far as the candidates agree, and the
+/// candidates, directories ending with a `/`. Hidden entries are only
+/// offered when asked for with a leading dot.
+fn complete(input: &str) -> (String, Vec<String>) {
+    let (dir, prefix) = match input.rfind('/') {
+        Some(slash) => input.split_at(slash + 1),
+        None => ("", input),
+    };
+    let listed = if dir.is_empty() {
+        PathBuf::from(".")
+    } else {
+        expand(dir)
+    };
+    let mut candidates: Vec<String> = std::fs::read_dir(listed)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            Some(if entry.path().is_dir() {
+                name + "/"
+            } else {
+                name
+            })
+        })
+        .collect();
+    candidates.sort();
+    let completed = match candidates.split_first() {
+        Some((first, rest)) => rest.iter().fold(first.as_str(), |common, candidate| {
+            common_prefix(common, candidate)
+        }),
+        None => prefix,
+    };
+    (format!("{dir}{completed}"), candidates)
+}
+
+/// Modal prompt for the file to export to.
+///
+/// Tab completes the path, and the recent destinations can be picked with
+/// ↓↑. Like [`crate::query::QueryPicker`], the owner takes the
+/// [`ExportPicker::answer`] once the picker is done.
+#[derive(Debug)]
+pub struct ExportPicker {
+    input: String,
+    recent: Vec<String>,
+    /// Index of the recent destination in the input, if picked
+    selected: Option<usize>,
+    /// Completions of the input, when Tab left a choice
+    candidates: Vec<String>,
+    /// How the format is chosen, e.g. by the extension
+    format: String,
+    /// Whether to export, once the picker is done
+    answer: Option<bool>,
+}
+
+impl ExportPicker {
+    pub fn new<'a>(recent: impl IntoIterator<Item = &'a String>, format: String) -> Self {
+        Self {
+            input: String::new(),
+            recent: recent.into_iter().cloned().collect(),
+            selected: None,
+            candidates: Vec::new(),
+            format,
+            answer: None,
+        }
+    }
+
+    /// The path picked, as typed, e.g. to remember it, and expanded.
+    ///
+    /// Returns `Err(self)` while still picking.
+    pub fn answer(self) -> Result<Option<(String, PathBuf)>, Self> {
+        match self.answer {
+            Some(true) => {
+                let path = expand(self.input.trim());
+                Ok(Some((self.input, path)))
+            }
+            Some(false) => Ok(None),
+            None => Err(self),
+        }
+    }
+
+    fn select(&mut self, delta: isize) {
+        if self.recent.is_empty() {
+            return;
+        }
+        let len = self.recent.len() as isize;
+        let selected = match self.selected {
+            Some(selected) => (selected as isize + delta).rem_euclid(len),
+            None if delta > 0 => 0,
+            None => len - 1,
+        } as usize;
+        self.selected = Some(selected);
+        self.input.clone_from(&self.recent[selected]);
+        self.candidates.clear();
+    }
+}
+
+impl DiscoveryWidget for ExportPicker {
+    fn title(&self) -> String {
+        "Export to".to_string()
+    }
+
+    fn controls(&self) -> String {
+        "Type the path, Tab to complete, ↓↑ for the recent ones, ↵ to export, Esc to cancel"
+            .to_string()
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        match key_event.code {
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                self.selected = None;
+                self.candidates.clear();
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.selected = None;
+                self.candidates.clear();
+            }
+            KeyCode::Tab => {
+                let (completed, candidates) = complete(&self.input);
+                self.input = completed;
+                // A single candidate is in the input already
+                self.candidates = if candidates.len() > 1 {
+                    candidates
+                } else {
+                    Vec::new()
+                };
+            }
+            KeyCode::Down => self.select(1),
+            KeyCode::Up => self.select(-1),
+            KeyCode::Enter if !self.input.trim().is_empty() => self.answer = Some(true),
+            KeyCode::Esc => self.answer = Some(false),
+            _ => {}
+        }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(theme().selected_fg).bold())
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .padding(Padding::horizontal(1))
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        let inner_area = block.inner(area);
+        block.render(area, buf);
+        let [input_area, list_area] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).areas(inner_area);
+
+        Paragraph::new(vec![
+            Line::from(vec![
+                Span::styled("Path: ", Style::new().bold().light_cyan()),
+                Span::raw(format!("{}▏", self.input)).fg(theme().selected_fg),
+            ]),
+            Line::from(self.format.clone()).dark_gray(),
+        ])
+        .render(input_area, buf);
+
+        let (title, entries) = if self.candidates.is_empty() {
+            ("Recent", &self.recent)
+        } else {
+            ("Completions", &self.candidates)
+        };
+        let items: Vec<_> = entries
+            .iter()
+            .map(|entry| ListItem::new(entry.clone()))
+            .collect();
+        let mut state = ListState::default().with_selected(if self.candidates.is_empty() {
+            self.selected
+        } else {
+            None
+        });
+        StatefulWidget::render(
+            List::new(items)
+                .block(
+                    Block::new()
+                        .title(title)
+                        .title_style(Style::new().bold().light_cyan()),
+                )
+                .highlight_style(Style::new().bold().reversed().fg(theme().selected_fg))
+                .highlight_symbol(">")
+                .highlight_spacing(HighlightSpacing::Always),
+            list_area,
+            buf,
+            &mut state,
+        );
+    }
+}
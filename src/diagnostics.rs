@@ -0,0 +1,72 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::colors::theme;
+use crate::widget::DiscoveryWidget;
+
+/// Problems found in the settings, shown once on startup or reload.
+///
+/// Everything listed has already been worked around, the screen only
+/// tells what the UI does differently from what was configured.
+#[derive(Debug)]
+pub struct Diagnostics {
+    title: String,
+    problems: Vec<String>,
+    scroll: usize,
+}
+
+impl Diagnostics {
+    /// `None` when there is nothing to report.
+    pub fn new(title: impl Into<String>, problems: Vec<String>) -> Option<Self> {
+        if problems.is_empty() {
+            return None;
+        }
+        Some(Self {
+            title: title.into(),
+            problems,
+            scroll: 0,
+        })
+    }
+}
+
+impl DiscoveryWidget for Diagnostics {
+    fn title(&self) -> String {
+        self.title.clone()
+    }
+
+    fn controls(&self) -> String {
+        "Use ↓↑ to scroll, Esc to close".to_string()
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        match key_event.code {
+            KeyCode::Down => {
+                self.scroll = (self.scroll + 1).min(self.problems.len().saturating_sub(1))
+            }
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(theme().warning).bold())
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .padding(Padding::horizontal(1))
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        Paragraph::new(
+            self.problems
+                .iter()
+                .skip(self.scroll)
+                .map(|problem| Line::from(format!("• {problem}")))
+                .collect::<Vec<_>>(),
+        )
+        .wrap(Wrap { trim: true })
+        .block(block)
+        .render(area, buf);
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::Context;
+use clap_derive::Args;
+use flume::RecvTimeoutError;
+use serde::Serialize;
+
+use crate::backend::BackendKind;
+use crate::browse::Headless;
+use crate::config::Profile;
+use crate::error::Error;
+use crate::eventlog::{Event, EventKind};
+use crate::events::QueueSettings;
+use crate::units;
+
+#[cfg(feature = "webhook")]
+use webhook::post;
+
+/// Events waiting to be tallied are dropped beyond this many.
+const K_BACKLOG: usize = 10_000;
+/// What is found this early is where the first digest starts from, rather
+/// than all of it being new.
+const K_SETTLE: Duration = Duration::from_secs(10);
+
+#[derive(Args, Debug)]
+pub struct DigestOpts {
+    #[arg(long, value_parser = units::nonzero_duration, default_value = "1d")]
+    /// How often a digest is sent, e.g. 1h or 1d
+    every: Duration,
+    #[arg(long)]
+    /// URL to POST every digest to as JSON, e.g. a Slack or Mattermost incoming webhook
+    webhook: Option<String>,
+    #[arg(long)]
+    /// Address to mail every digest to, with --sendmail
+    mail_to: Option<String>,
+    #[arg(long, default_value = "sendmail -t")]
+    /// Command mailing the digest, which it reads on its standard input
+    sendmail: String,
+    #[arg(long, default_value_t = 2)]
+    /// Times an instance must come back after going away to count as flapping
+    flaps: u32,
+}
+
+/// An instance in a digest.
+#[derive(Debug, Clone, Serialize)]
+struct Instance {
+    service_type: String,
+    name: String,
+    /// Times it came back after going away, for the flapping ones
+    #[serde(skip_serializing_if = "Option::is_none")]
+    flaps: Option<u32>,
+}
+
+/// What changed between two digests, as POSTed to the webhook.
+#[derive(Debug, Serialize)]
+struct Digest {
+    since: String,
+    until: String,
+    new: Vec<Instance>,
+    removed: Vec<Instance>,
+    flapping: Vec<Instance>,
+    /// The digest as text, which chat webhooks show
+    text: String,
+}
+
+impl Digest {
+    fn subject(&self) -> String {
+        format!(
+            "mDNS digest: {} new, {} removed, {} flapping",
+            self.new.len(),
+            self.removed.len(),
+            self.flapping.len()
+        )
+    }
+}
+
+/// Tally of the instances since the previous digest.
+#[derive(Debug, Default)]
+struct Tally {
+    /// Resolved and not removed since, by fullname, with their type
+    present: BTreeMap<String, String>,
+    /// Present at the previous digest
+    previous: BTreeMap<String, String>,
+    /// Removed since the previous digest
+    removed: BTreeSet<String>,
+    /// Times the removed ones came back, by fullname
+    returns: BTreeMap<String, u32>,
+}
+
+impl Tally {
+    fn apply(&mut self, event: &Event) {
+        match event.kind {
+            EventKind::Resolved | EventKind::Updated => {
+                let back = self
+                    .present
+                    .insert(event.name.clone(), event.service_type.clone())
+                    .is_none()
+                    && self.removed.contains(&event.name);
+                if back {
+                    *self.returns.entry(event.name.clone()).or_default() += 1;
+                }
+            }
+            EventKind::Removed(_) => {
+                if self.present.remove(&event.name).is_some() {
+                    self.removed.insert(event.name.clone());
+                }
+            }
+            EventKind::Found => {}
+        }
+    }
+
+    /// Start afresh from what is present now.
+    fn reset(&mut self) {
+        self.previous = self.present.clone();
+        self.removed.clear();
+        self.returns.clear();
+    }
+
+    /// What changed since the previous digest, starting the next one.
+    fn digest(&mut self, since: SystemTime, until: SystemTime, flaps: u32) -> Digest {
+        let instance = |(name, service_type): (&String, &String)| Instance {
+            service_type: service_type.clone(),
+            name: name.clone(),
+            flaps: None,
+        };
+        let new: Vec<_> = self
+            .present
+            .iter()
+            .filter(|(name, _)| !self.previous.contains_key(*name))
+            .map(instance)
+            .collect();
+        let removed: Vec<_> = self
+            .previous
+            .iter()
+            .filter(|(name, _)| !self.present.contains_key(*name))
+            .map(instance)
+            .collect();
+        let flapping: Vec<_> = self
+            .returns
+            .iter()
+            .filter(|(_, returns)| **returns >= flaps)
+            .map(|(name, returns)| Instance {
+                service_type: self
+                    .present
+                    .get(name)
+                    .or_else(|| self.previous.get(name))
+                    .cloned()
+                    .unwrap_or_default(),
+                name: name.clone(),
+                flaps: Some(*returns),
+            })
+            .collect();
+        self.reset();
+
+        let since = humantime::format_rfc3339_seconds(since).to_string();
+        let until = humantime::format_rfc3339_seconds(until).to_string();
+        let mut text = format!("From {since} to {until}:");
+        for (title, mark, instances) in [
+            ("New", '+', &new),
+            ("Removed", '-', &removed),
+            ("Flapping", '~', &flapping),
+        ] {
+            text += &format!("\n{title}: {}", instances.len());
+            for instance in instances {
+                text += &format!("\n  {mark} {}", instance.name);
+                if let Some(flaps) = instance.flaps {
+                    text += &format!(", back {flaps} times");
+                }
+            }
+        }
+        Digest {
+            since,
+            until,
+            new,
+            removed,
+            flapping,
+            text,
+        }
+    }
+}
+
+/// The POST to the webhook, left out of builds without the `webhook`
+/// feature along with its HTTP client.
+#[cfg(feature = "webhook")]
+mod webhook {
+    use std::time::Duration;
+
+    use anyhow::Context;
+
+    const K_TIMEOUT: Duration = Duration::from_secs(10);
+
+    pub fn post(url: &str, body: &str) -> anyhow::Result<()> {
+        ureq::post(url)
+            .set("Content-Type", "application/json")
+            .set(
+                "User-Agent",
+                concat!("discovery-rs/", env!("CARGO_PKG_VERSION")),
+            )
+            .timeout(K_TIMEOUT)
+            .send_string(body)
+            .with_context(|| format!("Failed to post the digest to {url}"))?;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "webhook"))]
+fn post(_url: &str, _body: &str) -> anyhow::Result<()> {
+    anyhow::bail!("This build doesn't include the webhook feature")
+}
+
+/// Mail `digest` to `to` by piping it to `sendmail`, a shell command.
+fn mail(digest: &Digest, to: &str, sendmail: &str) -> anyhow::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(sendmail)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {sendmail}"))?;
+    let message = format!(
+        "To: {to}\nSubject: {}\nContent-Type: text/plain; charset=utf-8\n\n{}\n",
+        digest.subject(),
+        digest.text
+    );
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(message.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        anyhow::bail!("{sendmail} failed: {status}");
+    }
+    Ok(())
+}
+
+fn send(digest: &Digest, opts: &DigestOpts) -> anyhow::Result<()> {
+    if let Some(url) = opts.webhook.as_deref() {
+        post(url, &serde_json::to_string(digest)?)?;
+    }
+    if let Some(to) = opts.mail_to.as_deref() {
+        mail(digest, to, &opts.sendmail)?;
+    }
+    if opts.webhook.is_none() && opts.mail_to.is_none() {
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{}\n{}\n", digest.subject(), digest.text)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+/// Browse like `browse` does, sending a digest of the instances new,
+/// removed and flapping since the previous one on a schedule, e.g. a
+/// morning report of the changes on the network.
+///
+/// Digests go to a webhook, are mailed, or are printed otherwise. Failing
+/// to send one is reported, and the next one is sent nonetheless.
+pub fn run(
+    opts: &DigestOpts,
+    profile: &Profile,
+    backend: BackendKind,
+    queue_settings: QueueSettings,
+) -> Result<(), Error> {
+    if opts.webhook.is_some() && !cfg!(feature = "webhook") {
+        return Err(Error::Config(anyhow::anyhow!(
+            "This build doesn't include the webhook feature"
+        )));
+    }
+    let headless = Headless::start(profile, backend, queue_settings)?;
+    let events = headless.model.event_log.subscribe(K_BACKLOG);
+
+    let mut tally = Tally::default();
+    let mut since = SystemTime::now();
+    let mut settling = Some(Instant::now() + K_SETTLE);
+    let mut due = Instant::now();
+    loop {
+        let deadline = settling.unwrap_or(due);
+        match events.recv_deadline(deadline) {
+            Ok(event) => tally.apply(&event),
+            Err(RecvTimeoutError::Timeout) if settling.is_some() => {
+                tally.reset();
+                since = SystemTime::now();
+                due = Instant::now() + opts.every;
+                settling = None;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                let until = SystemTime::now();
+                let digest = tally.digest(since, until, opts.flaps);
+                if let Err(e) = send(&digest, opts) {
+                    tracing::warn!("{e:#}");
+                    eprintln!("{e:#}");
+                }
+                since = until;
+                // A send outlasting the period would otherwise be followed
+                // by a burst of empty digests catching up
+                due += opts.every;
+                let now = Instant::now();
+                if due <= now {
+                    due = now + opts.every;
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    headless.stop()
+}
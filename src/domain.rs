@@ -0,0 +1,83 @@
+/// Multicast DNS domain every query starts in.
+pub const K_LOCAL_DOMAIN: &str = "local.";
+
+/// Queries enumerating the recommended (`b`) and legacy (`lb`) browse domains.
+///
+/// Sites using wide-area Bonjour advertise their unicast DNS-SD domains
+/// under these names, see RFC 6763, section 11.
+pub const K_BROWSE_DOMAIN_QUERIES: [&str; 2] = ["b._dns-sd._udp.local.", "lb._dns-sd._udp.local."];
+
+pub fn is_browse_domain_query(query: &str) -> bool {
+    K_BROWSE_DOMAIN_QUERIES.contains(&query)
+}
+
+/// Whether `query` enumerates the service types of a domain, i.e. its
+/// `ServiceFound` events carry service types rather than instances.
+pub fn is_type_enumeration(query: &str) -> bool {
+    query.starts_with("_services._dns-sd._udp.")
+}
+
+/// Strip the subtype off a service type, e.g. `_printer._sub._http._tcp.local.`
+/// becomes `_http._tcp.local.`
+pub fn base_type(service_type: &str) -> &str {
+    match service_type.split_once("._sub.") {
+        Some((_, base)) => base,
+        None => service_type,
+    }
+}
+
+/// Normalize a discovered domain name to its fully qualified form.
+pub fn normalize(domain: &str) -> String {
+    format!("{}.", domain.trim_end_matches('.').to_lowercase())
+}
+
+/// Move a `.local.` query to `domain`, e.g. `_http._tcp.local.` to
+/// `_http._tcp.example.com.`
+///
+/// Queries for other domains are left as they are.
+pub fn in_domain(query: &str, domain: &str) -> String {
+    match query.strip_suffix(K_LOCAL_DOMAIN) {
+        Some(service_type) => format!("{service_type}{domain}"),
+        None => query.to_string(),
+    }
+}
+
+/// Fully qualify a service type, looking types without a domain up in
+/// `local.`, e.g. `_http._tcp` becomes `_http._tcp.local.`
+pub fn qualify(service_type: &str) -> String {
+    if service_type.ends_with("._tcp") || service_type.ends_with("._udp") {
+        format!("{service_type}.{K_LOCAL_DOMAIN}")
+    } else if service_type.ends_with('.') {
+        service_type.to_string()
+    } else {
+        format!("{service_type}.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_types() {
+        assert_eq!(
+            base_type("_printer._sub._http._tcp.local."),
+            "_http._tcp.local."
+        );
+        assert_eq!(
+            base_type("_universal._sub._ipp._tcp.example.com."),
+            "_ipp._tcp.example.com."
+        );
+        assert_eq!(base_type("_http._tcp.local."), "_http._tcp.local.");
+        // Only the `._sub.` label marks a subtype
+        assert_eq!(base_type("_sub_http._tcp.local."), "_sub_http._tcp.local.");
+    }
+
+    #[test]
+    fn type_enumerations() {
+        assert!(is_type_enumeration("_services._dns-sd._udp.local."));
+        assert!(is_type_enumeration("_services._dns-sd._udp.example.com."));
+        assert!(!is_type_enumeration("_http._tcp.local."));
+        assert!(!is_type_enumeration("b._dns-sd._udp.local."));
+    }
+}
@@ -0,0 +1,64 @@
+use std::process::ExitCode;
+
+/// Failure classes the process exits with.
+///
+/// Each class maps to its own exit code, following `sysexits.h`, so that
+/// scripts and operators can tell them apart without parsing messages.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Invalid or unreadable configuration
+    #[error("Configuration error: {0:#}")]
+    Config(anyhow::Error),
+    /// Not allowed to open the mDNS sockets
+    #[error("Permission error: {0:#}")]
+    Permission(anyhow::Error),
+    /// The mDNS backend failed
+    #[error("mDNS daemon failure: {0:#}")]
+    Daemon(anyhow::Error),
+    /// Writing an export failed
+    #[error("Export error: {0:#}")]
+    Export(anyhow::Error),
+    /// Anything else, e.g. the terminal
+    #[error("{0:#}")]
+    Other(anyhow::Error),
+}
+
+impl Error {
+    /// Classify a backend failure, telling permission problems apart.
+    pub fn daemon(e: anyhow::Error) -> Self {
+        let denied = e.chain().any(|cause| {
+            cause
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|e| e.kind() == std::io::ErrorKind::PermissionDenied)
+                // mdns-sd only keeps the message of the socket errors
+                || cause.to_string().contains("Permission denied")
+        });
+        if denied {
+            Error::Permission(e)
+        } else {
+            Error::Daemon(e)
+        }
+    }
+
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            Error::Config(_) => 78,
+            Error::Permission(_) => 77,
+            Error::Daemon(_) => 69,
+            Error::Export(_) => 74,
+            Error::Other(_) => 1,
+        })
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(e: anyhow::Error) -> Self {
+        Error::Other(e)
+    }
+}
+
+impl From<color_eyre::Report> for Error {
+    fn from(e: color_eyre::Report) -> Self {
+        Error::Other(anyhow::anyhow!("{e}"))
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use crossterm::event::{KeyCode, KeyEvent};
+use flume::{Receiver, Sender, TrySendError};
+use parking_lot::Mutex;
+use ratatui::{prelude::*, widgets::*};
+
+use crate::clock::Clock;
+use crate::colors::theme;
+use crate::schema::{self, LogEntry, TxtChange};
+use crate::widget::DiscoveryWidget;
+
+/// Oldest entries are dropped beyond this many.
+const K_MAX_ENTRIES: usize = 10_000;
+
+/// Why an instance went away.
+///
+/// Goodbyes are records sent again with a TTL of 0, which the model hears
+/// when it can listen to the responses. Otherwise it is inferred from the
+/// instance's TTL: one removed before its records could have expired must
+/// have said goodbye.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Removal {
+    /// The device left cleanly
+    Goodbye,
+    /// The records ran out without being refreshed, e.g. the device crashed
+    Expired,
+}
+
+impl Removal {
+    fn name(self) -> &'static str {
+        match self {
+            Removal::Goodbye => "goodbye",
+            Removal::Expired => "expired",
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            Removal::Goodbye => "✗",
+            Removal::Expired => "◷",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventKind {
+    Found,
+    Resolved,
+    /// Resolved again with a different TXT record
+    Updated,
+    /// Along with the reason, unless the instance was never resolved
+    Removed(Option<Removal>),
+}
+
+impl EventKind {
+    fn name(self) -> &'static str {
+        match self {
+            EventKind::Found => "found",
+            EventKind::Resolved => "resolved",
+            EventKind::Updated => "updated",
+            EventKind::Removed(_) => "removed",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub time: SystemTime,
+    pub kind: EventKind,
+    pub service_type: String,
+    /// Instance fullname, or the service type found by a type enumeration
+    pub name: String,
+    /// TXT entries changed by an update
+    pub changes: Vec<TxtChange>,
+}
+
+impl Event {
+    /// A single line, e.g. `2024-05-01T12:00:00Z removed ✗ _http._tcp.local. My NAS._http._tcp.local. (goodbye)`
+    pub fn line(&self) -> String {
+        let (icon, reason) = match self.kind {
+            EventKind::Removed(Some(removal)) => (removal.icon(), format!(" ({})", removal.name())),
+            EventKind::Updated => {
+                let changes: Vec<_> = self.changes.iter().map(ToString::to_string).collect();
+                ("~", format!(" ({})", changes.join(", ")))
+            }
+            _ => (" ", String::new()),
+        };
+        format!(
+            "{} {:<8} {icon} {} {}{reason}",
+            humantime::format_rfc3339_seconds(self.time),
+            self.kind.name(),
+            self.service_type,
+            self.name
+        )
+    }
+
+    /// The event as written to NDJSON.
+    pub fn entry(&self) -> LogEntry {
+        LogEntry {
+            time: self
+                .time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            event: self.kind.name().to_string(),
+            reason: match self.kind {
+                EventKind::Removed(removal) => removal.map(|removal| removal.name().to_string()),
+                _ => None,
+            },
+            service_type: self.service_type.clone(),
+            name: self.name.clone(),
+            changes: self.changes.clone(),
+            probe: None,
+        }
+    }
+}
+
+/// Timeline of the services found, resolved and removed, independent of
+/// tracing.
+///
+/// Cloning is cheap and yields a handle to the same log.
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    events: Arc<Mutex<VecDeque<Event>>>,
+    /// Receive every event as it is recorded, see [`EventLog::subscribe`]
+    subscribers: Arc<Mutex<Vec<Sender<Event>>>>,
+    /// Timestamps the events
+    clock: Arc<dyn Clock>,
+}
+
+impl EventLog {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            events: Arc::new(Mutex::new(VecDeque::new())),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            clock,
+        }
+    }
+
+    pub fn record(&self, kind: EventKind, service_type: &str, name: &str) {
+        self.push(Event {
+            time: self.clock.system_time(),
+            kind,
+            service_type: service_type.to_string(),
+            name: name.to_string(),
+            changes: Vec::new(),
+        });
+    }
+
+    /// Record the TXT `changes` of the instance `name`.
+    pub fn record_update(&self, service_type: &str, name: &str, changes: Vec<TxtChange>) {
+        self.push(Event {
+            time: self.clock.system_time(),
+            kind: EventKind::Updated,
+            service_type: service_type.to_string(),
+            name: name.to_string(),
+            changes,
+        });
+    }
+
+    /// Receive the events recorded from now on, up to `capacity` of them
+    /// behind. Events are dropped for a subscriber that falls further behind,
+    /// rather than holding up the model.
+    pub fn subscribe(&self, capacity: usize) -> Receiver<Event> {
+        let (tx, rx) = flume::bounded(capacity);
+        self.subscribers.lock().push(tx);
+        rx
+    }
+
+    fn push(&self, event: Event) {
+        self.subscribers
+            .lock()
+            .retain(|subscriber| match subscriber.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    tracing::debug!("Dropped an event for a subscriber behind");
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+        let mut events = self.events.lock();
+        events.push_back(event);
+        while events.len() > K_MAX_ENTRIES {
+            events.pop_front();
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<Event> {
+        self.events.lock().iter().cloned().collect()
+    }
+}
+
+/// Save `events` in `dir` as both plain text and NDJSON.
+///
+/// Returns the paths of the two files.
+pub fn save(events: &[Event], dir: &Path) -> anyhow::Result<(PathBuf, PathBuf)> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let text_path = dir.join(format!("discovery-rs-events-{stamp}.log"));
+    let ndjson_path = dir.join(format!("discovery-rs-events-{stamp}.ndjson"));
+
+    let mut text = String::new();
+    let mut ndjson = String::new();
+    for event in events {
+        let _ = writeln!(text, "{}", event.line());
+        let _ = writeln!(ndjson, "{}", schema::to_json_line(&event.entry())?);
+    }
+    std::fs::write(&text_path, text)
+        .with_context(|| format!("Failed to write {}", text_path.display()))?;
+    std::fs::write(&ndjson_path, ndjson)
+        .with_context(|| format!("Failed to write {}", ndjson_path.display()))?;
+    Ok((text_path, ndjson_path))
+}
+
+/// The event log, most recent first.
+///
+/// Like the host card, a snapshot taken when opened.
+#[derive(Debug)]
+pub struct EventLogView {
+    pub events: Vec<Event>,
+    scroll: usize,
+}
+
+impl EventLogView {
+    pub fn new(log: &EventLog) -> Self {
+        Self {
+            events: log.snapshot(),
+            scroll: 0,
+        }
+    }
+}
+
+impl DiscoveryWidget for EventLogView {
+    fn title(&self) -> String {
+        format!("Event log, {} events", self.events.len())
+    }
+
+    fn controls(&self) -> String {
+        "Use ↓↑ to scroll, s to save as text and NDJSON, Esc to close".to_string()
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        match key_event.code {
+            KeyCode::Down => {
+                self.scroll = (self.scroll + 1).min(self.events.len().saturating_sub(1))
+            }
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(theme().selected_fg).bold())
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .padding(Padding::horizontal(1))
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        let lines: Vec<_> = self
+            .events
+            .iter()
+            .rev()
+            .skip(self.scroll)
+            .map(|event| {
+                let line = Line::from(event.line());
+                match event.kind {
+                    EventKind::Found => line,
+                    EventKind::Resolved => line.green(),
+                    EventKind::Updated => line.light_cyan(),
+                    EventKind::Removed(Some(Removal::Expired)) => line.yellow(),
+                    EventKind::Removed(_) => line.red(),
+                }
+            })
+            .collect();
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use clap_derive::ValueEnum;
+use flume::{Receiver, Sender, TrySendError};
+use mdns_sd::ServiceEvent;
+use serde::Deserialize;
+
+/// What to do with a new event when the event queue is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backpressure {
+    /// Wait until the model catches up
+    #[default]
+    Block,
+    /// Discard the incoming event
+    DropNewest,
+    /// Discard the oldest queued event to make room for the incoming one
+    DropOldest,
+}
+
+/// Sizing and overflow policy of the [`EventQueue`].
+#[derive(Debug, Clone, Copy)]
+pub struct QueueSettings {
+    pub capacity: usize,
+    pub backpressure: Backpressure,
+}
+
+impl Default for QueueSettings {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            backpressure: Backpressure::default(),
+        }
+    }
+}
+
+/// Counters describing how well the model keeps up with the mDNS events.
+#[derive(Debug, Default)]
+pub struct EventStats {
+    pub received: AtomicU64,
+    pub applied: AtomicU64,
+    pub coalesced: AtomicU64,
+    pub dropped: AtomicU64,
+    pub depth: AtomicUsize,
+    pub capacity: AtomicUsize,
+}
+
+impl EventStats {
+    pub fn summary(&self) -> String {
+        format!(
+            "events {}, coalesced {}, dropped {}, queue {}/{}",
+            self.received.load(Ordering::Relaxed),
+            self.coalesced.load(Ordering::Relaxed),
+            self.dropped.load(Ordering::Relaxed),
+            self.depth.load(Ordering::Relaxed),
+            self.capacity.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Bounded queue between the mDNS receivers and the model.
+///
+/// Applies the configured [`Backpressure`] policy once the queue is full
+/// and accounts for every event in [`EventStats`].
+pub struct EventQueue<T> {
+    tx: Sender<T>,
+    rx: Receiver<T>,
+    policy: Backpressure,
+    stats: Arc<EventStats>,
+}
+
+impl<T> EventQueue<T> {
+    pub fn new(
+        capacity: usize,
+        policy: Backpressure,
+        stats: Arc<EventStats>,
+    ) -> (Self, Receiver<T>) {
+        let (tx, rx) = flume::bounded(capacity);
+        stats.capacity.store(capacity, Ordering::Relaxed);
+        (
+            Self {
+                tx,
+                rx: rx.clone(),
+                policy,
+                stats,
+            },
+            rx,
+        )
+    }
+
+    /// Queue `event`, failing only when the consumer is gone.
+    pub fn push(&self, event: T) -> anyhow::Result<()> {
+        self.stats.received.fetch_add(1, Ordering::Relaxed);
+        match self.policy {
+            Backpressure::Block => self
+                .tx
+                .send(event)
+                .map_err(|_| anyhow::anyhow!("Event queue is closed"))?,
+            Backpressure::DropNewest => match self.tx.try_send(event) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(TrySendError::Disconnected(_)) => anyhow::bail!("Event queue is closed"),
+            },
+            Backpressure::DropOldest => {
+                let mut event = event;
+                loop {
+                    match self.tx.try_send(event) {
+                        Ok(()) => break,
+                        Err(TrySendError::Full(rejected)) => {
+                            if self.rx.try_recv().is_ok() {
+                                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+                            }
+                            event = rejected;
+                        }
+                        Err(TrySendError::Disconnected(_)) => {
+                            anyhow::bail!("Event queue is closed")
+                        }
+                    }
+                }
+            }
+        }
+        self.stats.depth.store(self.tx.len(), Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Collapse repeated resolutions of the same instance within `batch`.
+///
+/// Only the latest [`ServiceEvent::ServiceResolved`] per instance is kept,
+/// as applying the earlier ones would be overwritten anyway.
+pub fn coalesce(batch: Vec<ServiceEvent>, stats: &EventStats) -> Vec<ServiceEvent> {
+    let latest: HashMap<String, usize> = batch
+        .iter()
+        .enumerate()
+        .filter_map(|(index, event)| match event {
+            ServiceEvent::ServiceResolved(info) => Some((info.get_fullname().to_string(), index)),
+            _ => None,
+        })
+        .collect();
+    let len = batch.len();
+    let batch: Vec<_> = batch
+        .into_iter()
+        .enumerate()
+        .filter(|(index, event)| match event {
+            ServiceEvent::ServiceResolved(info) => latest.get(info.get_fullname()) == Some(index),
+            _ => true,
+        })
+        .map(|(_, event)| event)
+        .collect();
+    stats
+        .coalesced
+        .fetch_add((len - batch.len()) as u64, Ordering::Relaxed);
+    batch
+}
@@ -0,0 +1,447 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::domain;
+use crate::info::Info;
+use crate::model::Model;
+use crate::schema::{self, Document, ExportIndex, IndexEntry, Instance, Snapshot};
+use crate::zone::Zones;
+use anyhow::Context;
+
+/// How many TXT entries a summary lists at most
+const K_SUMMARY_PROPERTIES: usize = 8;
+/// Longer TXT values are cut in summaries
+const K_SUMMARY_VALUE_LENGTH: usize = 40;
+/// Page of the HTML exports, the snapshot replacing `{{snapshot}}`
+const K_HTML_TEMPLATE: &str = include_str!("export.html");
+/// Columns of the CSV exports, unless configured with `csv_columns`
+const K_CSV_COLUMNS: [&str; 6] = ["type", "name", "hostname", "addresses", "port", "txt"];
+/// Index of the exports split per service type, always JSON
+const K_INDEX_FILE: &str = "index.json";
+
+/// A format everything resolved can be exported in.
+///
+/// Formats are kept in a [`Registry`] and picked by name or by file
+/// extension, so adding one, e.g. YAML or XLSX with their own crates, only
+/// takes implementing this trait and registering it.
+pub trait Exporter: Send + Sync {
+    /// What the format is selected by, e.g. `json`
+    fn name(&self) -> &'static str;
+    /// File extension of the format, without the dot
+    fn extension(&self) -> &'static str;
+    fn serialize(&self, snapshot: &Snapshot) -> anyhow::Result<Vec<u8>>;
+}
+
+/// Versioned, pretty printed JSON, see [`schema`].
+struct Json;
+
+impl Exporter for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+
+    fn serialize(&self, snapshot: &Snapshot) -> anyhow::Result<Vec<u8>> {
+        Ok((schema::to_json(snapshot)? + "\n").into_bytes())
+    }
+}
+
+/// The same document as [`Json`], as TOML.
+struct Toml;
+
+impl Exporter for Toml {
+    fn name(&self) -> &'static str {
+        "toml"
+    }
+
+    fn extension(&self) -> &'static str {
+        "toml"
+    }
+
+    fn serialize(&self, snapshot: &Snapshot) -> anyhow::Result<Vec<u8>> {
+        Ok(toml::to_string_pretty(&Document::new(snapshot))?.into_bytes())
+    }
+}
+
+/// A standalone page listing the instances in a table with a search, for
+/// sharing with whoever won't run a terminal tool.
+///
+/// The [`Json`] document is embedded in the page, which needs nothing else.
+struct Html;
+
+impl Exporter for Html {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn extension(&self) -> &'static str {
+        "html"
+    }
+
+    fn serialize(&self, snapshot: &Snapshot) -> anyhow::Result<Vec<u8>> {
+        // Escaped so that no value can end the script element early
+        let json = schema::to_json(snapshot)?.replace('<', "\\u003c");
+        Ok(K_HTML_TEMPLATE.replace("{{snapshot}}", &json).into_bytes())
+    }
+}
+
+/// Comma-separated values, a row per instance, for pasting an inventory
+/// into a spreadsheet.
+///
+/// Columns are picked among `type`, `name`, `hostname`, `model`,
+/// `addresses`, `port`, `txt` for every TXT entry, `txt.<key>` for a single
+/// one and `computed.<name>` for a computed column.
+pub struct Csv {
+    columns: Vec<String>,
+}
+
+impl Default for Csv {
+    fn default() -> Self {
+        Self {
+            columns: K_CSV_COLUMNS
+                .iter()
+                .map(|column| column.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl Csv {
+    /// Export the `columns`, in order.
+    pub fn new(columns: &[String]) -> anyhow::Result<Self> {
+        for column in columns {
+            let known = matches!(
+                column.as_str(),
+                "type" | "name" | "hostname" | "model" | "addresses" | "port" | "txt"
+            ) || column.starts_with("txt.")
+                || column.starts_with("computed.");
+            if !known {
+                anyhow::bail!("Unknown CSV column \"{column}\"");
+            }
+        }
+        Ok(Self {
+            columns: columns.to_vec(),
+        })
+    }
+
+    fn value(column: &str, service_type: &str, instance: &Instance, snapshot: &Snapshot) -> String {
+        match column {
+            "type" => service_type.to_string(),
+            "name" => instance
+                .fullname
+                .strip_suffix(service_type)
+                .map_or(instance.fullname.as_str(), |name| {
+                    name.trim_end_matches('.')
+                })
+                .to_string(),
+            "hostname" => instance.hostname.clone(),
+            "model" => snapshot
+                .hosts
+                .get(&instance.hostname)
+                .and_then(|host| host.model.clone())
+                .unwrap_or_default(),
+            "addresses" => instance.addresses.join(" "),
+            "port" => instance.port.to_string(),
+            "txt" => instance
+                .properties
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+            column => match column.split_once('.') {
+                Some(("txt", key)) => instance.properties.get(key).cloned().unwrap_or_default(),
+                Some(("computed", name)) => {
+                    instance.computed.get(name).cloned().unwrap_or_default()
+                }
+                _ => String::new(),
+            },
+        }
+    }
+}
+
+/// `value` as a CSV field, quoted when needed.
+///
+/// Values starting like a formula are prefixed with a `'`, as they come
+/// from the network and spreadsheets would evaluate them.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+impl Exporter for Csv {
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn extension(&self) -> &'static str {
+        "csv"
+    }
+
+    fn serialize(&self, snapshot: &Snapshot) -> anyhow::Result<Vec<u8>> {
+        let mut csv = String::new();
+        let header: Vec<_> = self
+            .columns
+            .iter()
+            .map(|column| csv_field(column))
+            .collect();
+        csv.push_str(&(header.join(",") + "\r\n"));
+        for (service_type, instances) in snapshot.types.iter() {
+            for instance in instances {
+                let row: Vec<_> = self
+                    .columns
+                    .iter()
+                    .map(|column| csv_field(&Self::value(column, service_type, instance, snapshot)))
+                    .collect();
+                csv.push_str(&(row.join(",") + "\r\n"));
+            }
+        }
+        Ok(csv.into_bytes())
+    }
+}
+
+/// Every export format available.
+pub struct Registry {
+    exporters: Vec<Box<dyn Exporter>>,
+}
+
+impl Registry {
+    /// The formats built in: JSON, the default, TOML, HTML and CSV.
+    pub fn builtin() -> Self {
+        let mut registry = Self {
+            exporters: Vec::new(),
+        };
+        registry.register(Box::new(Json));
+        registry.register(Box::new(Toml));
+        registry.register(Box::new(Html));
+        registry.register(Box::new(Csv::default()));
+        registry
+    }
+
+    /// Add `exporter`, replacing any format of the same name.
+    pub fn register(&mut self, exporter: Box<dyn Exporter>) {
+        self.exporters
+            .retain(|existing| existing.name() != exporter.name());
+        self.exporters.push(exporter);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Exporter> {
+        self.exporters
+            .iter()
+            .find(|exporter| exporter.name().eq_ignore_ascii_case(name))
+            .map(Box::as_ref)
+    }
+
+    /// Names of the formats, in the order they were registered.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.exporters
+            .iter()
+            .map(|exporter| exporter.name())
+            .collect()
+    }
+
+    /// The format of `path` by its extension, JSON when none matches.
+    pub fn for_path(&self, path: &Path) -> &dyn Exporter {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        self.exporters
+            .iter()
+            .find(|exporter| exporter.extension().eq_ignore_ascii_case(extension))
+            .or_else(|| self.exporters.first())
+            .map(Box::as_ref)
+            .expect("The registry has the built-in formats")
+    }
+}
+
+/// Write a [`Snapshot`] of `model` to `path` in the `format` of `registry`,
+/// or in the one of the path's extension when not given.
+pub fn write(
+    model: &Model,
+    path: &Path,
+    registry: &Registry,
+    format: Option<&str>,
+) -> anyhow::Result<()> {
+    let exporter = match format {
+        Some(format) => registry.get(format).with_context(|| {
+            format!(
+                "Unknown export format \"{format}\", one of: {}",
+                registry.names().join(", ")
+            )
+        })?,
+        None => registry.for_path(path),
+    };
+    if is_split(path) {
+        return write_split(model.snapshot(), path, exporter);
+    }
+    let bytes = exporter.serialize(&model.snapshot())?;
+    std::fs::write(path, bytes).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Whether an export to `path` is split per service type, i.e. the path is
+/// a directory, or ends with a `/` for one to create.
+fn is_split(path: &Path) -> bool {
+    path.is_dir() || path.as_os_str().to_string_lossy().ends_with('/')
+}
+
+/// File name of `service_type` in a split export, e.g. `_http._tcp.json`
+/// for `_http._tcp.local.`, keeping any other domain.
+fn type_file(service_type: &str, extension: &str) -> String {
+    let name = service_type
+        .strip_suffix(domain::K_LOCAL_DOMAIN)
+        .unwrap_or(service_type)
+        .trim_end_matches('.')
+        .replace('/', "_");
+    format!("{name}.{extension}")
+}
+
+/// Write a file per service type of `snapshot` into the directory `dir`,
+/// each with the hosts of its instances, and an index of the files.
+fn write_split(snapshot: Snapshot, dir: &Path, exporter: &dyn Exporter) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let Snapshot {
+        exported,
+        types,
+        domains,
+        hosts,
+    } = snapshot;
+    let mut index = ExportIndex {
+        exported,
+        domains: domains.clone(),
+        types: Vec::new(),
+    };
+    for (service_type, instances) in types {
+        let file = type_file(&service_type, exporter.extension());
+        let path = dir.join(&file);
+        index.types.push(IndexEntry {
+            service_type: service_type.clone(),
+            file,
+            instances: instances.len(),
+        });
+        let type_hosts = instances
+            .iter()
+            .filter_map(|instance| {
+                let host = hosts.get(&instance.hostname)?.clone();
+                Some((instance.hostname.clone(), host))
+            })
+            .collect();
+        let type_snapshot = Snapshot {
+            exported,
+            types: BTreeMap::from([(service_type, instances)]),
+            domains: domains.clone(),
+            hosts: type_hosts,
+        };
+        let bytes = exporter.serialize(&type_snapshot)?;
+        std::fs::write(&path, bytes)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    let path = dir.join(K_INDEX_FILE);
+    std::fs::write(&path, schema::to_json(&index)? + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Compact plain text description of `info`, for pasting into chats and bug
+/// trackers, optionally fenced as a markdown code block.
+pub fn summary(info: &Info, markdown: bool) -> String {
+    let service_type = info.info.get_type();
+    let fullname = info.info.get_fullname();
+    let name = fullname
+        .strip_suffix(service_type)
+        .map_or(fullname, |name| name.trim_end_matches('.'));
+    let zones = Zones::local();
+    let addresses: Vec<_> = info
+        .addresses()
+        .into_iter()
+        .map(|addr| zones.display(addr))
+        .collect();
+    let mut properties: Vec<_> = info
+        .info
+        .get_properties()
+        .iter()
+        .take(K_SUMMARY_PROPERTIES)
+        .map(|property| {
+            let value = property.val_str();
+            match value.char_indices().nth(K_SUMMARY_VALUE_LENGTH) {
+                Some((end, _)) => format!("{}={}…", property.key(), &value[..end]),
+                None => format!("{}={value}", property.key()),
+            }
+        })
+        .collect();
+    let more = info
+        .info
+        .get_properties()
+        .len()
+        .saturating_sub(K_SUMMARY_PROPERTIES);
+    if more > 0 {
+        properties.push(format!("(+{more} more)"));
+    }
+
+    let (port, assumed) = info.port();
+    let assumed = if assumed { " (assumed)" } else { "" };
+    let mut summary = format!(
+        "{name} ({service_type})\nhost:  {}\naddrs: {}\nport:  {port}{assumed}\ntxt:   {}\n",
+        info.info.get_hostname(),
+        addresses.join(", "),
+        properties.join(" "),
+    );
+    if let Some(uri) = info.uri() {
+        summary.push_str(&format!("uri:   {uri}{assumed}\n"));
+    }
+    if markdown {
+        format!("```\n{summary}```\n")
+    } else {
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_csv_fields() {
+        assert_eq!(csv_field("Office Printer"), "Office Printer");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("rp=ipp/print"), "rp=ipp/print");
+        assert_eq!(csv_field("fe80::1%eth0"), "fe80::1%eth0");
+    }
+
+    #[test]
+    fn quoted_csv_fields() {
+        assert_eq!(csv_field("Printer, 2nd floor"), "\"Printer, 2nd floor\"");
+        assert_eq!(csv_field("the \"good\" one"), "\"the \"\"good\"\" one\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("two\r\nlines"), "\"two\r\nlines\"");
+    }
+
+    #[test]
+    fn formulas_are_defused() {
+        assert_eq!(csv_field("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+        assert_eq!(csv_field("+1 555 0100"), "'+1 555 0100");
+        assert_eq!(csv_field("-2"), "'-2");
+        assert_eq!(csv_field("@SUM(A1:A2)"), "'@SUM(A1:A2)");
+        // Only at the start
+        assert_eq!(csv_field("a=b"), "a=b");
+    }
+
+    #[test]
+    fn csv_columns() {
+        assert!(Csv::new(&["type".to_string(), "txt.rp".to_string()]).is_ok());
+        assert!(Csv::new(&["computed.floor".to_string()]).is_ok());
+        assert_eq!(
+            Csv::new(&["ttl".to_string()]).err().unwrap().to_string(),
+            "Unknown CSV column \"ttl\""
+        );
+    }
+}
@@ -0,0 +1,405 @@
+use std::fmt::{Display, Formatter};
+
+use mdns_sd::ServiceInfo;
+use regex::{Regex, RegexBuilder};
+
+use crate::domain;
+
+/// Most memory a search regex may compile to, in bytes.
+///
+/// The regex engine runs in linear time, so patterns can't backtrack
+/// catastrophically, but counted repetitions such as `(a{1000}){1000}` still
+/// blow up when compiled. Such patterns are rejected rather than stalling
+/// the UI, well above what a search needs.
+const K_REGEX_SIZE_LIMIT: usize = 1 << 20;
+/// Deepest nesting of groups and repetitions a search regex may have.
+const K_REGEX_NEST_LIMIT: u32 = 32;
+
+/// Field of an instance that can be compared or matched on its own.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    Hostname,
+    /// Instance name, without the service type
+    Name,
+    Type,
+    Txt(String),
+}
+
+impl Field {
+    /// Parse `hostname`, `name`, `type` or `txt.<key>`.
+    pub fn parse(field: &str) -> Option<Self> {
+        match field.trim() {
+            "hostname" => Some(Field::Hostname),
+            "name" => Some(Field::Name),
+            "type" => Some(Field::Type),
+            field => field
+                .strip_prefix("txt.")
+                .filter(|key| !key.is_empty())
+                .map(|key| Field::Txt(key.to_string())),
+        }
+    }
+
+    /// Value of the field in `info`, `None` for a missing TXT key.
+    pub fn value<'a>(&self, info: &'a ServiceInfo) -> Option<&'a str> {
+        match self {
+            Field::Hostname => Some(info.get_hostname()),
+            Field::Name => Some(
+                info.get_fullname()
+                    .strip_suffix(info.get_type())
+                    .map_or(info.get_fullname(), |name| name.trim_end_matches('.')),
+            ),
+            Field::Type => Some(info.get_type()),
+            Field::Txt(key) => info.get_property_val_str(key),
+        }
+    }
+
+    /// Whether `value` of the field equals `expected`, the way users write it:
+    /// hostnames and types regardless of case and domain, e.g. `_ssh._tcp`.
+    fn equals(&self, value: &str, expected: &str) -> bool {
+        match self {
+            Field::Hostname => value
+                .trim_end_matches('.')
+                .eq_ignore_ascii_case(expected.trim_end_matches('.')),
+            Field::Type => {
+                domain::base_type(value).eq_ignore_ascii_case(&domain::qualify(expected))
+            }
+            Field::Name | Field::Txt(_) => value == expected,
+        }
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Field::Hostname => write!(f, "hostname"),
+            Field::Name => write!(f, "name"),
+            Field::Type => write!(f, "type"),
+            Field::Txt(key) => write!(f, "txt.{key}"),
+        }
+    }
+}
+
+/// The parts of `expression` between the `separator`s outside of quotes,
+/// e.g. not the one in `txt.path == "a||b"`.
+fn split_unquoted<'a>(expression: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut parts = Vec::new();
+    let (mut start, mut quoted) = (0, false);
+    let mut chars = expression.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '"' {
+            quoted = !quoted;
+        } else if !quoted && expression[i..].starts_with(separator) {
+            parts.push(&expression[start..i]);
+            start = i + separator.len();
+            // The separator is ASCII, skip the rest of it
+            chars.nth(separator.len() - 2);
+        }
+    }
+    parts.push(&expression[start..]);
+    parts
+}
+
+/// Compiled search expression.
+///
+/// A search is a set of terms combined with `!` (negation), `&&`
+/// (conjunction) and `||` (disjunction), e.g. `http && !192.168.2.`.
+/// `!` binds the tightest, followed by `&&` and then `||`.
+///
+/// A term is a regular expression matched against the search text, or a
+/// comparison of a [`Field`] with a quoted value, e.g. `type == "_ssh._tcp"`
+/// or `txt.os != "macOS"`, which only matches entries that have fields, such
+/// as instances. The same expressions filter the TUI lists and the headless
+/// modes.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    Match(Regex),
+    /// The field equals the value, or differs from it when negated
+    Compare {
+        field: Field,
+        equal: bool,
+        value: String,
+    },
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+impl Filter {
+    /// Parse a search expression into a [`Filter`] tree.
+    pub fn parse(expression: &str) -> anyhow::Result<Self> {
+        let mut alternatives = split_unquoted(expression, "||")
+            .into_iter()
+            .map(Self::parse_conjunction)
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter();
+        let first = alternatives
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Empty search expression"))?;
+        Ok(alternatives.fold(first, |acc, filter| {
+            Filter::Or(Box::new(acc), Box::new(filter))
+        }))
+    }
+
+    fn parse_conjunction(expression: &str) -> anyhow::Result<Self> {
+        let mut terms = split_unquoted(expression, "&&")
+            .into_iter()
+            .map(Self::parse_term)
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter();
+        let first = terms
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Empty search expression"))?;
+        Ok(terms.fold(first, |acc, filter| {
+            Filter::And(Box::new(acc), Box::new(filter))
+        }))
+    }
+
+    fn parse_term(term: &str) -> anyhow::Result<Self> {
+        let term = term.trim();
+        if let Some(negated) = term.strip_prefix('!') {
+            Ok(Filter::Not(Box::new(Self::parse_term(negated)?)))
+        } else if term.is_empty() {
+            anyhow::bail!("Empty search term")
+        } else if let Some(compare) = Self::parse_comparison(term) {
+            compare
+        } else {
+            Ok(Filter::Match(
+                RegexBuilder::new(term)
+                    .size_limit(K_REGEX_SIZE_LIMIT)
+                    .nest_limit(K_REGEX_NEST_LIMIT)
+                    .build()?,
+            ))
+        }
+    }
+
+    /// `<field> == "value"` or `<field> != "value"`, `None` when `term`
+    /// doesn't compare a known field and is taken as a regular expression.
+    fn parse_comparison(term: &str) -> Option<anyhow::Result<Self>> {
+        // The first operator, as the value may hold the other
+        let (at, equal) = [("==", true), ("!=", false)]
+            .into_iter()
+            .filter_map(|(operator, equal)| Some((term.find(operator)?, equal)))
+            .min()?;
+        let (field, value) = (&term[..at], &term[at + 2..]);
+        let field = Field::parse(field)?;
+        let value = value.trim();
+        let Some(value) = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+        else {
+            return Some(Err(anyhow::anyhow!(
+                "The value compared with {field} must be quoted, e.g. {field} == \"value\""
+            )));
+        };
+        Some(Ok(Filter::Compare {
+            field,
+            equal,
+            value: value.to_string(),
+        }))
+    }
+
+    /// Check whether `haystack` satisfies the expression, with `info` the
+    /// fields are compared in, if any.
+    pub fn matches(&self, haystack: &str, info: Option<&ServiceInfo>) -> bool {
+        match self {
+            Filter::Match(regex) => regex.is_match(haystack),
+            Filter::Compare {
+                field,
+                equal,
+                value,
+            } => match info {
+                // A missing TXT key differs from any value
+                Some(info) => match field.value(info) {
+                    Some(actual) => field.equals(actual, value) == *equal,
+                    None => !equal,
+                },
+                None => false,
+            },
+            Filter::Not(filter) => !filter.matches(haystack, info),
+            Filter::And(lhs, rhs) => lhs.matches(haystack, info) && rhs.matches(haystack, info),
+            Filter::Or(lhs, rhs) => lhs.matches(haystack, info) || rhs.matches(haystack, info),
+        }
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Filter::Match(regex) => write!(f, "{}", regex),
+            Filter::Compare {
+                field,
+                equal,
+                value,
+            } => write!(
+                f,
+                "{field} {} \"{value}\"",
+                if *equal { "==" } else { "!=" }
+            ),
+            Filter::Not(filter) => write!(f, "!{}", filter),
+            Filter::And(lhs, rhs) => write!(f, "{} && {}", lhs, rhs),
+            Filter::Or(lhs, rhs) => write!(f, "{} || {}", lhs, rhs),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printer() -> ServiceInfo {
+        ServiceInfo::new(
+            "_ipp._tcp.local.",
+            "Office Printer",
+            "printer.local.",
+            "192.168.2.10",
+            631,
+            &[("os", "macOS"), ("rp", "ipp/print")][..],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn regex_terms_match_the_search_text() {
+        let filter = Filter::parse("http").unwrap();
+        assert!(filter.matches("_http._tcp.local.", None));
+        assert!(!filter.matches("_ipp._tcp.local.", None));
+    }
+
+    #[test]
+    fn negation_binds_tighter_than_and_tighter_than_or() {
+        let filter = Filter::parse("ipp && !192.168.2. || ssh").unwrap();
+        assert_eq!(filter.to_string(), "ipp && !192.168.2. || ssh");
+        assert!(matches!(filter, Filter::Or(..)));
+        assert!(filter.matches("_ipp._tcp 10.0.0.1", None));
+        assert!(!filter.matches("_ipp._tcp 192.168.2.10", None));
+        assert!(filter.matches("_ssh._tcp 192.168.2.10", None));
+    }
+
+    #[test]
+    fn double_negation_cancels_out() {
+        let filter = Filter::parse("!!ipp").unwrap();
+        assert!(filter.matches("_ipp._tcp", None));
+        assert!(!filter.matches("_ssh._tcp", None));
+    }
+
+    #[test]
+    fn empty_terms_are_rejected() {
+        for expression in ["", "  ", "ipp &&", "|| ipp", "!"] {
+            assert!(Filter::parse(expression).is_err(), "{expression:?}");
+        }
+    }
+
+    #[test]
+    fn invalid_and_oversized_regexes_are_rejected() {
+        assert!(Filter::parse("(ipp").is_err());
+        assert!(Filter::parse("(a{1000}){1000}").is_err());
+    }
+
+    #[test]
+    fn fields_compare_with_quoted_values() {
+        let info = printer();
+        let matching = [
+            "type == \"_ipp._tcp\"",
+            "type == \"_IPP._tcp.local.\"",
+            "hostname == \"Printer.local\"",
+            "name == \"Office Printer\"",
+            "txt.os == \"macOS\"",
+            "txt.os != \"Linux\"",
+            "txt.missing != \"anything\"",
+        ];
+        for expression in matching {
+            let filter = Filter::parse(expression).unwrap();
+            assert!(filter.matches("", Some(&info)), "{expression}");
+        }
+        let failing = [
+            "type != \"_ipp._tcp\"",
+            "txt.os == \"macos\"",
+            "txt.missing == \"anything\"",
+        ];
+        for expression in failing {
+            let filter = Filter::parse(expression).unwrap();
+            assert!(!filter.matches("", Some(&info)), "{expression}");
+        }
+    }
+
+    #[test]
+    fn comparisons_only_match_entries_with_fields() {
+        let filter = Filter::parse("type == \"_ipp._tcp\"").unwrap();
+        assert!(!filter.matches("_ipp._tcp.local.", None));
+    }
+
+    #[test]
+    fn comparisons_need_quoted_values() {
+        let error = Filter::parse("txt.os == macOS").unwrap_err();
+        assert!(error.to_string().contains("must be quoted"), "{error}");
+    }
+
+    #[test]
+    fn unknown_fields_are_taken_as_regexes() {
+        let filter = Filter::parse("port == \"631\"").unwrap();
+        assert!(matches!(filter, Filter::Match(_)));
+    }
+
+    #[test]
+    fn operators_within_quotes_are_values() {
+        let info = ServiceInfo::new(
+            "_http._tcp.local.",
+            "Dashboard",
+            "nas.local.",
+            "192.168.2.11",
+            80,
+            &[("path", "a||b"), ("query", "x==y&&z!=w")][..],
+        )
+        .unwrap();
+        let matching = [
+            "txt.path == \"a||b\"",
+            "txt.path == \"a||b\" && type == \"_http._tcp\"",
+            "txt.query == \"x==y&&z!=w\"",
+            "txt.query != \"x!=y\"",
+            "txt.path != \"a&&b\" || nothing",
+        ];
+        for expression in matching {
+            let filter = Filter::parse(expression).unwrap();
+            assert!(filter.matches("", Some(&info)), "{expression}");
+        }
+        let filter = Filter::parse("txt.path == \"a||b\" || ssh").unwrap();
+        assert_eq!(filter.to_string(), "txt.path == \"a||b\" || ssh");
+        assert!(matches!(filter, Filter::Or(..)));
+    }
+
+    #[test]
+    fn the_first_operator_compares() {
+        let filter = Filter::parse("txt.query != \"x==y\"").unwrap();
+        assert!(matches!(
+            filter,
+            Filter::Compare { equal: false, ref value, .. } if value == "x==y"
+        ));
+        let filter = Filter::parse("txt.query == \"x!=y\"").unwrap();
+        assert!(matches!(
+            filter,
+            Filter::Compare { equal: true, ref value, .. } if value == "x!=y"
+        ));
+    }
+
+    #[test]
+    fn unquoted_separators() {
+        assert_eq!(split_unquoted("a || b||c", "||"), ["a ", " b", "c"]);
+        assert_eq!(
+            split_unquoted("x == \"a||b\" || c", "||"),
+            ["x == \"a||b\" ", " c"]
+        );
+        assert_eq!(split_unquoted("é&&ü", "&&"), ["é", "ü"]);
+        assert_eq!(split_unquoted("", "&&"), [""]);
+        // An unterminated quote runs to the end
+        assert_eq!(split_unquoted("x == \"a||b", "||"), ["x == \"a||b"]);
+        assert!(Filter::parse("txt.path == \"a||b").is_err());
+    }
+
+    #[test]
+    fn field_names() {
+        assert_eq!(Field::parse(" hostname "), Some(Field::Hostname));
+        assert_eq!(Field::parse("txt.rp"), Some(Field::Txt("rp".to_string())));
+        assert_eq!(Field::parse("txt."), None);
+        assert_eq!(Field::parse("port"), None);
+    }
+}
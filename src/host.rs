@@ -0,0 +1,371 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::IpAddr;
+use std::time::SystemTime;
+
+use crossterm::event::{KeyCode, KeyEvent};
+use if_addrs::IfAddr;
+use ratatui::{prelude::*, widgets::*};
+
+use crate::audit::Action;
+use crate::colors::theme;
+use crate::list::ListEntry;
+use crate::model::Model;
+use crate::oui;
+use crate::widget::DiscoveryWidget;
+
+/// When a host was resolved for the first and the last time.
+#[derive(Debug, Clone, Copy)]
+pub struct Sighting {
+    pub first: SystemTime,
+    pub last: SystemTime,
+}
+
+impl Sighting {
+    /// A host resolved for the first time at `time`.
+    pub fn at(time: SystemTime) -> Self {
+        Self {
+            first: time,
+            last: time,
+        }
+    }
+}
+
+/// Everything known about a single host, across all the browsed types.
+///
+/// A snapshot of the [`Model`] taken when the card is opened, rendered as a
+/// set of cards: identity, addresses by the local interface they're reachable
+/// through, the notes taken on its instances and the services the host runs.
+#[derive(Debug)]
+pub struct Host {
+    hostname: String,
+    device_model: Option<String>,
+    sighting: Option<Sighting>,
+    /// Local interface name to the host addresses on its networks
+    addresses: BTreeMap<String, BTreeSet<IpAddr>>,
+    /// Hardware addresses from the neighbour table, and their vendors
+    macs: BTreeMap<IpAddr, (String, Option<String>)>,
+    /// Notes taken with `n` on the instances of the host: when, on which
+    /// instance and what, oldest first
+    notes: Vec<(SystemTime, String, String)>,
+    /// Instance fullnames and their ports
+    services: Vec<(String, u16)>,
+    /// Addresses other hosts resolve to as well, and those hosts
+    shared: Vec<(IpAddr, String)>,
+    /// Index of the other host picked in `shared`
+    linked: usize,
+    /// Host whose card to open instead, once picked
+    follow: Option<String>,
+    scroll: usize,
+}
+
+impl Host {
+    /// Gather what `model` knows about `hostname`.
+    pub fn collect(model: &Model, hostname: &str) -> Self {
+        let mut services = Vec::new();
+        let mut addresses = BTreeSet::new();
+        for list in model.instances().values_mut() {
+            for info in list.items_mut().filter(|info| info.id() == hostname) {
+                services.push((info.info.get_fullname().to_string(), info.info.get_port()));
+                addresses.extend(info.addresses());
+            }
+        }
+        services.sort();
+
+        let mut notes: Vec<_> = services
+            .iter()
+            .flat_map(|(fullname, _)| {
+                let instance = fullname.split('.').next().unwrap_or(fullname).to_string();
+                model
+                    .audit
+                    .entries(fullname)
+                    .into_iter()
+                    .filter_map(move |entry| match entry.action {
+                        Action::Noted(note) => Some((entry.at, instance.clone(), note)),
+                        _ => None,
+                    })
+            })
+            .collect();
+        notes.sort();
+
+        let neighbours = neighbours();
+        let macs = addresses
+            .iter()
+            .filter_map(|addr| {
+                let mac = neighbours.get(addr)?;
+                Some((*addr, (mac.clone(), oui::vendor(mac))))
+            })
+            .collect();
+
+        let shared = model
+            .shared
+            .of(addresses.iter().copied(), hostname)
+            .into_iter()
+            .flat_map(|(addr, others)| others.into_iter().map(move |other| (addr, other)))
+            .collect();
+
+        Self {
+            hostname: hostname.to_string(),
+            device_model: model.device_models.lock().get(hostname).cloned(),
+            sighting: model.sightings.lock().get(hostname).copied(),
+            addresses: by_interface(addresses),
+            macs,
+            notes,
+            services,
+            shared,
+            linked: 0,
+            follow: None,
+            scroll: 0,
+        }
+    }
+
+    /// The host picked among those sharing an address, whose card the owner
+    /// opens instead.
+    pub fn follow(&mut self) -> Option<String> {
+        self.follow.take()
+    }
+}
+
+/// Group `addresses` by the local interface whose network contains them.
+pub fn by_interface(addresses: BTreeSet<IpAddr>) -> BTreeMap<String, BTreeSet<IpAddr>> {
+    let interfaces = if_addrs::get_if_addrs().unwrap_or_default();
+    let mut grouped: BTreeMap<String, BTreeSet<IpAddr>> = BTreeMap::new();
+    for addr in addresses {
+        let interface = interfaces
+            .iter()
+            .find(|interface| match (&interface.addr, addr) {
+                (IfAddr::V4(local), IpAddr::V4(addr)) => {
+                    let mask = u32::from(local.netmask);
+                    u32::from(local.ip) & mask == u32::from(addr) & mask
+                }
+                (IfAddr::V6(local), IpAddr::V6(addr)) => {
+                    let mask = u128::from(local.netmask);
+                    u128::from(local.ip) & mask == u128::from(addr) & mask
+                }
+                _ => false,
+            })
+            .map_or_else(|| "unknown".to_string(), |interface| interface.name.clone());
+        grouped.entry(interface).or_default().insert(addr);
+    }
+    grouped
+}
+
+/// Hardware addresses of the neighbours the kernel knows about.
+///
+/// Only available on Linux, through `/proc/net/arp`, and only for IPv4.
+pub fn neighbours() -> HashMap<IpAddr, String> {
+    let Ok(table) = std::fs::read_to_string("/proc/net/arp") else {
+        return HashMap::new();
+    };
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            let mac = *fields.get(3)?;
+            // Incomplete entries have no hardware address yet
+            if mac == "00:00:00:00:00:00" {
+                return None;
+            }
+            Some((fields.first()?.parse().ok()?, mac.to_string()))
+        })
+        .collect()
+}
+
+/// Rough, human friendly age of `time`.
+pub fn ago(time: SystemTime) -> String {
+    let secs = time.elapsed().unwrap_or_default().as_secs();
+    match secs {
+        0..=59 => format!("{secs}s ago"),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+fn card(title: &str) -> Block<'_> {
+    Block::new()
+        .borders(Borders::ALL)
+        .title(title)
+        .title_style(Style::new().bold().light_cyan())
+        .padding(Padding::horizontal(1))
+}
+
+fn field<'a>(name: &'a str, value: String) -> Line<'a> {
+    Line::from(vec![
+        Span::styled(format!("{name:<11}"), Style::new().bold().light_cyan()),
+        Span::raw(value),
+    ])
+}
+
+impl DiscoveryWidget for Host {
+    fn title(&self) -> String {
+        self.hostname.clone()
+    }
+
+    fn controls(&self) -> String {
+        if self.shared.is_empty() {
+            "Use ↓↑ to scroll the services, Esc to close".to_string()
+        } else {
+            "Use ↓↑ to scroll the services, ←→ to pick a host sharing an address, ↵ to open its card, Esc to close".to_string()
+        }
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        match key_event.code {
+            KeyCode::Down => {
+                self.scroll = (self.scroll + 1).min(self.services.len().saturating_sub(1))
+            }
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Right if !self.shared.is_empty() => {
+                self.linked = (self.linked + 1) % self.shared.len()
+            }
+            KeyCode::Left if !self.shared.is_empty() => {
+                self.linked = (self.linked + self.shared.len() - 1) % self.shared.len()
+            }
+            KeyCode::Enter => {
+                self.follow = self.shared.get(self.linked).map(|(_, other)| other.clone())
+            }
+            _ => {}
+        }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(theme().selected_fg).bold())
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        let inner_area = block.inner(area);
+        block.render(area, buf);
+
+        let shared_height = if self.shared.is_empty() {
+            0
+        } else {
+            self.shared.len() as u16 + 2
+        };
+        let notes_height = if self.notes.is_empty() {
+            0
+        } else {
+            self.notes.len() as u16 + 2
+        };
+        let [cards_area, shared_area, notes_area, services_area] = Layout::vertical([
+            Constraint::Length(7),
+            Constraint::Length(shared_height),
+            Constraint::Length(notes_height),
+            Constraint::Min(0),
+        ])
+        .areas(inner_area);
+        let [identity_area, addresses_area] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(cards_area);
+
+        let (first, last) = match self.sighting {
+            Some(sighting) => (ago(sighting.first), ago(sighting.last)),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        let macs: BTreeSet<_> = self
+            .macs
+            .values()
+            .map(|(mac, vendor)| match vendor {
+                Some(vendor) => format!("{mac} ({vendor})"),
+                None => mac.clone(),
+            })
+            .collect();
+        Paragraph::new(vec![
+            field(
+                "Model",
+                self.device_model.clone().unwrap_or_else(|| "-".to_string()),
+            ),
+            field(
+                "MAC",
+                if macs.is_empty() {
+                    "-".to_string()
+                } else {
+                    macs.into_iter().collect::<Vec<_>>().join(" ")
+                },
+            ),
+            field("First seen", first),
+            field("Last seen", last),
+            field("Services", self.services.len().to_string()),
+        ])
+        .block(card("Identity"))
+        .render(identity_area, buf);
+
+        Paragraph::new(
+            self.addresses
+                .iter()
+                .map(|(interface, addresses)| {
+                    field(
+                        interface,
+                        addresses
+                            .iter()
+                            .map(IpAddr::to_string)
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .wrap(Wrap { trim: true })
+        .block(card("Addresses"))
+        .render(addresses_area, buf);
+
+        if !self.shared.is_empty() {
+            Paragraph::new(
+                self.shared
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (addr, other))| {
+                        let other = Span::raw(other.as_str());
+                        Line::from(vec![
+                            Span::styled(format!("{addr} "), theme().warning),
+                            Span::raw("also resolved by ").dark_gray(),
+                            if index == self.linked {
+                                other.reversed().fg(theme().selected_fg)
+                            } else {
+                                other
+                            },
+                        ])
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .block(card("Shared addresses"))
+            .render(shared_area, buf);
+        }
+
+        if !self.notes.is_empty() {
+            Paragraph::new(
+                self.notes
+                    .iter()
+                    .map(|(at, instance, note)| {
+                        Line::from(vec![
+                            Span::raw(format!("{:<9}", ago(*at))).dark_gray(),
+                            Span::raw(note.as_str()),
+                            Span::raw(format!(" on {instance}")).dark_gray(),
+                        ])
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .block(card("Notes"))
+            .render(notes_area, buf);
+        }
+
+        Paragraph::new(
+            self.services
+                .iter()
+                .skip(self.scroll)
+                .map(|(fullname, port)| {
+                    Line::from(vec![
+                        Span::raw(fullname.as_str()),
+                        Span::raw(format!(" :{port}")).dark_gray(),
+                    ])
+                })
+                .collect::<Vec<_>>(),
+        )
+        .block(card("Services"))
+        .render(services_area, buf);
+    }
+}
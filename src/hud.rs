@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use ratatui::{prelude::*, widgets::*};
+
+use crate::colors::theme;
+use crate::events::EventStats;
+use crate::model::Model;
+
+const K_FRAME_WINDOW: usize = 60;
+const K_HUD_WIDTH: u16 = 40;
+
+/// Debug overlay with the figures that matter when the UI feels slow.
+///
+/// Shows how long frames take to render, how far behind the model is and
+/// how long the UI and the model worker wait for each other.
+#[derive(Debug, Default)]
+pub struct Hud {
+    pub visible: bool,
+    frame_times: VecDeque<Duration>,
+}
+
+impl Hud {
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn record_frame(&mut self, time: Duration) {
+        self.frame_times.push_back(time);
+        while self.frame_times.len() > K_FRAME_WINDOW {
+            self.frame_times.pop_front();
+        }
+    }
+
+    /// Render in the top right corner of `area`.
+    pub fn render(&self, area: Rect, buf: &mut Buffer, stats: &EventStats, model: &Model) {
+        let last = self.frame_times.back().copied().unwrap_or_default();
+        let average =
+            self.frame_times.iter().sum::<Duration>() / (self.frame_times.len().max(1) as u32);
+        let slowest = self.frame_times.iter().max().copied().unwrap_or_default();
+        let view = model.view();
+        let services = view.services.len();
+        let instances = view.total;
+        let (wait_average, wait_max) = model.lock_wait.summary();
+
+        let lines = vec![
+            Line::from(format!(
+                "frame    {last:.1?} (avg {average:.1?}, max {slowest:.1?})"
+            )),
+            Line::from(format!(
+                "queue    {}/{}",
+                stats.depth.load(Ordering::Relaxed),
+                stats.capacity.load(Ordering::Relaxed)
+            )),
+            Line::from(format!(
+                "applied  {}",
+                stats.applied.load(Ordering::Relaxed)
+            )),
+            Line::from(format!(
+                "model    {services} types, {instances} instances, {} evicted",
+                model.evicted.load(Ordering::Relaxed)
+            )),
+            Line::from(format!(
+                "hosts    {} seen, {} models",
+                model.sightings.lock().len(),
+                model.device_models.lock().len()
+            )),
+            Line::from(format!(
+                "lock     avg {wait_average:.1?}, max {wait_max:.1?}"
+            )),
+        ];
+
+        let width = K_HUD_WIDTH.min(area.width);
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let hud_area = Rect::new(area.right() - width, area.top(), width, height);
+        Clear.render(hud_area, buf);
+        Paragraph::new(lines)
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .border_style(Style::new().fg(theme().search_border))
+                    .title("Debug")
+                    .bg(theme().header_bg),
+            )
+            .fg(theme().text)
+            .render(hud_area, buf);
+    }
+}
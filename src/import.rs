@@ -0,0 +1,258 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+use regex::Regex;
+
+use crate::avahi::{Record, RecordKind};
+use crate::colors::theme;
+use crate::domain;
+use crate::model::Model;
+use crate::widget::DiscoveryWidget;
+
+/// Instances browsed by another tool, loaded from its saved output.
+///
+/// Both `avahi-browse --parsable` and `dns-sd -B` output are understood, the
+/// format is told from the contents.
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub path: PathBuf,
+    /// Instance fullnames by service type
+    pub instances: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Import {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let instances = if contents.lines().any(|line| Record::parse(line).is_some()) {
+            parse_avahi(&contents)
+        } else if contents.lines().any(|line| line.starts_with("Timestamp")) {
+            parse_dns_sd(&contents)
+        } else {
+            anyhow::bail!(
+                "{} is neither avahi-browse --parsable nor dns-sd -B output",
+                path.display()
+            )
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            instances,
+        })
+    }
+}
+
+/// Replay `+`, `-` and `=` records, resolved ones count as added.
+fn parse_avahi(contents: &str) -> BTreeMap<String, BTreeSet<String>> {
+    let mut instances: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for record in contents.lines().filter_map(Record::parse) {
+        let service_type = domain::normalize(&record.ty_domain());
+        let fullname = format!("{}.{service_type}", record.name);
+        match record.kind {
+            RecordKind::Added | RecordKind::Resolved => {
+                instances.entry(service_type).or_default().insert(fullname);
+            }
+            RecordKind::Removed => {
+                if let Some(names) = instances.get_mut(&service_type) {
+                    names.remove(&fullname);
+                }
+            }
+        }
+    }
+    instances
+}
+
+/// Replay the `Add` and `Rmv` lines, e.g.
+///
+/// ```text
+/// Timestamp     A/R    Flags  if Domain               Service Type         Instance Name
+/// 12:00:00.123  Add        3   4 local.               _http._tcp.          My Printer
+/// ```
+fn parse_dns_sd(contents: &str) -> BTreeMap<String, BTreeSet<String>> {
+    let line_regex = Regex::new(r"^\S+\s+(Add|Rmv)\s+\S+\s+\S+\s+(\S+)\s+(\S+)\s+(.+)$")
+        .expect("The dns-sd line regex is valid");
+    let mut instances: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for captures in contents
+        .lines()
+        .filter_map(|line| line_regex.captures(line))
+    {
+        let service_type = domain::normalize(&format!("{}{}", &captures[3], &captures[2]));
+        let fullname = format!("{}.{service_type}", captures[4].trim_end());
+        if &captures[1] == "Add" {
+            instances.entry(service_type).or_default().insert(fullname);
+        } else if let Some(names) = instances.get_mut(&service_type) {
+            names.remove(&fullname);
+        }
+    }
+    instances
+}
+
+/// Side by side comparison of an [`Import`] with the live results.
+///
+/// Like the host card, a snapshot taken when opened.
+#[derive(Debug)]
+pub struct ImportDiff {
+    path: PathBuf,
+    /// Service type, then instance names and where they were seen
+    lines: Vec<Line<'static>>,
+    scroll: usize,
+}
+
+impl ImportDiff {
+    pub fn compare(import: &Import, model: &Model) -> Self {
+        let mut live: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for (service_type, list) in model.instances().iter() {
+            live.entry(service_type.clone()).or_default().extend(
+                list.items()
+                    .map(|info| info.info.get_fullname().to_string()),
+            );
+        }
+
+        let empty = BTreeSet::new();
+        let service_types: BTreeSet<_> = import.instances.keys().chain(live.keys()).collect();
+        let mut lines = Vec::new();
+        for service_type in service_types {
+            let imported = import.instances.get(service_type).unwrap_or(&empty);
+            let seen = live.get(service_type).unwrap_or(&empty);
+            lines.push(Line::from(service_type.clone()).bold().light_cyan());
+            for fullname in imported.union(seen) {
+                let name = fullname
+                    .strip_suffix(service_type.as_str())
+                    .map_or(fullname.as_str(), |name| name.trim_end_matches('.'));
+                lines.push(
+                    match (imported.contains(fullname), seen.contains(fullname)) {
+                        (true, true) => Line::from(format!("  {name}")),
+                        (true, false) => Line::from(format!("- {name}")).red(),
+                        _ => Line::from(format!("+ {name}")).green(),
+                    },
+                );
+            }
+        }
+
+        Self {
+            path: import.path.clone(),
+            lines,
+            scroll: 0,
+        }
+    }
+}
+
+impl DiscoveryWidget for ImportDiff {
+    fn title(&self) -> String {
+        format!("{} vs. live", self.path.display())
+    }
+
+    fn controls(&self) -> String {
+        "- only imported, + only live. Use ↓↑ to scroll, Esc to close".to_string()
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        match key_event.code {
+            KeyCode::Down => {
+                self.scroll = (self.scroll + 1).min(self.lines.len().saturating_sub(1))
+            }
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(theme().selected_fg).bold())
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .padding(Padding::horizontal(1))
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        Paragraph::new(
+            self.lines
+                .iter()
+                .skip(self.scroll)
+                .cloned()
+                .collect::<Vec<_>>(),
+        )
+        .block(block)
+        .render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(instances: &BTreeMap<String, BTreeSet<String>>, service_type: &str) -> Vec<String> {
+        instances
+            .get(service_type)
+            .map(|names| names.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn avahi_browse_output() {
+        let contents = "\
++;eth0;IPv4;Office\\032Printer;_ipp._tcp;local
++;eth0;IPv6;Office\\032Printer;_ipp._tcp;local
++;eth0;IPv4;nas;_smb._tcp;local
++;eth0;IPv4;kitchen;_smb._tcp;local
+=;eth0;IPv4;Office\\032Printer;_ipp._tcp;local;printer.local;192.168.2.10;631;\"rp=ipp/print\"
+=;eth0;IPv4;Living\\032Room;_airplay._tcp;local;tv.local;192.168.2.20;7000;
+-;eth0;IPv4;kitchen;_smb._tcp;local
+";
+        let instances = parse_avahi(contents);
+        assert_eq!(
+            instances.keys().collect::<Vec<_>>(),
+            [
+                "_airplay._tcp.local.",
+                "_ipp._tcp.local.",
+                "_smb._tcp.local."
+            ]
+        );
+        assert_eq!(
+            names(&instances, "_ipp._tcp.local."),
+            ["Office Printer._ipp._tcp.local."]
+        );
+        assert_eq!(
+            names(&instances, "_airplay._tcp.local."),
+            ["Living Room._airplay._tcp.local."]
+        );
+        assert_eq!(
+            names(&instances, "_smb._tcp.local."),
+            ["nas._smb._tcp.local."]
+        );
+    }
+
+    #[test]
+    fn dns_sd_output() {
+        let contents = "\
+Browsing for _http._tcp
+DATE: ---Fri 16 Oct 2026---
+12:00:00.001  ...STARTING...
+Timestamp     A/R    Flags  if Domain               Service Type         Instance Name
+12:00:00.123  Add        3   4 local.               _http._tcp.          My Printer
+12:00:00.124  Add        3   4 local.               _http._tcp.          NAS
+12:00:00.125  Add        2   4 Local.               _http._tcp.          Router  
+12:00:05.000  Rmv        0   4 local.               _http._tcp.          NAS
+";
+        let instances = parse_dns_sd(contents);
+        assert_eq!(instances.keys().collect::<Vec<_>>(), ["_http._tcp.local."]);
+        assert_eq!(
+            names(&instances, "_http._tcp.local."),
+            ["My Printer._http._tcp.local.", "Router._http._tcp.local."]
+        );
+        // Nothing in it reads as avahi-browse output
+        assert!(contents.lines().all(|line| Record::parse(line).is_none()));
+    }
+
+    #[test]
+    fn removals_of_unknown_instances_are_ignored() {
+        assert!(parse_avahi("-;eth0;IPv4;gone;_ssh._tcp;local").is_empty());
+        assert!(parse_dns_sd(
+            "12:00:05.000  Rmv        0   4 local.               _ssh._tcp.           gone"
+        )
+        .is_empty());
+    }
+}
@@ -1,90 +1,552 @@
-use crate::colors::*;
+use crate::audit::Audit;
+use crate::colors::theme;
+use crate::known;
+use crate::label::Label;
 use crate::list::ListEntry;
+use crate::overlay::{OverlayPeers, Peer};
+use crate::packet::{self, K_TYPE_A, K_TYPE_AAAA, K_TYPE_SRV, K_TYPE_TXT};
+use crate::ping::{Pings, Probe};
+use crate::portcheck::{PortChecks, PortState};
+use crate::reverse::{self, Lookup, ReverseNames};
+use crate::shared::SharedAddresses;
+use crate::sources::Sources;
 use crate::widget::DiscoveryWidget;
-use crossterm::event::KeyEvent;
+use crate::zone::Zones;
+use crossterm::event::{KeyCode, KeyEvent};
 use mdns_sd::ServiceInfo;
 use ratatui::{prelude::*, widgets::*};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// How long instances stay highlighted once bumped to the top.
+const K_HIGHLIGHT: Duration = Duration::from_secs(2);
+
+/// Most actions on the instance listed in the Audit row.
+const K_AUDIT_SHOWN: usize = 3;
+
+/// How long an instance has been around, which it is styled by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Age {
+    /// Resolved lately, shown bright
+    #[default]
+    New,
+    /// Around for a while, shown as usual
+    Settled,
+    /// Its records are about to run out, or have, shown dimmed
+    Expiring,
+}
+
+/// When instances stop being new, and how long before their records run
+/// out they are expiring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AgeThresholds {
+    pub new: Duration,
+    pub expiring: Duration,
+}
+
+impl Default for AgeThresholds {
+    fn default() -> Self {
+        Self {
+            new: Duration::from_secs(60),
+            expiring: Duration::from_secs(30),
+        }
+    }
+}
 
 /// [`ServiceInfo`] wrapper.
 ///
 /// Implements traits, necessary for the [`ServiceInfo`] to be
 /// rendered either as a [`Widget`] or simply as an entry in the [`List`]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Info {
     pub info: ServiceInfo,
+    /// The host isn't part of the profile's baseline
+    pub anomalous: bool,
+    /// Hardware model advertised by the host through `_device-info._tcp`
+    pub device_model: Option<String>,
+    /// Shown in place of the hostname, set by the scripts
+    pub name: Option<String>,
+    /// Set by the scripts
+    pub labels: Vec<String>,
+    /// Set by the label rules of the config
+    pub rule_labels: Vec<Label>,
+    /// Names and values of the computed columns of the config
+    pub computed: Vec<(String, String)>,
+    /// Reverse names to check the addresses against the SRV target
+    pub reverse_names: ReverseNames,
+    /// Round-trip times to the addresses, while pinged
+    pub pings: Pings,
+    /// Whether the port is listened on at the addresses, once checked
+    pub ports: PortChecks,
+    /// Where the records came from, to check them against the addresses
+    pub sources: Sources,
+    /// Overlay network peers, to tell them apart from LAN devices
+    pub overlay: OverlayPeers,
+    /// Addresses other hostnames resolve to as well
+    pub shared: SharedAddresses,
+    /// What has been done to the instance so far
+    pub audit: Audit,
+    /// First time the instance was resolved, the order it is listed in
+    pub first_seen: Instant,
+    /// Last time the instance was resolved, evictions go by it
+    pub last_seen: Instant,
+    /// When the records outlived their TTL, the instance is only kept for
+    /// the stale grace from then on
+    pub stale: Option<Instant>,
+    /// When the instance was last moved to the top for its activity, on
+    /// real time as it is only for the highlight
+    pub bumped: Option<Instant>,
+    /// Percentage of the selections among the instances of the same SRV
+    /// priority, when listed by priority
+    pub share: Option<u32>,
+    /// Kept up to date by the expiry
+    pub age: Age,
+    selected_address: usize,
 }
 
+impl Info {
+    /// `info` as resolved at `seen`.
+    pub fn new(info: ServiceInfo, seen: Instant) -> Self {
+        Self {
+            info,
+            anomalous: false,
+            device_model: None,
+            name: None,
+            labels: Vec::new(),
+            rule_labels: Vec::new(),
+            computed: Vec::new(),
+            reverse_names: ReverseNames::default(),
+            pings: Pings::default(),
+            ports: PortChecks::default(),
+            sources: Sources::default(),
+            overlay: OverlayPeers::default(),
+            shared: SharedAddresses::default(),
+            audit: Audit::default(),
+            first_seen: seen,
+            last_seen: seen,
+            stale: None,
+            bumped: None,
+            share: None,
+            age: Age::New,
+            selected_address: 0,
+        }
+    }
+
+    /// Resolved addresses in a stable order.
+    pub fn addresses(&self) -> Vec<IpAddr> {
+        let mut addresses: Vec<_> = self.info.get_addresses().iter().copied().collect();
+        addresses.sort();
+        addresses
+    }
+
+    /// The address currently picked in the Addresses row.
+    ///
+    /// This is the target for actions operating on a single address.
+    pub fn selected_address(&self) -> Option<IpAddr> {
+        let addresses = self.addresses();
+        addresses
+            .get(self.selected_address.min(addresses.len().saturating_sub(1)))
+            .copied()
+    }
+
+    /// Port to connect to, the type's well-known one when port 0 is advertised.
+    ///
+    /// Also tells whether the port is assumed rather than advertised.
+    pub fn port(&self) -> (u16, bool) {
+        match (self.info.get_port(), known::lookup(self.info.get_type())) {
+            (0, Some(known)) => (known.port, true),
+            (port, _) => (port, false),
+        }
+    }
+
+    /// URI of the instance, for types with a well-known scheme.
+    pub fn uri(&self) -> Option<String> {
+        let known = known::lookup(self.info.get_type())?;
+        let (port, _) = self.port();
+        Some(format!(
+            "{}://{}:{port}{}",
+            known.scheme,
+            self.info.get_hostname().trim_end_matches('.'),
+            known::path(&self.info)
+        ))
+    }
+
+    /// The overlay network peer the instance is on, by its first address on
+    /// one.
+    pub fn overlay_peer(&self) -> Option<Peer> {
+        self.addresses()
+            .iter()
+            .find_map(|addr| self.overlay.get(addr))
+    }
+
+    /// Whether another hostname resolves to any of the addresses.
+    pub fn shares_addresses(&self) -> bool {
+        !self.shared.of(self.addresses(), &self.id()).is_empty()
+    }
+
+    /// The mDNS reflector the instance was likely relayed by, see
+    /// [`Sources::reflector`].
+    pub fn reflector(&self) -> Option<IpAddr> {
+        self.sources
+            .reflector(self.info.get_fullname(), &self.addresses())
+    }
+
+    fn select_address_delta(&mut self, delta: isize) {
+        let len = self.info.get_addresses().len() as isize;
+        if len > 0 {
+            self.selected_address =
+                (self.selected_address as isize + delta).rem_euclid(len) as usize;
+        }
+    }
+}
+
+/// Instances are told apart by their fullname, a host may run several
+/// instances of the same type.
 impl PartialEq for Info {
     fn eq(&self, other: &Self) -> bool {
-        self.info.get_hostname() == other.info.get_hostname()
+        self.info.get_fullname() == other.info.get_fullname()
     }
 }
 
 impl ListEntry for Info {
-    fn entry(&self) -> Line {
-        Line::styled(format!("{}", self.info.get_hostname()), TEXT_COLOR)
+    fn entry(&self) -> Line<'_> {
+        let hostname = Span::styled(
+            self.name
+                .clone()
+                .unwrap_or_else(|| self.info.get_hostname().to_string()),
+            if self.anomalous {
+                Style::new().fg(theme().warning)
+            } else if self
+                .bumped
+                .is_some_and(|bumped| bumped.elapsed() < K_HIGHLIGHT)
+            {
+                Style::new().fg(theme().success).bold()
+            } else {
+                Style::new().fg(theme().text)
+            },
+        );
+        let hostname = match self.age {
+            Age::New => hostname.bold(),
+            Age::Settled => hostname,
+            Age::Expiring => hostname.dim(),
+        };
+        let mut spans = vec![hostname];
+        if self.age == Age::Expiring {
+            spans.push(" ◷".dark_gray());
+        }
+        if let Some(model) = self.device_model.as_ref() {
+            spans.push(format!(" ({model})").dark_gray());
+        }
+        spans.extend(self.rule_labels.iter().map(|label| {
+            Span::styled(
+                format!(" [{}]", label.name),
+                Style::new().fg(label.color.unwrap_or(Color::LightCyan)),
+            )
+        }));
+        spans.extend(
+            self.labels
+                .iter()
+                .map(|label| format!(" [{label}]").light_cyan()),
+        );
+        spans.extend(
+            self.computed
+                .iter()
+                .map(|(name, value)| format!(" {name}={value}").light_blue()),
+        );
+        if self.shares_addresses() {
+            spans.push(Span::styled(" [shared IP]", theme().warning));
+        }
+        if let Some(peer) = self.overlay_peer() {
+            spans.push(format!(" [{} {}]", peer.network, peer.name).light_magenta());
+        }
+        if self.reflector().is_some() {
+            spans.push(" [reflected]".dark_gray());
+        }
+        if self.stale.is_some() {
+            spans.push(" [stale]".dark_gray());
+        }
+        if let Some(share) = self.share {
+            spans.push(
+                format!(
+                    " p{} w{} {share}%",
+                    self.info.get_priority(),
+                    self.info.get_weight()
+                )
+                .dark_gray(),
+            );
+        }
+        Line::from(spans)
     }
 
     fn id(&self) -> String {
         self.info.get_hostname().to_string()
     }
+
+    /// The hostname and `label:<name>` for every label, so that labels can
+    /// be searched for.
+    fn search_text(&self) -> String {
+        self.rule_labels
+            .iter()
+            .map(|label| label.name.as_str())
+            .chain(self.labels.iter().map(String::as_str))
+            .chain(self.shares_addresses().then_some("shared-ip"))
+            .chain(self.overlay_peer().map(|peer| peer.network))
+            .chain(self.reflector().map(|_| "reflected"))
+            .chain(self.stale.map(|_| "stale"))
+            .fold(self.id(), |text, label| format!("{text} label:{label}"))
+    }
+
+    fn info(&self) -> Option<&ServiceInfo> {
+        Some(&self.info)
+    }
 }
 
-impl DiscoveryWidget for &Info {
+impl DiscoveryWidget for Info {
     fn title(&self) -> String {
-        self.id()
+        let name = match self.name.as_ref() {
+            Some(name) => format!("{name} [{}]", self.id()),
+            None => self.id(),
+        };
+        match self.device_model.as_ref() {
+            Some(model) => format!("{name} ({model})"),
+            None => name,
+        }
     }
 
     fn controls(&self) -> String {
-        "".to_string()
+        "Use ←→ to select an address, i to interact, h for the host card, w to query now, W to wake the host, x/X to forget the instance/host, c/C to copy a summary (C as markdown), m/n to mark as ignored/note in the audit trail, Esc to go back"
+            .to_string()
     }
 
-    fn process_key_event(&mut self, _key_event: &KeyEvent) {}
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        match key_event.code {
+            KeyCode::Left => self.select_address_delta(-1),
+            KeyCode::Right => self.select_address_delta(1),
+            _ => {}
+        }
+    }
 
     fn render(&self, area: Rect, buf: &mut Buffer, selected: bool) {
         let outer_block = Block::new()
             .borders(Borders::ALL)
             .border_style(if selected {
-                Style::new().fg(SELECTED_STYLE_FG)
+                Style::new().fg(theme().selected_fg)
             } else {
                 Style::default()
             })
             .title_alignment(Alignment::Center)
             .title(self.title())
             .title_style(Style::new().bold())
-            .fg(TEXT_COLOR)
-            .bg(HEADER_BG);
+            .fg(theme().text)
+            .bg(theme().header_bg);
         let inner_area = outer_block.inner(area);
         outer_block.render(area, buf);
 
         let inner_block = Block::new()
             .borders(Borders::NONE)
             .padding(Padding::horizontal(1))
-            .bg(NORMAL_ROW_COLOR);
+            .bg(theme().normal_row);
         let properties = textwrap::wrap(
             &self.info.get_properties().to_string(),
             // Fit to end, minus "properties" and cell spacing
             textwrap::Options::new((area.width as usize).saturating_sub(10 + 1)),
         )
         .join("\n");
+        // Grow with the pane, e.g. when zoomed, to show big TXT records in full
+        let properties_height = properties.lines().count().max(2) as u16;
+        let selected_address = self.selected_address();
+        let target = self.info.get_hostname();
+        let zones = Zones::local();
+        let addresses = self
+            .addresses()
+            .into_iter()
+            .map(|addr| {
+                let span = Span::raw(zones.display(addr));
+                let span = if selected && Some(addr) == selected_address {
+                    span.reversed().fg(theme().selected_fg)
+                } else {
+                    span
+                };
+                // Flag addresses whose name isn't the SRV target
+                let mut spans = match self.reverse_names.get(&addr) {
+                    Lookup::Found(name) if !reverse::same_host(&name, target) => {
+                        vec![
+                            span,
+                            Span::styled(format!(" (PTR {name})"), theme().warning),
+                        ]
+                    }
+                    Lookup::Missing => vec![span, " (no PTR)".dark_gray()],
+                    _ => vec![span],
+                };
+                let others = self.shared.others(&addr, &self.id());
+                if !others.is_empty() {
+                    spans.push(Span::styled(
+                        format!(" (also {})", others.join(", ")),
+                        theme().warning,
+                    ));
+                }
+                if let Some(peer) = self.overlay.get(&addr) {
+                    spans.push(format!(" ({} {})", peer.network, peer.name).light_magenta());
+                }
+                spans
+            })
+            .fold(Vec::new(), |mut acc, spans| {
+                if !acc.is_empty() {
+                    acc.push(Span::raw(" "));
+                }
+                acc.extend(spans);
+                acc
+            });
+        let (port, _) = self.port();
+        let pings: Vec<_> = self
+            .addresses()
+            .into_iter()
+            .filter_map(|addr| {
+                let probe = self.pings.get(&zones.socket_addr(addr, port))?;
+                let outcome = match probe {
+                    Probe::Pending => "…".dark_gray(),
+                    Probe::Reachable { rtt, method } => Span::raw(format!(
+                        "{:.1} ms ({})",
+                        rtt.as_secs_f64() * 1000.,
+                        method.name()
+                    )),
+                    Probe::Unreachable(method) => {
+                        Span::styled(format!("unreachable ({})", method.name()), theme().warning)
+                    }
+                };
+                Some(vec![
+                    Span::raw(format!("{} ", zones.display(addr))),
+                    outcome,
+                ])
+            })
+            .fold(Vec::new(), |mut acc, spans| {
+                if !acc.is_empty() {
+                    acc.push(Span::raw("  "));
+                }
+                acc.extend(spans);
+                acc
+            });
+        let pings = if pings.is_empty() {
+            Line::from("- (P to ping)".dark_gray())
+        } else {
+            Line::from(pings)
+        };
+        let target = if self.info.get_addresses().is_empty() {
+            Line::from(vec![
+                Span::raw(target),
+                Span::styled(" never resolved", theme().warning),
+            ])
+        } else {
+            Line::from(target)
+        };
+        let sources = self.sources.get(self.info.get_fullname());
+        let advertised = self.info.get_addresses();
+        let source = if sources.is_empty() {
+            Line::from("unknown".dark_gray())
+        } else {
+            let listed = sources
+                .iter()
+                .map(|addr| zones.display(*addr))
+                .collect::<Vec<_>>()
+                .join(" ");
+            if let Some(reflector) = self.reflector() {
+                Line::from(vec![
+                    Span::raw(listed),
+                    Span::styled(
+                        format!(" (reflected by {})", zones.display(reflector)),
+                        theme().warning,
+                    ),
+                ])
+            } else if advertised.is_empty() || sources.iter().any(|addr| advertised.contains(addr))
+            {
+                Line::from(listed)
+            } else {
+                // Relayed by a proxy, or spoofed
+                Line::from(vec![
+                    Span::raw(listed),
+                    Span::styled(" (not an advertised address)", theme().warning),
+                ])
+            }
+        };
+        let hostname = self.info.get_hostname();
+        let denied: Vec<_> = self
+            .sources
+            .denied(self.info.get_fullname(), &[K_TYPE_SRV, K_TYPE_TXT])
+            .into_iter()
+            .map(packet::type_name)
+            .chain(
+                self.sources
+                    .denied(hostname, &[K_TYPE_A, K_TYPE_AAAA])
+                    .into_iter()
+                    .map(|record_type| format!("{} of {hostname}", packet::type_name(record_type))),
+            )
+            .collect();
+        let denied = if denied.is_empty() {
+            Line::from("-")
+        } else {
+            Line::from(denied.join(", "))
+        };
+        // The latest actions, newest first
+        let entries = self.audit.entries(self.info.get_fullname());
+        let mut audit: Vec<Line> = entries
+            .iter()
+            .rev()
+            .take(K_AUDIT_SHOWN)
+            .map(|entry| Line::from(entry.to_string()))
+            .collect();
+        if entries.len() > K_AUDIT_SHOWN {
+            audit
+                .push(Line::from(format!("{} earlier", entries.len() - K_AUDIT_SHOWN)).dark_gray());
+        }
+        if audit.is_empty() {
+            audit.push(Line::from("-"));
+        }
+        let audit_height = audit.len() as u16;
+        let checks: Vec<_> = self
+            .addresses()
+            .into_iter()
+            .filter_map(|addr| {
+                let state = self.ports.get(&zones.socket_addr(addr, port))?;
+                let outcome = match state {
+                    PortState::Checking => "…".dark_gray(),
+                    PortState::Open(rtt) => {
+                        Span::raw(format!("open ({:.1} ms)", rtt.as_secs_f64() * 1000.))
+                    }
+                    PortState::Closed | PortState::Filtered => {
+                        Span::styled(state.name(), theme().warning)
+                    }
+                };
+                Some([Span::raw(format!("  {} ", zones.display(addr))), outcome])
+            })
+            .flatten()
+            .collect();
+        let mut port = match self.port() {
+            (port, true) => vec![
+                Span::raw(port.to_string()),
+                " (assumed, 0 advertised)".dark_gray(),
+            ],
+            (port, false) => vec![Span::raw(port.to_string())],
+        };
+        if checks.is_empty() {
+            port.push(" (o to check)".dark_gray());
+        } else {
+            port.extend(checks);
+        }
+        let port = Line::from(port);
         let rows = [
+            Row::new([Cell::new("Target").bold().light_cyan(), target.into()]),
             Row::new([
-                Cell::new("Hostname").bold().light_cyan(),
-                self.info.get_hostname().into(),
+                Cell::new("Model").bold().light_cyan(),
+                self.device_model.as_deref().unwrap_or("-").into(),
             ]),
             Row::new([
                 Cell::new("Addresses").bold().light_cyan(),
-                self.info
-                    .get_addresses()
-                    .into_iter()
-                    .map(|addr| addr.to_string())
-                    .fold(String::new(), |acc, addr| acc + &addr + " ")
-                    .into(),
-            ]),
-            Row::new([
-                Cell::new("Port").bold().light_cyan(),
-                self.info.get_port().to_string().into(),
+                Line::from(addresses).into(),
             ]),
+            Row::new([Cell::new("Ping").bold().light_cyan(), pings.into()]),
+            Row::new([Cell::new("Source").bold().light_cyan(), source.into()]),
+            Row::new([Cell::new("Denied").bold().light_cyan(), denied.into()]),
+            Row::new([Cell::new("Port").bold().light_cyan(), port.into()]),
             Row::new([
                 Cell::new("Host TTL").bold().light_cyan(),
                 self.info.get_host_ttl().to_string().into(),
@@ -101,11 +563,16 @@ impl DiscoveryWidget for &Info {
                 Cell::new("Weight").bold().light_cyan(),
                 self.info.get_weight().to_string().into(),
             ]),
+            Row::new([
+                Cell::new("Audit").bold().light_cyan(),
+                Text::from(audit).into(),
+            ])
+            .height(audit_height),
             Row::new([
                 Cell::new("Properties").bold().light_cyan(),
                 Cell::new(properties),
             ])
-            .height(2),
+            .height(properties_height),
         ];
         let widths = [Constraint::Percentage(10), Constraint::Percentage(90)];
 
@@ -0,0 +1,215 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyCode, KeyEvent};
+use flume::{Receiver, Sender, TryRecvError};
+use parking_lot::Mutex;
+use ratatui::{prelude::*, widgets::*};
+
+use crate::colors::theme;
+use crate::widget::DiscoveryWidget;
+
+const K_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const K_WRITE_TIMEOUT: Duration = Duration::from_secs(3);
+const K_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const K_IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+const K_MAX_TRANSCRIPT_LINES: usize = 500;
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Sent(String),
+    Received(String),
+    Status(String),
+}
+
+type Transcript = Arc<Mutex<Vec<Entry>>>;
+
+fn record(transcript: &Transcript, entry: Entry) {
+    let mut transcript = transcript.lock();
+    transcript.push(entry);
+    let len = transcript.len();
+    if len > K_MAX_TRANSCRIPT_LINES {
+        transcript.drain(..len - K_MAX_TRANSCRIPT_LINES);
+    }
+}
+
+/// Tiny line-based TCP console.
+///
+/// Opens a raw TCP connection to a resolved instance and lets the user
+/// exchange a few lines with it, e.g. to check a service banner. All socket
+/// I/O happens on a dedicated thread with connect/write/idle timeouts, so a
+/// misbehaving peer can never stall the UI. The connection is closed once
+/// the widget is dropped.
+#[derive(Debug)]
+pub struct Interact {
+    target: SocketAddr,
+    input: String,
+    transcript: Transcript,
+    outgoing: Sender<String>,
+}
+
+impl Interact {
+    pub fn connect(target: SocketAddr) -> Self {
+        let transcript = Arc::new(Mutex::new(vec![Entry::Status(format!(
+            "Connecting to {target}..."
+        ))]));
+        let (outgoing_tx, outgoing_rx) = flume::unbounded();
+
+        {
+            let transcript = transcript.clone();
+            std::thread::spawn(move || {
+                let _span = tracing::span!(tracing::Level::TRACE, "Interact worker").entered();
+                let status = match Self::session(target, &transcript, outgoing_rx) {
+                    Ok(()) => "Connection closed".to_string(),
+                    Err(e) => format!("Connection error: {e}"),
+                };
+                tracing::debug!("{target}: {status}");
+                record(&transcript, Entry::Status(status));
+            });
+        }
+
+        Self {
+            target,
+            input: String::new(),
+            transcript,
+            outgoing: outgoing_tx,
+        }
+    }
+
+    fn session(
+        target: SocketAddr,
+        transcript: &Transcript,
+        outgoing: Receiver<String>,
+    ) -> anyhow::Result<()> {
+        let mut stream = TcpStream::connect_timeout(&target, K_CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(K_POLL_INTERVAL))?;
+        stream.set_write_timeout(Some(K_WRITE_TIMEOUT))?;
+        record(transcript, Entry::Status("Connected".to_string()));
+
+        let mut pending = String::new();
+        let mut buf = [0u8; 4096];
+        let mut last_activity = Instant::now();
+        loop {
+            match outgoing.try_recv() {
+                Ok(line) => {
+                    stream.write_all(format!("{line}\r\n").as_bytes())?;
+                    record(transcript, Entry::Sent(line));
+                    last_activity = Instant::now();
+                }
+                Err(TryRecvError::Disconnected) => return Ok(()),
+                Err(TryRecvError::Empty) => {}
+            }
+
+            match stream.read(&mut buf) {
+                Ok(0) => {
+                    if !pending.is_empty() {
+                        record(transcript, Entry::Received(std::mem::take(&mut pending)));
+                    }
+                    return Ok(());
+                }
+                Ok(n) => {
+                    pending.push_str(&String::from_utf8_lossy(&buf[..n]));
+                    while let Some(pos) = pending.find('\n') {
+                        let line: String = pending.drain(..=pos).collect();
+                        record(
+                            transcript,
+                            Entry::Received(line.trim_end_matches(['\r', '\n']).to_string()),
+                        );
+                    }
+                    last_activity = Instant::now();
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    // Flush prompts that don't end with a new line
+                    if !pending.is_empty() {
+                        record(transcript, Entry::Received(std::mem::take(&mut pending)));
+                    }
+                    if last_activity.elapsed() > K_IDLE_TIMEOUT {
+                        anyhow::bail!("idle for {}s", K_IDLE_TIMEOUT.as_secs());
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl DiscoveryWidget for Interact {
+    fn title(&self) -> String {
+        format!("Interact: {}", self.target)
+    }
+
+    fn controls(&self) -> String {
+        "Use ↵ to send a line. Esc to disconnect".to_string()
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        match key_event.code {
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Enter => {
+                let line = std::mem::take(&mut self.input);
+                // The worker may be gone already, the transcript will tell why
+                let _ = self.outgoing.send(line);
+            }
+            _ => {}
+        }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(theme().search_border).bold())
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        let inner_area = block.inner(area);
+        block.render(area, buf);
+
+        let [transcript_area, input_area, footer_area] = Layout::vertical([
+            Constraint::Min(0),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(inner_area);
+
+        let transcript = self.transcript.lock();
+        let lines: Vec<_> = transcript
+            .iter()
+            .skip(
+                transcript
+                    .len()
+                    .saturating_sub(transcript_area.height as usize),
+            )
+            .map(|entry| match entry {
+                Entry::Sent(line) => Line::from(vec![
+                    Span::styled("> ", Style::default().fg(Color::DarkGray)),
+                    Span::raw(line.as_str()),
+                ]),
+                Entry::Received(line) => Line::from(vec![
+                    Span::styled("< ", Style::default().fg(Color::DarkGray)),
+                    Span::raw(line.as_str()),
+                ]),
+                Entry::Status(line) => Line::styled(line.as_str(), Style::new().italic()),
+            })
+            .collect();
+        Paragraph::new(lines)
+            .block(Block::new().bg(theme().normal_row))
+            .render(transcript_area, buf);
+
+        Paragraph::new(Line::from(vec![
+            Span::styled(" > ", Style::default().fg(Color::DarkGray)),
+            Span::from(self.input.as_str()),
+        ]))
+        .render(input_area, buf);
+
+        Paragraph::new(self.controls())
+            .centered()
+            .render(footer_area, buf);
+    }
+}
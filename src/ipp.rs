@@ -0,0 +1,420 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use flume::Receiver;
+
+const K_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// Printers may take a while to spool the job before answering.
+const K_ANSWER_TIMEOUT: Duration = Duration::from_secs(30);
+const K_PRINT_JOB: u16 = 0x0002;
+const K_JOB_NAME: &str = "discovery-rs test page";
+
+/// IPP attribute tags, RFC 8010 section 3.5.
+const K_OPERATION_ATTRIBUTES: u8 = 0x01;
+const K_END_OF_ATTRIBUTES: u8 = 0x03;
+const K_INTEGER: u8 = 0x21;
+const K_TEXT: u8 = 0x41;
+const K_NAME: u8 = 0x42;
+const K_URI: u8 = 0x45;
+const K_CHARSET: u8 = 0x47;
+const K_LANGUAGE: u8 = 0x48;
+const K_MIME_TYPE: u8 = 0x49;
+
+/// Document formats the test page can be written in, the preferred first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Pdf,
+    PostScript,
+    Text,
+}
+
+impl Format {
+    const K_ALL: [Format; 3] = [Format::Pdf, Format::PostScript, Format::Text];
+
+    fn mime_type(self) -> &'static str {
+        match self {
+            Format::Pdf => "application/pdf",
+            Format::PostScript => "application/postscript",
+            Format::Text => "text/plain",
+        }
+    }
+
+    /// The first format in the `pdl` TXT value of the printer, PDF when it
+    /// has none.
+    fn pick(pdl: Option<&str>) -> anyhow::Result<Self> {
+        let Some(pdl) = pdl else {
+            return Ok(Format::Pdf);
+        };
+        let accepted: Vec<_> = pdl.split(',').map(str::trim).collect();
+        Self::K_ALL
+            .into_iter()
+            .find(|format| accepted.contains(&format.mime_type()))
+            .with_context(|| format!("The printer accepts none of PDF, PostScript or text: {pdl}"))
+    }
+
+    /// A page with `lines` of text in large print.
+    fn page(self, lines: &[String]) -> Vec<u8> {
+        // Only ASCII is sure to come out right in the standard fonts
+        let lines: Vec<String> = lines
+            .iter()
+            .map(|line| {
+                line.chars()
+                    .map(|c| {
+                        if c.is_ascii() && !c.is_ascii_control() {
+                            c
+                        } else {
+                            '?'
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let escaped = || {
+            lines.iter().map(|line| {
+                line.replace('\\', "\\\\")
+                    .replace('(', "\\(")
+                    .replace(')', "\\)")
+            })
+        };
+        match self {
+            Format::Text => (lines.join("\r\n") + "\r\n\x0c").into_bytes(),
+            Format::PostScript => {
+                let mut ps =
+                    "%!PS-Adobe-3.0\n/Helvetica findfont 20 scalefont setfont\n".to_string();
+                for (i, line) in escaped().enumerate() {
+                    ps += &format!("72 {} moveto ({line}) show\n", 720 - 32 * i);
+                }
+                (ps + "showpage\n").into_bytes()
+            }
+            Format::Pdf => {
+                let mut content = "BT /F1 20 Tf 72 720 Td 32 TL\n".to_string();
+                for line in escaped() {
+                    content += &format!("({line}) '\n");
+                }
+                content += "ET";
+                let objects = [
+                    "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+                    "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 792] \
+                     /Resources << /Font << /F1 4 0 R >> >> /Contents 5 0 R >>"
+                        .to_string(),
+                    "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+                    format!(
+                        "<< /Length {} >>\nstream\n{content}\nendstream",
+                        content.len()
+                    ),
+                ];
+                let mut pdf = "%PDF-1.4\n".to_string();
+                let mut offsets = Vec::new();
+                for (i, object) in objects.iter().enumerate() {
+                    offsets.push(pdf.len());
+                    pdf += &format!("{} 0 obj\n{object}\nendobj\n", i + 1);
+                }
+                let xref = pdf.len();
+                pdf += &format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+                for offset in offsets {
+                    pdf += &format!("{offset:010} 00000 n \n");
+                }
+                pdf += &format!(
+                    "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+                    objects.len() + 1
+                );
+                pdf.into_bytes()
+            }
+        }
+    }
+}
+
+fn attribute(request: &mut Vec<u8>, tag: u8, name: &str, value: &[u8]) {
+    request.push(tag);
+    request.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    request.extend_from_slice(name.as_bytes());
+    request.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    request.extend_from_slice(value);
+}
+
+/// The status code of an IPP response, with its `job-id` and
+/// `status-message` attributes when it has them.
+fn parse(response: &[u8]) -> anyhow::Result<(u16, Option<u32>, Option<String>)> {
+    let status = response
+        .get(2..4)
+        .map(|status| u16::from_be_bytes([status[0], status[1]]))
+        .context("The printer sent a truncated IPP response")?;
+    let (mut job_id, mut message) = (None, None);
+    let mut pos = 8;
+    while let Some(&tag) = response.get(pos) {
+        pos += 1;
+        if tag == K_END_OF_ATTRIBUTES {
+            break;
+        }
+        // Group delimiters come without a name or a value
+        if tag < 0x10 {
+            continue;
+        }
+        let mut field = || {
+            let len = usize::from(u16::from_be_bytes(
+                response.get(pos..pos + 2)?.try_into().ok()?,
+            ));
+            let end = pos.checked_add(2 + len)?;
+            let value = response.get(pos + 2..end)?;
+            pos = end;
+            Some(value)
+        };
+        let (Some(name), Some(value)) = (field(), field()) else {
+            break;
+        };
+        match (tag, name) {
+            (K_INTEGER, b"job-id") if value.len() == 4 => {
+                job_id = Some(u32::from_be_bytes(value.try_into().unwrap()));
+            }
+            (K_TEXT, b"status-message") => {
+                message = Some(String::from_utf8_lossy(value).into_owned());
+            }
+            _ => {}
+        }
+    }
+    Ok((status, job_id, message))
+}
+
+/// The body of an HTTP response, unchunked.
+fn body(response: &[u8]) -> anyhow::Result<(u16, Vec<u8>)> {
+    let end = response
+        .windows(4)
+        .position(|end| end == b"\r\n\r\n")
+        .context("The printer sent a truncated HTTP response")?;
+    let head = String::from_utf8_lossy(&response[..end]).to_ascii_lowercase();
+    let status = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .context("The printer didn't answer over HTTP")?;
+    let mut rest = &response[end + 4..];
+    if !head.contains("transfer-encoding: chunked") {
+        return Ok((status, rest.to_vec()));
+    }
+    let mut body = Vec::new();
+    while let Some(line) = rest.windows(2).position(|end| end == b"\r\n") {
+        let len = std::str::from_utf8(&rest[..line])
+            .ok()
+            .and_then(|len| usize::from_str_radix(len.split(';').next()?.trim(), 16).ok())
+            .context("The printer sent a malformed chunk")?;
+        if len == 0 {
+            break;
+        }
+        // Lengths near usize::MAX would overflow the offsets
+        let end = (line + 2)
+            .checked_add(len)
+            .context("The printer sent a malformed chunk")?;
+        let next = (line + 4)
+            .checked_add(len)
+            .context("The printer sent a malformed chunk")?;
+        let chunk = rest
+            .get(line + 2..end)
+            .context("The printer sent a truncated chunk")?;
+        body.extend_from_slice(chunk);
+        rest = rest.get(next..).unwrap_or_default();
+    }
+    Ok((status, body))
+}
+
+fn print(target: SocketAddr, resource: &str, pdl: Option<&str>, name: &str) -> anyhow::Result<u32> {
+    let format = Format::pick(pdl)?;
+    let document = format.page(&[
+        "discovery-rs test page".to_string(),
+        String::new(),
+        format!("Printer: {name}"),
+        format!("Address: {target}"),
+        format!(
+            "Printed: {}",
+            humantime::format_rfc3339_seconds(SystemTime::now())
+        ),
+    ]);
+
+    let path = format!("/{}", resource.trim_start_matches('/'));
+    let user = std::env::var("USER").unwrap_or_else(|_| "discovery-rs".to_string());
+    let mut request = vec![2, 0];
+    request.extend_from_slice(&K_PRINT_JOB.to_be_bytes());
+    request.extend_from_slice(&1u32.to_be_bytes());
+    request.push(K_OPERATION_ATTRIBUTES);
+    attribute(&mut request, K_CHARSET, "attributes-charset", b"utf-8");
+    attribute(
+        &mut request,
+        K_LANGUAGE,
+        "attributes-natural-language",
+        b"en",
+    );
+    attribute(
+        &mut request,
+        K_URI,
+        "printer-uri",
+        format!("ipp://{target}{path}").as_bytes(),
+    );
+    attribute(
+        &mut request,
+        K_NAME,
+        "requesting-user-name",
+        user.as_bytes(),
+    );
+    attribute(&mut request, K_NAME, "job-name", K_JOB_NAME.as_bytes());
+    attribute(
+        &mut request,
+        K_MIME_TYPE,
+        "document-format",
+        format.mime_type().as_bytes(),
+    );
+    request.push(K_END_OF_ATTRIBUTES);
+    request.extend(document);
+
+    let mut stream = TcpStream::connect_timeout(&target, K_CONNECT_TIMEOUT)
+        .with_context(|| format!("Failed to connect to {target}"))?;
+    stream.set_read_timeout(Some(K_ANSWER_TIMEOUT))?;
+    let head = format!(
+        "POST {path} HTTP/1.1\r\nHost: {target}\r\nContent-Type: application/ipp\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        request.len()
+    );
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(&request)?;
+    let mut response = Vec::new();
+    stream
+        .read_to_end(&mut response)
+        .context("The printer didn't answer")?;
+
+    let (status, body) = body(&response)?;
+    match status {
+        200 => {}
+        401 | 403 => anyhow::bail!("The printer asks for authentication, which isn't supported"),
+        426 => anyhow::bail!("The printer only prints over TLS, which isn't supported"),
+        status => anyhow::bail!("The printer refused the job: HTTP {status}"),
+    }
+    let (status, job_id, message) = parse(&body)?;
+    // Successful status codes are 0x0000 to 0x00ff, some with warnings
+    if status > 0x00ff {
+        anyhow::bail!(
+            "The printer refused the job: {}",
+            message.unwrap_or_else(|| format!("IPP status {status:#06x}"))
+        );
+    }
+    Ok(job_id.unwrap_or_default())
+}
+
+/// Print a page naming the printer at `target` on it, in the background,
+/// to tell which of the printers in the office it is.
+///
+/// `resource` and `pdl` are the `rp` and `pdl` TXT values of the printer,
+/// the queue's path and the document formats it accepts. The page is
+/// written in PDF, PostScript or plain text, the first the printer accepts.
+/// Reports the job ID the printer assigned.
+pub fn print_test_page(
+    target: SocketAddr,
+    resource: &str,
+    pdl: Option<&str>,
+    name: &str,
+) -> Receiver<anyhow::Result<u32>> {
+    let (tx, rx) = flume::bounded(1);
+    let resource = resource.to_string();
+    let pdl = pdl.map(str::to_string);
+    let name = name.to_string();
+    std::thread::spawn(move || {
+        let _ = tx.send(print(target, &resource, pdl.as_deref(), &name));
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// CUPS's answer to a Print-Job request: job 12 was queued.
+    const K_PRINT_JOB_RESPONSE: &[u8] = b"\
+        \x01\x01\x00\x00\x00\x00\x00\x01\
+        \x01\
+        \x47\x00\x12attributes-charset\x00\x05utf-8\
+        \x48\x00\x1battributes-natural-language\x00\x02en\
+        \x41\x00\x0estatus-message\x00\x0dsuccessful-ok\
+        \x02\
+        \x45\x00\x07job-uri\x00\x1fipp://printer.local:631/jobs/12\
+        \x21\x00\x06job-id\x00\x04\x00\x00\x00\x0c\
+        \x23\x00\x09job-state\x00\x04\x00\x00\x00\x03\
+        \x44\x00\x11job-state-reasons\x00\x04none\
+        \x03";
+
+    #[test]
+    fn print_job_responses() {
+        assert_eq!(
+            parse(K_PRINT_JOB_RESPONSE).unwrap(),
+            (0x0000, Some(12), Some("successful-ok".to_string()))
+        );
+        // client-error-document-format-not-supported, without any job
+        assert_eq!(
+            parse(b"\x01\x01\x04\x0a\x00\x00\x00\x01\x01\x03").unwrap(),
+            (0x040a, None, None)
+        );
+    }
+
+    #[test]
+    fn truncated_responses() {
+        assert_eq!(
+            parse(b"\x01\x01\x04").unwrap_err().to_string(),
+            "The printer sent a truncated IPP response"
+        );
+        // The job attributes group is cut short before the job ID
+        let cut = K_PRINT_JOB_RESPONSE.len() - 56;
+        assert_eq!(
+            parse(&K_PRINT_JOB_RESPONSE[..cut]).unwrap(),
+            (0x0000, None, Some("successful-ok".to_string()))
+        );
+        // A value said to be longer than the rest of the response
+        assert_eq!(
+            parse(b"\x01\x01\x00\x00\x00\x00\x00\x01\x01\x21\x00\x06job-id\xff\xff\x00\x00")
+                .unwrap(),
+            (0x0000, None, None)
+        );
+        assert_eq!(
+            parse(b"\x01\x01\x00\x00\x00\x00\x00\x01\x01\x41\x00\x0estatus-message\x00\x0dsuccess")
+                .unwrap(),
+            (0x0000, None, None)
+        );
+    }
+
+    #[test]
+    fn plain_bodies() {
+        let response =
+            b"HTTP/1.1 200 OK\r\nContent-Type: application/ipp\r\nContent-Length: 4\r\n\r\nbody";
+        assert_eq!(body(response).unwrap(), (200, b"body".to_vec()));
+    }
+
+    #[test]
+    fn chunked_bodies() {
+        let response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            4\r\nWiki\r\n7;ext=1\r\npedia i\r\n0\r\n\r\n";
+        assert_eq!(body(response).unwrap(), (200, b"Wikipedia i".to_vec()));
+    }
+
+    #[test]
+    fn broken_responses() {
+        let error = |response: &[u8]| body(response).unwrap_err().to_string();
+        assert_eq!(
+            error(b"HTTP/1.1 200 OK\r\nContent-Length: 4"),
+            "The printer sent a truncated HTTP response"
+        );
+        assert_eq!(
+            error(b"\x01\x01\x00\x00\r\n\r\n"),
+            "The printer didn't answer over HTTP"
+        );
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+        assert_eq!(
+            error(&[&chunked[..], b"zz\r\nWiki\r\n"].concat()),
+            "The printer sent a malformed chunk"
+        );
+        assert_eq!(
+            error(&[&chunked[..], b"ffffffffffffffff\r\nWiki\r\n"].concat()),
+            "The printer sent a malformed chunk"
+        );
+        assert_eq!(
+            error(&[&chunked[..], b"10\r\nWiki\r\n"].concat()),
+            "The printer sent a truncated chunk"
+        );
+    }
+}
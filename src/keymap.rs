@@ -0,0 +1,544 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::Context;
+use clap_derive::Args;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+use crate::error::Error;
+use crate::update;
+
+/// How long the cheat sheet waits for the latest version.
+const K_UPDATE_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Keys that can't be rebound, by where they apply.
+const K_FIXED_KEYS: [(&str, &str, &str); 22] = [
+    ("Lists", "↓↑", "select next/prev"),
+    ("Lists", "g/G", "go top/bottom"),
+    ("Lists", "/", "search"),
+    ("Lists", "←→", "switch panes"),
+    ("Lists", "↵", "inspect an instance"),
+    ("Instances", "h", "host card"),
+    ("Instances", "w", "query now"),
+    ("Instances", "W", "wake on LAN"),
+    (
+        "Instances",
+        "t",
+        "test pattern (Cast/AirPlay) or page (IPP)",
+    ),
+    ("Instances", "x/X", "forget the instance/host"),
+    ("Instances", "c/C", "copy a summary (C as markdown)"),
+    (
+        "Instances",
+        "y",
+        "copy an address, the hostname or the record",
+    ),
+    ("Instances", "P", "ping the addresses (toggle)"),
+    ("Instances", "o", "check the port is open"),
+    ("Instances", "m", "mark as ignored in the audit trail"),
+    ("Instances", "n", "note in the audit trail"),
+    ("Info", "←→", "select an address"),
+    ("Info", "i", "interact"),
+    ("Info", "Esc", "go back"),
+    ("Anywhere", "Tab/S-Tab", "focus the next/previous pane"),
+    ("Anywhere", "F12", "debug overlay"),
+    ("Anywhere", "Esc", "close a popup"),
+];
+
+#[derive(Args, Debug)]
+pub struct KeysOpts {
+    #[arg(long, action)]
+    /// Print a markdown table rather than plain text
+    markdown: bool,
+    #[arg(long)]
+    /// File to write the cheat sheet to, default: standard output
+    output: Option<PathBuf>,
+}
+
+/// Global actions of the main view that can be bound to other keys.
+///
+/// ```toml
+/// [keys]
+/// zoom = "Z"
+/// pick-query = "C-o"
+/// ```
+///
+/// The instance actions (`h`, `i`, `w`, `W`, `x`/`X`, `c`/`C`, `y`, `P`, `o`) and the list keys
+/// keep their bindings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyAction {
+    Quit,
+    NextProfile,
+    NextDomain,
+    RecordBaseline,
+    Screenshot,
+    PickQuery,
+    Zoom,
+    Visibility,
+    ImportDiff,
+    EventLog,
+    RecentFirst,
+    PriorityOrder,
+    Requery,
+    Export,
+    GroupApple,
+    Catalog,
+    Repeat,
+}
+
+impl KeyAction {
+    /// Every action, essential ones first so that they get their keys first.
+    const K_ALL: [KeyAction; 17] = [
+        KeyAction::Quit,
+        KeyAction::NextProfile,
+        KeyAction::NextDomain,
+        KeyAction::RecordBaseline,
+        KeyAction::Screenshot,
+        KeyAction::PickQuery,
+        KeyAction::Zoom,
+        KeyAction::Visibility,
+        KeyAction::ImportDiff,
+        KeyAction::EventLog,
+        KeyAction::RecentFirst,
+        KeyAction::PriorityOrder,
+        KeyAction::Requery,
+        KeyAction::Export,
+        KeyAction::GroupApple,
+        KeyAction::Catalog,
+        KeyAction::Repeat,
+    ];
+
+    fn default_key(self) -> Key {
+        match self {
+            KeyAction::Quit => Key::control('q'),
+            KeyAction::NextProfile => Key::control('p'),
+            KeyAction::NextDomain => Key::control('d'),
+            KeyAction::RecordBaseline => Key::control('b'),
+            KeyAction::Screenshot => Key::control('s'),
+            KeyAction::PickQuery => Key::plain('q'),
+            KeyAction::Zoom => Key::plain('z'),
+            KeyAction::Visibility => Key::plain('v'),
+            KeyAction::ImportDiff => Key::plain('I'),
+            KeyAction::EventLog => Key::plain('L'),
+            KeyAction::RecentFirst => Key::plain('r'),
+            KeyAction::PriorityOrder => Key::plain('p'),
+            KeyAction::Requery => Key::plain('R'),
+            KeyAction::Export => Key::plain('e'),
+            KeyAction::GroupApple => Key::plain('A'),
+            KeyAction::Catalog => Key::plain('K'),
+            KeyAction::Repeat => Key::plain('.'),
+        }
+    }
+
+    /// Actions the UI can't be used, or left, without.
+    fn essential(self) -> bool {
+        matches!(self, KeyAction::Quit)
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            KeyAction::Quit => "exit",
+            KeyAction::NextProfile => "switch profile",
+            KeyAction::NextDomain => "switch domain",
+            KeyAction::RecordBaseline => "save baseline",
+            KeyAction::Screenshot => "take a screenshot",
+            KeyAction::PickQuery => "pick a query",
+            KeyAction::Zoom => "zoom the focused pane",
+            KeyAction::Visibility => "visibility by interface",
+            KeyAction::ImportDiff => "compare with --import",
+            KeyAction::EventLog => "event log",
+            KeyAction::RecentFirst => "list instances by recent activity",
+            KeyAction::PriorityOrder => "list instances by SRV priority and weight",
+            KeyAction::Requery => "query the selected service type again",
+            KeyAction::Export => "export to a file",
+            KeyAction::GroupApple => "group Apple continuity services",
+            KeyAction::Catalog => "compare with --catalog",
+            KeyAction::Repeat => "repeat the last action on the selection",
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            KeyAction::Quit => "quit",
+            KeyAction::NextProfile => "next-profile",
+            KeyAction::NextDomain => "next-domain",
+            KeyAction::RecordBaseline => "record-baseline",
+            KeyAction::Screenshot => "screenshot",
+            KeyAction::PickQuery => "pick-query",
+            KeyAction::Zoom => "zoom",
+            KeyAction::Visibility => "visibility",
+            KeyAction::ImportDiff => "import-diff",
+            KeyAction::EventLog => "event-log",
+            KeyAction::RecentFirst => "recent-first",
+            KeyAction::PriorityOrder => "priority-order",
+            KeyAction::Requery => "requery",
+            KeyAction::Export => "export",
+            KeyAction::GroupApple => "group-apple",
+            KeyAction::Catalog => "catalog",
+            KeyAction::Repeat => "repeat",
+        }
+    }
+}
+
+/// A key, optionally with Control, e.g. `z`, `C-p` or `F5`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    control: bool,
+}
+
+impl Key {
+    const fn plain(c: char) -> Self {
+        Self {
+            code: KeyCode::Char(c),
+            control: false,
+        }
+    }
+
+    const fn control(c: char) -> Self {
+        Self {
+            code: KeyCode::Char(c),
+            control: true,
+        }
+    }
+
+    fn matches(&self, event: &KeyEvent) -> bool {
+        event.code == self.code && event.modifiers.contains(KeyModifiers::CONTROL) == self.control
+    }
+}
+
+/// Keys taken by the panes and the instance actions, which can't be rebound.
+const K_RESERVED: [Key; 26] = [
+    Key::plain('/'),
+    Key::plain('g'),
+    Key::plain('G'),
+    Key::plain('h'),
+    Key::plain('i'),
+    Key::plain('w'),
+    Key::plain('W'),
+    Key::plain('t'),
+    Key::plain('x'),
+    Key::plain('X'),
+    Key::plain('c'),
+    Key::plain('C'),
+    Key::plain('y'),
+    Key::plain('P'),
+    Key::plain('o'),
+    Key::plain('m'),
+    Key::plain('n'),
+    Key {
+        code: KeyCode::Left,
+        control: false,
+    },
+    Key {
+        code: KeyCode::Right,
+        control: false,
+    },
+    Key {
+        code: KeyCode::Up,
+        control: false,
+    },
+    Key {
+        code: KeyCode::Down,
+        control: false,
+    },
+    Key {
+        code: KeyCode::Enter,
+        control: false,
+    },
+    Key {
+        code: KeyCode::Esc,
+        control: false,
+    },
+    Key {
+        code: KeyCode::Tab,
+        control: false,
+    },
+    Key {
+        code: KeyCode::BackTab,
+        control: false,
+    },
+    Key {
+        code: KeyCode::F(12),
+        control: false,
+    },
+];
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (control, key) = match s.strip_prefix("C-") {
+            Some(key) => (true, key),
+            None => (false, s),
+        };
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match key.strip_prefix('F').and_then(|n| n.parse().ok()) {
+                Some(n @ 1..=24) => KeyCode::F(n),
+                _ => return Err(format!("\"{s}\" isn't a key, e.g. z, C-p or F5")),
+            },
+        };
+        Ok(Self { code, control })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.control {
+            write!(f, "C-")?;
+        }
+        match self.code {
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+/// Keys of the global actions.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    keys: BTreeMap<KeyAction, Key>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            keys: KeyAction::K_ALL
+                .iter()
+                .map(|action| (*action, action.default_key()))
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Build the keymap out of the `[keys]` of the config.
+    ///
+    /// An empty key unbinds an action. Broken bindings never make it into the
+    /// keymap: invalid keys and keys already taken, by the panes or by another
+    /// action, fall back to the action's default key when that one is free,
+    /// and leave the action unbound otherwise. Essential actions can't be
+    /// unbound. Returns the keymap along with the problems found.
+    pub fn load(bindings: &BTreeMap<KeyAction, String>) -> (Self, Vec<String>) {
+        let mut keys: BTreeMap<KeyAction, Key> = BTreeMap::new();
+        let mut problems = Vec::new();
+        let taken = |keys: &BTreeMap<KeyAction, Key>, key: &Key| {
+            K_RESERVED.contains(key) || keys.values().any(|bound| bound == key)
+        };
+        for action in KeyAction::K_ALL {
+            let name = action.name();
+            let default = action.default_key();
+            let key = match bindings.get(&action).map(|key| key.trim()) {
+                None => default,
+                Some("") if action.essential() => {
+                    problems.push(format!("{name} can't be unbound, using {default}"));
+                    default
+                }
+                Some("") => continue,
+                Some(key) => match key.parse::<Key>() {
+                    Ok(key) if taken(&keys, &key) => {
+                        let owner = keys
+                            .iter()
+                            .find(|(_, bound)| **bound == key)
+                            .map_or("the panes".to_string(), |(owner, _)| {
+                                owner.name().to_string()
+                            });
+                        problems.push(format!(
+                            "{key} for {name} is already bound to {owner}, using {default}"
+                        ));
+                        default
+                    }
+                    Ok(key) => key,
+                    Err(e) => {
+                        problems.push(format!("{name}: {e}, using {default}"));
+                        default
+                    }
+                },
+            };
+            if taken(&keys, &key) {
+                problems.push(format!(
+                    "The default {key} for {name} is taken too, {name} is unbound"
+                ));
+                continue;
+            }
+            keys.insert(action, key);
+        }
+        (Self { keys }, problems)
+    }
+
+    /// The action bound to the key of `event`, if any.
+    pub fn action(&self, event: &KeyEvent) -> Option<KeyAction> {
+        self.keys
+            .iter()
+            .find(|(_, key)| key.matches(event))
+            .map(|(action, _)| *action)
+    }
+
+    pub fn key(&self, action: KeyAction) -> Option<Key> {
+        self.keys.get(&action).copied()
+    }
+
+    /// Every key and what it does, as plain text or as a markdown table,
+    /// headed by the running version and the `latest` one if newer.
+    pub fn cheat_sheet(&self, markdown: bool, latest: Option<&str>) -> String {
+        let mut version = format!("discovery-rs v{}", env!("CARGO_PKG_VERSION"));
+        if let Some(latest) = latest {
+            let _ = write!(version, " (v{latest} available)");
+        }
+        let rows: Vec<(&str, String, &str)> = self
+            .keys
+            .iter()
+            .map(|(action, key)| ("Global", key.to_string(), action.description()))
+            .chain(
+                K_FIXED_KEYS
+                    .iter()
+                    .map(|(context, key, description)| (*context, key.to_string(), *description)),
+            )
+            .collect();
+
+        let mut sheet = String::new();
+        if markdown {
+            let _ = writeln!(sheet, "# {version} keys\n");
+            let _ = writeln!(sheet, "| Where | Key | Action |");
+            let _ = writeln!(sheet, "|---|---|---|");
+            for (context, key, description) in rows {
+                let key = key.replace('|', "\\|");
+                let _ = writeln!(sheet, "| {context} | `{key}` | {description} |");
+            }
+        } else {
+            let _ = writeln!(sheet, "{version} keys");
+            let mut current = "";
+            for (context, key, description) in rows {
+                if context != current {
+                    let _ = writeln!(sheet, "\n{context}");
+                    current = context;
+                }
+                let _ = writeln!(sheet, "  {key:<6} {description}");
+            }
+        }
+        sheet
+    }
+}
+
+/// Print the cheat sheet of the keymap configured by `bindings`.
+///
+/// Problems with the bindings are reported on the standard error, the sheet
+/// shows the keys actually used.
+pub fn run(
+    opts: &KeysOpts,
+    bindings: &BTreeMap<KeyAction, String>,
+    update_check: bool,
+) -> Result<(), Error> {
+    let (keymap, problems) = Keymap::load(bindings);
+    for problem in problems {
+        eprintln!("Key binding: {problem}");
+    }
+    let latest = if update_check {
+        update::check().recv_timeout(K_UPDATE_CHECK_TIMEOUT).ok()
+    } else {
+        None
+    };
+    let sheet = keymap.cheat_sheet(opts.markdown, latest.as_deref());
+    match opts.output.as_ref() {
+        Some(path) => std::fs::write(path, sheet)
+            .with_context(|| format!("Failed to write {}", path.display())),
+        None => write!(std::io::stdout(), "{sheet}").context("Failed to write the cheat sheet"),
+    }
+    .map_err(Error::Export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, control: bool) -> Key {
+        Key { code, control }
+    }
+
+    #[test]
+    fn plain_keys() {
+        assert_eq!("z".parse(), Ok(Key::plain('z')));
+        assert_eq!("Z".parse(), Ok(Key::plain('Z')));
+        assert_eq!(".".parse(), Ok(Key::plain('.')));
+        assert_eq!("F".parse(), Ok(Key::plain('F')));
+    }
+
+    #[test]
+    fn modifiers() {
+        assert_eq!("C-p".parse(), Ok(Key::control('p')));
+        assert_eq!("C-F5".parse(), Ok(key(KeyCode::F(5), true)));
+        // A lone C is the key, not the modifier
+        assert_eq!("C".parse(), Ok(Key::plain('C')));
+    }
+
+    #[test]
+    fn named_keys() {
+        assert_eq!("F1".parse(), Ok(key(KeyCode::F(1), false)));
+        assert_eq!("F24".parse(), Ok(key(KeyCode::F(24), false)));
+    }
+
+    #[test]
+    fn invalid_keys() {
+        for spec in [
+            "", "C-", "F0", "F25", "f5", "Ctrl-p", "C-C-p", "zz", "Enter",
+        ] {
+            assert_eq!(
+                spec.parse::<Key>(),
+                Err(format!("\"{spec}\" isn't a key, e.g. z, C-p or F5")),
+                "{spec}"
+            );
+        }
+    }
+
+    #[test]
+    fn keys_are_written_back() {
+        for spec in ["z", "C-p", "F5", "C-F12"] {
+            assert_eq!(spec.parse::<Key>().unwrap().to_string(), spec);
+        }
+    }
+
+    #[test]
+    fn events() {
+        let control = KeyEvent::new(KeyCode::Char('p'), KeyModifiers::CONTROL);
+        let plain = KeyEvent::new(KeyCode::Char('p'), KeyModifiers::NONE);
+        assert!(Key::control('p').matches(&control));
+        assert!(!Key::control('p').matches(&plain));
+        assert!(Key::plain('p').matches(&plain));
+        assert!(!Key::plain('p').matches(&control));
+    }
+
+    #[test]
+    fn broken_bindings() {
+        let bindings = BTreeMap::from([
+            (KeyAction::Zoom, "zz".to_string()),
+            (KeyAction::Export, "g".to_string()),
+            (KeyAction::Catalog, "F5".to_string()),
+            (KeyAction::Quit, String::new()),
+        ]);
+        let (keymap, problems) = Keymap::load(&bindings);
+        assert_eq!(keymap.key(KeyAction::Zoom), Some(Key::plain('z')));
+        assert_eq!(keymap.key(KeyAction::Export), Some(Key::plain('e')));
+        assert_eq!(
+            keymap.key(KeyAction::Catalog),
+            Some(key(KeyCode::F(5), false))
+        );
+        assert_eq!(keymap.key(KeyAction::Quit), Some(Key::control('q')));
+        assert_eq!(
+            problems,
+            [
+                "quit can't be unbound, using C-q",
+                "zoom: \"zz\" isn't a key, e.g. z, C-p or F5, using z",
+                "g for export is already bound to the panes, using e",
+            ]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use mdns_sd::ServiceInfo;
+
+use crate::domain;
+
+/// Default port and URI scheme of common service types, e.g. from their
+/// IANA registrations, for instances that advertise port 0 or leave out
+/// what clients would need to connect.
+const K_WELL_KNOWN: [(&str, u16, &str); 16] = [
+    ("_http._tcp", 80, "http"),
+    ("_https._tcp", 443, "https"),
+    ("_ipp._tcp", 631, "ipp"),
+    ("_ipps._tcp", 631, "ipps"),
+    ("_printer._tcp", 515, "lpd"),
+    ("_pdl-datastream._tcp", 9100, "socket"),
+    ("_ssh._tcp", 22, "ssh"),
+    ("_sftp-ssh._tcp", 22, "sftp"),
+    ("_ftp._tcp", 21, "ftp"),
+    ("_smb._tcp", 445, "smb"),
+    ("_afpovertcp._tcp", 548, "afp"),
+    ("_nfs._tcp", 2049, "nfs"),
+    ("_rfb._tcp", 5900, "vnc"),
+    ("_daap._tcp", 3689, "daap"),
+    ("_mqtt._tcp", 1883, "mqtt"),
+    ("_sip._udp", 5060, "sip"),
+];
+
+/// TXT keys commonly holding the resource path, in order of preference.
+const K_PATH_KEYS: [&str; 2] = ["path", "rp"];
+
+/// What is commonly known about a service type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WellKnown {
+    pub port: u16,
+    pub scheme: &'static str,
+}
+
+/// Look the type of `service_type` up, regardless of its subtype and domain.
+pub fn lookup(service_type: &str) -> Option<WellKnown> {
+    let service_type = domain::base_type(service_type);
+    K_WELL_KNOWN
+        .iter()
+        .find(|(known, _, _)| {
+            service_type
+                .strip_prefix(known)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+        .map(|(_, port, scheme)| WellKnown {
+            port: *port,
+            scheme,
+        })
+}
+
+/// Resource path advertised in the TXT record, `/` when there is none.
+pub fn path(info: &ServiceInfo) -> String {
+    let path = K_PATH_KEYS
+        .iter()
+        .find_map(|key| info.get_property_val_str(key))
+        .unwrap_or_default()
+        .trim_start_matches('/');
+    format!("/{path}")
+}
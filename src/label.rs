@@ -0,0 +1,180 @@
+use std::str::FromStr;
+
+use anyhow::Context;
+use mdns_sd::ServiceInfo;
+use ratatui::style::Color;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::filter::Field;
+
+/// Config rule attaching a label to the instances it matches.
+///
+/// ```toml
+/// [[labels]]
+/// label = "media"
+/// color = "green"
+/// match = "txt.md=Chromecast"
+/// ```
+///
+/// `match` is a field, one of `hostname`, `name`, `type` or `txt.<key>`, and
+/// a regular expression its value must match.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LabelRule {
+    pub label: String,
+    /// Color name or `#rrggbb`, default: the theme's
+    pub color: Option<String>,
+    #[serde(rename = "match")]
+    pub pattern: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub name: String,
+    pub color: Option<Color>,
+}
+
+/// A compiled [`LabelRule`].
+#[derive(Debug, Clone)]
+pub struct Labeler {
+    label: Label,
+    field: Field,
+    regex: Regex,
+}
+
+impl LabelRule {
+    pub fn compile(&self) -> anyhow::Result<Labeler> {
+        let (field, pattern) = self
+            .pattern
+            .split_once('=')
+            .with_context(|| format!("\"{}\" isn't <field>=<regex>", self.pattern))?;
+        let field = Field::parse(field).with_context(|| {
+            format!("Unknown field \"{}\" in \"{}\"", field.trim(), self.pattern)
+        })?;
+        let color = self
+            .color
+            .as_deref()
+            .map(|color| {
+                Color::from_str(color).map_err(|_| anyhow::anyhow!("Unknown color \"{color}\""))
+            })
+            .transpose()?;
+        Ok(Labeler {
+            label: Label {
+                name: self.label.clone(),
+                color,
+            },
+            field,
+            regex: Regex::new(pattern.trim())?,
+        })
+    }
+}
+
+impl Labeler {
+    fn matches(&self, info: &ServiceInfo) -> bool {
+        self.field
+            .value(info)
+            .is_some_and(|value| self.regex.is_match(value))
+    }
+}
+
+/// Compile all the `rules`.
+pub fn compile(rules: &[LabelRule]) -> anyhow::Result<Vec<Labeler>> {
+    rules
+        .iter()
+        .map(|rule| {
+            rule.compile()
+                .with_context(|| format!("Invalid label rule \"{}\"", rule.label))
+        })
+        .collect()
+}
+
+/// Labels of every rule matching `info`, without duplicates.
+pub fn labels(labelers: &[Labeler], info: &ServiceInfo) -> Vec<Label> {
+    let mut labels: Vec<Label> = Vec::new();
+    for labeler in labelers.iter().filter(|labeler| labeler.matches(info)) {
+        if !labels.iter().any(|label| label.name == labeler.label.name) {
+            labels.push(labeler.label.clone());
+        }
+    }
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(label: &str, color: Option<&str>, pattern: &str) -> LabelRule {
+        LabelRule {
+            label: label.to_string(),
+            color: color.map(str::to_string),
+            pattern: pattern.to_string(),
+        }
+    }
+
+    fn chromecast() -> ServiceInfo {
+        ServiceInfo::new(
+            "_googlecast._tcp.local.",
+            "Chromecast-4e2d",
+            "4e2d.local.",
+            "192.168.2.20",
+            8009,
+            &[("md", "Chromecast"), ("fn", "Living Room TV")][..],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn rules() {
+        let labelers = compile(&[
+            rule("media", Some("green"), "txt.md=Chromecast"),
+            rule("tv", Some("#ff8800"), "txt.fn = (?i)\\btv$"),
+            rule("cast", None, "type=^_googlecast\\."),
+            // Matching too, but already labeled
+            rule("media", Some("blue"), "hostname=\\.local\\.$"),
+            rule("printer", None, "type=_ipp"),
+            rule("unnamed", None, "txt.missing=.*"),
+        ])
+        .unwrap();
+        assert_eq!(
+            labels(&labelers, &chromecast()),
+            [
+                Label {
+                    name: "media".to_string(),
+                    color: Some(Color::Green),
+                },
+                Label {
+                    name: "tv".to_string(),
+                    color: Some(Color::Rgb(0xff, 0x88, 0x00)),
+                },
+                Label {
+                    name: "cast".to_string(),
+                    color: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let error = |rule: LabelRule| format!("{:#}", compile(&[rule]).unwrap_err());
+        assert_eq!(
+            error(rule("media", None, "Chromecast")),
+            "Invalid label rule \"media\": \"Chromecast\" isn't <field>=<regex>"
+        );
+        assert_eq!(
+            error(rule("media", None, "model=Chromecast")),
+            "Invalid label rule \"media\": Unknown field \"model\" in \"model=Chromecast\""
+        );
+        assert_eq!(
+            error(rule("media", None, "txt.=Chromecast")),
+            "Invalid label rule \"media\": Unknown field \"txt.\" in \"txt.=Chromecast\""
+        );
+        assert_eq!(
+            error(rule("media", Some("chartreuse"), "txt.md=Chromecast")),
+            "Invalid label rule \"media\": Unknown color \"chartreuse\""
+        );
+        assert!(error(rule("media", None, "txt.md=(Chromecast"))
+            .starts_with("Invalid label rule \"media\": regex parse error"));
+    }
+}
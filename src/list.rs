@@ -1,16 +1,18 @@
 use crossterm::event::{KeyCode, KeyEvent};
+use mdns_sd::ServiceInfo;
 use ratatui::{prelude::*, widgets::*};
-use regex::Regex;
-use std::cell::RefCell;
 use std::fmt::Display;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use tracing::instrument;
 
-use crate::colors::*;
+use crate::colors::theme;
+use crate::filter::Filter;
 use crate::search::Search;
 use crate::utils::centered_rect;
 use crate::widget::DiscoveryWidget;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default)]
 enum Mode {
     #[default]
     Display,
@@ -22,13 +24,21 @@ enum Mode {
 /// Implementing this trait for a type will make it possible
 /// for the type to be rendered as a line in the [`List`].
 pub trait ListEntry {
-    fn entry(&self) -> Line;
+    fn entry(&self) -> Line<'_>;
     fn id(&self) -> String;
+    /// What searches and ignore expressions are matched against.
+    fn search_text(&self) -> String {
+        self.id()
+    }
+    /// The instance whose fields searches can compare, if any.
+    fn info(&self) -> Option<&ServiceInfo> {
+        None
+    }
 }
 
 impl<D: Display> ListEntry for D {
-    fn entry(&self) -> Line {
-        Line::styled(format!("{}", self), TEXT_COLOR)
+    fn entry(&self) -> Line<'_> {
+        Line::styled(format!("{}", self), theme().text)
     }
 
     fn id(&self) -> String {
@@ -40,12 +50,18 @@ impl<D: Display> ListEntry for D {
 ///
 /// Keeps track of the list elements and implements [`Widget`] so
 /// that the list can be rendered as part of the TUI.
-#[derive(Debug)]
+///
+/// Clones share the scroll offset and the search checks, so that a snapshot
+/// rendered in place of the list scrolls it as well.
+#[derive(Debug, Clone)]
 pub struct ListWidget<Item> {
     name: String,
     items: Vec<Item>,
-    state: RefCell<ListState>,
-    search_regex: Option<Regex>,
+    state: ListState,
+    /// Where the list is scrolled to, as of the last render
+    offset: Arc<AtomicUsize>,
+    filter: Option<Filter>,
+    ignore: Option<Filter>,
     search: Search,
     current_mode: Mode,
 }
@@ -55,9 +71,11 @@ impl<Item> Default for ListWidget<Item> {
         Self {
             name: "ListWidget".to_string(),
             items: Default::default(),
-            state: RefCell::new(ListState::default()),
+            state: ListState::default(),
+            offset: Arc::default(),
             search: Search::default(),
-            search_regex: None,
+            filter: None,
+            ignore: None,
             current_mode: Mode::default(),
         }
     }
@@ -72,11 +90,161 @@ where
         self
     }
 
+    /// Hide the items matching `ignore` regardless of the current search.
+    pub fn ignore(mut self, ignore: Option<Filter>) -> Self {
+        self.ignore = ignore;
+        self
+    }
+
+    pub fn set_ignore(&mut self, ignore: Option<Filter>) {
+        self.ignore = ignore;
+        let filtered = self.filtered();
+        if !filtered.is_empty() {
+            self.state.select(Some(0));
+        }
+    }
+
     pub fn selected(&self) -> Option<&Item> {
         let filtered = self.filtered();
-        filtered
-            .get(self.state.borrow().selected().unwrap_or(0))
-            .copied()
+        filtered.get(self.state.selected().unwrap_or(0)).copied()
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &Item> {
+        self.items.iter()
+    }
+
+    pub fn items_mut(&mut self) -> impl Iterator<Item = &mut Item> {
+        self.items.iter_mut()
+    }
+
+    /// Select the item `id` among the ones shown, if it is.
+    ///
+    /// Returns whether it is selected.
+    pub fn select_id(&mut self, id: &str) -> bool {
+        let index = self.filtered().iter().position(|item| item.id() == id);
+        if index.is_some() {
+            self.state.select(index);
+        }
+        index.is_some()
+    }
+
+    /// Search for `search` as if it had been typed in.
+    pub fn restore_search(&mut self, search: &str) -> anyhow::Result<()> {
+        let filter = Filter::parse(search)?;
+        self.search.set(search);
+        self.update_filter(Some(filter));
+        Ok(())
+    }
+
+    /// The current search, if any.
+    pub fn filter(&self) -> Option<&Filter> {
+        self.filter.as_ref()
+    }
+
+    pub fn selected_mut(&mut self) -> Option<&mut Item> {
+        // Several items may share an id, find the very one that is selected
+        let selected: *const Item = self.selected()?;
+        self.items
+            .iter_mut()
+            .find(|item| std::ptr::eq(&**item, selected))
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Render like [`DiscoveryWidget::render`], with what `guidance` tells
+    /// in place of a list showing nothing.
+    pub fn render_or(
+        &self,
+        area: Rect,
+        buf: &mut Buffer,
+        selected: bool,
+        guidance: impl FnOnce() -> Text<'static>,
+    ) {
+        self.render_list(area, buf, selected, Some(guidance));
+    }
+
+    fn render_list(
+        &self,
+        area: Rect,
+        buf: &mut Buffer,
+        selected: bool,
+        guidance: Option<impl FnOnce() -> Text<'static>>,
+    ) {
+        let outer_block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(if selected {
+                Style::new().fg(theme().selected_fg)
+            } else {
+                Style::default()
+            })
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        let inner_area = outer_block.inner(area);
+        outer_block.render(area, buf);
+
+        let inner_block = Block::new()
+            .borders(Borders::NONE)
+            .fg(theme().text)
+            .bg(theme().normal_row);
+
+        let items: Vec<_> = self
+            .filtered()
+            .into_iter()
+            .enumerate()
+            .map(|(index, item)| {
+                ListItem::new(item.entry()).bg(if (index % 2) == 0 {
+                    theme().normal_row
+                } else {
+                    theme().alt_row
+                })
+            })
+            .collect();
+        match guidance {
+            Some(guidance) if items.is_empty() => {
+                Paragraph::new(guidance())
+                    .block(inner_block.padding(Padding::uniform(1)))
+                    .wrap(Wrap { trim: true })
+                    .render(inner_area, buf);
+            }
+            _ => {
+                let list = List::new(items)
+                    .block(inner_block)
+                    .highlight_style(
+                        Style::default()
+                            .add_modifier(Modifier::BOLD)
+                            .add_modifier(Modifier::REVERSED)
+                            .fg(theme().selected_fg),
+                    )
+                    .highlight_symbol(">")
+                    .highlight_spacing(HighlightSpacing::Always);
+                let mut state = self
+                    .state
+                    .clone()
+                    .with_offset(self.offset.load(Ordering::Relaxed));
+                StatefulWidget::render(list, inner_area, buf, &mut state);
+                self.offset.store(state.offset(), Ordering::Relaxed);
+            }
+        }
+
+        if matches!(self.current_mode, Mode::Search) {
+            let search_area = centered_rect(60, (5. / area.height as f64 * 100.) as u16, area);
+            Clear.render(search_area, buf);
+            self.search.render(search_area, buf, true);
+        }
+    }
+
+    /// Whether the search popup is currently capturing input.
+    pub fn is_searching(&self) -> bool {
+        matches!(self.current_mode, Mode::Search)
     }
 
     pub fn push(&mut self, item: Item) {
@@ -85,9 +253,8 @@ where
         }
 
         // Select the first item once we have at least one
-        let state = self.state.get_mut();
-        if state.selected().is_none() {
-            state.select(Some(0));
+        if self.state.selected().is_none() {
+            self.state.select(Some(0));
         }
     }
 
@@ -98,10 +265,69 @@ where
 
         // Deselect when all the items are gone
         if self.items.is_empty() {
-            self.state.get_mut().select(None);
+            self.state.select(None);
+        }
+    }
+
+    /// Keep only the items satisfying `keep`.
+    pub fn retain(&mut self, keep: impl FnMut(&Item) -> bool) {
+        self.items.retain(keep);
+        if self.items.is_empty() {
+            self.state.select(None);
         }
     }
 
+    /// Put the items in `order`, indexes of the current items, keeping the
+    /// selection on the item it is on.
+    fn reorder(&mut self, order: Vec<usize>) {
+        let selected = self
+            .state
+            .selected()
+            .and(self.selected())
+            .and_then(|selected| {
+                self.items
+                    .iter()
+                    .position(|item| std::ptr::eq(item, selected))
+            });
+        let mut items: Vec<_> = std::mem::take(&mut self.items)
+            .into_iter()
+            .map(Some)
+            .collect();
+        self.items = order
+            .iter()
+            .filter_map(|index| items[*index].take())
+            .collect();
+        if let Some(selected) =
+            selected.and_then(|selected| order.iter().position(|index| *index == selected))
+        {
+            let selected: *const Item = &self.items[selected];
+            let index = self
+                .filtered()
+                .iter()
+                .position(|item| std::ptr::eq(*item, selected));
+            self.state.select(index.or(Some(0)));
+        }
+    }
+
+    /// Sort the items by `key`, keeping the selection on the item it is on.
+    pub fn sort_by_key<K: Ord>(&mut self, mut key: impl FnMut(&Item) -> K) {
+        let mut order: Vec<usize> = (0..self.items.len()).collect();
+        order.sort_by_key(|index| key(&self.items[*index]));
+        self.reorder(order);
+    }
+
+    /// Move the first item satisfying `bumped` to the top, keeping the
+    /// selection on the item it is on.
+    pub fn bump(&mut self, bumped: impl FnMut(&Item) -> bool) {
+        let Some(bumped) = self.items.iter().position(bumped) else {
+            return;
+        };
+        let order = std::iter::once(bumped)
+            .chain((0..self.items.len()).filter(|index| *index != bumped))
+            .collect();
+        self.reorder(order);
+    }
+
     pub fn next(&mut self) {
         self.select_delta(1);
     }
@@ -111,12 +337,12 @@ where
     }
 
     pub fn top(&mut self) {
-        let s = self.state.get_mut().selected().unwrap_or(0) as isize;
-        self.select_delta(-1 * s);
+        let s = self.state.selected().unwrap_or(0) as isize;
+        self.select_delta(-s);
     }
 
     pub fn bottom(&mut self) {
-        let s = self.state.get_mut().selected().unwrap_or(0) as isize;
+        let s = self.state.selected().unwrap_or(0) as isize;
         self.select_delta(s);
     }
 
@@ -124,44 +350,46 @@ where
     /// it underflows/overflows.
     #[instrument]
     fn select_delta(&mut self, delta: isize) {
-        tracing::trace!(
-            "List state before the update: {:?}",
-            self.state.get_mut().selected()
-        );
+        tracing::trace!("List state before the update: {:?}", self.state.selected());
         let filtered = self.filtered();
         // If there's nothing in the list, we can't do anything
         if !filtered.is_empty() {
             let len = filtered.len() as isize;
-            let index = match self.state.get_mut().selected() {
+            let index = match self.state.selected() {
                 Some(i) => (i as isize + delta).rem_euclid(len) as usize,
                 // Nothing selected yet, pick the first item
                 None => 0,
             };
-            self.state.get_mut().select(Some(index));
+            self.state.select(Some(index));
         }
-        tracing::trace!(
-            "List state after the update: {:?}",
-            self.state.get_mut().selected()
-        );
+        tracing::trace!("List state after the update: {:?}", self.state.selected());
     }
 
     fn filtered(&self) -> Vec<&Item> {
-        if let Some(regex) = self.search_regex.as_ref() {
-            self.items
-                .iter()
-                .filter(|item| regex.is_match(&item.id()))
-                .collect()
-        } else {
-            self.items.iter().collect()
-        }
+        self.items
+            .iter()
+            .filter(|item| {
+                let text = item.search_text();
+                let info = item.info();
+                let ignored = self
+                    .ignore
+                    .as_ref()
+                    .is_some_and(|ignore| ignore.matches(&text, info));
+                let matched = self
+                    .filter
+                    .as_ref()
+                    .map_or(true, |filter| filter.matches(&text, info));
+                !ignored && matched
+            })
+            .collect()
     }
 
     #[instrument]
-    fn update_filter(&mut self, regex: Option<Regex>) {
-        self.search_regex = regex;
+    pub fn update_filter(&mut self, filter: Option<Filter>) {
+        self.filter = filter;
         let filtered = self.filtered();
         if !filtered.is_empty() {
-            self.state.get_mut().select(Some(0));
+            self.state.select(Some(0));
         }
         tracing::debug!("Filter has been updated");
     }
@@ -175,8 +403,8 @@ where
         format!(
             "{}{}",
             self.name,
-            if let Some(regex) = self.search_regex.as_ref() {
-                format!("(/{}/)", regex.to_string())
+            if let Some(filter) = self.filter.as_ref() {
+                format!("(/{}/)", filter)
             } else {
                 "".to_string()
             }
@@ -193,10 +421,14 @@ where
                 KeyCode::Esc => {
                     self.current_mode = Mode::Display;
                 }
-                KeyCode::Enter => {
-                    self.current_mode = Mode::Display;
-                    self.update_filter(self.search.compile_regex().ok().flatten());
-                }
+                // An invalid search stays open, showing what is wrong
+                KeyCode::Enter => match self.search.compile_filter() {
+                    Ok(filter) => {
+                        self.current_mode = Mode::Display;
+                        self.update_filter(filter);
+                    }
+                    Err(e) => self.search.set_error(&e),
+                },
                 KeyCode::Char(_) | KeyCode::Backspace => {
                     self.search.process_key_event(event);
                 }
@@ -214,61 +446,6 @@ where
     }
 
     fn render(&self, area: Rect, buf: &mut Buffer, selected: bool) {
-        let outer_block = Block::new()
-            .borders(Borders::ALL)
-            .border_style(if selected {
-                Style::new().fg(SELECTED_STYLE_FG)
-            } else {
-                Style::default()
-            })
-            .title_alignment(Alignment::Center)
-            .title(self.title())
-            .title_style(Style::new().bold())
-            .fg(TEXT_COLOR)
-            .bg(HEADER_BG);
-        let inner_area = outer_block.inner(area);
-        outer_block.render(area, buf);
-
-        let inner_block = Block::new()
-            .borders(Borders::NONE)
-            .fg(TEXT_COLOR)
-            .bg(NORMAL_ROW_COLOR);
-
-        let items: Vec<_> = self
-            .items
-            .iter()
-            .filter(|item| {
-                if let Some(regex) = self.search_regex.as_ref() {
-                    regex.is_match(&item.id())
-                } else {
-                    true
-                }
-            })
-            .enumerate()
-            .map(|(index, item)| {
-                ListItem::new(item.entry()).bg(if (index % 2) == 0 {
-                    NORMAL_ROW_COLOR
-                } else {
-                    ALT_ROW_COLOR
-                })
-            })
-            .collect();
-        let list = List::new(items)
-            .block(inner_block)
-            .highlight_style(
-                Style::default()
-                    .add_modifier(Modifier::BOLD)
-                    .add_modifier(Modifier::REVERSED)
-                    .fg(SELECTED_STYLE_FG),
-            )
-            .highlight_symbol(">")
-            .highlight_spacing(HighlightSpacing::Always);
-        StatefulWidget::render(list, inner_area, buf, &mut self.state.borrow_mut());
-
-        if matches!(self.current_mode, Mode::Search) {
-            let search_area = centered_rect(60, (5. / area.height as f64 * 100.) as u16, area);
-            Clear.render(search_area, buf);
-            self.search.render(search_area, buf, true);
-        }
+        self.render_list(area, buf, selected, None::<fn() -> Text<'static>>);
     }
 }
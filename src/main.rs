@@ -1,24 +1,29 @@
 use anyhow::Context;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::File;
+use std::io::stdout;
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread::JoinHandle;
-use std::time::Duration;
-use std::{error::Error, io::stdout};
+use std::time::{Duration, Instant};
 
 use clap::Parser;
-use clap_derive::Parser;
+use clap_derive::{Parser, Subcommand};
 use color_eyre::config::HookBuilder;
-use crossterm::event::KeyModifiers;
 use crossterm::{
-    event::{self, poll, Event, KeyCode, KeyEventKind},
+    event::{self, poll, DisableFocusChange, EnableFocusChange, Event, KeyCode, KeyEventKind},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
-use flume::{Selector, Sender};
-use mdns_sd::{IfKind, ServiceDaemon, ServiceEvent};
+use flume::{Receiver, RecvTimeoutError, Selector, Sender};
+use mdns_sd::ServiceEvent;
+use notify::RecommendedWatcher;
 use parking_lot::Mutex;
 use ratatui::{prelude::*, widgets::*};
 use tracing::{instrument, Level};
@@ -26,38 +31,259 @@ use tracing_appender::non_blocking;
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::EnvFilter;
 
+use crate::aggregate::{AggregateOpts, Aggregator};
+use crate::audit::NotePrompt;
+use crate::backend::BackendKind;
+use crate::baseline::Baseline;
+use crate::browse::BrowseOpts;
+use crate::catalog::{Catalog, CatalogView, CheckOpts};
+use crate::census::CensusOpts;
+use crate::clipboard::CopyPicker;
+use crate::config::{Config, Profile};
+use crate::confirm::Confirm;
+use crate::continuity::Continuity;
+use crate::control::ControlCommand;
+use crate::destination::ExportPicker;
+use crate::diagnostics::Diagnostics;
+use crate::digest::DigestOpts;
+use crate::error::Error;
+use crate::eventlog::EventLogView;
+use crate::events::{Backpressure, EventQueue, EventStats, QueueSettings};
+use crate::export::{Csv, Registry};
+use crate::filter::Filter;
+use crate::host::Host;
+use crate::hud::Hud;
+use crate::import::{Import, ImportDiff};
 use crate::info::Info;
-use crate::list::ListWidget;
+use crate::interact::Interact;
+use crate::keymap::{KeyAction, Keymap, KeysOpts};
+use crate::list::ListEntry;
+use crate::model::{Model, Order, View};
+use crate::polite::Limiter;
+use crate::publish::PublishOpts;
+use crate::query::QueryPicker;
+use crate::recent::Recent;
+use crate::resolve::ResolveOpts;
+use crate::script::Scripts;
+use crate::statusbar::StatusbarOpts;
+use crate::toast::Toasts;
+use crate::uistate::UiState;
+use crate::utils::centered_rect;
+use crate::visibility::Visibility;
 use crate::widget::DiscoveryWidget;
+use crate::zone::Zones;
 
+mod aggregate;
+mod audit;
+mod avahi;
+mod backend;
+mod baseline;
+mod browse;
+mod cast;
+mod catalog;
+mod census;
+mod clipboard;
+mod clock;
 mod colors;
+mod computed;
+mod config;
+mod confirm;
+mod container;
+mod continuity;
+mod control;
+mod destination;
+mod diagnostics;
+mod digest;
+mod domain;
+mod error;
+mod eventlog;
+mod events;
+mod export;
+mod filter;
+mod host;
+mod hud;
+mod import;
 mod info;
+mod interact;
+mod ipp;
+mod keymap;
+mod known;
+mod label;
 mod list;
+mod model;
+mod oui;
+mod overlay;
+mod packet;
+mod ping;
+mod polite;
+mod portcheck;
+mod probe;
+mod publish;
+#[cfg(feature = "dns-push")]
+mod push;
+mod query;
+mod recent;
+mod resolve;
+mod reverse;
+mod schema;
+mod screenshot;
+#[cfg_attr(not(feature = "scripting"), path = "noscript.rs")]
+mod script;
 mod search;
+mod service;
+mod servicedb;
+mod session;
+mod shared;
+#[cfg(unix)]
+mod socket;
+mod sources;
+mod statusbar;
+mod stream;
+mod toast;
+mod txt;
+mod uistate;
+mod units;
+mod update;
 mod utils;
+mod visibility;
+mod wake;
+mod whois;
 mod widget;
+mod wizard;
+mod wsd;
+mod zone;
 
 #[derive(Parser, Debug, Default)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 struct CliOpts {
     #[arg(long)]
-    /// mDNS service query, default: _services._dns-sd._udp.local.
-    query: Option<String>,
+    /// mDNS service query, repeated to browse several at once, default: _services._dns-sd._udp.local.
+    query: Vec<String>,
     #[arg(long)]
-    /// Interface to perform discovery on, default: All
-    interface: Option<IfKind>,
+    /// Interface to perform discovery on (all, ipv4, ipv6, a name or an address), default: all
+    interface: Option<String>,
+    #[arg(long, action)]
+    /// Check whether mDNS can work from here, e.g. in a container, and exit
+    diagnose_container: bool,
+    #[arg(long, action)]
+    /// Browse and query over IPv6 only, e.g. to check an IPv6-only network
+    ipv6_only: bool,
+    #[arg(long, action)]
+    /// Pace the queries as RFC 6762 has it and cap their rate, e.g. on a large network
+    polite: bool,
+    #[arg(long, action)]
+    /// Probe for WS-Discovery endpoints too, e.g. ONVIF cameras only answering that
+    ws_discovery: bool,
+    #[arg(long)]
+    /// Profile from the config file to start with
+    profile: Option<String>,
+    #[arg(long)]
+    /// Path to the config file, default: ~/.config/discovery-rs/config.toml
+    config: Option<PathBuf>,
+    #[arg(long)]
+    /// Capacity of the queue between mDNS events and the UI model, default: 1024
+    event_queue_size: Option<NonZeroUsize>,
+    #[arg(long, value_enum)]
+    /// What to do with new mDNS events when the queue is full, default: block
+    backpressure: Option<Backpressure>,
+    #[arg(long)]
+    /// Most instances kept at once, the least recently resolved are evicted beyond it, default: unlimited
+    max_instances: Option<NonZeroUsize>,
+    #[arg(long, value_parser = units::duration)]
+    /// How long instances are kept past their TTL, marked stale, before they expire, e.g. 30s, default: 0s
+    stale_grace: Option<Duration>,
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..))]
+    /// Frames drawn per second at most, default: 24
+    refresh_rate: Option<u8>,
+    #[arg(long, value_enum)]
+    /// mDNS implementation to discover with, default: mdns-sd
+    backend: Option<BackendKind>,
+    #[arg(long)]
+    /// Named pipe, or - for standard input, to read commands from: filter, query, export and quit
+    control_pipe: Option<PathBuf>,
+    #[arg(long)]
+    /// avahi-browse --parsable or dns-sd -B output to compare the live results with (I)
+    import: Option<PathBuf>,
+    #[arg(long)]
+    /// YAML catalog of the services every host should advertise, to check the live results against (K)
+    catalog: Option<PathBuf>,
+    #[arg(long, value_name = "HEIGHT", num_args = 0..=1, default_missing_value = "24")]
+    /// Render inline in the normal screen, HEIGHT lines tall, instead of the alternate screen
+    inline: Option<u16>,
+    #[arg(long)]
+    /// Format of the exports, e.g. json or toml, default: by the file extension, JSON otherwise
+    output: Option<String>,
+    #[arg(long)]
+    /// File to write the session summary to as JSON on exit, rather than printing it
+    summary: Option<PathBuf>,
+    #[arg(long, value_name = "HOST:PORT")]
+    /// Collector to stream the events to as NDJSON over TCP, reconnecting when the connection drops
+    stream_to: Option<String>,
+    #[arg(long)]
+    /// Unix socket to serve the discovery state on as JSON, e.g. for status bar widgets
+    state_socket: Option<PathBuf>,
+    #[arg(long, action)]
+    /// Skip the startup check for a newer release, even if enabled in the config
+    no_update_check: bool,
+    #[arg(long, action)]
+    /// Start afresh rather than where the previous run was left, e.g. its selection and searches
+    no_restore: bool,
     #[arg(long, action)]
     /// Enable tracing and debug logging
     tracing: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-const K_SERVICE_TYPE_ENUMERATION: &'static str = "_services._dns-sd._udp.local.";
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Merge the events streamed by remote probes with --stream-to into a single view
+    Aggregate(AggregateOpts),
+    /// Browse without the TUI, printing every service found, resolved and removed as it happens
+    Browse(BrowseOpts),
+    /// Browse the service types listed in a file without the TUI and print a JSON census
+    Census(CensusOpts),
+    /// Check the network against a YAML catalog of expected services without the TUI and print a pass/fail report
+    Check(CheckOpts),
+    /// Browse without the TUI, sending a digest of the instances new, removed and flapping on a schedule
+    Digest(DigestOpts),
+    /// Print a cheat sheet of the configured keys
+    Keys(KeysOpts),
+    /// Register a service instance until interrupted, e.g. to test discovery end to end
+    Publish(PublishOpts),
+    /// Resolve a single instance or hostname, print its address and exit
+    Resolve(ResolveOpts),
+    /// Browse without the TUI, printing a single line summary for a status bar, e.g. waybar or polybar
+    Statusbar(StatusbarOpts),
+}
+
+const K_SERVICE_TYPE_ENUMERATION: &str = "_services._dns-sd._udp.local.";
 const K_REFRESH_RATE: u8 = 24;
+const K_MAX_EVENT_BATCH: usize = 64;
+/// How often the model checks for expired instances
+const K_EXPIRY_INTERVAL: Duration = Duration::from_secs(1);
+/// How long a woken host is waited for to advertise its services again
+const K_WAKE_TIMEOUT: Duration = Duration::from_secs(300);
+/// How long the service types the restored UI state refers to are waited for
+const K_RESTORE_TIMEOUT: Duration = Duration::from_secs(10);
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let opts = CliOpts::parse();
+/// The terminal renders inline rather than on the alternate screen, which
+/// the error hooks need to know to restore it.
+static INLINE: AtomicBool = AtomicBool::new(false);
+
+fn main() -> ExitCode {
+    match run(CliOpts::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            tracing::error!("{e}");
+            eprintln!("{e}");
+            e.exit_code()
+        }
+    }
+}
 
+fn run(opts: CliOpts) -> Result<(), Error> {
     init_error_hooks()?;
 
     // setup tracing and keep its guard
@@ -66,22 +292,325 @@ fn main() -> Result<(), Box<dyn Error>> {
         _tracing_guard = Some(init_tracing()?);
     }
 
-    let terminal = init_terminal()?;
+    if opts.diagnose_container {
+        return container::diagnose();
+    }
+
+    // First run, ask for the essential settings rather than assuming them
+    if opts.command.is_none() && opts.config.is_none() && wizard::needed() {
+        if let Some(path) = Config::default_path() {
+            let terminal = init_terminal(None)?;
+            let written = wizard::run(terminal, &path);
+            restore_terminal()?;
+            written.map_err(Error::Config)?;
+        }
+    }
+
+    let config = Config::load(opts.config.as_deref()).map_err(Error::Config)?;
+    let stale_grace = match opts.stale_grace {
+        Some(grace) => grace,
+        None => config
+            .stale_grace()
+            .map_err(Error::Config)?
+            .unwrap_or_default(),
+    };
+    let refresh_rate = match opts.refresh_rate.or(config.refresh_rate) {
+        Some(0) => {
+            return Err(Error::Config(anyhow::anyhow!(
+                "Invalid refresh_rate: at least 1 frame per second is drawn"
+            )))
+        }
+        rate => rate.unwrap_or(K_REFRESH_RATE),
+    };
+    let mut profiles: Vec<_> = config.profiles.into_iter().collect();
+    let current_profile = match opts.profile.or(config.default_profile) {
+        Some(name) => profiles
+            .iter()
+            .position(|(profile, _)| *profile == name)
+            .ok_or_else(|| Error::Config(anyhow::anyhow!("Unknown profile \"{name}\"")))?,
+        None => {
+            profiles.insert(0, ("default".to_string(), Profile::default()));
+            0
+        }
+    };
+    // Command line options take precedence over the selected profile
+    if !opts.query.is_empty() {
+        let mut queries: Vec<String> = Vec::new();
+        for query in opts.query.iter().map(|query| domain::qualify(query)) {
+            if !queries.contains(&query) {
+                queries.push(query);
+            }
+        }
+        profiles[current_profile].1.queries = queries;
+    }
+    if let Some(interface) = opts.interface {
+        profiles[current_profile].1.interfaces = vec![interface];
+    }
+    if opts.ipv6_only {
+        for (_, profile) in profiles.iter_mut() {
+            profile.ipv6_only = true;
+        }
+    }
+    if opts.polite {
+        for (_, profile) in profiles.iter_mut() {
+            profile.polite = true;
+        }
+    }
+    if opts.ws_discovery {
+        for (_, profile) in profiles.iter_mut() {
+            profile.ws_discovery = true;
+        }
+    }
+
+    // A queue of 0 would hand each event over directly, which dropping the
+    // oldest event can't make room in
+    let capacity = match opts
+        .event_queue_size
+        .map(NonZeroUsize::get)
+        .or(config.event_queue_size)
+    {
+        Some(0) => {
+            return Err(Error::Config(anyhow::anyhow!(
+                "Invalid event_queue_size: the queue holds at least 1 event"
+            )))
+        }
+        capacity => capacity.unwrap_or(QueueSettings::default().capacity),
+    };
+    let queue_settings = QueueSettings {
+        capacity,
+        backpressure: opts
+            .backpressure
+            .or(config.backpressure)
+            .unwrap_or_default(),
+    };
+
+    let backend = opts.backend.or(config.backend).unwrap_or_default();
+    let summary = opts.summary.clone();
+    // Keeping 0 instances would evict every one as soon as it resolves
+    let max_instances = match opts
+        .max_instances
+        .map(NonZeroUsize::get)
+        .or(config.max_instances)
+    {
+        Some(0) => {
+            return Err(Error::Config(anyhow::anyhow!(
+                "Invalid max_instances: at least 1 instance is kept"
+            )))
+        }
+        max_instances => max_instances,
+    };
+    let update_check = !opts.no_update_check && config.update_check.unwrap_or(false);
+    let no_restore = opts.no_restore;
+    match opts.command {
+        Some(Command::Aggregate(aggregate)) => {
+            let aggregator = Aggregator::listen(&aggregate).map_err(Error::Config)?;
+            let terminal = init_terminal(None)?;
+            let result = aggregator.run(terminal);
+            restore_terminal()?;
+            return result.map_err(Error::Other);
+        }
+        Some(Command::Browse(browse)) => {
+            let profile = &profiles[current_profile].1;
+            return browse::run(&browse, profile, backend, queue_settings);
+        }
+        Some(Command::Census(census)) => {
+            let profile = &profiles[current_profile].1;
+            let labelers = label::compile(&config.labels).map_err(Error::Config)?;
+            return census::run(
+                &census,
+                backend,
+                profile.if_kinds(),
+                profile.ipv6_only,
+                &labelers,
+                summary.as_deref(),
+            );
+        }
+        Some(Command::Check(check)) => {
+            let profile = &profiles[current_profile].1;
+            return catalog::run(
+                &check,
+                backend,
+                profile.if_kinds(),
+                profile.ipv6_only,
+                summary.as_deref(),
+            );
+        }
+        Some(Command::Keys(keys)) => return keymap::run(&keys, &config.keys, update_check),
+        Some(Command::Resolve(resolve)) => {
+            let profile = &profiles[current_profile].1;
+            return resolve::run(&resolve, backend, profile.if_kinds(), profile.ipv6_only);
+        }
+        Some(Command::Digest(digest)) => {
+            let profile = &profiles[current_profile].1;
+            return digest::run(&digest, profile, backend, queue_settings);
+        }
+        Some(Command::Publish(publish)) => {
+            let profile = &profiles[current_profile].1;
+            return publish::run(&publish, profile);
+        }
+        Some(Command::Statusbar(statusbar)) => {
+            let profile = &profiles[current_profile].1;
+            return statusbar::run(&statusbar, profile, backend, queue_settings);
+        }
+        None => {}
+    }
+
+    let mut exporters = Registry::builtin();
+    if !config.csv_columns.is_empty() {
+        exporters.register(Box::new(
+            Csv::new(&config.csv_columns).map_err(Error::Config)?,
+        ));
+    }
+    if let Some(format) = opts.output.as_deref() {
+        if exporters.get(format).is_none() {
+            return Err(Error::Config(anyhow::anyhow!(
+                "Unknown export format \"{format}\", one of: {}",
+                exporters.names().join(", ")
+            )));
+        }
+    }
+
+    if let Some(addr) = opts.stream_to.as_deref() {
+        if !addr
+            .rsplit_once(':')
+            .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok())
+        {
+            return Err(Error::Config(anyhow::anyhow!(
+                "Expected HOST:PORT to stream to, got \"{addr}\""
+            )));
+        }
+    }
+
+    let control = opts
+        .control_pipe
+        .as_deref()
+        .map(control::listen)
+        .transpose()
+        .map_err(Error::Config)?;
+
+    let import = opts
+        .import
+        .as_deref()
+        .map(Import::load)
+        .transpose()
+        .map_err(Error::Config)?;
+    let catalog = opts
+        .catalog
+        .as_deref()
+        .map(Catalog::load)
+        .transpose()
+        .map_err(Error::Config)?;
+    let labelers = label::compile(&config.labels).map_err(Error::Config)?;
+    let computed = computed::compile(&config.computed_columns).map_err(Error::Config)?;
+    let config_path = opts.config.or_else(Config::default_path);
+    let scripts = match config_path.as_deref() {
+        Some(path) => Scripts::load(&Scripts::dir(path)).map_err(Error::Config)?,
+        None => Scripts::default(),
+    };
+    // Settings needing a feature left out of this build are pointed out, and
+    // otherwise ignored
+    let mut unsupported = Vec::new();
+    #[cfg(not(feature = "scripting"))]
+    if !scripts.ignored.is_empty() {
+        unsupported.push(format!(
+            "{} scripts ignored, this build doesn't include the scripting feature",
+            scripts.ignored.len()
+        ));
+    }
+    if update_check && !cfg!(feature = "update-check") {
+        unsupported.push(
+            "No update check, this build doesn't include the update-check feature".to_string(),
+        );
+    }
+    let push_servers: BTreeMap<_, _> = if cfg!(feature = "dns-push") {
+        config
+            .dns_push
+            .iter()
+            .map(|(domain, server)| (domain::normalize(domain), server.clone()))
+            .collect()
+    } else {
+        if !config.dns_push.is_empty() {
+            unsupported.push(
+                "DNS Push servers ignored, this build doesn't include the dns-push feature"
+                    .to_string(),
+            );
+        }
+        BTreeMap::new()
+    };
+
+    let terminal = init_terminal(opts.inline)?;
+    if config.pause_unfocused.unwrap_or(false) {
+        stdout()
+            .execute(EnableFocusChange)
+            .context("Failed to enable the focus reporting")?;
+    }
 
     // create app and run it
-    let mut app = App::new(
-        opts.query
-            .as_ref()
-            .map(|q| q.as_str())
-            .unwrap_or(K_SERVICE_TYPE_ENUMERATION),
-        opts.interface.unwrap_or(IfKind::All),
-    )?;
-    app.run(terminal)?;
-    app.shutdown()?;
+    let result = App::new(profiles, current_profile, backend, queue_settings, scripts)
+        .map_err(Error::daemon)
+        .and_then(|app| {
+            let mut app = match config_path {
+                Some(path) => app.watch_config(path),
+                None => app,
+            };
+            app.control = control;
+            app.refresh_rate = refresh_rate;
+            app.set_push_servers(push_servers);
+            app.exporters = exporters;
+            app.export_format = opts.output;
+            app.import = import;
+            app.catalog = catalog;
+            app.query_templates = config.query_templates;
+            app.model.set_labelers(labelers);
+            app.model.set_computed(computed);
+            app.model.set_max_instances(max_instances);
+            app.model.set_stale_grace(stale_grace);
+            app.model.set_txt_alerts(config.txt_alerts);
+            if let Some(addr) = opts.stream_to {
+                stream::spawn(addr, &app.model.event_log);
+            }
+            // Removed once the app is done
+            #[cfg(unix)]
+            let _state_socket = opts
+                .state_socket
+                .as_deref()
+                .map(|path| socket::serve(path, app.model.clone()))
+                .transpose()
+                .map_err(Error::Config)?;
+            #[cfg(not(unix))]
+            if opts.state_socket.is_some() {
+                return Err(Error::Config(anyhow::anyhow!(
+                    "--state-socket needs Unix domain sockets"
+                )));
+            }
+            app.update = update_check.then(update::check);
+            for notice in unsupported {
+                app.toasts.warning(notice);
+            }
+            app.pause_unfocused = config.pause_unfocused.unwrap_or(false);
+            if !config.remember_queries.unwrap_or(true) {
+                app.recent_queries = Recent::default();
+            }
+            app.remember_ui_state = config.remember_ui_state.unwrap_or(true);
+            if app.remember_ui_state && !no_restore {
+                if let Some(state) = UiState::load(&app.profiles[app.current_profile].0) {
+                    app.restore_ui_state(state);
+                }
+            }
+            app.set_keys(&config.keys);
+            app.run(terminal)?;
+            if app.remember_ui_state {
+                app.save_ui_state();
+            }
+            app.shutdown().map_err(Error::daemon)?;
+            Ok(app.model.session.clone())
+        });
 
+    // The error is reported once the terminal is usable again
     restore_terminal()?;
 
-    Ok(())
+    let session = result?;
+    session::report(&session, summary.as_deref(), std::io::stdout()).map_err(Error::Export)
 }
 
 fn init_error_hooks() -> color_eyre::Result<()> {
@@ -99,17 +628,39 @@ fn init_error_hooks() -> color_eyre::Result<()> {
     Ok(())
 }
 
-fn init_terminal() -> color_eyre::Result<Terminal<impl Backend>> {
+/// Set up the terminal, on the alternate screen or, with an `inline` height,
+/// in a viewport of the normal screen for multiplexer panes and terminals
+/// without an alternate screen.
+fn init_terminal(inline: Option<u16>) -> color_eyre::Result<Terminal<impl Backend>> {
     enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?;
     let backend = CrosstermBackend::new(stdout());
-    let terminal = Terminal::new(backend)?;
+    let terminal = match inline {
+        Some(height) => {
+            INLINE.store(true, Ordering::Relaxed);
+            Terminal::with_options(
+                backend,
+                TerminalOptions {
+                    viewport: Viewport::Inline(height),
+                },
+            )?
+        }
+        None => {
+            stdout().execute(EnterAlternateScreen)?;
+            Terminal::new(backend)?
+        }
+    };
     Ok(terminal)
 }
 
 fn restore_terminal() -> color_eyre::Result<()> {
     disable_raw_mode()?;
-    stdout().execute(LeaveAlternateScreen)?;
+    stdout().execute(DisableFocusChange)?;
+    if INLINE.load(Ordering::Relaxed) {
+        // Keep the last frame and continue below it
+        println!();
+    } else {
+        stdout().execute(LeaveAlternateScreen)?;
+    }
     Ok(())
 }
 
@@ -136,11 +687,63 @@ fn init_tracing() -> anyhow::Result<WorkerGuard> {
     Ok(guard)
 }
 
-#[derive(Debug, Default)]
+/// Focusable panes, in the order Tab cycles through them.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 enum Tab {
     #[default]
     Services,
     Instances,
+    Info,
+}
+
+impl Tab {
+    const K_ORDER: [Tab; 3] = [Tab::Services, Tab::Instances, Tab::Info];
+
+    /// The pane `delta` steps away, wrapping around.
+    fn cycle(self, delta: isize) -> Self {
+        let index = Self::K_ORDER
+            .iter()
+            .position(|tab| *tab == self)
+            .unwrap_or(0) as isize;
+        Self::K_ORDER[(index + delta).rem_euclid(Self::K_ORDER.len() as isize) as usize]
+    }
+}
+
+/// Destructive actions that are confirmed before being run.
+#[derive(Debug, Clone, Copy)]
+enum Action {
+    /// Overwrite the stored baseline of the current profile
+    RecordBaseline,
+    /// Drop everything discovered and switch to the next profile
+    NextProfile,
+    /// Drop everything discovered and switch to the next browse domain
+    NextDomain,
+}
+
+/// Actions on the selection that `.` repeats, e.g. on instance after
+/// instance while going through a list.
+#[derive(Debug, Clone)]
+enum Repeatable {
+    /// Export to the path, as typed, and expanded
+    Export(String, PathBuf),
+    /// Query the selected instance once
+    Query,
+    /// Copy a summary of the selected instance, as markdown or not
+    Copy(bool),
+    /// Open the host card of the selected instance
+    HostCard,
+    /// Connect to the address picked in the Info pane
+    Interact,
+    /// Check the port of the selected instance
+    PortCheck,
+}
+
+/// What the UI asks of the mDNS worker.
+#[derive(Debug)]
+enum WorkerCommand {
+    /// Query a base service type again, or every query of the profile
+    Requery(Option<String>),
+    Stop,
 }
 
 #[derive(Debug, Default)]
@@ -150,232 +753,2295 @@ enum State {
     Exit,
 }
 
-struct App {
-    stop: Sender<()>,
-    services: Arc<Mutex<ListWidget<String>>>,
-    instances: Arc<Mutex<HashMap<String, ListWidget<Info>>>>,
-    current_tab: Tab,
-    worker_handle: Option<JoinHandle<anyhow::Result<()>>>,
-}
+/// Spawn the mDNS worker browsing according to `profile` in `domain`.
+///
+/// The worker forwards the mDNS events through an [`EventQueue`] to a
+/// model thread, which applies them to the `model`. Browse domains are
+/// always enumerated too. A wide-area domain with a DNS Push server is
+/// subscribed to through `push_server` rather than browsed with the
+/// `backend`. Returns the worker's command channel and its handle.
+///
+/// Drives both the TUI and the headless `browse`.
+#[allow(clippy::type_complexity)]
+fn spawn_worker(
+    profile: &Profile,
+    domain: &str,
+    backend: BackendKind,
+    push_server: Option<&str>,
+    settings: QueueSettings,
+    stats: Arc<EventStats>,
+    model: Model,
+) -> anyhow::Result<(Sender<WorkerCommand>, JoinHandle<anyhow::Result<()>>)> {
+    let mdns = match push_server {
+        Some(server) => backend::start_push(server)?,
+        None => {
+            if domain != domain::K_LOCAL_DOMAIN && !backend.wide_area() {
+                anyhow::bail!(
+                    "The {backend:?} backend can only browse {}",
+                    domain::K_LOCAL_DOMAIN
+                );
+            }
+            backend::start(backend, profile.if_kinds(), profile.ipv6_only)?
+        }
+    };
+    let mdns = Arc::new(Mutex::new(mdns));
+    // WS-Discovery is link-local, like mDNS
+    let wsd = (profile.ws_discovery && domain == domain::K_LOCAL_DOMAIN)
+        .then(|| wsd::start(profile.ipv6_only))
+        .transpose()?;
+    let (command_tx, command_rx) = flume::unbounded();
+    let queries: Vec<_> = if profile.queries.is_empty() {
+        vec![K_SERVICE_TYPE_ENUMERATION.to_string()]
+    } else {
+        profile.queries.clone()
+    }
+    .iter()
+    .map(|query| domain::in_domain(query, domain))
+    .collect();
+    let ignore = profile.ignore_filter()?;
+    let polite = profile.polite;
+    let device_info = format!("{}{domain}", model::K_DEVICE_INFO_TYPE);
+    let now = Instant::now();
+    // Service types to query again, their interval and when they are due next
+    let mut schedule: Vec<_> = profile
+        .refresh_intervals()?
+        .into_iter()
+        .map(|(query, interval)| (domain::in_domain(&query, domain), interval, now + interval))
+        .collect();
 
-impl App {
-    #[instrument]
-    fn new<T: AsRef<str> + std::fmt::Debug>(query: T, interface: IfKind) -> anyhow::Result<Self> {
-        let mdns = ServiceDaemon::new()?;
-        let mdns = Arc::new(Mutex::new(mdns));
-        let services = Arc::new(Mutex::new(
-            ListWidget::default().name("Services".to_string()),
-        ));
-        let instances = Arc::new(Mutex::new(HashMap::new()));
-        let (stop_tx, stop_rx) = flume::bounded(1);
+    let worker = std::thread::spawn(move || -> anyhow::Result<()> {
+        let _span = tracing::span!(Level::TRACE, "mDNS worker").entered();
 
-        let worker = {
-            let mdns = mdns.clone();
-            let services = services.clone();
-            let instances = instances.clone();
-            let query = query.as_ref().to_string();
-            std::thread::spawn(move || -> anyhow::Result<()> {
-                let _span = tracing::span!(Level::TRACE, "mDNS worker").entered();
-
-                let base = {
-                    let mdns = mdns.lock();
-                    mdns.enable_interface(interface.clone())?;
-                    mdns.browse(query.as_str())?
-                };
+        let (queue, events) =
+            EventQueue::new(settings.capacity, settings.backpressure, stats.clone());
+        let paused = model.paused.clone();
+        let model = {
+            // The WS-Discovery endpoints are instances of a type of their own
+            let queries: Vec<_> = queries
+                .iter()
+                .cloned()
+                .chain(wsd.is_some().then(|| wsd::K_SERVICE_TYPE.to_string()))
+                .collect();
+            let stats = stats.clone();
+            std::thread::spawn(move || {
+                let _span = tracing::span!(Level::TRACE, "Model worker").entered();
+                let mut expired = Instant::now();
+                loop {
+                    match events.recv_timeout(K_EXPIRY_INTERVAL) {
+                        Ok(first) => {
+                            let batch = std::iter::once(first)
+                                .chain(events.try_iter().take(K_MAX_EVENT_BATCH))
+                                .collect::<Vec<_>>();
+                            stats.depth.store(events.len(), Ordering::Relaxed);
+                            let batch = events::coalesce(batch, &stats);
+                            stats
+                                .applied
+                                .fetch_add(batch.len() as u64, Ordering::Relaxed);
+                            for event in batch {
+                                model.apply(event, &queries, &ignore);
+                            }
+                            model.refresh_shared();
+                            model.publish();
+                        }
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                    // Expired on a schedule even while events keep coming
+                    if expired.elapsed() >= K_EXPIRY_INTERVAL {
+                        model.expire();
+                        model.refresh_shared();
+                        model.publish();
+                        expired = Instant::now();
+                    }
+                }
+            })
+        };
 
-                tracing::info!("Started the mDNS browsing");
-
-                let receivers = Rc::new(RefCell::new(vec![base]));
-                let event_handler = {
-                    let receivers = receivers.clone();
-                    let mdns = mdns.clone();
-                    move |event| -> anyhow::Result<()> {
-                        if let Ok(event) = event {
-                            match event {
-                                ServiceEvent::ServiceFound(service_type, full_name) => {
-                                    tracing::debug!("New service found: {full_name}");
-                                    if service_type == query {
-                                        services.lock().push(full_name.clone());
-                                        instances.lock().insert(
-                                            full_name.clone(),
-                                            ListWidget::default().name(full_name.clone()),
-                                        );
-                                        let receiver = mdns.lock().browse(&full_name)?;
-                                        let mut receivers = receivers.borrow_mut();
-                                        receivers.push(receiver);
-                                    }
-                                }
-                                ServiceEvent::ServiceResolved(info) => {
-                                    tracing::debug!("Service resolved: {info:#?}");
-                                    if let Some(resolved) =
-                                        instances.lock().get_mut(info.get_type())
-                                    {
-                                        resolved.push(Info { info });
-                                    }
-                                }
-                                ServiceEvent::ServiceRemoved(service_type, full_name) => {
-                                    tracing::debug!("Service removed: {full_name}");
-                                    if service_type == query {
-                                        services.lock().remove(&full_name);
-                                        instances.lock().remove(&full_name);
-                                    } else if let Some(resolved) =
-                                        instances.lock().get_mut(&service_type)
-                                    {
-                                        resolved.remove(&full_name);
-                                    }
-                                }
-                                ServiceEvent::SearchStarted(service) => {
-                                    tracing::trace!("Search Started for {service}");
-                                }
-                                ServiceEvent::SearchStopped(service) => {
-                                    tracing::trace!("Search Stopped for {service}");
+        // Paces the queries in polite mode, they are sent right away otherwise
+        let limiter = polite.then(|| Rc::new(RefCell::new(Limiter::new(Instant::now()))));
+        let base = {
+            let mdns = mdns.lock();
+            queries
+                .iter()
+                .map(String::as_str)
+                .chain(domain::K_BROWSE_DOMAIN_QUERIES)
+                .chain((!queries.contains(&device_info)).then_some(device_info.as_str()))
+                .map(|query| Ok((query.to_string(), mdns.browse(query)?)))
+                .collect::<anyhow::Result<Vec<_>>>()?
+        };
+        if let Some(limiter) = limiter.as_ref() {
+            let now = Instant::now();
+            for (query, _) in base.iter() {
+                limiter.borrow_mut().sent(query, now);
+            }
+        }
+
+        tracing::info!("Started the mDNS browsing");
+
+        let receivers = Rc::new(RefCell::new(base));
+        let event_handler = {
+            let receivers = receivers.clone();
+            let mdns = mdns.clone();
+            let queries = queries.clone();
+            let limiter = limiter.clone();
+            move |event| -> anyhow::Result<()> {
+                if let Ok(event) = event {
+                    if let ServiceEvent::ServiceFound(service_type, full_name) = &event {
+                        // Only type enumerations find types to browse, and
+                        // device info is being browsed already
+                        if queries.contains(service_type)
+                            && domain::is_type_enumeration(service_type)
+                            && *full_name != device_info
+                        {
+                            match limiter.as_ref() {
+                                Some(limiter) => limiter.borrow_mut().request(full_name),
+                                None => {
+                                    let receiver = mdns.lock().browse(full_name)?;
+                                    let mut receivers = receivers.borrow_mut();
+                                    receivers.push((full_name.clone(), receiver));
                                 }
                             }
                         }
-
-                        Ok(())
                     }
-                };
-
-                let mut stop = false;
-                while !stop {
-                    let receivers = receivers.borrow().clone();
-                    let mut selector = Selector::new();
-                    for receiver in receivers.iter() {
-                        selector = selector.recv(receiver, &event_handler);
-                    }
-                    selector = selector.recv(&stop_rx, |_| {
-                        stop = true;
-                        Ok(())
-                    });
-                    selector.wait()?;
+                    queue.push(event)?;
                 }
 
-                mdns.lock().shutdown()?;
+                Ok(())
+            }
+        };
 
-                tracing::info!("Stopped the mDNS browsing");
+        // Browse `query` afresh, which sends the queries again, or for
+        // the first time
+        let browse = |query: &str| -> anyhow::Result<()> {
+            let mut receivers = receivers.borrow_mut();
+            let mdns = mdns.lock();
+            let Some((_, receiver)) = receivers.iter_mut().find(|(browsed, _)| browsed == query)
+            else {
+                receivers.push((query.to_string(), mdns.browse(query)?));
+                return Ok(());
+            };
+            tracing::debug!("Querying {query} again");
+            if let Err(e) = mdns.stop_browse(query) {
+                tracing::warn!("Failed to stop browsing {query}: {e:#}");
+            }
+            *receiver = mdns.browse(query)?;
+            Ok(())
+        };
+        // Query again now, or once polite to
+        let requery = |query: &str| -> anyhow::Result<()> {
+            match limiter.as_ref() {
+                Some(limiter) => {
+                    limiter.borrow_mut().request(query);
+                    Ok(())
+                }
+                None => browse(query),
+            }
+        };
 
+        let mut stop = false;
+        while !stop {
+            let mut requested = None;
+            let browsing = receivers.borrow().clone();
+            let mut selector = Selector::new();
+            for (_, receiver) in browsing.iter() {
+                selector = selector.recv(receiver, &event_handler);
+            }
+            if let Some(wsd) = wsd.as_ref() {
+                selector = selector.recv(wsd, &event_handler);
+            }
+            selector = selector.recv(&command_rx, |command| {
+                match command {
+                    Ok(WorkerCommand::Requery(service_type)) => requested = Some(service_type),
+                    Ok(WorkerCommand::Stop) | Err(_) => stop = true,
+                }
                 Ok(())
-            })
-        };
+            });
+            let held = limiter.as_ref().and_then(|limiter| limiter.borrow().next());
+            match schedule.iter().map(|(_, _, next)| *next).chain(held).min() {
+                // Timing out only means some type or held query is due
+                Some(deadline) => selector.wait_deadline(deadline).unwrap_or(Ok(()))?,
+                None => selector.wait()?,
+            }
+
+            // Browsing afresh sends the queries again, the answers
+            // refresh the instances
+            let now = Instant::now();
+            for (query, interval, next) in schedule.iter_mut().filter(|(_, _, next)| *next <= now) {
+                *next = now + *interval;
+                if paused.load(Ordering::Relaxed) {
+                    tracing::debug!("Not querying {query} again while paused");
+                    continue;
+                }
+                requery(query)?;
+            }
+            // Asked for from the UI, every query browsing the type
+            if let Some(service_type) = requested {
+                let browsed: Vec<_> = receivers
+                    .borrow()
+                    .iter()
+                    .map(|(query, _)| query.clone())
+                    .filter(|query| match service_type.as_deref() {
+                        Some(service_type) => domain::base_type(query) == service_type,
+                        None => queries.contains(query),
+                    })
+                    .collect();
+                for query in browsed {
+                    requery(&query)?;
+                }
+            }
+            if let Some(limiter) = limiter.as_ref() {
+                let due = limiter.borrow_mut().due(Instant::now());
+                for query in due {
+                    browse(&query)?;
+                }
+                let held = limiter.borrow().held();
+                if held > 0 {
+                    tracing::trace!("Holding {held} queries back");
+                }
+            }
+        }
+
+        // Closing the queue lets the model thread finish
+        drop(event_handler);
+        model.join().expect("The model worker has panicked");
+        mdns.lock().shutdown()?;
+
+        tracing::info!("Stopped the mDNS browsing: {}", stats.summary());
+
+        Ok(())
+    });
+
+    Ok((command_tx, worker))
+}
+
+struct App {
+    worker: Sender<WorkerCommand>,
+    model: Model,
+    current_domain: usize,
+    current_tab: Tab,
+    /// The focused pane takes the whole screen
+    zoomed: bool,
+    interact: Option<Interact>,
+    host: Option<Host>,
+    /// Session loaded with `--import`
+    import: Option<Import>,
+    import_diff: Option<ImportDiff>,
+    /// Catalog loaded with `--catalog`
+    catalog: Option<Catalog>,
+    catalog_view: Option<CatalogView>,
+    query_picker: Option<QueryPicker>,
+    visibility: Option<Visibility>,
+    event_log: Option<EventLogView>,
+    /// Formats everything resolved can be exported in
+    exporters: Registry,
+    /// Format picked with `--output`, by the file extension otherwise
+    export_format: Option<String>,
+    /// Templates from the config, offered by the query picker
+    query_templates: Vec<String>,
+    recent_queries: Recent,
+    export_picker: Option<ExportPicker>,
+    recent_exports: Recent,
+    copy_picker: Option<CopyPicker>,
+    note_prompt: Option<NotePrompt>,
+    /// Instances pane of the grouped Apple continuity services
+    continuity: Continuity,
+    confirm: Option<Confirm<Action>>,
+    profiles: Vec<(String, Profile)>,
+    current_profile: usize,
+    backend: BackendKind,
+    queue_settings: QueueSettings,
+    stats: Arc<EventStats>,
+    config_path: Option<PathBuf>,
+    config_watcher: Option<(RecommendedWatcher, Receiver<()>)>,
+    /// Commands read from `--control-pipe`
+    control: Option<Receiver<anyhow::Result<ControlCommand>>>,
+    /// Frames drawn per second at most
+    refresh_rate: u8,
+    /// DNS Push servers of the wide-area domains, by domain
+    push_servers: BTreeMap<String, String>,
+    /// Pending check for a newer release
+    update: Option<Receiver<String>>,
+    /// Newer release than the running one, if any
+    latest_version: Option<String>,
+    /// Pending one-shot query and the instance it is for
+    whois: Option<(String, Receiver<anyhow::Result<Vec<whois::Answer>>>)>,
+    /// Instance a test pattern is being shown on, until it is
+    test_pattern: Option<(String, Receiver<anyhow::Result<()>>)>,
+    /// Printer a test page is being sent to, until it is
+    test_page: Option<(String, Receiver<anyhow::Result<u32>>)>,
+    /// Host sent a wake-on-LAN packet and when, until it advertises again
+    wake: Option<(String, Instant)>,
+    /// Whether the UI state is saved on exit for the next run
+    remember_ui_state: bool,
+    /// Whether the addresses of the selected instance are pinged
+    pinging: bool,
+    /// What is left to restore of the previous run's UI state, until when
+    restore: Option<(Instant, UiState)>,
+    /// What `.` repeats
+    last_action: Option<Repeatable>,
+    keymap: Keymap,
+    /// Problems with the settings, shown until dismissed
+    diagnostics: Option<Diagnostics>,
+    toasts: Toasts,
+    hud: Hud,
+    /// Save the next frame once drawn
+    screenshot: bool,
+    /// Pause the enrichment and probing while the terminal is unfocused
+    pause_unfocused: bool,
+    worker_handle: Option<JoinHandle<anyhow::Result<()>>>,
+}
+
+impl App {
+    #[instrument]
+    fn new(
+        profiles: Vec<(String, Profile)>,
+        current_profile: usize,
+        backend: BackendKind,
+        queue_settings: QueueSettings,
+        scripts: Scripts,
+    ) -> anyhow::Result<Self> {
+        let (name, profile) = &profiles[current_profile];
+        colors::set_theme(profile.theme()?);
+        let model = Model::new(
+            profile.ignore_filter()?,
+            Self::load_baseline(name, profile)?,
+            scripts,
+        );
+        model.set_columns(profile.columns.clone());
+        model.set_age_thresholds(profile.age_thresholds()?);
+        model.set_group_apple(profile.group_apple);
+        let stats = Arc::new(EventStats::default());
+        let (worker, worker_handle) = spawn_worker(
+            profile,
+            domain::K_LOCAL_DOMAIN,
+            backend,
+            None,
+            queue_settings,
+            stats.clone(),
+            model.clone(),
+        )?;
 
         Ok(Self {
-            services,
-            instances,
-            stop: stop_tx,
+            model,
+            current_domain: 0,
+            worker,
             current_tab: Tab::Services,
-            worker_handle: Some(worker),
+            zoomed: false,
+            interact: None,
+            host: None,
+            import: None,
+            import_diff: None,
+            catalog: None,
+            catalog_view: None,
+            query_picker: None,
+            visibility: None,
+            event_log: None,
+            exporters: Registry::builtin(),
+            export_format: None,
+            query_templates: Vec::new(),
+            recent_queries: Recent::load("recent_queries.txt"),
+            export_picker: None,
+            copy_picker: None,
+            recent_exports: Recent::load("recent_exports.txt"),
+            note_prompt: None,
+            continuity: Continuity::default(),
+            confirm: None,
+            profiles,
+            current_profile,
+            backend,
+            queue_settings,
+            stats,
+            config_path: None,
+            config_watcher: None,
+            control: None,
+            refresh_rate: K_REFRESH_RATE,
+            push_servers: BTreeMap::new(),
+            update: None,
+            latest_version: None,
+            whois: None,
+            test_pattern: None,
+            test_page: None,
+            remember_ui_state: false,
+            pinging: false,
+            restore: None,
+            wake: None,
+            last_action: None,
+            keymap: Keymap::default(),
+            diagnostics: None,
+            toasts: Toasts::default(),
+            hud: Hud::default(),
+            screenshot: false,
+            pause_unfocused: false,
+            worker_handle: Some(worker_handle),
         })
     }
 
-    fn handle_event(&mut self, event: Event) -> anyhow::Result<State> {
-        if let Event::Key(key) = event {
-            if key.kind == KeyEventKind::Press {
-                match key.code {
-                    KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        return Ok(State::Exit)
-                    }
-                    KeyCode::Left => self.current_tab = Tab::Services,
-                    KeyCode::Right => self.current_tab = Tab::Instances,
-                    _ => {
-                        let mut services = self.services.lock();
-                        let mut instances = self.instances.lock();
+    /// Hot-reload the config at `path` whenever it changes.
+    fn watch_config(mut self, path: PathBuf) -> Self {
+        match config::watch(&path) {
+            Ok(watcher) => self.config_watcher = Some(watcher),
+            Err(e) => tracing::warn!("Not watching {}: {e:#}", path.display()),
+        }
+        self.config_path = Some(path);
+        self
+    }
 
-                        match self.current_tab {
-                            Tab::Services => {
-                                services.process_key_event(&key);
-                            }
-                            Tab::Instances => {
-                                if let Some(selected) = services
-                                    .selected()
-                                    .and_then(|service| instances.get_mut(service))
-                                {
-                                    selected.process_key_event(&key);
-                                }
-                            }
-                        }
-                    }
+    /// Re-read the config and apply the settings that don't need browsing
+    /// to restart, i.e. the theme, ignore lists and columns.
+    ///
+    /// Queries and interfaces of the active profile are left untouched until
+    /// it is switched to again.
+    #[instrument(skip(self))]
+    fn reload_config(&mut self) {
+        let Some(path) = self.config_path.as_ref() else {
+            return;
+        };
+        let config = match Config::load(Some(path)) {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::warn!("Failed to reload the config: {e:#}");
+                self.toasts
+                    .error(format!("Failed to reload the config: {e:#}"));
+                return;
+            }
+        };
+
+        self.query_templates = config.query_templates;
+        self.set_keys(&config.keys);
+        self.model.set_txt_alerts(config.txt_alerts);
+        match label::compile(&config.labels) {
+            Ok(labelers) => self.model.set_labelers(labelers),
+            Err(e) => {
+                self.toasts
+                    .error(format!("Failed to apply the labels: {e:#}"));
+                return;
+            }
+        }
+        match computed::compile(&config.computed_columns) {
+            Ok(computed) => self.model.set_computed(computed),
+            Err(e) => {
+                self.toasts
+                    .error(format!("Failed to apply the computed columns: {e:#}"));
+                return;
+            }
+        }
+        let current = self.profiles[self.current_profile].0.clone();
+        for (name, profile) in config.profiles {
+            match self
+                .profiles
+                .iter_mut()
+                .find(|(existing, _)| *existing == name)
+            {
+                Some((_, existing)) if name == current => {
+                    existing.ignore = profile.ignore;
+                    existing.theme = profile.theme;
+                    existing.baseline = profile.baseline;
+                    existing.columns = profile.columns;
+                    existing.age = profile.age;
+                    existing.group_apple = profile.group_apple;
                 }
+                Some((_, existing)) => *existing = profile,
+                None => self.profiles.push((name, profile)),
             }
         }
+        // Set anew only when the config says so, the A key toggles it meanwhile
+        self.model
+            .set_group_apple(self.profiles[self.current_profile].1.group_apple);
 
-        Ok(State::Running)
+        if let Err(e) = self.apply_profile_settings() {
+            self.toasts
+                .error(format!("Failed to apply the config: {e:#}"));
+            return;
+        }
+        tracing::info!("Reloaded the config");
+        self.toasts.info("Config reloaded");
     }
 
-    fn run(&mut self, mut terminal: Terminal<impl Backend>) -> anyhow::Result<()> {
-        loop {
-            terminal.draw(|frame| {
-                frame.render_widget(self as &mut App, frame.size());
-            })?;
-
-            if poll(Duration::from_millis(
-                (K_REFRESH_RATE as f64 / 1000.) as u64,
-            ))? {
-                match self.handle_event(event::read()?)? {
-                    State::Exit => {
-                        return Ok(());
-                    }
-                    _ => {}
-                }
-            }
+    /// Bind the keys of the config's `[keys]`, reporting what had to be fixed up.
+    fn set_keys(&mut self, bindings: &BTreeMap<KeyAction, String>) {
+        let (keymap, problems) = Keymap::load(bindings);
+        for problem in problems.iter() {
+            tracing::warn!("Key binding: {problem}");
         }
+        self.keymap = keymap;
+        self.diagnostics = Diagnostics::new("Key bindings", problems);
     }
 
-    fn shutdown(&mut self) -> anyhow::Result<()> {
-        self.stop.send(())?;
-        if let Some(handle) = self.worker_handle.take() {
-            handle
-                .join()
-                .expect("The worker being joined has panicked")?;
+    /// Apply the settings of the current profile that don't affect browsing.
+    fn apply_profile_settings(&mut self) -> anyhow::Result<()> {
+        let (name, profile) = &self.profiles[self.current_profile];
+        colors::set_theme(profile.theme()?);
+        let ignore = profile.ignore_filter()?;
+        *self.model.baseline.lock() = Self::load_baseline(name, profile)?;
+        self.model.services().set_ignore(ignore.clone());
+        self.model.set_columns(profile.columns.clone());
+        self.model.set_age_thresholds(profile.age_thresholds()?);
+        for instances in self.model.instances().values_mut() {
+            instances.set_ignore(ignore.clone());
         }
         Ok(())
     }
-}
 
-impl Widget for &mut App {
-    fn render(self, area: Rect, buf: &mut Buffer) {
-        let vertical = Layout::vertical([
-            Constraint::Length(2),
-            Constraint::Min(0),
-            Constraint::Length(12),
-            Constraint::Length(2),
-        ]);
-        let [header_area, list_area, info_area, footer_area] = vertical.areas(area);
+    fn load_baseline(name: &str, profile: &Profile) -> anyhow::Result<Option<Baseline>> {
+        match profile.baseline_path(name) {
+            Some(path) => Baseline::load(&path),
+            None => Ok(None),
+        }
+    }
 
-        Paragraph::new(format!(
-            "{}, v{}",
-            env!("CARGO_PKG_DESCRIPTION"),
-            env!("CARGO_PKG_VERSION")
-        ))
-        .bold()
-        .centered()
-        .render(header_area, buf);
+    /// Store all the currently resolved hosts as the profile's baseline.
+    #[instrument(skip(self))]
+    fn record_baseline(&mut self) -> anyhow::Result<PathBuf> {
+        let (name, profile) = &self.profiles[self.current_profile];
+        let Some(path) = profile.baseline_path(name) else {
+            anyhow::bail!("No location to store the baseline of profile \"{name}\"");
+        };
+        let mut instances = self.model.instances();
+        let hosts: Vec<_> = instances
+            .values_mut()
+            .flat_map(|list| list.items_mut())
+            .map(|info| {
+                info.anomalous = false;
+                info.id()
+            })
+            .collect();
+        let baseline = Baseline::record(&path, hosts)?;
+        tracing::info!("Recorded baseline to {}", baseline.path().display());
+        *self.model.baseline.lock() = Some(baseline);
+        Ok(path)
+    }
 
-        let list_layout =
-            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]);
-        let [service_area, instances_area] = list_layout.areas(list_area);
-
-        let services = self.services.lock();
-        services.render(service_area, buf, matches!(self.current_tab, Tab::Services));
-        if let Some(selected) = services.selected() {
-            let instances = self.instances.lock();
-            if let Some(resolved_instances) = instances.get(selected) {
-                resolved_instances.render(
-                    instances_area,
-                    buf,
-                    matches!(self.current_tab, Tab::Instances),
-                );
-                if let Some(info) = resolved_instances.selected() {
-                    info.render(info_area, buf, false);
-                }
+    /// Restart browsing with the next configured profile.
+    ///
+    /// The current profile is kept when the next one can't be applied, e.g.
+    /// after a hot-reload broke it.
+    #[instrument(skip(self))]
+    fn next_profile(&mut self) -> anyhow::Result<()> {
+        if self.profiles.len() < 2 {
+            return Ok(());
+        }
+        let next = (self.current_profile + 1) % self.profiles.len();
+        let (name, profile) = &self.profiles[next];
+        if let Err(e) = profile
+            .validate()
+            .and_then(|()| Self::load_baseline(name, profile).map(drop))
+        {
+            tracing::warn!("Not switching to profile {name}: {e:#}");
+            self.toasts
+                .error(format!("Not switching to profile {name}: {e:#}"));
+            return Ok(());
+        }
+        if !self.switch_browsing(next, self.current_domain)? {
+            return Ok(());
+        }
+        tracing::info!(
+            "Switched to profile {}",
+            self.profiles[self.current_profile].0
+        );
+        self.toasts.info(format!(
+            "Switched to profile {}",
+            self.profiles[self.current_profile].0
+        ));
+        Ok(())
+    }
+
+    /// Subscribe to the wide-area domains of `push_servers` through them,
+    /// which makes them selectable right away.
+    fn set_push_servers(&mut self, push_servers: BTreeMap<String, String>) {
+        let mut domains = self.model.domains.lock();
+        for domain in push_servers.keys() {
+            if !domains.contains(domain) {
+                domains.push(domain.clone());
             }
         }
+        drop(domains);
+        self.push_servers = push_servers;
+    }
 
-        Paragraph::new(vec![
-            Line::from(services.controls()),
-            Line::from("←→ to switch panes, C-q to exit."),
-        ])
-        .centered()
-        .render(footer_area, buf);
+    /// Whether `domain` can be browsed, with the backend or a DNS Push server.
+    fn browsable(&self, domain: &str) -> bool {
+        domain == domain::K_LOCAL_DOMAIN
+            || self.backend.wide_area()
+            || self.push_servers.contains_key(domain)
+    }
+
+    /// Restart browsing in the next browsable domain.
+    #[instrument(skip(self))]
+    fn next_domain(&mut self) -> anyhow::Result<()> {
+        let next = {
+            let domains = self.model.domains.lock();
+            (1..domains.len())
+                .map(|step| (self.current_domain + step) % domains.len())
+                .find(|index| self.browsable(&domains[*index]))
+        };
+        let Some(next) = next else {
+            return Ok(());
+        };
+        if !self.switch_browsing(self.current_profile, next)? {
+            return Ok(());
+        }
+        let domain = self.model.domains.lock()[self.current_domain].clone();
+        tracing::info!("Switched to domain {domain}");
+        self.toasts.info(format!("Browsing {domain}"));
+        Ok(())
+    }
+
+    /// Shut browsing down and restart it with `profile` in `domain`.
+    ///
+    /// When that fails, e.g. as the backend can't start, browsing restarts
+    /// with the previous profile and domain and the error is toasted. Only
+    /// failing that is an error.
+    ///
+    /// Returns whether browsing switched.
+    fn switch_browsing(&mut self, profile: usize, domain: usize) -> anyhow::Result<bool> {
+        let previous = (self.current_profile, self.current_domain);
+        self.shutdown()?;
+        (self.current_profile, self.current_domain) = (profile, domain);
+        self.model
+            .set_group_apple(self.profiles[self.current_profile].1.group_apple);
+        let Err(e) = self.restart_browsing() else {
+            return Ok(true);
+        };
+        tracing::warn!("Failed to switch browsing: {e:#}");
+        self.toasts.error(format!("Failed to switch: {e:#}"));
+        (self.current_profile, self.current_domain) = previous;
+        self.model
+            .set_group_apple(self.profiles[self.current_profile].1.group_apple);
+        self.restart_browsing()?;
+        Ok(false)
+    }
+
+    /// Clear the model and spawn a new worker for the current profile and
+    /// domain, once the previous one has been shut down.
+    fn restart_browsing(&mut self) -> anyhow::Result<()> {
+        self.model.clear();
+        self.apply_profile_settings()?;
+        self.current_tab = Tab::Services;
+        self.interact = None;
+        self.host = None;
+        self.import_diff = None;
+        self.catalog_view = None;
+        self.visibility = None;
+        self.respawn_worker()
+    }
+
+    /// Spawn a new worker, with a new mDNS daemon and so an empty cache, for
+    /// the current profile and domain, keeping the model.
+    fn respawn_worker(&mut self) -> anyhow::Result<()> {
+        let domain = self.model.domains.lock()[self.current_domain].clone();
+        let (worker, worker_handle) = spawn_worker(
+            &self.profiles[self.current_profile].1,
+            &domain,
+            self.backend,
+            self.push_servers.get(&domain).map(String::as_str),
+            self.queue_settings,
+            self.stats.clone(),
+            self.model.clone(),
+        )?;
+        self.worker = worker;
+        self.worker_handle = Some(worker_handle);
+        Ok(())
+    }
+
+    /// Drop the selected instance, or every instance of its host, from the
+    /// model and from the mDNS cache.
+    ///
+    /// The cache can only be dropped as a whole, so browsing restarts with a
+    /// new daemon querying from scratch. Whatever is still around is resolved
+    /// again, while stale entries are gone for good.
+    #[instrument(skip(self))]
+    fn forget(&mut self, host: bool) -> anyhow::Result<()> {
+        let selected = {
+            let services = self.model.services();
+            let instances = self.model.instances();
+            services.selected().and_then(|service| {
+                let info = instances.get(&service.service_type)?.selected()?;
+                Some((
+                    service.service_type.clone(),
+                    info.info.get_fullname().to_string(),
+                    info.id(),
+                ))
+            })
+        };
+        let Some((service_type, fullname, hostname)) = selected else {
+            return Ok(());
+        };
+        // Stop first, so that no queued event brings the entry back
+        self.shutdown()?;
+        let forgotten = if host {
+            self.model.forget_host(&hostname);
+            hostname
+        } else {
+            self.model.forget_instance(&service_type, &fullname);
+            fullname
+        };
+        tracing::info!("Forgot {forgotten}");
+        self.current_tab = Tab::Instances;
+        self.respawn_worker()?;
+        self.toasts
+            .info(format!("Forgot {forgotten}, querying from scratch"));
+        Ok(())
+    }
+
+    /// Save the event log being viewed in the current directory.
+    fn save_event_log(&mut self) {
+        let Some(event_log) = self.event_log.as_ref() else {
+            return;
+        };
+        match eventlog::save(&event_log.events, Path::new(".")) {
+            Ok((text, ndjson)) => self.toasts.success(format!(
+                "Event log saved to {} and {}",
+                text.display(),
+                ndjson.display()
+            )),
+            Err(e) => self
+                .toasts
+                .error(format!("Failed to save the event log: {e:#}")),
+        }
+    }
+
+    /// Query the selected instance once, rather than waiting for it to be
+    /// announced again.
+    fn query_instance(&mut self) {
+        self.last_action = Some(Repeatable::Query);
+        let selected = {
+            let services = self.model.services();
+            let instances = self.model.instances();
+            services
+                .selected()
+                .and_then(|service| instances.get(&service.service_type)?.selected())
+                .map(|info| {
+                    let service_type = info.info.get_type();
+                    let fullname = info.info.get_fullname();
+                    let instance = fullname
+                        .strip_suffix(service_type)
+                        .map_or(fullname, |name| name.trim_end_matches('.'));
+                    (
+                        fullname.to_string(),
+                        instance.to_string(),
+                        domain::base_type(service_type).to_string(),
+                    )
+                })
+        };
+        let Some((fullname, instance, service_type)) = selected else {
+            return;
+        };
+        self.audit(&fullname, audit::Action::Probed);
+        self.toasts.info(format!("Querying {instance}..."));
+        let ipv6 = self.profiles[self.current_profile].1.ipv6_only;
+        self.whois = Some((
+            instance.clone(),
+            whois::query(&instance, &service_type, ipv6),
+        ));
+    }
+
+    /// Send a wake-on-LAN packet to the host of the selected instance, then
+    /// watch for it to advertise again.
+    ///
+    /// The hardware address is looked up in the neighbour table by the
+    /// host's addresses, so the host must have been reached lately.
+    fn wake(&mut self) {
+        let Some(hostname) = self.selected_hostname() else {
+            return;
+        };
+        let addresses: BTreeSet<IpAddr> = self
+            .model
+            .instances()
+            .values()
+            .flat_map(|list| list.items().filter(|info| info.id() == hostname))
+            .flat_map(Info::addresses)
+            .collect();
+        let neighbours = host::neighbours();
+        let Some(mac) = addresses.iter().find_map(|addr| neighbours.get(addr)) else {
+            self.toasts
+                .warning(format!("No MAC address known for {hostname}"));
+            return;
+        };
+        let addresses: Vec<_> = addresses.into_iter().collect();
+        match wake::send(mac, &addresses) {
+            Ok(()) => {
+                tracing::info!("Sent a wake-on-LAN packet to {hostname} ({mac})");
+                self.toasts.info(format!(
+                    "Woke {hostname} ({mac}), waiting for its services..."
+                ));
+                self.wake = Some((hostname, self.model.now()));
+            }
+            Err(e) => self.toasts.error(format!("{e:#}")),
+        }
+    }
+
+    /// Tell how long the woken host took to advertise again, or that it
+    /// didn't.
+    fn check_wake(&mut self) {
+        let Some((hostname, sent)) = self.wake.as_ref() else {
+            return;
+        };
+        let elapsed = self.model.now().saturating_duration_since(*sent);
+        if self.model.heard_from(hostname, *sent) {
+            let message = format!(
+                "{hostname} advertised {} after waking",
+                humantime::format_duration(Duration::from_secs(elapsed.as_secs()))
+            );
+            tracing::info!("{message}");
+            self.toasts.success(message);
+            self.wake = None;
+        } else if elapsed >= K_WAKE_TIMEOUT {
+            self.toasts.warning(format!(
+                "{hostname} didn't advertise within {} of waking",
+                humantime::format_duration(K_WAKE_TIMEOUT)
+            ));
+            self.wake = None;
+        }
+    }
+
+    /// Show a test pattern on the selected Cast or AirPlay device for a few
+    /// seconds, or print a test page on the selected IPP printer, to tell
+    /// which one it is.
+    fn test_device(&mut self) {
+        let selected = {
+            let services = self.model.services();
+            let instances = self.model.instances();
+            services
+                .selected()
+                .and_then(|service| instances.get(&service.service_type))
+                .and_then(|selected| selected.selected())
+                .map(|info| {
+                    (
+                        info.info.get_fullname().to_string(),
+                        info.info.get_type().to_string(),
+                        info.info.get_property_val_str("rp").map(str::to_string),
+                        info.info.get_property_val_str("pdl").map(str::to_string),
+                    )
+                })
+        };
+        let Some((fullname, service_type, resource, pdl)) = selected else {
+            return;
+        };
+        let device = cast::Device::of(&service_type);
+        let printer = domain::base_type(&service_type).starts_with("_ipp._tcp.");
+        if device.is_none() && !printer {
+            self.toasts
+                .warning("Only Cast, AirPlay and IPP devices can be tested");
+            return;
+        }
+        let Some((target, _)) = self.selected_target() else {
+            self.toasts
+                .warning(format!("No address resolved for {fullname}"));
+            return;
+        };
+        if let Some(device) = device {
+            self.toasts
+                .info(format!("Showing a test pattern on {fullname}..."));
+            self.test_pattern = Some((fullname, cast::show(device, target)));
+        } else {
+            self.toasts
+                .info(format!("Printing a test page on {fullname}..."));
+            let job = ipp::print_test_page(
+                target,
+                resource.as_deref().unwrap_or_default(),
+                pdl.as_deref(),
+                &fullname,
+            );
+            self.test_page = Some((fullname, job));
+        }
+    }
+
+    fn report_whois(&mut self, name: &str, answers: anyhow::Result<Vec<whois::Answer>>) {
+        match answers {
+            Ok(answers) if answers.is_empty() => self
+                .toasts
+                .warning(format!("{name} didn't answer the query")),
+            Ok(answers) => {
+                let zones = Zones::local();
+                let answers: Vec<_> = answers
+                    .iter()
+                    .map(|answer| {
+                        let records = answer
+                            .records
+                            .iter()
+                            .map(|record| record.to_string())
+                            .chain(answer.denied.iter().map(|record| format!("no {record}")))
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        format!(
+                            "{} in {:.1?} ({records})",
+                            zones.display(answer.from),
+                            answer.rtt,
+                        )
+                    })
+                    .collect();
+                self.toasts
+                    .success(format!("{name} answered from {}", answers.join("; ")));
+            }
+            Err(e) => self.toasts.error(format!("Failed to query {name}: {e:#}")),
+        }
+    }
+
+    /// Open the host card of the selected instance.
+    fn open_host_card(&mut self) {
+        self.last_action = Some(Repeatable::HostCard);
+        self.host = self
+            .selected_hostname()
+            .map(|hostname| Host::collect(&self.model, &hostname));
+    }
+
+    /// Connect to the address picked in the Info pane.
+    fn open_interact(&mut self) {
+        self.last_action = Some(Repeatable::Interact);
+        if let Some(fullname) = self.selected_fullname() {
+            if self.selected_target().is_some() {
+                self.audit(&fullname, audit::Action::Opened);
+            }
+        }
+        self.interact = self.selected_target().map(|(target, assumed)| {
+            if assumed {
+                self.toasts.info(format!(
+                    "Port 0 advertised, assuming {} for the type",
+                    target.port()
+                ));
+            }
+            Interact::connect(target)
+        });
+    }
+
+    /// Add `action` on the instance `fullname` to the audit trail.
+    fn audit(&mut self, fullname: &str, action: audit::Action) {
+        if let Err(e) = self.model.audit.record(fullname, action) {
+            self.toasts
+                .error(format!("Failed to record the action: {e:#}"));
+        }
+    }
+
+    /// Mark the selected instance as checked and nothing to worry about.
+    fn mark_ignored(&mut self) {
+        let Some(fullname) = self.selected_fullname() else {
+            return;
+        };
+        self.audit(&fullname, audit::Action::Ignored);
+        self.toasts.info(format!("Marked {fullname} as ignored"));
+    }
+
+    /// Open the prompt for a note on the selected instance.
+    fn open_note_prompt(&mut self) {
+        self.note_prompt = self.selected_fullname().map(NotePrompt::new);
+    }
+
+    /// Run the last action on the selection again.
+    fn repeat(&mut self) {
+        match self.last_action.clone() {
+            Some(Repeatable::Export(typed, path)) => self.export_to(&typed, &path),
+            Some(Repeatable::Query) => self.query_instance(),
+            Some(Repeatable::Copy(markdown)) => self.copy_summary(markdown),
+            Some(Repeatable::HostCard) => self.open_host_card(),
+            Some(Repeatable::Interact) => self.open_interact(),
+            Some(Repeatable::PortCheck) => self.check_port(),
+            None => self.toasts.info("Nothing to repeat yet"),
+        }
+    }
+
+    /// Copy a summary of the selected instance to the clipboard.
+    fn copy_summary(&mut self, markdown: bool) {
+        self.last_action = Some(Repeatable::Copy(markdown));
+        let summary = {
+            let services = self.model.services();
+            let instances = self.model.instances();
+            services
+                .selected()
+                .and_then(|service| instances.get(&service.service_type)?.selected())
+                .map(|info| export::summary(info, markdown))
+        };
+        let Some(summary) = summary else {
+            return;
+        };
+        match clipboard::copy(&summary) {
+            Ok(()) => self.toasts.success("Summary copied"),
+            Err(e) => self
+                .toasts
+                .error(format!("Failed to copy the summary: {e:#}")),
+        }
+    }
+
+    /// Start or stop pinging the addresses of the selected instance.
+    fn toggle_ping(&mut self) {
+        self.pinging = !self.pinging;
+        if self.pinging {
+            self.toasts.info("Pinging the selected instance");
+        } else {
+            self.model.pings.clear();
+            self.toasts.info("Stopped pinging");
+        }
+    }
+
+    /// Ping the addresses of the selected instance, those not pinged lately.
+    fn ping_selected(&self) {
+        let services = self.model.services();
+        let instances = self.model.instances();
+        let Some(info) = services
+            .selected()
+            .and_then(|service| instances.get(&service.service_type)?.selected())
+        else {
+            return;
+        };
+        let (port, _) = info.port();
+        let zones = Zones::local();
+        for addr in info.addresses() {
+            self.model.pings.request(zones.socket_addr(addr, port));
+        }
+    }
+
+    /// Check whether the port of the selected instance is listened on, at
+    /// each of its addresses.
+    fn check_port(&mut self) {
+        self.last_action = Some(Repeatable::PortCheck);
+        let targets = {
+            let services = self.model.services();
+            let instances = self.model.instances();
+            services
+                .selected()
+                .and_then(|service| instances.get(&service.service_type)?.selected())
+                .map(|info| {
+                    (
+                        info.info.get_hostname().to_string(),
+                        info.port(),
+                        info.addresses(),
+                    )
+                })
+        };
+        let Some((hostname, (port, _), addresses)) = targets else {
+            return;
+        };
+        if addresses.is_empty() {
+            self.toasts
+                .warning(format!("{hostname} has no address to check the port of"));
+            return;
+        }
+        let zones = Zones::local();
+        for addr in addresses {
+            self.model.ports.request(zones.socket_addr(addr, port));
+        }
+        self.toasts.info(format!(
+            "Checking whether {hostname} listens on port {port}"
+        ));
+    }
+
+    /// Open the popup choosing what of the selected instance to copy: one
+    /// of its addresses, the one picked in the Info pane first, its
+    /// hostname, or its whole record as JSON.
+    fn open_copy_picker(&mut self) {
+        let fields = {
+            let services = self.model.services();
+            let instances = self.model.instances();
+            services
+                .selected()
+                .and_then(|service| instances.get(&service.service_type)?.selected())
+                .map(|info| {
+                    let zones = Zones::local();
+                    let mut addresses = info.addresses();
+                    if let Some(selected) = info.selected_address() {
+                        addresses.retain(|addr| *addr != selected);
+                        addresses.insert(0, selected);
+                    }
+                    let mut fields: Vec<_> = addresses
+                        .into_iter()
+                        .map(|addr| ("Address".to_string(), zones.display(addr)))
+                        .collect();
+                    fields.push(("Hostname".to_string(), info.id()));
+                    match serde_json::to_string_pretty(&schema::Instance::from(&info.info)) {
+                        Ok(record) => fields.push(("Record".to_string(), record)),
+                        Err(e) => tracing::warn!("Failed to serialize the record: {e}"),
+                    }
+                    fields
+                })
+        };
+        self.copy_picker = fields.map(|fields| CopyPicker::new(fields, 0));
+    }
+
+    /// Copy `text`, the `field` picked in the copy popup, to the clipboard.
+    fn copy_field(&mut self, field: &str, text: &str) {
+        let field = field.to_lowercase();
+        match clipboard::copy(text) {
+            Ok(()) => self.toasts.success(format!("Copied the {field}")),
+            Err(e) => self
+                .toasts
+                .error(format!("Failed to copy the {field}: {e:#}")),
+        }
+    }
+
+    /// Restart browsing the current profile with the single `query`.
+    #[instrument(skip(self))]
+    fn set_query(&mut self, query: String) -> anyhow::Result<()> {
+        self.shutdown()?;
+        self.profiles[self.current_profile].1.queries = vec![query.clone()];
+        self.restart_browsing()?;
+        if let Err(e) = self.recent_queries.remember(&query) {
+            tracing::warn!("Failed to remember {query}: {e:#}");
+        }
+        self.toasts.info(format!("Querying {query}"));
+        Ok(())
+    }
+
+    /// Open the query picker with the configured, or else the default, templates.
+    fn open_query_picker(&mut self) {
+        let defaults: Vec<_> = query::K_DEFAULT_TEMPLATES
+            .iter()
+            .map(|template| template.to_string())
+            .collect();
+        let templates = if self.query_templates.is_empty() {
+            &defaults
+        } else {
+            &self.query_templates
+        };
+        self.query_picker = Some(QueryPicker::new(templates, self.recent_queries.iter()));
+    }
+
+    /// Open the prompt for the file to export to.
+    fn open_export_picker(&mut self) {
+        let format = match self.export_format.as_deref() {
+            Some(format) => format!("Format: {format}, from --output"),
+            None => format!(
+                "Format by the extension: {}, JSON otherwise",
+                self.exporters.names().join(", ")
+            ),
+        };
+        self.export_picker = Some(ExportPicker::new(self.recent_exports.iter(), format));
+    }
+
+    /// Export everything resolved to `path`, typed as `typed`.
+    fn export_to(&mut self, typed: &str, path: &Path) {
+        self.last_action = Some(Repeatable::Export(typed.to_string(), path.to_path_buf()));
+        match export::write(
+            &self.model,
+            path,
+            &self.exporters,
+            self.export_format.as_deref(),
+        ) {
+            Ok(()) => {
+                self.toasts
+                    .success(format!("Exported to {}", path.display()));
+                if let Err(e) = self.recent_exports.remember(typed) {
+                    tracing::warn!("Failed to remember {typed}: {e:#}");
+                }
+            }
+            Err(e) => self.toasts.error(format!("Failed to export: {e:#}")),
+        }
+    }
+
+    /// Run an `action`, which has been confirmed if it needed to be.
+    #[instrument(skip(self))]
+    fn run_action(&mut self, action: Action) -> anyhow::Result<()> {
+        match action {
+            Action::RecordBaseline => match self.record_baseline() {
+                Ok(path) => self
+                    .toasts
+                    .success(format!("Baseline saved to {}", path.display())),
+                Err(e) => self
+                    .toasts
+                    .error(format!("Failed to save the baseline: {e:#}")),
+            },
+            Action::NextProfile => self.next_profile()?,
+            Action::NextDomain => self.next_domain()?,
+        }
+        Ok(())
+    }
+
+    /// Run a command read from the control pipe.
+    #[instrument(skip(self))]
+    fn run_command(&mut self, command: ControlCommand) -> anyhow::Result<State> {
+        match command {
+            ControlCommand::Filter(expression) => {
+                let filter = if expression.is_empty() {
+                    None
+                } else {
+                    match Filter::parse(&expression) {
+                        Ok(filter) => Some(filter),
+                        Err(e) => {
+                            self.toasts.error(format!("Invalid filter: {e:#}"));
+                            return Ok(State::Running);
+                        }
+                    }
+                };
+                let mut services = self.model.services();
+                match self.current_tab {
+                    Tab::Services => services.update_filter(filter),
+                    Tab::Instances | Tab::Info => {
+                        let mut instances = self.model.instances();
+                        if let Some(selected) = services
+                            .selected()
+                            .and_then(|service| instances.get_mut(&service.service_type))
+                        {
+                            selected.update_filter(filter);
+                        }
+                    }
+                }
+            }
+            ControlCommand::Query(query) => self.set_query(domain::qualify(&query))?,
+            ControlCommand::Export(path) => match export::write(
+                &self.model,
+                &path,
+                &self.exporters,
+                self.export_format.as_deref(),
+            ) {
+                Ok(()) => self
+                    .toasts
+                    .success(format!("Exported to {}", path.display())),
+                Err(e) => self.toasts.error(format!("Failed to export: {e:#}")),
+            },
+            ControlCommand::Quit => return Ok(State::Exit),
+        }
+        Ok(State::Running)
+    }
+
+    fn handle_event(&mut self, event: Event) -> anyhow::Result<State> {
+        match event {
+            Event::FocusLost if self.pause_unfocused => {
+                tracing::debug!("Unfocused, pausing");
+                self.model.paused.store(true, Ordering::Relaxed);
+            }
+            Event::FocusGained if self.model.paused.swap(false, Ordering::Relaxed) => {
+                tracing::debug!("Focused, resuming");
+            }
+            _ => {}
+        }
+        if let Event::Key(key) = event {
+            if key.kind == KeyEventKind::Press {
+                let action = self.keymap.action(&key);
+                match key.code {
+                    _ if action == Some(KeyAction::Quit) => return Ok(State::Exit),
+                    KeyCode::F(12) => self.hud.toggle(),
+                    KeyCode::Esc if self.diagnostics.is_some() => self.diagnostics = None,
+                    _ if self.diagnostics.is_some() => {
+                        if let Some(diagnostics) = self.diagnostics.as_mut() {
+                            diagnostics.process_key_event(&key);
+                        }
+                    }
+                    _ if self.confirm.is_some() => {
+                        if let Some(mut confirm) = self.confirm.take() {
+                            confirm.process_key_event(&key);
+                            match confirm.answer() {
+                                Ok(Some(action)) => self.run_action(action)?,
+                                Ok(None) => {}
+                                Err(confirm) => self.confirm = Some(confirm),
+                            }
+                        }
+                    }
+                    _ if self.query_picker.is_some() => {
+                        if let Some(mut query_picker) = self.query_picker.take() {
+                            query_picker.process_key_event(&key);
+                            match query_picker.answer() {
+                                Ok(Some(query)) => self.set_query(query)?,
+                                Ok(None) => {}
+                                Err(query_picker) => self.query_picker = Some(query_picker),
+                            }
+                        }
+                    }
+                    _ if self.export_picker.is_some() => {
+                        if let Some(mut export_picker) = self.export_picker.take() {
+                            export_picker.process_key_event(&key);
+                            match export_picker.answer() {
+                                Ok(Some((typed, path))) => self.export_to(&typed, &path),
+                                Ok(None) => {}
+                                Err(export_picker) => self.export_picker = Some(export_picker),
+                            }
+                        }
+                    }
+                    _ if self.copy_picker.is_some() => {
+                        if let Some(mut copy_picker) = self.copy_picker.take() {
+                            copy_picker.process_key_event(&key);
+                            match copy_picker.answer() {
+                                Ok(Some((field, text))) => self.copy_field(&field, &text),
+                                Ok(None) => {}
+                                Err(copy_picker) => self.copy_picker = Some(copy_picker),
+                            }
+                        }
+                    }
+                    _ if self.note_prompt.is_some() => {
+                        if let Some(mut note_prompt) = self.note_prompt.take() {
+                            note_prompt.process_key_event(&key);
+                            match note_prompt.answer() {
+                                Ok(Some((fullname, note))) => {
+                                    self.audit(&fullname, audit::Action::Noted(note))
+                                }
+                                Ok(None) => {}
+                                Err(note_prompt) => self.note_prompt = Some(note_prompt),
+                            }
+                        }
+                    }
+                    _ if action == Some(KeyAction::NextProfile) => {
+                        if self.profiles.len() < 2 || self.model.services().is_empty() {
+                            self.run_action(Action::NextProfile)?
+                        } else {
+                            self.confirm = Some(Confirm::new(
+                                "Switching the profile discards everything discovered so far.",
+                                Action::NextProfile,
+                            ));
+                        }
+                    }
+                    _ if action == Some(KeyAction::NextDomain) => {
+                        if self.model.domains.lock().len() < 2 {
+                            self.toasts
+                                .info("No wide-area browse domains discovered yet");
+                        } else if !self.backend.wide_area() && self.push_servers.is_empty() {
+                            self.toasts.error(format!(
+                                "The {:?} backend can only browse {}, try --backend avahi",
+                                self.backend,
+                                domain::K_LOCAL_DOMAIN
+                            ));
+                        } else if self.model.services().is_empty() {
+                            self.run_action(Action::NextDomain)?
+                        } else {
+                            self.confirm = Some(Confirm::new(
+                                "Switching the domain discards everything discovered so far.",
+                                Action::NextDomain,
+                            ));
+                        }
+                    }
+                    _ if action == Some(KeyAction::Screenshot) => self.screenshot = true,
+                    _ if action == Some(KeyAction::RecordBaseline) => {
+                        let (name, profile) = &self.profiles[self.current_profile];
+                        match profile.baseline_path(name).filter(|path| path.exists()) {
+                            Some(path) => {
+                                self.confirm = Some(Confirm::new(
+                                    format!("Overwrite the baseline stored in {}?", path.display()),
+                                    Action::RecordBaseline,
+                                ))
+                            }
+                            None => self.run_action(Action::RecordBaseline)?,
+                        }
+                    }
+                    KeyCode::Esc if self.interact.is_some() => self.interact = None,
+                    _ if self.interact.is_some() => {
+                        if let Some(interact) = self.interact.as_mut() {
+                            interact.process_key_event(&key);
+                        }
+                    }
+                    KeyCode::Esc if self.visibility.is_some() => self.visibility = None,
+                    _ if self.visibility.is_some() => {
+                        if let Some(visibility) = self.visibility.as_mut() {
+                            visibility.process_key_event(&key);
+                        }
+                    }
+                    _ if action == Some(KeyAction::Visibility) && !self.focused_searching() => {
+                        self.visibility = Some(Visibility::collect(&self.model))
+                    }
+                    KeyCode::Esc if self.event_log.is_some() => self.event_log = None,
+                    KeyCode::Char('s') if self.event_log.is_some() => self.save_event_log(),
+                    _ if self.event_log.is_some() => {
+                        if let Some(event_log) = self.event_log.as_mut() {
+                            event_log.process_key_event(&key);
+                        }
+                    }
+                    _ if action == Some(KeyAction::EventLog) && !self.focused_searching() => {
+                        self.event_log = Some(EventLogView::new(&self.model.event_log))
+                    }
+                    KeyCode::Esc if self.import_diff.is_some() => self.import_diff = None,
+                    _ if self.import_diff.is_some() => {
+                        if let Some(import_diff) = self.import_diff.as_mut() {
+                            import_diff.process_key_event(&key);
+                        }
+                    }
+                    _ if action == Some(KeyAction::ImportDiff)
+                        && self.import.is_some()
+                        && !self.focused_searching() =>
+                    {
+                        self.import_diff = self
+                            .import
+                            .as_ref()
+                            .map(|import| ImportDiff::compare(import, &self.model));
+                    }
+                    KeyCode::Esc if self.catalog_view.is_some() => self.catalog_view = None,
+                    _ if self.catalog_view.is_some() => {
+                        if let Some(catalog_view) = self.catalog_view.as_mut() {
+                            catalog_view.process_key_event(&key);
+                        }
+                    }
+                    _ if action == Some(KeyAction::Catalog)
+                        && self.catalog.is_some()
+                        && !self.focused_searching() =>
+                    {
+                        self.catalog_view = self
+                            .catalog
+                            .as_ref()
+                            .map(|catalog| CatalogView::check(catalog, &self.model));
+                    }
+                    KeyCode::Esc if self.host.is_some() => self.host = None,
+                    _ if self.host.is_some() => {
+                        let follow = self.host.as_mut().and_then(|host| {
+                            host.process_key_event(&key);
+                            host.follow()
+                        });
+                        if let Some(hostname) = follow {
+                            self.host = Some(Host::collect(&self.model, &hostname));
+                        }
+                    }
+                    KeyCode::Char('h')
+                        if matches!(self.current_tab, Tab::Info)
+                            || (matches!(self.current_tab, Tab::Instances)
+                                && !self.selected_instances_searching()) =>
+                    {
+                        self.open_host_card()
+                    }
+                    KeyCode::Char(c @ ('x' | 'X'))
+                        if matches!(self.current_tab, Tab::Info)
+                            || (matches!(self.current_tab, Tab::Instances)
+                                && !self.selected_instances_searching()) =>
+                    {
+                        self.forget(c == 'X')?
+                    }
+                    KeyCode::Char(c @ ('c' | 'C'))
+                        if matches!(self.current_tab, Tab::Info)
+                            || (matches!(self.current_tab, Tab::Instances)
+                                && !self.selected_instances_searching()) =>
+                    {
+                        self.copy_summary(c == 'C')
+                    }
+                    KeyCode::Char('P')
+                        if matches!(self.current_tab, Tab::Info)
+                            || (matches!(self.current_tab, Tab::Instances)
+                                && !self.selected_instances_searching()) =>
+                    {
+                        self.toggle_ping()
+                    }
+                    KeyCode::Char('o')
+                        if matches!(self.current_tab, Tab::Info)
+                            || (matches!(self.current_tab, Tab::Instances)
+                                && !self.selected_instances_searching()) =>
+                    {
+                        self.check_port()
+                    }
+                    KeyCode::Char('y')
+                        if matches!(self.current_tab, Tab::Info)
+                            || (matches!(self.current_tab, Tab::Instances)
+                                && !self.selected_instances_searching()) =>
+                    {
+                        self.open_copy_picker()
+                    }
+                    KeyCode::Char('w')
+                        if matches!(self.current_tab, Tab::Info)
+                            || (matches!(self.current_tab, Tab::Instances)
+                                && !self.selected_instances_searching()) =>
+                    {
+                        self.query_instance()
+                    }
+                    KeyCode::Char('W')
+                        if matches!(self.current_tab, Tab::Info)
+                            || (matches!(self.current_tab, Tab::Instances)
+                                && !self.selected_instances_searching()) =>
+                    {
+                        self.wake()
+                    }
+                    KeyCode::Char('t')
+                        if matches!(self.current_tab, Tab::Info)
+                            || (matches!(self.current_tab, Tab::Instances)
+                                && !self.selected_instances_searching()) =>
+                    {
+                        self.test_device()
+                    }
+                    KeyCode::Char('m')
+                        if matches!(self.current_tab, Tab::Info)
+                            || (matches!(self.current_tab, Tab::Instances)
+                                && !self.selected_instances_searching()) =>
+                    {
+                        self.mark_ignored()
+                    }
+                    KeyCode::Char('n')
+                        if matches!(self.current_tab, Tab::Info)
+                            || (matches!(self.current_tab, Tab::Instances)
+                                && !self.selected_instances_searching()) =>
+                    {
+                        self.open_note_prompt()
+                    }
+                    _ if action == Some(KeyAction::Requery) && !self.focused_searching() => {
+                        if let Err(e) = self.requery() {
+                            self.toasts.error(format!("{e:#}"));
+                        }
+                    }
+                    _ if action == Some(KeyAction::RecentFirst) && !self.focused_searching() => {
+                        self.toggle_order(Order::Recent)
+                    }
+                    _ if action == Some(KeyAction::PriorityOrder) && !self.focused_searching() => {
+                        self.toggle_order(Order::Priority)
+                    }
+                    _ if action == Some(KeyAction::GroupApple) && !self.focused_searching() => {
+                        let group = !self.model.grouping_apple();
+                        self.model.set_group_apple(group);
+                        self.toasts.info(if group {
+                            "Apple continuity services grouped by host"
+                        } else {
+                            "Apple continuity services listed apart"
+                        });
+                    }
+                    _ if action == Some(KeyAction::Repeat) && !self.focused_searching() => {
+                        self.repeat()
+                    }
+                    _ if action == Some(KeyAction::Zoom) && !self.focused_searching() => {
+                        self.zoomed = !self.zoomed
+                    }
+                    _ if action == Some(KeyAction::PickQuery) && !self.focused_searching() => {
+                        self.open_query_picker()
+                    }
+                    _ if action == Some(KeyAction::Export) && !self.focused_searching() => {
+                        self.open_export_picker()
+                    }
+                    KeyCode::Tab | KeyCode::BackTab if !self.focused_searching() => {
+                        self.cycle_focus(if key.code == KeyCode::Tab { 1 } else { -1 })
+                    }
+                    KeyCode::Left if !matches!(self.current_tab, Tab::Info) => {
+                        self.current_tab = Tab::Services
+                    }
+                    KeyCode::Right if !matches!(self.current_tab, Tab::Info) => {
+                        self.current_tab = Tab::Instances
+                    }
+                    KeyCode::Enter
+                        if matches!(self.current_tab, Tab::Instances)
+                            && !self.selected_instances_searching()
+                            && !self.apple_group_selected() =>
+                    {
+                        self.current_tab = Tab::Info
+                    }
+                    KeyCode::Esc if matches!(self.current_tab, Tab::Info) => {
+                        self.current_tab = Tab::Instances
+                    }
+                    KeyCode::Char('i') if matches!(self.current_tab, Tab::Info) => {
+                        self.open_interact()
+                    }
+                    _ => {
+                        let mut services = self.model.services();
+                        let mut instances = self.model.instances();
+
+                        match self.current_tab {
+                            Tab::Services => {
+                                services.process_key_event(&key);
+                            }
+                            Tab::Instances
+                                if services.selected().is_some_and(|service| {
+                                    service.service_type == continuity::K_GROUP_TYPE
+                                }) =>
+                            {
+                                self.continuity.process_key_event(&key);
+                            }
+                            Tab::Instances => {
+                                if let Some(selected) = services
+                                    .selected()
+                                    .and_then(|service| instances.get_mut(&service.service_type))
+                                {
+                                    selected.process_key_event(&key);
+                                }
+                            }
+                            Tab::Info => {
+                                if let Some(info) = services
+                                    .selected()
+                                    .and_then(|service| instances.get_mut(&service.service_type))
+                                    .and_then(|selected| selected.selected_mut())
+                                {
+                                    info.process_key_event(&key);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(State::Running)
+    }
+
+    /// Apply what can be of the UI state saved by the previous run, the
+    /// rest once the service types it refers to are found.
+    fn restore_ui_state(&mut self, state: UiState) {
+        self.zoomed = state.zoomed;
+        self.model.set_order(state.order);
+        if let Some(search) = state.services_search.as_deref() {
+            if let Err(e) = self.model.services().restore_search(search) {
+                tracing::warn!("Not restoring the search {search}: {e:#}");
+            }
+        }
+        self.restore = Some((Instant::now() + K_RESTORE_TIMEOUT, state));
+    }
+
+    /// Select the service and apply the searches of the restored UI state
+    /// as their service types are found, giving up on the rest after a while.
+    fn finish_restoring(&mut self) {
+        let Some((until, state)) = self.restore.as_mut() else {
+            return;
+        };
+        if Instant::now() >= *until {
+            self.restore = None;
+            return;
+        }
+        if let Some(service) = state.service.as_deref() {
+            if self.model.services().select_id(service) {
+                if state.instances_focused {
+                    self.current_tab = Tab::Instances;
+                }
+                state.service = None;
+            }
+        }
+        state.instances_searches.retain(|service_type, search| {
+            match self.model.instances().get_mut(service_type) {
+                Some(list) => {
+                    if let Err(e) = list.restore_search(search) {
+                        tracing::warn!("Not restoring the search {search}: {e:#}");
+                    }
+                    false
+                }
+                None => true,
+            }
+        });
+        if state.service.is_none() && state.instances_searches.is_empty() {
+            self.restore = None;
+        }
+    }
+
+    /// Save where the UI is for the next run to restore.
+    fn save_ui_state(&self) {
+        let state = {
+            let services = self.model.services();
+            let instances = self.model.instances();
+            // A selection still to be restored stays the one to restore
+            let pending = self
+                .restore
+                .as_ref()
+                .and_then(|(_, state)| state.service.clone());
+            UiState {
+                service: pending.or_else(|| services.selected().map(ListEntry::id)),
+                instances_focused: matches!(self.current_tab, Tab::Instances | Tab::Info),
+                zoomed: self.zoomed,
+                order: self.model.order(),
+                services_search: services.filter().map(ToString::to_string),
+                instances_searches: instances
+                    .iter()
+                    .filter_map(|(service_type, list)| {
+                        Some((service_type.clone(), list.filter()?.to_string()))
+                    })
+                    .collect(),
+            }
+        };
+        if let Err(e) = state.save(&self.profiles[self.current_profile].0) {
+            tracing::warn!("Failed to save the UI state: {e:#}");
+        }
+    }
+
+    /// List the instances by `order`, or in the order they were found when
+    /// they already are by `order`.
+    fn toggle_order(&mut self, order: Order) {
+        let order = if self.model.order() == order {
+            Order::Found
+        } else {
+            order
+        };
+        self.model.set_order(order);
+        self.toasts.info(order.description());
+    }
+
+    /// Move the focus `delta` panes away, skipping the Info pane when no
+    /// instance is selected.
+    fn cycle_focus(&mut self, delta: isize) {
+        let mut tab = self.current_tab.cycle(delta);
+        if tab == Tab::Info && self.selected_hostname().is_none() {
+            tab = tab.cycle(delta);
+        }
+        self.current_tab = tab;
+    }
+
+    /// The queries of the current profile, in the current domain.
+    fn queries(&self) -> Vec<String> {
+        let domain = self.model.domains.lock()[self.current_domain].clone();
+        let profile = &self.profiles[self.current_profile].1;
+        if profile.queries.is_empty() {
+            vec![domain::in_domain(K_SERVICE_TYPE_ENUMERATION, &domain)]
+        } else {
+            profile
+                .queries
+                .iter()
+                .map(|query| domain::in_domain(query, &domain))
+                .collect()
+        }
+    }
+
+    /// Query the selected service type again, or the profile's queries while
+    /// none is selected.
+    fn requery(&mut self) -> anyhow::Result<()> {
+        let selected = self
+            .model
+            .services()
+            .selected()
+            .map(|service| service.service_type.clone());
+        let service_types = match selected.as_deref() {
+            Some(continuity::K_GROUP_TYPE) => self.model.grouped_types(),
+            Some(service_type) => vec![service_type.to_string()],
+            None => self.queries(),
+        };
+        for service_type in service_types.iter() {
+            self.model.requeried(service_type);
+        }
+        match selected {
+            Some(_) => {
+                for service_type in service_types.iter() {
+                    self.worker
+                        .send(WorkerCommand::Requery(Some(service_type.clone())))?;
+                }
+            }
+            None => self.worker.send(WorkerCommand::Requery(None))?,
+        }
+        self.toasts
+            .info(format!("Querying {} again", service_types.join(", ")));
+        Ok(())
+    }
+
+    /// What to tell in place of an empty Instances pane, from how browsing
+    /// `service_types` is going, and how many instances the searches hide.
+    fn guidance(&self, service_types: &[String], hidden: usize) -> Text<'static> {
+        let hint = |action: KeyAction, hint: &str| {
+            self.keymap.key(action).map(|key| format!("{key} {hint}"))
+        };
+        let mut lines = Vec::new();
+        if hidden > 0 {
+            lines.push(Line::from(format!(
+                "{hidden} instances, all hidden by the search or the ignore list"
+            )));
+            lines.push(Line::from("Press / to search for something else").dark_gray());
+            return Text::from(lines);
+        }
+
+        lines.push(Line::from(format!("Browsing {}…", service_types.join(", "))).bold());
+        let browses: Vec<_> = service_types
+            .iter()
+            .filter_map(|service_type| self.model.browse(service_type))
+            .collect();
+        let answers: u32 = browses.iter().map(|(browse, _)| browse.answers).sum();
+        let queries: u32 = browses.iter().map(|(browse, _)| browse.queries).sum();
+        let elapsed = browses
+            .iter()
+            .map(|(_, elapsed)| *elapsed)
+            .max()
+            .unwrap_or_default();
+        let elapsed = humantime::format_duration(Duration::from_secs(elapsed.as_secs()));
+        lines.push(Line::from(format!(
+            "{answers} answers in {elapsed}, {queries} queries sent"
+        )));
+        let profile = &self.profiles[self.current_profile].1;
+        let interfaces = if profile.interfaces.is_empty() {
+            "all interfaces".to_string()
+        } else {
+            profile.interfaces.join(", ")
+        };
+        lines.push(Line::from(format!(
+            "On {interfaces}{}",
+            if profile.ipv6_only { ", IPv6 only" } else { "" }
+        )));
+        if self.model.paused.load(Ordering::Relaxed) {
+            lines.push(
+                Line::from("Paused while the terminal is unfocused").fg(colors::theme().warning),
+            );
+        } else if answers > 0 {
+            lines.push(
+                Line::from("Found, but not resolved yet: the instances may not answer, or be on another network")
+                    .fg(colors::theme().warning),
+            );
+        } else if queries == 0 {
+            lines.push(Line::from("No query sent yet").fg(colors::theme().warning));
+        }
+
+        lines.push(Line::default());
+        let hints: Vec<_> = [
+            hint(KeyAction::Requery, "to query again"),
+            hint(
+                KeyAction::NextProfile,
+                "to switch to another profile's interfaces",
+            ),
+            hint(KeyAction::PickQuery, "to pick another query"),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if !hints.is_empty() {
+            lines.push(Line::from(format!("Press {}", hints.join(", "))).dark_gray());
+        }
+        Text::from(lines)
+    }
+
+    /// Where the focus is: the panes down to the focused one, e.g.
+    /// `Services ▸ _http._tcp.local. ▸ printer.local.`, with the focused one
+    /// highlighted.
+    fn breadcrumbs(&self, view: &View) -> Line<'static> {
+        let service = view.services.selected();
+        let instance = view
+            .instances
+            .as_ref()
+            .and_then(|selected| selected.selected());
+        let crumbs = [
+            (Tab::Services, Some("Services".to_string())),
+            (Tab::Instances, service.map(|service| service.id())),
+            (
+                Tab::Info,
+                instance.map(|info| info.name.clone().unwrap_or_else(|| info.id())),
+            ),
+        ];
+        let mut spans = Vec::new();
+        for (tab, crumb) in crumbs {
+            let Some(crumb) = crumb else {
+                break;
+            };
+            if !spans.is_empty() {
+                spans.push(" ▸ ".dark_gray());
+            }
+            spans.push(if tab == self.current_tab {
+                Span::styled(
+                    format!(" {crumb} "),
+                    Style::new()
+                        .bold()
+                        .reversed()
+                        .fg(colors::theme().selected_fg),
+                )
+            } else {
+                Span::raw(crumb)
+            });
+        }
+        Line::from(spans)
+    }
+
+    /// Whether the focused pane is capturing input for a search.
+    fn focused_searching(&self) -> bool {
+        match self.current_tab {
+            Tab::Services => self.model.services().is_searching(),
+            Tab::Instances => self.selected_instances_searching(),
+            Tab::Info => false,
+        }
+    }
+
+    /// Whether the row of the grouped Apple continuity services is selected.
+    fn apple_group_selected(&self) -> bool {
+        self.model
+            .services()
+            .selected()
+            .is_some_and(|service| service.service_type == continuity::K_GROUP_TYPE)
+    }
+
+    fn selected_instances_searching(&self) -> bool {
+        let services = self.model.services();
+        let instances = self.model.instances();
+        services
+            .selected()
+            .and_then(|service| instances.get(&service.service_type))
+            .is_some_and(|selected| selected.is_searching())
+    }
+
+    /// Hostname of the selected instance.
+    fn selected_fullname(&self) -> Option<String> {
+        let services = self.model.services();
+        let instances = self.model.instances();
+        services
+            .selected()
+            .and_then(|service| instances.get(&service.service_type))
+            .and_then(|selected| selected.selected())
+            .map(|info| info.info.get_fullname().to_string())
+    }
+
+    fn selected_hostname(&self) -> Option<String> {
+        let services = self.model.services();
+        let instances = self.model.instances();
+        services
+            .selected()
+            .and_then(|service| instances.get(&service.service_type))
+            .and_then(|selected| selected.selected())
+            .map(|info| info.id())
+    }
+
+    /// Socket address of the address picked in the Info pane, and whether
+    /// its port is assumed.
+    fn selected_target(&self) -> Option<(SocketAddr, bool)> {
+        let services = self.model.services();
+        let instances = self.model.instances();
+        let info = services
+            .selected()
+            .and_then(|service| instances.get(&service.service_type))
+            .and_then(|selected| selected.selected())?;
+        let (port, assumed) = info.port();
+        let zones = Zones::local();
+        info.selected_address()
+            .map(|addr| (zones.socket_addr(addr, port), assumed))
+    }
+
+    fn run(&mut self, mut terminal: Terminal<impl Backend>) -> anyhow::Result<()> {
+        loop {
+            let reloaded = self
+                .config_watcher
+                .as_ref()
+                .is_some_and(|(_, changes)| changes.drain().count() > 0);
+            if reloaded {
+                self.reload_config();
+            }
+
+            if let Some(latest) = self
+                .update
+                .as_ref()
+                .and_then(|update| update.try_recv().ok())
+            {
+                self.latest_version = Some(latest);
+                self.update = None;
+            }
+
+            let answered = self
+                .whois
+                .as_ref()
+                .and_then(|(name, answers)| Some((name.clone(), answers.try_recv().ok()?)));
+            if let Some((name, answers)) = answered {
+                self.whois = None;
+                self.report_whois(&name, answers);
+            }
+            let shown = self
+                .test_pattern
+                .as_ref()
+                .and_then(|(name, shown)| Some((name.clone(), shown.try_recv().ok()?)));
+            if let Some((name, shown)) = shown {
+                self.test_pattern = None;
+                match shown {
+                    Ok(()) => self.toasts.success(format!("{name} shows a test pattern")),
+                    Err(e) => self.toasts.error(format!("{name}: {e:#}")),
+                }
+            }
+            let printed = self
+                .test_page
+                .as_ref()
+                .and_then(|(name, job)| Some((name.clone(), job.try_recv().ok()?)));
+            if let Some((name, job)) = printed {
+                self.test_page = None;
+                match job {
+                    Ok(job) => self
+                        .toasts
+                        .success(format!("{name} accepted the test page as job {job}")),
+                    Err(e) => self.toasts.error(format!("{name}: {e:#}")),
+                }
+            }
+            self.finish_restoring();
+            if self.pinging {
+                self.ping_selected();
+            }
+            self.check_wake();
+
+            for alert in self.model.alerts.drain() {
+                self.toasts.warning(alert);
+            }
+
+            let commands: Vec<_> = self
+                .control
+                .as_ref()
+                .map(|control| control.drain().collect())
+                .unwrap_or_default();
+            for command in commands {
+                match command {
+                    Ok(command) => {
+                        tracing::info!("Control command: {command:?}");
+                        if let State::Exit = self.run_command(command)? {
+                            return Ok(());
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!("Bad control command: {e:#}");
+                        self.toasts.error(format!("{e:#}"));
+                    }
+                }
+            }
+
+            // Whatever changed in the meantime is published by the model
+            // worker if it holds the lists
+            self.model.try_publish();
+            let start = Instant::now();
+            let frame = terminal.draw(|frame| {
+                frame.render_widget(self as &mut App, frame.size());
+            })?;
+            self.hud.record_frame(start.elapsed());
+            if std::mem::take(&mut self.screenshot) {
+                match screenshot::save(frame.buffer, Path::new(".")) {
+                    Ok((ansi, svg)) => self.toasts.success(format!(
+                        "Screenshot saved to {} and {}",
+                        ansi.display(),
+                        svg.display()
+                    )),
+                    Err(e) => self
+                        .toasts
+                        .error(format!("Failed to save the screenshot: {e:#}")),
+                }
+            }
+
+            if poll(Duration::from_secs(1) / u32::from(self.refresh_rate))? {
+                if let State::Exit = self.handle_event(event::read()?)? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn shutdown(&mut self) -> anyhow::Result<()> {
+        self.worker.send(WorkerCommand::Stop)?;
+        if let Some(handle) = self.worker_handle.take() {
+            handle
+                .join()
+                .expect("The worker being joined has panicked")?;
+        }
+        Ok(())
+    }
+}
+
+impl Widget for &mut App {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let vertical = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(13),
+            Constraint::Length(2),
+        ]);
+        let [header_area, list_area, info_area, footer_area] = vertical.areas(area);
+
+        Paragraph::new(format!(
+            "{}, v{} [{}] in {}",
+            env!("CARGO_PKG_DESCRIPTION"),
+            env!("CARGO_PKG_VERSION"),
+            self.profiles[self.current_profile].0,
+            self.model.domains.lock()[self.current_domain]
+        ))
+        .bold()
+        .centered()
+        .render(header_area, buf);
+        // Event accounting and the focus on the next header lines
+        let [_, stats_area, breadcrumbs_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(header_area);
+        Paragraph::new(self.stats.summary())
+            .dark_gray()
+            .centered()
+            .render(stats_area, buf);
+        let view = self.model.view();
+        Paragraph::new(self.breadcrumbs(&view))
+            .centered()
+            .render(breadcrumbs_area, buf);
+
+        let list_layout =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]);
+        let [mut service_area, mut instances_area] = list_layout.areas(list_area);
+        let mut info_area = info_area;
+        if self.zoomed {
+            // Hide all the panes, but the focused one
+            let body = list_area.union(info_area);
+            [service_area, instances_area, info_area] = match self.current_tab {
+                Tab::Services => [body, Rect::default(), Rect::default()],
+                Tab::Instances => [Rect::default(), body, Rect::default()],
+                Tab::Info => [Rect::default(), Rect::default(), body],
+            };
+        }
+
+        let services = &view.services;
+        let mut controls = services.controls();
+        if !service_area.is_empty() {
+            services.render(service_area, buf, matches!(self.current_tab, Tab::Services));
+        }
+        if services
+            .selected()
+            .is_some_and(|selected| selected.service_type == continuity::K_GROUP_TYPE)
+        {
+            // The groups of the previous frame do while the lists are busy
+            if let Some(instances) = self.model.try_instances() {
+                self.continuity.refresh(&instances);
+            }
+            if !instances_area.is_empty() {
+                self.continuity.render(
+                    instances_area,
+                    buf,
+                    matches!(self.current_tab, Tab::Instances),
+                );
+            }
+            if matches!(self.current_tab, Tab::Instances) {
+                controls = self.continuity.controls();
+            }
+        } else if let Some(selected) = services.selected() {
+            if let Some(resolved_instances) = view.instances.as_ref() {
+                if !instances_area.is_empty() {
+                    resolved_instances.render_or(
+                        instances_area,
+                        buf,
+                        matches!(self.current_tab, Tab::Instances),
+                        || {
+                            self.guidance(
+                                std::slice::from_ref(&selected.service_type),
+                                resolved_instances.len(),
+                            )
+                        },
+                    );
+                }
+                if let Some(info) = resolved_instances.selected() {
+                    if !info_area.is_empty() {
+                        info.render(info_area, buf, matches!(self.current_tab, Tab::Info));
+                    }
+                    if matches!(self.current_tab, Tab::Info) {
+                        controls = info.controls();
+                    }
+                }
+            }
+        } else if !instances_area.is_empty() {
+            // No service type to list the instances of yet
+            let block = Block::new()
+                .borders(Borders::ALL)
+                .title_alignment(Alignment::Center)
+                .title("Instances")
+                .title_style(Style::new().bold())
+                .fg(colors::theme().text)
+                .bg(colors::theme().header_bg);
+            Paragraph::new(self.guidance(&self.queries(), services.len()))
+                .block(block.padding(Padding::uniform(1)))
+                .wrap(Wrap { trim: true })
+                .render(instances_area, buf);
+        }
+
+        let mut shortcuts = vec![
+            "Tab/S-Tab to switch panes".to_string(),
+            "↵ to inspect an instance".to_string(),
+        ];
+        for (action, hint) in [
+            (KeyAction::NextProfile, "to switch profile"),
+            (KeyAction::NextDomain, "to switch domain"),
+            (KeyAction::RecordBaseline, "to save baseline"),
+            (KeyAction::PickQuery, "to pick a query"),
+            (KeyAction::Export, "to export"),
+            (KeyAction::Screenshot, "for a screenshot"),
+            (KeyAction::Zoom, "to zoom"),
+            (KeyAction::Quit, "to exit"),
+        ] {
+            if let Some(key) = self.keymap.key(action) {
+                shortcuts.push(format!("{key} {hint}"));
+            }
+        }
+        let mut hints = vec![Span::raw(shortcuts.join(", ") + ".")];
+        if let Some(latest) = self.latest_version.as_ref() {
+            hints.push(
+                format!(
+                    " Update available: v{} → v{latest}",
+                    env!("CARGO_PKG_VERSION")
+                )
+                .yellow(),
+            );
+        }
+        let evicted = self.model.evicted.load(Ordering::Relaxed);
+        if evicted > 0 {
+            hints.push(
+                format!(" Truncated: {evicted} least recently resolved instances evicted")
+                    .fg(colors::theme().warning),
+            );
+        }
+        Paragraph::new(vec![Line::from(controls), Line::from(hints)])
+            .centered()
+            .render(footer_area, buf);
+
+        if let Some(interact) = self.interact.as_ref() {
+            let interact_area = centered_rect(80, 60, area);
+            Clear.render(interact_area, buf);
+            interact.render(interact_area, buf, true);
+        }
+
+        if let Some(host) = self.host.as_ref() {
+            let host_area = centered_rect(80, 70, area);
+            Clear.render(host_area, buf);
+            host.render(host_area, buf, true);
+            Paragraph::new(host.controls())
+                .centered()
+                .render(footer_area, buf);
+        }
+
+        if let Some(import_diff) = self.import_diff.as_ref() {
+            let import_diff_area = centered_rect(80, 70, area);
+            Clear.render(import_diff_area, buf);
+            import_diff.render(import_diff_area, buf, true);
+            Paragraph::new(import_diff.controls())
+                .centered()
+                .render(footer_area, buf);
+        }
+
+        if let Some(catalog_view) = self.catalog_view.as_ref() {
+            let catalog_view_area = centered_rect(80, 70, area);
+            Clear.render(catalog_view_area, buf);
+            catalog_view.render(catalog_view_area, buf, true);
+            Paragraph::new(catalog_view.controls())
+                .centered()
+                .render(footer_area, buf);
+        }
+
+        if let Some(visibility) = self.visibility.as_ref() {
+            let visibility_area = centered_rect(80, 70, area);
+            Clear.render(visibility_area, buf);
+            visibility.render(visibility_area, buf, true);
+            Paragraph::new(visibility.controls())
+                .centered()
+                .render(footer_area, buf);
+        }
+
+        if let Some(event_log) = self.event_log.as_ref() {
+            let event_log_area = centered_rect(80, 70, area);
+            Clear.render(event_log_area, buf);
+            event_log.render(event_log_area, buf, true);
+            Paragraph::new(event_log.controls())
+                .centered()
+                .render(footer_area, buf);
+        }
+
+        if let Some(query_picker) = self.query_picker.as_ref() {
+            let query_picker_area = centered_rect(50, 50, area);
+            Clear.render(query_picker_area, buf);
+            query_picker.render(query_picker_area, buf, true);
+            Paragraph::new(query_picker.controls())
+                .centered()
+                .render(footer_area, buf);
+        }
+
+        if let Some(export_picker) = self.export_picker.as_ref() {
+            let export_picker_area = centered_rect(50, 50, area);
+            Clear.render(export_picker_area, buf);
+            export_picker.render(export_picker_area, buf, true);
+            Paragraph::new(export_picker.controls())
+                .centered()
+                .render(footer_area, buf);
+        }
+
+        if let Some(copy_picker) = self.copy_picker.as_ref() {
+            let copy_picker_area = centered_rect(50, 30, area);
+            Clear.render(copy_picker_area, buf);
+            copy_picker.render(copy_picker_area, buf, true);
+            Paragraph::new(copy_picker.controls())
+                .centered()
+                .render(footer_area, buf);
+        }
+
+        if let Some(note_prompt) = self.note_prompt.as_ref() {
+            let note_prompt_area = centered_rect(50, 20, area);
+            Clear.render(note_prompt_area, buf);
+            note_prompt.render(note_prompt_area, buf, true);
+            Paragraph::new(note_prompt.controls())
+                .centered()
+                .render(footer_area, buf);
+        }
+
+        if let Some(confirm) = self.confirm.as_ref() {
+            confirm.render(area, buf, true);
+            Paragraph::new(confirm.controls())
+                .centered()
+                .render(footer_area, buf);
+        }
+
+        if let Some(diagnostics) = self.diagnostics.as_ref() {
+            let diagnostics_area = centered_rect(60, 50, area);
+            Clear.render(diagnostics_area, buf);
+            diagnostics.render(diagnostics_area, buf, true);
+            Paragraph::new(diagnostics.controls())
+                .centered()
+                .render(footer_area, buf);
+        }
+
+        if self.hud.visible {
+            self.hud.render(list_area, buf, &self.stats, &self.model);
+        }
+
+        self.toasts.expire();
+        self.toasts.render(list_area, buf);
     }
 }
@@ -0,0 +1,1262 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use arc_swap::ArcSwap;
+use flume::{Receiver, Sender};
+use mdns_sd::ServiceEvent;
+use parking_lot::{Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+
+use crate::audit::Audit;
+use crate::baseline::Baseline;
+use crate::clock::{Clock, SystemClock};
+use crate::computed::{self, Computed};
+use crate::continuity;
+use crate::domain;
+use crate::eventlog::{EventKind, EventLog, Removal};
+use crate::filter::Filter;
+use crate::host::Sighting;
+use crate::info::{Age, AgeThresholds, Info};
+use crate::label::{self, Label, Labeler};
+use crate::list::{ListEntry, ListWidget};
+use crate::overlay::OverlayPeers;
+use crate::ping::Pings;
+use crate::portcheck::PortChecks;
+use crate::reverse::ReverseNames;
+use crate::schema::{HostState, Instance, Snapshot, TxtChange};
+use crate::script::Scripts;
+use crate::service::{Column, Service};
+use crate::session::Session;
+use crate::shared::SharedAddresses;
+use crate::sources::Sources;
+use crate::txt::{self, TxtAlert};
+
+/// Service type hosts advertise their hardware model with, e.g. `model=MacBookPro18,3`
+///
+/// Browsed alongside every query, the same way Finder does to pick device icons.
+pub const K_DEVICE_INFO_TYPE: &str = "_device-info._tcp.";
+/// Records are dropped this long after their goodbye, as RFC 6762 has it,
+/// rather than right away.
+const K_GOODBYE_DELAY: Duration = Duration::from_secs(1);
+
+/// How the instances of a type are listed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Order {
+    /// In the order they were found
+    #[default]
+    Found,
+    /// Most recently resolved first, as a live activity feed
+    Recent,
+    /// By SRV priority, lowest first, then weight, highest first, the way
+    /// DNS-SD clients pick among them (RFC 2782)
+    Priority,
+}
+
+impl Order {
+    pub fn description(self) -> &'static str {
+        match self {
+            Order::Found => "Instances listed in the order they were found",
+            Order::Recent => "Instances listed by recent activity",
+            Order::Priority => "Instances listed by SRV priority and weight",
+        }
+    }
+}
+
+/// Sort `list` by `order`, and tell each instance's share of the selections
+/// among the instances of the same priority when by priority.
+fn arrange(list: &mut ListWidget<Info>, order: Order) {
+    match order {
+        Order::Found => list.sort_by_key(|info| info.first_seen),
+        Order::Recent => list.sort_by_key(|info| Reverse(info.last_seen)),
+        Order::Priority => list.sort_by_key(|info| {
+            (
+                info.info.get_priority(),
+                Reverse(info.info.get_weight()),
+                info.first_seen,
+            )
+        }),
+    }
+    let mut weights: HashMap<u16, (u32, u32)> = HashMap::new();
+    for info in list.items() {
+        let (total, count) = weights.entry(info.info.get_priority()).or_default();
+        *total += u32::from(info.info.get_weight());
+        *count += 1;
+    }
+    for info in list.items_mut() {
+        info.share = (order == Order::Priority).then(|| {
+            let (total, count) = weights[&info.info.get_priority()];
+            (u32::from(info.info.get_weight()) * 100)
+                .checked_div(total)
+                // Without any weight, every instance is as likely to be picked
+                .unwrap_or(100 / count)
+        });
+    }
+}
+
+/// How browsing a service type is going, to tell why it has no instances.
+#[derive(Debug, Clone, Copy)]
+pub struct Browse {
+    /// When the type was first browsed, or queried again on request
+    pub since: Instant,
+    /// Queries sent since, retransmissions included
+    pub queries: u32,
+    /// Instances found since
+    pub answers: u32,
+}
+
+/// Accounting of the time spent waiting for the model locks.
+#[derive(Debug, Default)]
+pub struct LockWait {
+    count: AtomicU64,
+    total_ns: AtomicU64,
+    max_ns: AtomicU64,
+}
+
+impl LockWait {
+    fn lock<'a, T>(&self, mutex: &'a Mutex<T>) -> MutexGuard<'a, T> {
+        let start = Instant::now();
+        let guard = mutex.lock();
+        let waited = start.elapsed().as_nanos() as u64;
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_ns.fetch_add(waited, Ordering::Relaxed);
+        self.max_ns.fetch_max(waited, Ordering::Relaxed);
+        guard
+    }
+
+    /// Average and longest wait so far.
+    pub fn summary(&self) -> (Duration, Duration) {
+        let count = self.count.load(Ordering::Relaxed).max(1);
+        (
+            Duration::from_nanos(self.total_ns.load(Ordering::Relaxed) / count),
+            Duration::from_nanos(self.max_ns.load(Ordering::Relaxed)),
+        )
+    }
+}
+
+/// What the UI renders: copies of the lists, published by [`Model::publish`]
+/// so that drawing never waits for the model locks.
+#[derive(Debug, Default)]
+pub struct View {
+    pub services: ListWidget<Service>,
+    /// The instances of the selected service type, if any were listed
+    pub instances: Option<ListWidget<Info>>,
+    /// Instances listed across all the service types
+    pub total: usize,
+}
+
+/// Everything discovered so far.
+///
+/// Shared between the UI, which renders it, and the model worker, which
+/// applies the mDNS events to it. Cloning is cheap and yields a handle to
+/// the same data.
+///
+/// Whoever holds both `services` and `instances` must lock `services` first,
+/// as the UI does to find the instances of the selected type, or the two
+/// threads can deadlock. The other locks are taken after these.
+#[derive(Clone)]
+pub struct Model {
+    services: Arc<Mutex<ListWidget<Service>>>,
+    instances: Arc<Mutex<HashMap<String, ListWidget<Info>>>>,
+    /// Time spent waiting for `services` and `instances`
+    pub lock_wait: Arc<LockWait>,
+    /// The latest copy of `services` and `instances` to render
+    view: Arc<ArcSwap<View>>,
+    pub baseline: Arc<Mutex<Option<Baseline>>>,
+    /// Browse domains, `local.` and the wide-area ones discovered so far
+    pub domains: Arc<Mutex<Vec<String>>>,
+    /// Device models advertised through `_device-info._tcp` by hostname
+    pub device_models: Arc<Mutex<HashMap<String, String>>>,
+    /// When each host has been resolved, by hostname
+    pub sightings: Arc<Mutex<HashMap<String, Sighting>>>,
+    /// User hooks run on every found, resolved and removed service
+    scripts: Arc<Scripts>,
+    /// Reverse names of every resolved address
+    pub reverse_names: ReverseNames,
+    /// Round-trip times to the addresses of the selected instance, when pinged
+    pub pings: Pings,
+    /// Whether the ports of the instances are listened on, when checked
+    pub ports: PortChecks,
+    /// Enrichment and probing wait while set, e.g. while the terminal is
+    /// unfocused
+    pub paused: Arc<AtomicBool>,
+    /// Where the records of every instance came from
+    pub sources: Sources,
+    /// Overlay network peers the resolved addresses belong to
+    pub overlay: OverlayPeers,
+    /// Addresses several hostnames resolve to
+    pub shared: SharedAddresses,
+    /// Actions taken on the instances, across runs
+    pub audit: Audit,
+    labelers: Arc<Mutex<Vec<Labeler>>>,
+    /// Columns computed from every instance
+    computed: Arc<Mutex<Vec<Computed>>>,
+    /// Extra columns of the Services pane
+    columns: Arc<Mutex<Vec<Column>>>,
+    /// Services pane rows of the Apple continuity types while they are
+    /// grouped under a single row, `None` when they aren't
+    grouped: Arc<Mutex<Option<Vec<Service>>>>,
+    /// Most instances kept at once, unlimited if `None`
+    max_instances: Arc<Mutex<Option<usize>>>,
+    /// Instances dropped to stay within `max_instances`
+    pub evicted: Arc<AtomicU64>,
+    /// How the instances are listed
+    order: Arc<Mutex<Order>>,
+    /// Browsing state by base service type, or by query for type enumerations
+    browses: Arc<Mutex<HashMap<String, Browse>>>,
+    /// How long instances are kept past their TTL before expiring
+    stale_grace: Arc<Mutex<Duration>>,
+    /// When instances stop being new, and start expiring
+    age_thresholds: Arc<Mutex<AgeThresholds>>,
+    /// Instances the model expired ahead of the backend, whose own removal
+    /// is then left out
+    retired: Arc<Mutex<HashSet<String>>>,
+    /// Everything found, resolved and removed, across restarts
+    pub event_log: EventLog,
+    /// Statistics of the whole session, across restarts
+    pub session: Session,
+    /// What the model tells the time with
+    clock: Arc<dyn Clock>,
+    txt_alerts: Arc<Mutex<Vec<TxtAlert>>>,
+    alert_tx: Sender<String>,
+    /// Messages about the TXT changes `txt_alerts` watch
+    pub alerts: Receiver<String>,
+}
+
+impl Model {
+    pub fn new(ignore: Option<Filter>, baseline: Option<Baseline>, scripts: Scripts) -> Self {
+        Self::with_clock(ignore, baseline, scripts, Arc::new(SystemClock))
+    }
+
+    /// A model telling the time with `clock`, e.g. a simulated one.
+    pub fn with_clock(
+        ignore: Option<Filter>,
+        baseline: Option<Baseline>,
+        scripts: Scripts,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        let (alert_tx, alerts) = flume::unbounded();
+        let paused = Arc::new(AtomicBool::new(false));
+        Self {
+            services: Arc::new(Mutex::new(
+                ListWidget::default()
+                    .name("Services".to_string())
+                    .ignore(ignore),
+            )),
+            instances: Arc::new(Mutex::new(HashMap::new())),
+            lock_wait: Arc::new(LockWait::default()),
+            view: Arc::new(ArcSwap::from_pointee(View::default())),
+            baseline: Arc::new(Mutex::new(baseline)),
+            domains: Arc::new(Mutex::new(vec![domain::K_LOCAL_DOMAIN.to_string()])),
+            device_models: Arc::new(Mutex::new(HashMap::new())),
+            sightings: Arc::new(Mutex::new(HashMap::new())),
+            scripts: Arc::new(scripts),
+            reverse_names: ReverseNames::spawn(paused.clone()),
+            pings: Pings::spawn(paused.clone()),
+            ports: PortChecks::spawn(paused.clone()),
+            overlay: OverlayPeers::spawn(paused.clone()),
+            shared: SharedAddresses::default(),
+            paused,
+            sources: Sources::spawn(clock.clone()),
+            audit: Audit::load(),
+            labelers: Arc::new(Mutex::new(Vec::new())),
+            computed: Arc::new(Mutex::new(Vec::new())),
+            columns: Arc::new(Mutex::new(Vec::new())),
+            grouped: Arc::new(Mutex::new(None)),
+            max_instances: Arc::new(Mutex::new(None)),
+            evicted: Arc::new(AtomicU64::new(0)),
+            order: Arc::new(Mutex::new(Order::default())),
+            browses: Arc::new(Mutex::new(HashMap::new())),
+            stale_grace: Arc::new(Mutex::new(Duration::ZERO)),
+            age_thresholds: Arc::new(Mutex::new(AgeThresholds::default())),
+            retired: Arc::new(Mutex::new(HashSet::new())),
+            event_log: EventLog::new(clock.clone()),
+            session: Session::new(clock.clone()),
+            clock,
+            txt_alerts: Arc::new(Mutex::new(Vec::new())),
+            alert_tx,
+            alerts,
+        }
+    }
+
+    /// Discovered service types.
+    pub fn services(&self) -> MutexGuard<'_, ListWidget<Service>> {
+        self.lock_wait.lock(&self.services)
+    }
+
+    /// Instance lists by service type, without any subtype.
+    pub fn instances(&self) -> MutexGuard<'_, HashMap<String, ListWidget<Info>>> {
+        self.lock_wait.lock(&self.instances)
+    }
+
+    /// The instance lists, unless they are locked at the moment.
+    pub fn try_instances(&self) -> Option<MutexGuard<'_, HashMap<String, ListWidget<Info>>>> {
+        self.instances.try_lock()
+    }
+
+    /// The latest view published.
+    pub fn view(&self) -> Arc<View> {
+        self.view.load_full()
+    }
+
+    /// Publish the lists as they are now for the UI to render.
+    ///
+    /// The view is swapped in while the lists are still locked, so that a
+    /// view can't replace one taken after it.
+    pub fn publish(&self) {
+        let services = self.services();
+        let instances = self.instances();
+        self.store_view(&services, &instances);
+    }
+
+    /// Like [`Model::publish`], but give up rather than wait for the lists,
+    /// whoever holds them publishes once done.
+    ///
+    /// Returns whether the view was published.
+    pub fn try_publish(&self) -> bool {
+        let Some(services) = self.services.try_lock() else {
+            return false;
+        };
+        let Some(instances) = self.instances.try_lock() else {
+            return false;
+        };
+        self.store_view(&services, &instances);
+        true
+    }
+
+    fn store_view(
+        &self,
+        services: &ListWidget<Service>,
+        instances: &HashMap<String, ListWidget<Info>>,
+    ) {
+        let selected = services
+            .selected()
+            .and_then(|service| instances.get(&service.service_type));
+        self.view.store(Arc::new(View {
+            services: services.clone(),
+            instances: selected.cloned(),
+            total: instances.values().map(ListWidget::len).sum(),
+        }));
+    }
+
+    /// Forget all the discovered services and instances.
+    ///
+    /// Browse domains, device models, sightings and the event log are kept, as they
+    /// don't depend on what is browsed.
+    pub fn clear(&self) {
+        *self.services() = ListWidget::default().name("Services".to_string());
+        self.instances().clear();
+        if let Some(hidden) = self.grouped.lock().as_mut() {
+            hidden.clear();
+        }
+        self.browses.lock().clear();
+        self.retired.lock().clear();
+        self.evicted.store(0, Ordering::Relaxed);
+    }
+
+    /// The discovery state right now: the resolved instances, browse domains
+    /// and hosts.
+    ///
+    /// The snapshot is owned, each lock is only held while its part is
+    /// copied, so exports and embedders can read it at leisure without
+    /// holding up the model.
+    pub fn snapshot(&self) -> Snapshot {
+        let unix = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+        };
+        let types = self
+            .instances()
+            .iter()
+            .map(|(service_type, list)| {
+                (
+                    service_type.clone(),
+                    list.items()
+                        .map(|info| Instance {
+                            computed: info.computed.iter().cloned().collect(),
+                            ..Instance::from(&info.info)
+                        })
+                        .collect(),
+                )
+            })
+            .collect();
+        let device_models = self.device_models.lock().clone();
+        let hosts = self
+            .sightings
+            .lock()
+            .iter()
+            .map(|(hostname, sighting)| {
+                (
+                    hostname.clone(),
+                    HostState {
+                        model: device_models.get(hostname).cloned(),
+                        first_seen: unix(sighting.first),
+                        last_seen: unix(sighting.last),
+                    },
+                )
+            })
+            .collect();
+        Snapshot {
+            exported: unix(self.clock.system_time()),
+            types,
+            domains: self.domains.lock().clone(),
+            hosts,
+        }
+    }
+
+    /// How browsing `service_type` is going, along with how long it has been.
+    pub fn browse(&self, service_type: &str) -> Option<(Browse, Duration)> {
+        let browse = *self.browses.lock().get(domain::base_type(service_type))?;
+        Some((
+            browse,
+            self.clock.now().saturating_duration_since(browse.since),
+        ))
+    }
+
+    /// Start counting the queries and answers of `service_type` afresh, as
+    /// it is queried again.
+    pub fn requeried(&self, service_type: &str) {
+        self.browses.lock().insert(
+            domain::base_type(service_type).to_string(),
+            Browse {
+                since: self.clock.now(),
+                queries: 0,
+                answers: 0,
+            },
+        );
+    }
+
+    fn browse_mut(&self, service_type: &str, update: impl FnOnce(&mut Browse)) {
+        let now = self.clock.now();
+        let mut browses = self.browses.lock();
+        let browse = browses
+            .entry(domain::base_type(service_type).to_string())
+            .or_insert(Browse {
+                since: now,
+                queries: 0,
+                answers: 0,
+            });
+        update(browse);
+    }
+
+    /// Keep at most `max` instances from now on, evicting the least recently
+    /// resolved ones first.
+    pub fn set_max_instances(&self, max: Option<usize>) {
+        *self.max_instances.lock() = max;
+    }
+
+    /// Drop the least recently resolved instances beyond the cap.
+    ///
+    /// Returns the service types instances have been dropped from.
+    fn evict(&self) -> Vec<String> {
+        let Some(max) = *self.max_instances.lock() else {
+            return Vec::new();
+        };
+        let mut evicted = Vec::new();
+        let mut instances = self.instances();
+        while instances.values().map(|list| list.len()).sum::<usize>() > max {
+            let Some((service_type, fullname)) = instances
+                .iter()
+                .flat_map(|(service_type, list)| list.items().map(move |info| (service_type, info)))
+                .min_by_key(|(_, info)| info.last_seen)
+                .map(|(service_type, info)| {
+                    (service_type.clone(), info.info.get_fullname().to_string())
+                })
+            else {
+                break;
+            };
+            if let Some(list) = instances.get_mut(&service_type) {
+                list.retain(|info| info.info.get_fullname() != fullname);
+            }
+            tracing::debug!("Evicted {fullname}");
+            self.evicted.fetch_add(1, Ordering::Relaxed);
+            evicted.push(service_type);
+        }
+        evicted
+    }
+
+    pub fn order(&self) -> Order {
+        *self.order.lock()
+    }
+
+    /// List the instances by `order` from now on.
+    ///
+    /// By recent activity, instances move to the top whenever they resolve,
+    /// which turns the Instances pane into a live activity feed.
+    pub fn set_order(&self, order: Order) {
+        *self.order.lock() = order;
+        for list in self.instances().values_mut() {
+            arrange(list, order);
+        }
+    }
+
+    /// Compute the `computed` columns from now on, for everything resolved
+    /// so far too.
+    pub fn set_computed(&self, computed: Vec<Computed>) {
+        for list in self.instances().values_mut() {
+            for info in list.items_mut() {
+                info.computed = computed::values(&computed, info);
+            }
+        }
+        *self.computed.lock() = computed;
+    }
+
+    /// Label by `labelers` from now on, relabeling everything resolved so far.
+    pub fn set_labelers(&self, labelers: Vec<Labeler>) {
+        for list in self.instances().values_mut() {
+            for info in list.items_mut() {
+                info.rule_labels = label::labels(&labelers, &info.info);
+            }
+        }
+        *self.labelers.lock() = labelers;
+        let mut service_types: Vec<_> = self
+            .services()
+            .items()
+            .map(|service| service.id())
+            .collect();
+        service_types.extend(self.grouped_types());
+        for service_type in service_types {
+            self.refresh_service(&service_type);
+        }
+    }
+
+    /// Alert on the TXT changes `alerts` watch from now on.
+    pub fn set_txt_alerts(&self, alerts: Vec<TxtAlert>) {
+        *self.txt_alerts.lock() = alerts;
+    }
+
+    /// Show `columns` in the Services pane from now on.
+    pub fn set_columns(&self, columns: Vec<Column>) {
+        for service in self.services().items_mut() {
+            service.columns = columns.clone();
+        }
+        for service in self.grouped.lock().iter_mut().flatten() {
+            service.columns = columns.clone();
+        }
+        *self.columns.lock() = columns;
+    }
+
+    /// Group the Apple continuity types under a single row of the Services
+    /// pane, or list them apart again.
+    pub fn set_group_apple(&self, group: bool) {
+        let mut services = self.services();
+        let mut grouped = self.grouped.lock();
+        match (group, grouped.take()) {
+            (true, None) => {
+                let mut hidden = Vec::new();
+                services.retain(|service| {
+                    let continuity = continuity::is_continuity(&service.service_type);
+                    if continuity {
+                        hidden.push(service.clone());
+                    }
+                    !continuity
+                });
+                *grouped = Some(hidden);
+            }
+            (false, Some(hidden)) => {
+                services.remove(&continuity::K_GROUP_TYPE.to_string());
+                for service in hidden {
+                    services.push(service);
+                }
+            }
+            (_, unchanged) => *grouped = unchanged,
+        }
+        drop(grouped);
+        drop(services);
+        self.refresh_group();
+    }
+
+    /// Whether the Apple continuity types are grouped.
+    pub fn grouping_apple(&self) -> bool {
+        self.grouped.lock().is_some()
+    }
+
+    /// The Apple continuity types grouped under a single row.
+    pub fn grouped_types(&self) -> Vec<String> {
+        self.grouped
+            .lock()
+            .iter()
+            .flatten()
+            .map(|service| service.id())
+            .collect()
+    }
+
+    /// Show the row the Apple continuity types are grouped under, with the
+    /// number of hosts advertising them, as long as any type is grouped.
+    fn refresh_group(&self) {
+        let hosts = continuity::hosts(&self.instances());
+        let mut services = self.services();
+        let first_seen = self
+            .grouped
+            .lock()
+            .iter()
+            .flatten()
+            .map(|service| service.first_seen)
+            .min();
+        let group_type = continuity::K_GROUP_TYPE.to_string();
+        let Some(first_seen) = first_seen else {
+            services.remove(&group_type);
+            return;
+        };
+        if let Some(group) = services
+            .items_mut()
+            .find(|service| service.id() == group_type)
+        {
+            group.instances = hosts;
+            return;
+        }
+        let mut group = Service::new(group_type, self.columns.lock().clone(), first_seen);
+        group.instances = hosts;
+        services.push(group);
+    }
+
+    /// Recount the instances of `service_type` and collect their labels.
+    fn refresh_service(&self, service_type: &str) {
+        if service_type == continuity::K_GROUP_TYPE {
+            self.refresh_group();
+            return;
+        }
+        let (count, labels) = match self.instances().get(service_type) {
+            Some(list) => {
+                let mut labels: Vec<Label> = Vec::new();
+                for label in list.items().flat_map(|info| info.rule_labels.iter()) {
+                    if !labels.iter().any(|known| known.name == label.name) {
+                        labels.push(label.clone());
+                    }
+                }
+                (list.len(), labels)
+            }
+            None => (0, Vec::new()),
+        };
+        let mut services = self.services();
+        let mut grouped = self.grouped.lock();
+        if let Some(service) = services
+            .items_mut()
+            .chain(grouped.iter_mut().flatten())
+            .find(|service| service.service_type == service_type)
+        {
+            service.instances = count;
+            service.labels = labels;
+        }
+        drop(grouped);
+        drop(services);
+        if continuity::is_continuity(service_type) {
+            self.refresh_group();
+        }
+    }
+
+    /// Drop the instance `fullname` of `service_type`.
+    pub fn forget_instance(&self, service_type: &str, fullname: &str) {
+        if let Some(list) = self.instances().get_mut(service_type) {
+            list.retain(|info| info.info.get_fullname() != fullname);
+        }
+        self.refresh_service(service_type);
+    }
+
+    /// Drop every instance running on `hostname` and all that is known about it.
+    pub fn forget_host(&self, hostname: &str) {
+        let mut service_types = Vec::new();
+        for (service_type, list) in self.instances().iter_mut() {
+            list.retain(|info| info.id() != hostname);
+            service_types.push(service_type.clone());
+        }
+        for service_type in service_types {
+            self.refresh_service(&service_type);
+        }
+        self.device_models.lock().remove(hostname);
+        self.sightings.lock().remove(hostname);
+    }
+
+    /// The time on the model's clock.
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Whether an instance of `hostname` was resolved, or its records were
+    /// heard, since `since`.
+    pub fn heard_from(&self, hostname: &str, since: Instant) -> bool {
+        self.instances().values().any(|list| {
+            list.items()
+                .filter(|info| info.id() == hostname)
+                .any(|info| self.lifetime(info).0 >= since)
+        })
+    }
+
+    /// Keep instances for `grace` past their TTL from now on, marked stale,
+    /// before they expire.
+    pub fn set_stale_grace(&self, grace: Duration) {
+        *self.stale_grace.lock() = grace;
+    }
+
+    /// Style instances as new for `thresholds.new` after resolving them,
+    /// and as expiring `thresholds.expiring` before their records run out.
+    pub fn set_age_thresholds(&self, thresholds: AgeThresholds) {
+        *self.age_thresholds.lock() = thresholds;
+    }
+
+    /// When the records of `info` were last heard, and how long they live
+    /// from then, zero after a goodbye.
+    fn lifetime(&self, info: &Info) -> (Instant, Duration) {
+        match self.sources.heard(info.info.get_fullname()) {
+            Some(heard) if heard.at >= info.last_seen => (heard.at, heard.ttl),
+            _ => (
+                info.last_seen,
+                Duration::from_secs(info.info.get_other_ttl() as u64),
+            ),
+        }
+    }
+
+    /// Tell why the resolved instance `fullname` is being removed.
+    ///
+    /// Records live for their TTL, so an instance going away earlier than
+    /// that since last heard has sent a goodbye.
+    fn removal(&self, service_type: &str, fullname: &str) -> Option<Removal> {
+        let instances = self.instances();
+        let info = instances
+            .get(domain::base_type(service_type))?
+            .items()
+            .find(|info| info.info.get_fullname() == fullname)?;
+        let (heard, ttl) = self.lifetime(info);
+        let elapsed = self.clock.now().saturating_duration_since(heard);
+        Some(if !ttl.is_zero() && elapsed >= ttl {
+            Removal::Expired
+        } else {
+            Removal::Goodbye
+        })
+    }
+
+    /// Mark the resolved instance `fullname` stale instead of removing it,
+    /// for the expiry to remove once the stale grace is over.
+    ///
+    /// Returns whether it is kept, not without a grace.
+    fn linger(&self, service_type: &str, fullname: &str) -> bool {
+        if self.stale_grace.lock().is_zero() {
+            return false;
+        }
+        let now = self.clock.now();
+        let mut instances = self.instances();
+        let Some(info) = instances
+            .get_mut(domain::base_type(service_type))
+            .and_then(|list| {
+                list.items_mut()
+                    .find(|info| info.info.get_fullname() == fullname)
+            })
+        else {
+            return false;
+        };
+        info.stale.get_or_insert(now);
+        true
+    }
+
+    /// Drop the instance `fullname` from the list of `service_type`.
+    fn remove_instance(&self, service_type: &str, fullname: &str) {
+        let service_type = domain::base_type(service_type);
+        if let Some(resolved) = self.instances().get_mut(service_type) {
+            resolved.retain(|info| info.info.get_fullname() != fullname);
+            if self.order() == Order::Priority {
+                arrange(resolved, Order::Priority);
+            }
+        }
+        self.refresh_service(service_type);
+    }
+
+    /// Find the addresses several hostnames resolve to again, once the
+    /// instances changed.
+    pub fn refresh_shared(&self) {
+        self.shared.update(&self.instances());
+    }
+
+    /// Remove the instances whose records ran out, or said goodbye, on the
+    /// model's own rather than waiting on the backend.
+    ///
+    /// Meant to be called every second or so. Goodbyes are honored a second
+    /// after heard. Records past their TTL mark the instance stale, and it
+    /// expires once the stale grace is over too, unless heard from again
+    /// meanwhile. When the responses can't be listened to, only the removals
+    /// of the backend make instances stale. The instances' ages are updated
+    /// along the way.
+    pub fn expire(&self) {
+        let now = self.clock.now();
+        let grace = *self.stale_grace.lock();
+        let thresholds = *self.age_thresholds.lock();
+        let listening = self.sources.listening();
+        let mut removed = Vec::new();
+        for (service_type, list) in self.instances().iter_mut() {
+            for info in list.items_mut() {
+                let (heard, ttl) = self.lifetime(info);
+                if ttl.is_zero() {
+                    info.age = Age::Expiring;
+                    if now >= heard + K_GOODBYE_DELAY {
+                        removed.push((
+                            service_type.clone(),
+                            info.info.get_fullname().to_string(),
+                            Removal::Goodbye,
+                        ));
+                    }
+                    continue;
+                }
+                if listening {
+                    info.stale = (now >= heard + ttl).then_some(heard + ttl);
+                }
+                info.age = if info.stale.is_some() || now + thresholds.expiring >= heard + ttl {
+                    Age::Expiring
+                } else if now < info.first_seen + thresholds.new {
+                    Age::New
+                } else {
+                    Age::Settled
+                };
+                if info.stale.is_some_and(|stale| now >= stale + grace) {
+                    removed.push((
+                        service_type.clone(),
+                        info.info.get_fullname().to_string(),
+                        Removal::Expired,
+                    ));
+                }
+            }
+        }
+        for (service_type, fullname, removal) in removed {
+            tracing::info!("{fullname} removed: {removal:?}");
+            self.scripts.on_removed(&service_type, &fullname);
+            self.event_log
+                .record(EventKind::Removed(Some(removal)), &service_type, &fullname);
+            self.retired.lock().insert(fullname.clone());
+            self.remove_instance(&service_type, &fullname);
+        }
+    }
+
+    /// Log the TXT `changes` of `info`, alerting on the watched ones.
+    fn txt_updated(&self, service_type: &str, info: &Info, changes: Vec<TxtChange>) {
+        let fullname = info.info.get_fullname();
+        tracing::debug!("{fullname} updated: {changes:?}");
+        for change in txt::alerting(&self.txt_alerts.lock(), service_type, &changes) {
+            let _ = self.alert_tx.send(format!("{fullname}: {change}"));
+        }
+        self.event_log
+            .record_update(service_type, fullname, changes);
+    }
+
+    /// Attach the device `model` to every instance running on `hostname`.
+    fn set_device_model(&self, hostname: &str, model: &str) {
+        let previous = self
+            .device_models
+            .lock()
+            .insert(hostname.to_string(), model.to_string());
+        if previous.as_deref() == Some(model) {
+            return;
+        }
+        tracing::debug!("{hostname} is a {model}");
+        for list in self.instances().values_mut() {
+            for info in list.items_mut().filter(|info| info.id() == hostname) {
+                info.device_model = Some(model.to_string());
+            }
+        }
+    }
+
+    /// Add `service_type` to the Services pane, with an empty instance list.
+    fn add_type(&self, service_type: &str, ignore: &Option<Filter>) {
+        let mut services = self.services();
+        let mut instances = self.instances();
+        if instances.contains_key(service_type) {
+            return;
+        }
+        self.session.service_type(service_type);
+        instances.insert(
+            service_type.to_string(),
+            ListWidget::default()
+                .name(service_type.to_string())
+                .ignore(ignore.clone()),
+        );
+        let service = Service::new(
+            service_type.to_string(),
+            self.columns.lock().clone(),
+            self.clock.system_time(),
+        );
+        if continuity::is_continuity(service_type) {
+            let mut grouped = self.grouped.lock();
+            if let Some(hidden) = grouped.as_mut() {
+                hidden.push(service);
+                drop(grouped);
+                drop(instances);
+                drop(services);
+                self.refresh_group();
+                return;
+            }
+        }
+        services.push(service);
+    }
+
+    /// Apply a single mDNS event.
+    ///
+    /// `ServiceFound` and `ServiceRemoved` of a type enumeration query carry
+    /// service types, which make up the Services pane. For any other query
+    /// they carry instances of the queried type, so the type itself is added
+    /// instead. Instances are always filed under the base type they resolve
+    /// to, regardless of the subtype they were browsed with.
+    pub fn apply(&self, event: ServiceEvent, queries: &[String], ignore: &Option<Filter>) {
+        if !matches!(
+            event,
+            ServiceEvent::SearchStarted(_) | ServiceEvent::SearchStopped(_)
+        ) {
+            self.session.event();
+        }
+        match event {
+            ServiceEvent::ServiceFound(service_type, full_name)
+                if domain::is_browse_domain_query(&service_type) =>
+            {
+                tracing::debug!("New browse domain found: {full_name}");
+                let found = domain::normalize(&full_name);
+                let mut domains = self.domains.lock();
+                if !domains.contains(&found) {
+                    domains.push(found);
+                }
+            }
+            ServiceEvent::ServiceFound(service_type, full_name) => {
+                tracing::debug!("New service found: {full_name}");
+                self.scripts.on_service_found(&service_type, &full_name);
+                self.event_log
+                    .record(EventKind::Found, &service_type, &full_name);
+                self.browse_mut(&service_type, |browse| browse.answers += 1);
+                if queries.contains(&service_type) {
+                    if domain::is_type_enumeration(&service_type) {
+                        self.add_type(&full_name, ignore);
+                    } else {
+                        self.add_type(domain::base_type(&service_type), ignore);
+                    }
+                }
+            }
+            ServiceEvent::ServiceResolved(info) => {
+                tracing::debug!("Service resolved: {info:#?}");
+                let now = self.clock.system_time();
+                self.sightings
+                    .lock()
+                    .entry(info.get_hostname().to_string())
+                    .and_modify(|sighting| sighting.last = now)
+                    .or_insert_with(|| Sighting::at(now));
+                if info.get_type().starts_with(K_DEVICE_INFO_TYPE) {
+                    if let Some(model) = info.get_property_val_str("model") {
+                        self.set_device_model(info.get_hostname(), model);
+                    }
+                }
+                self.event_log
+                    .record(EventKind::Resolved, info.get_type(), info.get_fullname());
+                let reaction = self.scripts.on_resolved(&info);
+                let service_type = domain::base_type(info.get_type()).to_string();
+                if let Some(resolved) = self.instances().get_mut(&service_type) {
+                    let fullname = info.get_fullname().to_string();
+                    self.retired.lock().remove(&fullname);
+                    self.session.resolved(&info);
+                    let mut info = Info::new(info, self.clock.now());
+                    for addr in info.addresses() {
+                        self.reverse_names.request(addr);
+                    }
+                    info.reverse_names = self.reverse_names.clone();
+                    info.pings = self.pings.clone();
+                    info.ports = self.ports.clone();
+                    info.sources = self.sources.clone();
+                    info.overlay = self.overlay.clone();
+                    info.shared = self.shared.clone();
+                    info.audit = self.audit.clone();
+                    info.rule_labels = label::labels(&self.labelers.lock(), &info.info);
+                    info.computed = computed::values(&self.computed.lock(), &info);
+                    info.name = reaction.name;
+                    info.labels = reaction.labels;
+                    info.device_model = self.device_models.lock().get(&info.id()).cloned();
+                    info.anomalous = self
+                        .baseline
+                        .lock()
+                        .as_ref()
+                        .is_some_and(|baseline| !baseline.contains(&info.id()));
+                    // Re-resolving refreshes the instance in place
+                    let existing = resolved.items_mut().find(|existing| **existing == info);
+                    match existing {
+                        Some(existing) => {
+                            let changes = txt::changes(&existing.info, &info.info);
+                            if !changes.is_empty() {
+                                self.txt_updated(&service_type, &info, changes);
+                            }
+                            existing.name = info.name;
+                            existing.labels = info.labels;
+                            existing.rule_labels = info.rule_labels;
+                            existing.computed = info.computed;
+                            existing.info = info.info;
+                            existing.last_seen = info.last_seen;
+                            existing.stale = None;
+                        }
+                        None => resolved.push(info),
+                    }
+                    match self.order() {
+                        Order::Found => {}
+                        Order::Recent => {
+                            resolved.bump(|existing| existing.info.get_fullname() == fullname);
+                            if let Some(bumped) = resolved.items_mut().next() {
+                                bumped.bumped = Some(self.clock.now());
+                            }
+                        }
+                        Order::Priority => arrange(resolved, Order::Priority),
+                    }
+                }
+                for evicted in self.evict() {
+                    self.refresh_service(&evicted);
+                }
+                self.refresh_service(&service_type);
+            }
+            ServiceEvent::ServiceRemoved(service_type, full_name) => {
+                tracing::debug!("Service removed: {full_name}");
+                if self.retired.lock().remove(&full_name) {
+                    tracing::debug!("{full_name} already expired");
+                    return;
+                }
+                let removal = self.removal(&service_type, &full_name);
+                if removal == Some(Removal::Expired) && self.linger(&service_type, &full_name) {
+                    tracing::debug!("{full_name} is stale");
+                    return;
+                }
+                self.scripts.on_removed(&service_type, &full_name);
+                if let Some(removal) = removal {
+                    tracing::info!("{full_name} removed: {removal:?}");
+                }
+                self.event_log
+                    .record(EventKind::Removed(removal), &service_type, &full_name);
+                // Browse domains stay selectable once seen
+                if queries.contains(&service_type) && domain::is_type_enumeration(&service_type) {
+                    self.services().remove(&full_name);
+                    self.instances().remove(&full_name);
+                    if let Some(hidden) = self.grouped.lock().as_mut() {
+                        hidden.retain(|service| service.service_type != full_name);
+                    }
+                    self.refresh_group();
+                } else {
+                    self.remove_instance(&service_type, &full_name);
+                }
+            }
+            ServiceEvent::SearchStarted(service) => {
+                tracing::trace!("Search Started for {service}");
+                // mdns-sd tells the addresses queried from after the type
+                let service_type = service.split(' ').next().unwrap_or_default();
+                self.browse_mut(service_type, |browse| browse.queries += 1);
+            }
+            ServiceEvent::SearchStopped(service) => {
+                tracing::trace!("Search Stopped for {service}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mdns_sd::ServiceInfo;
+
+    use super::*;
+    use crate::clock::ManualClock;
+
+    const K_SUBTYPE: &str = "_printer._sub._http._tcp.local.";
+    const K_BASE_TYPE: &str = "_http._tcp.local.";
+    const K_FULLNAME: &str = "Office._http._tcp.local.";
+
+    fn printer(service_type: &str) -> ServiceInfo {
+        ServiceInfo::new(
+            service_type,
+            "Office",
+            "printer.local.",
+            "192.168.2.10",
+            80,
+            &[("path", "/")][..],
+        )
+        .unwrap()
+    }
+
+    fn service_types(model: &Model) -> Vec<String> {
+        model
+            .services()
+            .items()
+            .map(|service| service.id())
+            .collect()
+    }
+
+    fn fullnames(model: &Model, service_type: &str) -> Vec<String> {
+        model
+            .instances()
+            .get(service_type)
+            .map(|list| {
+                list.items()
+                    .map(|info| info.info.get_fullname().to_string())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn subtype_browses_are_filed_under_the_base_type() {
+        let model = Model::new(None, None, Scripts::default());
+        let queries = [K_SUBTYPE.to_string()];
+
+        model.apply(
+            ServiceEvent::ServiceFound(K_SUBTYPE.to_string(), K_FULLNAME.to_string()),
+            &queries,
+            &None,
+        );
+        assert_eq!(service_types(&model), [K_BASE_TYPE]);
+        assert!(fullnames(&model, K_BASE_TYPE).is_empty());
+
+        model.apply(
+            ServiceEvent::ServiceResolved(printer(K_SUBTYPE)),
+            &queries,
+            &None,
+        );
+        assert_eq!(fullnames(&model, K_BASE_TYPE), [K_FULLNAME]);
+        assert!(!model.instances().contains_key(K_SUBTYPE));
+
+        model.apply(
+            ServiceEvent::ServiceRemoved(K_SUBTYPE.to_string(), K_FULLNAME.to_string()),
+            &queries,
+            &None,
+        );
+        assert!(fullnames(&model, K_BASE_TYPE).is_empty());
+        assert_eq!(service_types(&model), [K_BASE_TYPE]);
+    }
+
+    #[test]
+    fn type_enumerations_list_the_types() {
+        let model = Model::new(None, None, Scripts::default());
+        let enumeration = "_services._dns-sd._udp.local.";
+        let queries = [enumeration.to_string()];
+
+        model.apply(
+            ServiceEvent::ServiceFound(enumeration.to_string(), K_BASE_TYPE.to_string()),
+            &queries,
+            &None,
+        );
+        assert_eq!(service_types(&model), [K_BASE_TYPE]);
+        assert!(model.instances().contains_key(K_BASE_TYPE));
+
+        model.apply(
+            ServiceEvent::ServiceRemoved(enumeration.to_string(), K_BASE_TYPE.to_string()),
+            &queries,
+            &None,
+        );
+        assert!(service_types(&model).is_empty());
+        assert!(!model.instances().contains_key(K_BASE_TYPE));
+    }
+
+    /// A model on simulated time with `K_FULLNAME` resolved, whose records
+    /// are heard with `ttl`.
+    fn resolved(ttl: Duration) -> (Model, Arc<ManualClock>) {
+        let clock = Arc::new(ManualClock::new());
+        let mut model = Model::with_clock(None, None, Scripts::default(), clock.clone());
+        model.sources = Sources::simulated(clock.clone());
+        let queries = [K_BASE_TYPE.to_string()];
+        model.apply(
+            ServiceEvent::ServiceFound(K_BASE_TYPE.to_string(), K_FULLNAME.to_string()),
+            &queries,
+            &None,
+        );
+        model.apply(
+            ServiceEvent::ServiceResolved(printer(K_BASE_TYPE)),
+            &queries,
+            &None,
+        );
+        model.sources.hear(K_FULLNAME, ttl);
+        (model, clock)
+    }
+
+    fn removals(model: &Model) -> Vec<Option<Removal>> {
+        model
+            .event_log
+            .snapshot()
+            .into_iter()
+            .filter_map(|event| match event.kind {
+                EventKind::Removed(removal) => Some(removal),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn stale(model: &Model) -> Option<Instant> {
+        model.instances()[K_BASE_TYPE]
+            .items()
+            .next()
+            .and_then(|info| info.stale)
+    }
+
+    #[test]
+    fn records_expire_with_their_ttl() {
+        let (model, clock) = resolved(Duration::from_secs(120));
+
+        clock.advance(Duration::from_secs(119));
+        model.expire();
+        assert_eq!(fullnames(&model, K_BASE_TYPE), [K_FULLNAME]);
+        assert!(removals(&model).is_empty());
+
+        clock.advance(Duration::from_secs(1));
+        model.expire();
+        assert!(fullnames(&model, K_BASE_TYPE).is_empty());
+        assert_eq!(removals(&model), [Some(Removal::Expired)]);
+
+        // The backend's late removal of the expired instance is dropped
+        model.apply(
+            ServiceEvent::ServiceRemoved(K_BASE_TYPE.to_string(), K_FULLNAME.to_string()),
+            &[K_BASE_TYPE.to_string()],
+            &None,
+        );
+        assert_eq!(removals(&model), [Some(Removal::Expired)]);
+    }
+
+    #[test]
+    fn goodbyes_are_honored_after_a_delay() {
+        let (model, clock) = resolved(Duration::from_secs(120));
+        clock.advance(Duration::from_secs(10));
+        model.sources.hear(K_FULLNAME, Duration::ZERO);
+
+        model.expire();
+        assert_eq!(fullnames(&model, K_BASE_TYPE), [K_FULLNAME]);
+
+        clock.advance(K_GOODBYE_DELAY);
+        model.expire();
+        assert!(fullnames(&model, K_BASE_TYPE).is_empty());
+        assert_eq!(removals(&model), [Some(Removal::Goodbye)]);
+    }
+
+    #[test]
+    fn stale_instances_are_kept_for_the_grace() {
+        let (model, clock) = resolved(Duration::from_secs(120));
+        model.set_stale_grace(Duration::from_secs(30));
+        let expiry = clock.now() + Duration::from_secs(120);
+
+        clock.advance(Duration::from_secs(120));
+        model.expire();
+        assert_eq!(stale(&model), Some(expiry));
+
+        clock.advance(Duration::from_secs(29));
+        model.expire();
+        assert_eq!(fullnames(&model, K_BASE_TYPE), [K_FULLNAME]);
+        assert!(removals(&model).is_empty());
+
+        clock.advance(Duration::from_secs(1));
+        model.expire();
+        assert!(fullnames(&model, K_BASE_TYPE).is_empty());
+        assert_eq!(removals(&model), [Some(Removal::Expired)]);
+    }
+
+    #[test]
+    fn stale_instances_heard_again_are_fresh() {
+        let (model, clock) = resolved(Duration::from_secs(120));
+        model.set_stale_grace(Duration::from_secs(30));
+
+        clock.advance(Duration::from_secs(130));
+        model.expire();
+        assert!(stale(&model).is_some());
+
+        model.sources.hear(K_FULLNAME, Duration::from_secs(120));
+        model.expire();
+        assert_eq!(stale(&model), None);
+
+        clock.advance(Duration::from_secs(100));
+        model.expire();
+        assert_eq!(fullnames(&model, K_BASE_TYPE), [K_FULLNAME]);
+        assert!(removals(&model).is_empty());
+    }
+}
@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+
+use mdns_sd::ServiceInfo;
+
+/// Stands in for the user scripts in builds without the `scripting`
+/// feature, which leaves the Rhai engine out.
+///
+/// The hooks do nothing, and the scripts found are only kept to tell the
+/// user they are ignored.
+#[derive(Debug, Default)]
+pub struct Scripts {
+    pub ignored: Vec<PathBuf>,
+}
+
+/// How the scripts want a resolved instance to be shown.
+#[derive(Debug, Default)]
+pub struct Reaction {
+    /// Shown in place of the hostname
+    pub name: Option<String>,
+    pub labels: Vec<String>,
+}
+
+impl Scripts {
+    /// `scripts` next to the config file.
+    pub fn dir(config_path: &Path) -> PathBuf {
+        config_path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join("scripts")
+    }
+
+    /// Find the `*.rhai` files in `dir`, without loading them.
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let ignored = std::fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "rhai")
+            })
+            .collect();
+        Ok(Self { ignored })
+    }
+
+    pub fn on_service_found(&self, _service_type: &str, _fullname: &str) {}
+
+    pub fn on_resolved(&self, _info: &ServiceInfo) -> Reaction {
+        Reaction::default()
+    }
+
+    pub fn on_removed(&self, _service_type: &str, _fullname: &str) {}
+}
@@ -0,0 +1,183 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/// Databases of the IEEE assignments shipped by distributions, e.g. by the
+/// ieee-data, hwdata, Wireshark and nmap packages, looked up in order.
+const K_DATABASES: [(&str, Format); 5] = [
+    ("/usr/share/ieee-data/oui.txt", Format::Ieee),
+    ("/usr/share/hwdata/oui.txt", Format::Ieee),
+    ("/usr/share/misc/oui.txt", Format::Ieee),
+    ("/usr/share/wireshark/manuf", Format::Wireshark),
+    ("/usr/share/nmap/nmap-mac-prefixes", Format::Nmap),
+];
+
+/// Makers commonly found on a LAN, for hosts without any of the databases.
+const K_VENDORS: [([u8; 3], &str); 32] = [
+    ([0x00, 0x00, 0x0c], "Cisco"),
+    ([0x00, 0x03, 0x93], "Apple"),
+    ([0x00, 0x0a, 0x95], "Apple"),
+    ([0x00, 0x0c, 0x29], "VMware"),
+    ([0x00, 0x0e, 0x58], "Sonos"),
+    ([0x00, 0x11, 0x32], "Synology"),
+    ([0x00, 0x17, 0x88], "Philips Lighting"),
+    ([0x00, 0x1a, 0x11], "Google"),
+    ([0x00, 0x1b, 0x63], "Apple"),
+    ([0x00, 0x1b, 0xa9], "Brother"),
+    ([0x00, 0x1e, 0xc2], "Apple"),
+    ([0x00, 0x25, 0x00], "Apple"),
+    ([0x00, 0x50, 0x56], "VMware"),
+    ([0x00, 0x80, 0x77], "Brother"),
+    ([0x04, 0x18, 0xd6], "Ubiquiti"),
+    ([0x08, 0x00, 0x27], "VirtualBox"),
+    ([0x24, 0x0a, 0xc4], "Espressif"),
+    ([0x24, 0xa4, 0x3c], "Ubiquiti"),
+    ([0x30, 0xae, 0xa4], "Espressif"),
+    ([0x44, 0x65, 0x0d], "Amazon"),
+    ([0x52, 0x54, 0x00], "QEMU/KVM"),
+    ([0x84, 0xf3, 0xeb], "Espressif"),
+    ([0x94, 0x9f, 0x3e], "Sonos"),
+    ([0xa4, 0xcf, 0x12], "Espressif"),
+    ([0xb0, 0xa7, 0x37], "Roku"),
+    ([0xb8, 0x27, 0xeb], "Raspberry Pi"),
+    ([0xb8, 0xe9, 0x37], "Sonos"),
+    ([0xd8, 0x3a, 0xdd], "Raspberry Pi"),
+    ([0xdc, 0xa6, 0x32], "Raspberry Pi"),
+    ([0xe4, 0x5f, 0x01], "Raspberry Pi"),
+    ([0xf4, 0xf5, 0xd8], "Google"),
+    ([0xfc, 0x65, 0xde], "Amazon"),
+];
+
+/// Layout of the lines of an OUI database.
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    /// `00-00-0C   (hex)` then tabs and `Cisco Systems, Inc`
+    Ieee,
+    /// `00:00:0C`, `Cisco` and `Cisco Systems, Inc`, tab separated
+    Wireshark,
+    /// `00000C Cisco Systems`
+    Nmap,
+}
+
+/// The first 3 bytes of `mac`, written with `:` or `-` separators, or none.
+fn prefix(mac: &str) -> Option<[u8; 3]> {
+    let hex: String = mac
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.'))
+        .take(6)
+        .collect();
+    if hex.len() < 6 {
+        return None;
+    }
+    let byte = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+    Some([byte(0)?, byte(2)?, byte(4)?])
+}
+
+/// The vendor of `line` of a database in `format`, if it is about `oui`.
+fn lookup_line(line: &str, format: Format, oui: [u8; 3]) -> Option<String> {
+    let (assignment, vendor) = match format {
+        Format::Ieee => {
+            let (assignment, vendor) = line.split_once("(hex)")?;
+            (assignment.trim(), vendor.trim())
+        }
+        Format::Wireshark => {
+            let mut fields = line.split('\t');
+            let assignment = fields.next()?;
+            let short = fields.next()?;
+            // Smaller blocks than an OUI come with a mask, e.g. `/36`
+            if assignment.contains('/') {
+                return None;
+            }
+            (assignment, fields.next().unwrap_or(short).trim())
+        }
+        Format::Nmap => line.split_once(' ')?,
+    };
+    (assignment.len() <= 8 && prefix(assignment)? == oui && !vendor.is_empty())
+        .then(|| vendor.trim().to_string())
+}
+
+/// The maker of the network interface with the hardware address `mac`, as
+/// registered with the IEEE.
+///
+/// Looked up in the databases the system has, or else among a few common
+/// makers. Locally administered addresses aren't registered, they are told
+/// as randomized, as phones and laptops use them for privacy.
+pub fn vendor(mac: &str) -> Option<String> {
+    let oui = prefix(mac)?;
+    let found = K_DATABASES.iter().find_map(|(path, format)| {
+        let file = File::open(path).ok()?;
+        BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .find_map(|line| lookup_line(&line, *format, oui))
+    });
+    found
+        .or_else(|| {
+            K_VENDORS
+                .iter()
+                .find(|(known, _)| *known == oui)
+                .map(|(_, vendor)| vendor.to_string())
+        })
+        .or_else(|| (oui[0] & 0x02 != 0).then(|| "randomized".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes_of_every_notation() {
+        assert_eq!(prefix("b8:27:eb:12:34:56"), Some([0xb8, 0x27, 0xeb]));
+        assert_eq!(prefix("B8-27-EB-12-34-56"), Some([0xb8, 0x27, 0xeb]));
+        assert_eq!(prefix("b827.eb12.3456"), Some([0xb8, 0x27, 0xeb]));
+        assert_eq!(prefix("b8:27"), None);
+        assert_eq!(prefix("zz:27:eb:12:34:56"), None);
+    }
+
+    #[test]
+    fn database_lines() {
+        let oui = [0x00, 0x00, 0x0c];
+        assert_eq!(
+            lookup_line("00-00-0C   (hex)\t\tCisco Systems, Inc", Format::Ieee, oui),
+            Some("Cisco Systems, Inc".to_string())
+        );
+        assert_eq!(
+            lookup_line(
+                "00000C     (base 16)\t\tCisco Systems, Inc",
+                Format::Ieee,
+                oui
+            ),
+            None
+        );
+        assert_eq!(
+            lookup_line(
+                "00:00:0C\tCisco\tCisco Systems, Inc",
+                Format::Wireshark,
+                oui
+            ),
+            Some("Cisco Systems, Inc".to_string())
+        );
+        assert_eq!(
+            lookup_line("00:00:0C\tCisco", Format::Wireshark, oui),
+            Some("Cisco".to_string())
+        );
+        assert_eq!(
+            lookup_line(
+                "00:00:0C:10:00:00/36\tSmall\tSmall Block",
+                Format::Wireshark,
+                oui
+            ),
+            None
+        );
+        assert_eq!(
+            lookup_line("00000C Cisco Systems", Format::Nmap, oui),
+            Some("Cisco Systems".to_string())
+        );
+        assert_eq!(lookup_line("00000D Other", Format::Nmap, oui), None);
+        assert_eq!(lookup_line("# comment", Format::Nmap, oui), None);
+    }
+
+    #[test]
+    fn the_built_in_vendors_are_sorted() {
+        assert!(K_VENDORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use parking_lot::Mutex;
+use serde::Deserialize;
+
+/// How often the peers are listed again, as they come and go.
+const K_REFRESH: Duration = Duration::from_secs(60);
+const K_PAUSE_POLL: Duration = Duration::from_millis(500);
+
+/// A device of an overlay network.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Peer {
+    /// The overlay, e.g. `tailscale`
+    pub network: &'static str,
+    pub name: String,
+}
+
+/// An address range routed to a peer.
+#[derive(Debug, Clone)]
+struct Route {
+    addr: IpAddr,
+    prefix: u8,
+    peer: Peer,
+}
+
+impl Route {
+    fn contains(&self, addr: &IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix.min(32)))
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(*addr) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix.min(128)))
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(*addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Somewhere the peers of an overlay network are listed, e.g. a VPN's CLI.
+trait PeerSource: Send {
+    fn name(&self) -> &'static str;
+    fn routes(&self) -> anyhow::Result<Vec<Route>>;
+}
+
+/// Run `program` with `args` and return its standard output.
+fn output(program: &str, args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run {program}"))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Tailscale nodes, from `tailscale status --json`.
+struct Tailscale;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TailscaleStatus {
+    #[serde(rename = "Self")]
+    own: Option<TailscaleNode>,
+    /// `null` rather than empty without peers
+    peer: Option<HashMap<String, TailscaleNode>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TailscaleNode {
+    #[serde(default)]
+    host_name: String,
+    /// MagicDNS name, e.g. `nas.tail1234.ts.net.`
+    #[serde(rename = "DNSName", default)]
+    dns_name: String,
+    #[serde(rename = "TailscaleIPs")]
+    tailscale_ips: Option<Vec<IpAddr>>,
+}
+
+impl PeerSource for Tailscale {
+    fn name(&self) -> &'static str {
+        "tailscale"
+    }
+
+    fn routes(&self) -> anyhow::Result<Vec<Route>> {
+        Self::parse(&output("tailscale", &["status", "--json"])?)
+    }
+}
+
+impl Tailscale {
+    /// The addresses of this node and of its peers, in the JSON `status`.
+    fn parse(status: &str) -> anyhow::Result<Vec<Route>> {
+        let status: TailscaleStatus =
+            serde_json::from_str(status).context("Unexpected tailscale status")?;
+        Ok(status
+            .own
+            .into_iter()
+            .chain(status.peer.unwrap_or_default().into_values())
+            .flat_map(|node| {
+                let name = match node.dns_name.split('.').next() {
+                    Some(name) if !name.is_empty() => name.to_string(),
+                    _ => node.host_name,
+                };
+                node.tailscale_ips
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(move |addr| Route {
+                        addr,
+                        prefix: if addr.is_ipv4() { 32 } else { 128 },
+                        peer: Peer {
+                            network: "tailscale",
+                            name: name.clone(),
+                        },
+                    })
+            })
+            .collect())
+    }
+}
+
+/// WireGuard peers, from `wg show all allowed-ips`, named after their
+/// interface and public key as WireGuard knows no names.
+struct WireGuard;
+
+impl PeerSource for WireGuard {
+    fn name(&self) -> &'static str {
+        "wireguard"
+    }
+
+    fn routes(&self) -> anyhow::Result<Vec<Route>> {
+        Ok(Self::parse(&output("wg", &["show", "all", "allowed-ips"])?))
+    }
+}
+
+impl WireGuard {
+    /// The networks allowed to each peer in `listed`, skipping the lines
+    /// and networks that don't make sense.
+    fn parse(listed: &str) -> Vec<Route> {
+        let mut routes = Vec::new();
+        // e.g. `wg0	<public key>	10.0.0.2/32 fd00::2/128`
+        for line in listed.lines() {
+            let mut fields = line.split('\t');
+            let (Some(interface), Some(key), Some(allowed)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let name = format!("{interface} {}", key.get(..8).unwrap_or(key));
+            for network in allowed.split_whitespace() {
+                let Some((addr, prefix)) = network.split_once('/') else {
+                    continue;
+                };
+                let (Ok(addr), Ok(prefix)) = (addr.parse::<IpAddr>(), prefix.parse::<u8>()) else {
+                    continue;
+                };
+                // Default routes, e.g. of a VPN gateway, would claim everything
+                // and prefixes longer than the address are nonsense
+                if prefix == 0 || prefix > if addr.is_ipv4() { 32 } else { 128 } {
+                    continue;
+                }
+                routes.push(Route {
+                    addr,
+                    prefix,
+                    peer: Peer {
+                        network: "wireguard",
+                        name: name.clone(),
+                    },
+                });
+            }
+        }
+        routes
+    }
+}
+
+/// Overlay network peers, e.g. Tailscale or WireGuard ones, the resolved
+/// addresses belong to.
+///
+/// The peers are listed in the background through the overlays' CLIs every
+/// minute, while not `paused`. Overlays whose CLI isn't installed are left
+/// out for the rest of the session. Cloning is cheap and yields a handle to
+/// the same peers.
+#[derive(Debug, Clone, Default)]
+pub struct OverlayPeers {
+    routes: Arc<Mutex<Vec<Route>>>,
+}
+
+impl OverlayPeers {
+    pub fn spawn(paused: Arc<AtomicBool>) -> Self {
+        let peers = Self::default();
+        let routes = peers.routes.clone();
+        std::thread::spawn(move || {
+            let mut sources: Vec<Box<dyn PeerSource>> =
+                vec![Box::new(Tailscale), Box::new(WireGuard)];
+            while !sources.is_empty() {
+                while paused.load(Ordering::Relaxed) {
+                    std::thread::sleep(K_PAUSE_POLL);
+                }
+                let mut listed = Vec::new();
+                sources.retain(|source| match source.routes() {
+                    Ok(routes) => {
+                        listed.extend(routes);
+                        true
+                    }
+                    Err(e) => {
+                        tracing::debug!("No {} peers: {e:#}", source.name());
+                        // Not installed, and not going to be
+                        !e.chain().any(|cause| {
+                            cause
+                                .downcast_ref::<std::io::Error>()
+                                .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+                        })
+                    }
+                });
+                // The most specific route wins, e.g. a peer within a routed subnet
+                listed.sort_by_key(|route| std::cmp::Reverse(route.prefix));
+                *routes.lock() = listed;
+                std::thread::sleep(K_REFRESH);
+            }
+        });
+        peers
+    }
+
+    /// The peer `addr` belongs to, if it is on an overlay network.
+    pub fn get(&self, addr: &IpAddr) -> Option<Peer> {
+        self.routes
+            .lock()
+            .iter()
+            .find(|route| route.contains(addr))
+            .map(|route| route.peer.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `tailscale status --json` of a node with 2 peers, trimmed.
+    const K_TAILSCALE_STATUS: &str = r#"{
+  "Version": "1.56.1-t906f85d10-g2dae8cb6e",
+  "BackendState": "Running",
+  "Self": {
+    "ID": "nGsyQq4CNTRL",
+    "HostName": "laptop",
+    "DNSName": "laptop.tail1234.ts.net.",
+    "OS": "linux",
+    "TailscaleIPs": ["100.101.102.103", "fd7a:115c:a1e0::1"],
+    "Online": true
+  },
+  "Peer": {
+    "nodekey:1e8a": {
+      "ID": "nYy8Ym3CNTRL",
+      "HostName": "nas",
+      "DNSName": "nas.tail1234.ts.net.",
+      "OS": "linux",
+      "TailscaleIPs": ["100.64.0.2", "fd7a:115c:a1e0::2"],
+      "Online": true
+    },
+    "nodekey:92ff": {
+      "ID": "nQ4d5z2CNTRL",
+      "HostName": "localhost",
+      "DNSName": "",
+      "OS": "android",
+      "TailscaleIPs": ["100.64.0.3"],
+      "Online": false
+    }
+  }
+}"#;
+
+    /// `wg show all allowed-ips` with a peer allowed no address and a
+    /// gateway allowed every one.
+    const K_WG_SHOW: &str = "\
+wg0\txTIBA5rboUvnH4htodjb6e697QjLERt1NAB4mZqp8Dg=\t10.0.0.2/32 fd00::2/128
+wg0\tTrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=\t(none)
+wg1\tgN65BkIKy1eCE9pP1wdc8ROUtkHLF2PfAqYdyYBz6EA=\t0.0.0.0/0 ::/0 192.168.50.0/24
+";
+
+    fn summary(mut routes: Vec<Route>) -> Vec<(String, &'static str, String)> {
+        routes.sort_by_key(|route| route.addr);
+        routes
+            .into_iter()
+            .map(|route| {
+                let network = format!("{}/{}", route.addr, route.prefix);
+                (network, route.peer.network, route.peer.name)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tailscale_nodes() {
+        let routes = Tailscale::parse(K_TAILSCALE_STATUS).unwrap();
+        let route =
+            |network: &str, name: &str| (network.to_string(), "tailscale", name.to_string());
+        assert_eq!(
+            summary(routes),
+            [
+                route("100.64.0.2/32", "nas"),
+                // Without a MagicDNS name
+                route("100.64.0.3/32", "localhost"),
+                route("100.101.102.103/32", "laptop"),
+                route("fd7a:115c:a1e0::1/128", "laptop"),
+                route("fd7a:115c:a1e0::2/128", "nas"),
+            ]
+        );
+    }
+
+    #[test]
+    fn tailscale_without_peers() {
+        let status = r#"{
+  "BackendState": "Stopped",
+  "Self": {"HostName": "laptop", "DNSName": "", "TailscaleIPs": null},
+  "Peer": null
+}"#;
+        assert!(Tailscale::parse(status).unwrap().is_empty());
+        assert!(Tailscale::parse("{}").unwrap().is_empty());
+    }
+
+    #[test]
+    fn garbled_tailscale_status() {
+        let error = |status| format!("{:#}", Tailscale::parse(status).unwrap_err());
+        assert!(error("Tailscale is stopped.").starts_with("Unexpected tailscale status: "));
+        assert!(error(&K_TAILSCALE_STATUS[..200]).starts_with("Unexpected tailscale status: "));
+        assert!(error(r#"{"Peer": {"a": {"TailscaleIPs": ["100.64.0"]}}}"#)
+            .starts_with("Unexpected tailscale status: "));
+    }
+
+    #[test]
+    fn wireguard_peers() {
+        let route =
+            |network: &str, name: &str| (network.to_string(), "wireguard", name.to_string());
+        assert_eq!(
+            summary(WireGuard::parse(K_WG_SHOW)),
+            [
+                route("10.0.0.2/32", "wg0 xTIBA5rb"),
+                // Not the default routes
+                route("192.168.50.0/24", "wg1 gN65BkIK"),
+                route("fd00::2/128", "wg0 xTIBA5rb"),
+            ]
+        );
+    }
+
+    #[test]
+    fn wireguard_without_peers() {
+        assert!(WireGuard::parse("").is_empty());
+        assert!(
+            WireGuard::parse("wg0\tTrMvSoP4jYQlY6RIzBgbssQqY3vxI2Pi+y71lOWWXX0=\t(none)\n")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn garbled_wireguard_output() {
+        let listed = "Unable to access interface: Operation not permitted\n\
+            wg0\tkey\tnot/an/address 10.0.0.300/32 10.0.0.3/33 10.0.0.4 fd00::4/129\n";
+        assert!(WireGuard::parse(listed).is_empty());
+    }
+}
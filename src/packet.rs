@@ -0,0 +1,323 @@
+pub const K_TYPE_A: u16 = 1;
+pub const K_TYPE_PTR: u16 = 12;
+pub const K_TYPE_TXT: u16 = 16;
+pub const K_TYPE_AAAA: u16 = 28;
+pub const K_TYPE_SRV: u16 = 33;
+pub const K_TYPE_NSEC: u16 = 47;
+/// Class IN with the unicast-response bit set
+const K_CLASS_IN_QU: u16 = 0x8001;
+/// Pointers followed at most while reading a name, against loops
+const K_MAX_JUMPS: usize = 16;
+
+/// A record of a response: its name, type, TTL in seconds and data.
+pub type Record<'a> = (String, u16, u32, &'a [u8]);
+
+/// A query for each of `types` of the name made of `labels`.
+pub fn query(id: u16, labels: &[&str], types: &[u16]) -> Vec<u8> {
+    let mut packet = Vec::new();
+    packet.extend_from_slice(&id.to_be_bytes());
+    // Flags, then the questions and no other records
+    packet.extend_from_slice(&[0, 0]);
+    packet.extend_from_slice(&(types.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    for record_type in types {
+        write_name(&mut packet, labels);
+        packet.extend_from_slice(&record_type.to_be_bytes());
+        packet.extend_from_slice(&K_CLASS_IN_QU.to_be_bytes());
+    }
+    packet
+}
+
+/// Append the uncompressed name made of `labels`, cutting them to 63 bytes.
+pub fn write_name(packet: &mut Vec<u8>, labels: &[&str]) {
+    for label in labels {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label);
+    }
+    packet.push(0);
+}
+
+pub fn u16_at(packet: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *packet.get(pos)?,
+        *packet.get(pos + 1)?,
+    ]))
+}
+
+pub fn u32_at(packet: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        packet.get(pos..pos + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Read the possibly compressed name at `pos`.
+///
+/// Returns the name, with a trailing dot, and the position right after it.
+pub fn read_name(packet: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    for _ in 0..K_MAX_JUMPS {
+        loop {
+            let len = *packet.get(pos)? as usize;
+            if len & 0xc0 == 0xc0 {
+                let pointer = (u16_at(packet, pos)? & 0x3fff) as usize;
+                end.get_or_insert(pos + 2);
+                pos = pointer;
+                break;
+            }
+            if len == 0 {
+                let name = labels.join(".") + ".";
+                return Some((name, end.unwrap_or(pos + 1)));
+            }
+            let label = packet.get(pos + 1..pos + 1 + len)?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            pos += 1 + len;
+        }
+    }
+    None
+}
+
+/// Name of the record type `record_type`, e.g. `AAAA`.
+pub fn type_name(record_type: u16) -> String {
+    match record_type {
+        K_TYPE_A => "A".to_string(),
+        K_TYPE_PTR => "PTR".to_string(),
+        13 => "HINFO".to_string(),
+        K_TYPE_TXT => "TXT".to_string(),
+        K_TYPE_AAAA => "AAAA".to_string(),
+        K_TYPE_SRV => "SRV".to_string(),
+        K_TYPE_NSEC => "NSEC".to_string(),
+        _ => format!("TYPE{record_type}"),
+    }
+}
+
+/// The record types an NSEC record's `data` says its name has, any other
+/// type being denied.
+///
+/// mDNS only uses NSEC for that (RFC 6762, section 6.1): the next name is
+/// the record's own, possibly compressed, and is skipped. `None` for a
+/// malformed bitmap, e.g. of a window over 32 bytes (RFC 4034, section
+/// 4.1.2).
+pub fn nsec_types(data: &[u8]) -> Option<Vec<u16>> {
+    let mut pos = 0;
+    loop {
+        let len = *data.get(pos)? as usize;
+        if len & 0xc0 == 0xc0 {
+            pos += 2;
+            break;
+        }
+        pos += 1 + len;
+        if len == 0 {
+            break;
+        }
+    }
+    let mut types = Vec::new();
+    while pos < data.len() {
+        let window = *data.get(pos)? as u16;
+        let len = *data.get(pos + 1)? as usize;
+        if len == 0 || len > 32 {
+            return None;
+        }
+        let bitmap = data.get(pos + 2..pos + 2 + len)?;
+        for (i, byte) in bitmap.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    let offset = u16::try_from(i * 8 + bit).ok()?;
+                    types.push(window.checked_mul(256)?.checked_add(offset)?);
+                }
+            }
+        }
+        pos += 2 + len;
+    }
+    Some(types)
+}
+
+/// Every record of the response `packet`.
+///
+/// `None` when the packet isn't a response, or to another query than `id`
+/// if given.
+pub fn records(packet: &[u8], id: Option<u16>) -> Option<Vec<Record<'_>>> {
+    let flags = u16_at(packet, 2)?;
+    if id.is_some_and(|id| u16_at(packet, 0) != Some(id)) || flags & 0x8000 == 0 {
+        return None;
+    }
+    let questions = u16_at(packet, 4)?;
+    let count = [6, 8, 10]
+        .iter()
+        .map(|offset| u16_at(packet, *offset).map(usize::from))
+        .sum::<Option<usize>>()?;
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        let (_, after) = read_name(packet, pos)?;
+        pos = after + 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..count {
+        let (record, after) = record_at(packet, pos)?;
+        pos = after;
+        records.push(record);
+    }
+    Some(records)
+}
+
+/// Read the record at `pos`, and the position right after it.
+pub fn record_at(packet: &[u8], pos: usize) -> Option<(Record<'_>, usize)> {
+    let (name, after) = read_name(packet, pos)?;
+    let record_type = u16_at(packet, after)?;
+    let ttl = u32_at(packet, after + 4)?;
+    let data_len = u16_at(packet, after + 8)? as usize;
+    let data = packet.get(after + 10..after + 10 + data_len)?;
+    Some(((name, record_type, ttl, data), after + 10 + data_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A response advertising an IPP printer, compressed like responders do.
+    #[rustfmt::skip]
+    const K_RESPONSE: [u8; 101] = [
+        // ID, flags of an authoritative answer, 0 questions, 1 answer, 0
+        // authority and 2 additional records
+        0x00, 0x00, 0x84, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02,
+        // 12: _ipp._tcp.local. PTR, IN, TTL 4500
+        4, b'_', b'i', b'p', b'p', 4, b'_', b't', b'c', b'p', 5, b'l', b'o', b'c', b'a', b'l', 0,
+        0x00, 0x0c, 0x00, 0x01, 0x00, 0x00, 0x11, 0x94, 0x00, 0x09,
+        // 39: Office, then a pointer to 12
+        6, b'O', b'f', b'f', b'i', b'c', b'e', 0xc0, 0x0c,
+        // 48: a pointer to 39, SRV, cache-flush IN, TTL 120
+        0xc0, 0x27, 0x00, 0x21, 0x80, 0x01, 0x00, 0x00, 0x00, 0x78, 0x00, 0x10,
+        // 60: priority 0, weight 0, port 631 and printer, then a pointer to local. at 22
+        0x00, 0x00, 0x00, 0x00, 0x02, 0x77,
+        7, b'p', b'r', b'i', b'n', b't', b'e', b'r', 0xc0, 0x16,
+        // 76: a pointer to 39, TXT, cache-flush IN, TTL 4500
+        0xc0, 0x27, 0x00, 0x10, 0x80, 0x01, 0x00, 0x00, 0x11, 0x94, 0x00, 0x0d,
+        12, b'r', b'p', b'=', b'i', b'p', b'p', b'/', b'p', b'r', b'i', b'n', b't',
+    ];
+
+    #[test]
+    fn uncompressed_names() {
+        let mut packet = Vec::new();
+        write_name(&mut packet, &["printer", "local"]);
+        assert_eq!(
+            read_name(&packet, 0),
+            Some(("printer.local.".to_string(), packet.len()))
+        );
+        assert_eq!(read_name(&[0], 0), Some((".".to_string(), 1)));
+    }
+
+    #[test]
+    fn compressed_names() {
+        // Ends at the pointer, not where it leads
+        assert_eq!(
+            read_name(&K_RESPONSE, 39),
+            Some(("Office._ipp._tcp.local.".to_string(), 48))
+        );
+        assert_eq!(
+            read_name(&K_RESPONSE, 48),
+            Some(("Office._ipp._tcp.local.".to_string(), 50))
+        );
+        assert_eq!(
+            read_name(&K_RESPONSE, 66),
+            Some(("printer.local.".to_string(), 76))
+        );
+    }
+
+    #[test]
+    fn broken_names() {
+        // Pointing at itself
+        assert_eq!(read_name(&[0xc0, 0x00], 0), None);
+        // A label longer than the packet
+        assert_eq!(read_name(&[5, b'l', b'o', b'c'], 0), None);
+        // A pointer past the end
+        assert_eq!(read_name(&[0xc0, 0x10], 0), None);
+        // No terminating label
+        assert_eq!(read_name(&[5, b'l', b'o', b'c', b'a', b'l'], 0), None);
+    }
+
+    #[test]
+    fn response_records() {
+        let parsed = records(&K_RESPONSE, None).unwrap();
+        assert_eq!(
+            parsed,
+            [
+                (
+                    "_ipp._tcp.local.".to_string(),
+                    K_TYPE_PTR,
+                    4500,
+                    &K_RESPONSE[39..48]
+                ),
+                (
+                    "Office._ipp._tcp.local.".to_string(),
+                    K_TYPE_SRV,
+                    120,
+                    &K_RESPONSE[60..76]
+                ),
+                (
+                    "Office._ipp._tcp.local.".to_string(),
+                    K_TYPE_TXT,
+                    4500,
+                    &b"\x0crp=ipp/print"[..]
+                ),
+            ]
+        );
+        assert_eq!(records(&K_RESPONSE, Some(0)), Some(parsed));
+    }
+
+    #[test]
+    fn rejected_packets() {
+        // Answering another query
+        assert_eq!(records(&K_RESPONSE, Some(1)), None);
+        // A query rather than a response
+        let query = query(7, &["printer", "local"], &[K_TYPE_A, K_TYPE_AAAA]);
+        assert_eq!(records(&query, None), None);
+        // Cut short within the last record
+        assert_eq!(records(&K_RESPONSE[..90], None), None);
+        // Shorter than a header
+        assert_eq!(records(&K_RESPONSE[..3], None), None);
+    }
+
+    #[test]
+    fn nsec_bitmaps() {
+        // A and AAAA, denying the other types, after a compressed next name
+        let data = [0xc0, 0x42, 0x00, 0x04, 0x40, 0x00, 0x00, 0x08];
+        assert_eq!(nsec_types(&data), Some(vec![K_TYPE_A, K_TYPE_AAAA]));
+        // The same, uncompressed, with types of the second window
+        let mut data = Vec::new();
+        write_name(&mut data, &["printer", "local"]);
+        data.extend_from_slice(&[0x00, 0x01, 0x40, 0x01, 0x01, 0x80]);
+        assert_eq!(nsec_types(&data), Some(vec![K_TYPE_A, 256]));
+        // The highest window and type
+        let mut data = vec![0];
+        data.extend_from_slice(&[0xff, 0x20]);
+        data.extend_from_slice(&[0; 31]);
+        data.push(0x01);
+        assert_eq!(nsec_types(&data), Some(vec![u16::MAX]));
+    }
+
+    #[test]
+    fn malformed_nsec_bitmaps() {
+        // An empty window
+        assert_eq!(nsec_types(&[0, 0x00, 0x00]), None);
+        // A window over 32 bytes
+        let mut data = vec![0, 0x00, 0x21];
+        data.extend_from_slice(&[0xff; 33]);
+        assert_eq!(nsec_types(&data), None);
+        // A bitmap cut short
+        assert_eq!(nsec_types(&[0, 0x00, 0x04, 0x40]), None);
+        // No name
+        assert_eq!(nsec_types(&[]), None);
+    }
+
+    #[test]
+    fn queries_are_read_back() {
+        let mut query = query(7, &["printer", "local"], &[K_TYPE_A, K_TYPE_AAAA]);
+        assert_eq!(u16_at(&query, 0), Some(7));
+        assert_eq!(u16_at(&query, 4), Some(2));
+        // Flag it as a response to read its questions back
+        query[2] = 0x84;
+        assert_eq!(records(&query, Some(7)), Some(Vec::new()));
+    }
+}
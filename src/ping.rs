@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use flume::Sender;
+use parking_lot::Mutex;
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::probe::{self, Connection};
+
+/// How often the same address is probed again.
+const K_INTERVAL: Duration = Duration::from_secs(2);
+/// How long a reply is waited for.
+const K_TIMEOUT: Duration = Duration::from_secs(1);
+const K_ECHO_PAYLOAD: &[u8] = b"discovery-rs";
+
+/// How an address was probed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// ICMP echo, where unprivileged ping sockets are allowed
+    Icmp,
+    /// TCP connection to the instance's port otherwise
+    Tcp,
+}
+
+impl Method {
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::Icmp => "ICMP",
+            Method::Tcp => "TCP",
+        }
+    }
+}
+
+/// Outcome of the latest probe of an address.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Probe {
+    Pending,
+    Reachable { rtt: Duration, method: Method },
+    Unreachable(Method),
+}
+
+/// Round-trip times to the addresses of the selected instance, probed in
+/// the background on request.
+///
+/// Probes are run by a small pool of threads, and the latest outcome of
+/// every address is kept until [`Pings::clear`]. They wait while `paused`.
+#[derive(Debug, Clone, Default)]
+pub struct Pings {
+    /// Latest outcome by address, and when it was last asked for
+    probes: Arc<Mutex<HashMap<SocketAddr, (Probe, Instant)>>>,
+    /// `None` for a cache nothing is probed for
+    requests: Option<Sender<SocketAddr>>,
+}
+
+impl Pings {
+    pub fn spawn(paused: Arc<AtomicBool>) -> Self {
+        let probes: Arc<Mutex<HashMap<SocketAddr, (Probe, Instant)>>> = Default::default();
+        let requests = probe::spawn_pool(paused, {
+            let probes = probes.clone();
+            move |target| {
+                let outcome = probe(target);
+                tracing::trace!("Probe of {target}: {outcome:?}");
+                // Left out once cleared meanwhile
+                if let Some((probe, _)) = probes.lock().get_mut(&target) {
+                    *probe = outcome;
+                }
+            }
+        });
+        Self {
+            probes,
+            requests: Some(requests),
+        }
+    }
+
+    /// Probe `target`, unless it has been lately.
+    ///
+    /// The port is the one to connect to when ICMP can't be used.
+    pub fn request(&self, target: SocketAddr) {
+        let Some(requests) = self.requests.as_ref() else {
+            return;
+        };
+        let mut probes = self.probes.lock();
+        let now = Instant::now();
+        let due = match probes.get_mut(&target) {
+            Some((_, requested)) if now.duration_since(*requested) < K_INTERVAL => false,
+            Some((_, requested)) => {
+                *requested = now;
+                true
+            }
+            None => {
+                probes.insert(target, (Probe::Pending, now));
+                true
+            }
+        };
+        if due {
+            let _ = requests.send(target);
+        }
+    }
+
+    /// The latest outcome of probing `target`, if it is probed.
+    pub fn get(&self, target: &SocketAddr) -> Option<Probe> {
+        self.probes.lock().get(target).map(|(probe, _)| *probe)
+    }
+
+    /// Forget every outcome, e.g. once probing is turned off.
+    pub fn clear(&self) {
+        self.probes.lock().clear();
+    }
+}
+
+/// RFC 1071 checksum of `data`.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Round-trip time of an ICMP echo to `target`, `None` without a reply.
+///
+/// Goes through an unprivileged ping socket, which fails unless the
+/// group is allowed to by `net.ipv4.ping_group_range` on Linux.
+fn icmp(target: SocketAddr) -> io::Result<Option<Duration>> {
+    let (domain, protocol, request, reply) = match target {
+        SocketAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4, 8, 0),
+        SocketAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6, 128, 129),
+    };
+    let mut socket = Socket::new(domain, Type::DGRAM, Some(protocol))?;
+    let mut target = target;
+    target.set_port(0);
+    socket.connect(&target.into())?;
+
+    // The kernel sets the identifier of ping sockets, the sequence number
+    // tells the replies apart
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    let sequence = (nanos as u16) ^ (std::process::id() as u16);
+    let mut packet = vec![request, 0, 0, 0, 0, 0];
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(K_ECHO_PAYLOAD);
+    let sum = checksum(&packet);
+    packet[2..4].copy_from_slice(&sum.to_be_bytes());
+
+    let sent = Instant::now();
+    socket.write_all(&packet)?;
+    let mut buf = [0u8; 1500];
+    loop {
+        let Some(remaining) = K_TIMEOUT.checked_sub(sent.elapsed()) else {
+            return Ok(None);
+        };
+        socket.set_read_timeout(Some(remaining.max(Duration::from_millis(1))))?;
+        match socket.read(&mut buf) {
+            Ok(len) if len >= 8 && buf[0] == reply && buf[6..8] == sequence.to_be_bytes() => {
+                return Ok(Some(sent.elapsed()));
+            }
+            Ok(_) => {}
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Round-trip time of a TCP handshake with `target`, `None` without an
+/// answer. A refused connection is an answer too.
+fn tcp(target: SocketAddr, timeout: Duration) -> Option<Duration> {
+    match probe::connect(target, timeout) {
+        Connection::Accepted(rtt) | Connection::Refused(rtt) => Some(rtt),
+        Connection::Unanswered => None,
+    }
+}
+
+fn probe(target: SocketAddr) -> Probe {
+    let (rtt, method) = match icmp(target) {
+        Ok(rtt) => (rtt, Method::Icmp),
+        Err(e) => {
+            tracing::trace!("ICMP to {target} failed, trying TCP: {e}");
+            (tcp(target, K_TIMEOUT), Method::Tcp)
+        }
+    };
+    match rtt {
+        Some(rtt) => Probe::Reachable { rtt, method },
+        None => Probe::Unreachable(method),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, TcpListener};
+
+    use super::*;
+
+    const K_TEST_TIMEOUT: Duration = Duration::from_millis(200);
+
+    #[test]
+    fn checksums() {
+        // An echo request with identifier 0x1234, sequence 1 and no payload
+        let packet = [0x08, 0x00, 0x00, 0x00, 0x12, 0x34, 0x00, 0x01];
+        assert_eq!(checksum(&packet), 0xe5ca);
+        let mut packet = packet.to_vec();
+        packet[2..4].copy_from_slice(&0xe5ca_u16.to_be_bytes());
+        assert_eq!(checksum(&packet), 0);
+        // An odd length is padded with a zero
+        assert_eq!(checksum(&[0x01]), !0x0100);
+    }
+
+    #[test]
+    fn tcp_listening() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let rtt = tcp(listener.local_addr().unwrap(), K_TEST_TIMEOUT).unwrap();
+        assert!(rtt < K_TEST_TIMEOUT);
+    }
+
+    #[test]
+    fn tcp_refused() {
+        // Nothing listens once the listener is gone, which is an answer too
+        let target = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let rtt = tcp(target, K_TEST_TIMEOUT).unwrap();
+        assert!(rtt < K_TEST_TIMEOUT);
+    }
+
+    #[test]
+    fn tcp_timeout() {
+        let (_socket, _queued, target) = probe::unanswered();
+        let started = Instant::now();
+        assert_eq!(tcp(target, K_TEST_TIMEOUT), None);
+        assert!(started.elapsed() >= K_TEST_TIMEOUT);
+    }
+
+    #[test]
+    fn probes() {
+        // Over ICMP where allowed, over TCP otherwise
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        assert!(matches!(
+            probe(listener.local_addr().unwrap()),
+            Probe::Reachable { .. }
+        ));
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Queries sent per second at most, on average, across all the browses.
+const K_RATE: f64 = 1.0;
+/// Queries sent at once after a quiet spell.
+const K_BURST: f64 = 4.0;
+/// Interval before a query is sent again the first time, then doubled every
+/// time, as RFC 6762 section 5.2 has it.
+const K_MIN_INTERVAL: Duration = Duration::from_secs(1);
+const K_MAX_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Paces the queries of every browse in polite mode.
+///
+/// Starting a browse, or restarting it to query again, sends a query. Those
+/// are capped at [`K_RATE`] a second overall, and a query is only sent again
+/// once its interval is over, the interval doubling up to an hour. Queries
+/// beyond that are held until due rather than dropped, and a query asked for
+/// again while held is sent once.
+#[derive(Debug)]
+pub struct Limiter {
+    tokens: f64,
+    refilled: Instant,
+    /// When each query was last sent, and the interval before the next time
+    sent: HashMap<String, (Instant, Duration)>,
+    /// Queries held, in the order asked for
+    pending: Vec<String>,
+}
+
+impl Limiter {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            tokens: K_BURST,
+            refilled: now,
+            sent: HashMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Ask for `query` to be sent as soon as it is polite to.
+    pub fn request(&mut self, query: &str) {
+        if !self.pending.iter().any(|pending| pending == query) {
+            self.pending.push(query.to_string());
+        }
+    }
+
+    /// Count `query` as sent at `now`, e.g. when browsing starts.
+    pub fn sent(&mut self, query: &str, now: Instant) {
+        self.refill(now);
+        self.tokens = (self.tokens - 1.0).max(0.0);
+        self.sent
+            .entry(query.to_string())
+            .and_modify(|(at, interval)| {
+                *at = now;
+                *interval = (*interval * 2).min(K_MAX_INTERVAL);
+            })
+            .or_insert((now, K_MIN_INTERVAL));
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * K_RATE).min(K_BURST);
+        self.refilled = now;
+    }
+
+    /// When `query` may be sent again as far as its own interval goes.
+    fn ready_at(&self, query: &str) -> Option<Instant> {
+        self.sent.get(query).map(|(at, interval)| *at + *interval)
+    }
+
+    /// The held queries that may be sent at `now`, counted as sent.
+    pub fn due(&mut self, now: Instant) -> Vec<String> {
+        self.refill(now);
+        let mut due = Vec::new();
+        let mut i = 0;
+        while i < self.pending.len() && self.tokens >= 1.0 {
+            if self
+                .ready_at(&self.pending[i])
+                .is_some_and(|ready| ready > now)
+            {
+                i += 1;
+                continue;
+            }
+            let query = self.pending.remove(i);
+            self.sent(&query, now);
+            due.push(query);
+        }
+        due
+    }
+
+    /// When the next held query may be sent, if any is.
+    pub fn next(&self) -> Option<Instant> {
+        let token = if self.tokens >= 1.0 {
+            self.refilled
+        } else {
+            self.refilled + Duration::from_secs_f64((1.0 - self.tokens) / K_RATE)
+        };
+        self.pending
+            .iter()
+            .map(|query| self.ready_at(query).map_or(token, |ready| ready.max(token)))
+            .min()
+    }
+
+    /// Queries held at the moment.
+    pub fn held(&self) -> usize {
+        self.pending.len()
+    }
+}
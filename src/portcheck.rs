@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+
+use flume::Sender;
+use parking_lot::Mutex;
+
+use crate::probe::{self, Connection};
+
+/// How long the handshake is waited for before the port counts as filtered.
+const K_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Outcome of the latest check of a port.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PortState {
+    Checking,
+    /// Accepted the connection, after the handshake's round trip
+    Open(Duration),
+    /// Refused the connection, nothing listens
+    Closed,
+    /// No answer in time, or an ICMP error, as firewalls drop or reject
+    Filtered,
+}
+
+impl PortState {
+    pub fn name(self) -> &'static str {
+        match self {
+            PortState::Checking => "checking",
+            PortState::Open(_) => "open",
+            PortState::Closed => "closed",
+            PortState::Filtered => "filtered",
+        }
+    }
+}
+
+/// Whether the ports of the instances are listened on, checked in the
+/// background on request by connecting to them.
+///
+/// Checks are run by a small pool of threads, and the latest outcome of
+/// every address and port is kept. They wait while `paused`.
+#[derive(Debug, Clone, Default)]
+pub struct PortChecks {
+    states: Arc<Mutex<HashMap<SocketAddr, PortState>>>,
+    /// `None` for a cache nothing is checked for
+    requests: Option<Sender<SocketAddr>>,
+}
+
+impl PortChecks {
+    pub fn spawn(paused: Arc<AtomicBool>) -> Self {
+        let states: Arc<Mutex<HashMap<SocketAddr, PortState>>> = Default::default();
+        let requests = probe::spawn_pool(paused, {
+            let states = states.clone();
+            move |target| {
+                let state = check(target, K_TIMEOUT);
+                tracing::debug!("Port check of {target}: {state:?}");
+                states.lock().insert(target, state);
+            }
+        });
+        Self {
+            states,
+            requests: Some(requests),
+        }
+    }
+
+    /// Check `target` again, unless it is being checked already.
+    pub fn request(&self, target: SocketAddr) {
+        let Some(requests) = self.requests.as_ref() else {
+            return;
+        };
+        let mut states = self.states.lock();
+        if states.get(&target) == Some(&PortState::Checking) {
+            return;
+        }
+        states.insert(target, PortState::Checking);
+        let _ = requests.send(target);
+    }
+
+    /// The latest outcome of checking `target`, if it has been.
+    pub fn get(&self, target: &SocketAddr) -> Option<PortState> {
+        self.states.lock().get(target).copied()
+    }
+}
+
+fn check(target: SocketAddr, timeout: Duration) -> PortState {
+    match probe::connect(target, timeout) {
+        Connection::Accepted(rtt) => PortState::Open(rtt),
+        Connection::Refused(_) => PortState::Closed,
+        Connection::Unanswered => PortState::Filtered,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, TcpListener};
+    use std::time::Instant;
+
+    use super::*;
+
+    const K_TEST_TIMEOUT: Duration = Duration::from_millis(200);
+
+    #[test]
+    fn open_ports() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let state = check(listener.local_addr().unwrap(), K_TEST_TIMEOUT);
+        assert!(matches!(state, PortState::Open(rtt) if rtt < K_TEST_TIMEOUT));
+    }
+
+    #[test]
+    fn closed_ports() {
+        let target = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        assert_eq!(check(target, K_TEST_TIMEOUT), PortState::Closed);
+    }
+
+    #[test]
+    fn filtered_ports() {
+        let (_socket, _queued, target) = probe::unanswered();
+        let started = Instant::now();
+        assert_eq!(check(target, K_TEST_TIMEOUT), PortState::Filtered);
+        assert!(started.elapsed() >= K_TEST_TIMEOUT);
+    }
+
+    #[test]
+    fn requests() {
+        let checks = PortChecks::spawn(Arc::new(AtomicBool::new(false)));
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let target = listener.local_addr().unwrap();
+        checks.request(target);
+        let started = Instant::now();
+        while checks.get(&target) == Some(PortState::Checking) {
+            assert!(started.elapsed() < K_TIMEOUT);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(matches!(checks.get(&target), Some(PortState::Open(_))));
+        // Nothing is checked by the default cache
+        PortChecks::default().request(target);
+        assert_eq!(PortChecks::default().get(&target), None);
+    }
+}
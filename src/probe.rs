@@ -0,0 +1,136 @@
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use flume::Sender;
+
+/// Probes run at once at most, by each pool.
+const K_WORKERS: usize = 4;
+/// How often paused probes check whether to resume.
+const K_PAUSE_POLL: Duration = Duration::from_millis(250);
+
+/// How an attempt to connect over TCP ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connection {
+    /// Accepted, after the handshake's round trip
+    Accepted(Duration),
+    /// Refused as nothing listens, after the round trip of the reset
+    Refused(Duration),
+    /// No answer in time, or an ICMP error, as firewalls drop or reject
+    Unanswered,
+}
+
+/// Connect to `target`, waiting up to `timeout` for the handshake.
+pub fn connect(target: SocketAddr, timeout: Duration) -> Connection {
+    let started = Instant::now();
+    match TcpStream::connect_timeout(&target, timeout) {
+        Ok(_) => Connection::Accepted(started.elapsed()),
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+            Connection::Refused(started.elapsed())
+        }
+        Err(e) => {
+            tracing::trace!("Connecting to {target} failed: {e}");
+            Connection::Unanswered
+        }
+    }
+}
+
+/// Spawn a small pool of threads running `probe` on every target sent to
+/// the returned channel, which wait while `paused`.
+///
+/// The threads end once the channel is dropped.
+pub fn spawn_pool(
+    paused: Arc<AtomicBool>,
+    probe: impl Fn(SocketAddr) + Clone + Send + 'static,
+) -> Sender<SocketAddr> {
+    let (requests, pending) = flume::unbounded::<SocketAddr>();
+    for _ in 0..K_WORKERS {
+        let pending = pending.clone();
+        let paused = paused.clone();
+        let probe = probe.clone();
+        std::thread::spawn(move || {
+            for target in pending.iter() {
+                while paused.load(Ordering::Relaxed) {
+                    std::thread::sleep(K_PAUSE_POLL);
+                }
+                probe(target);
+            }
+        });
+    }
+    requests
+}
+
+/// A port on loopback whose accept queue is full, so that connections to it
+/// go unanswered, along with what keeps it so.
+#[cfg(test)]
+pub fn unanswered() -> (socket2::Socket, Vec<TcpStream>, SocketAddr) {
+    use socket2::{Domain, Socket, Type};
+
+    let socket = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+    socket
+        .bind(&SocketAddr::from((std::net::Ipv4Addr::LOCALHOST, 0)).into())
+        .unwrap();
+    socket.listen(0).unwrap();
+    let target = socket.local_addr().unwrap().as_socket().unwrap();
+    let queued: Vec<_> =
+        std::iter::from_fn(|| TcpStream::connect_timeout(&target, Duration::from_millis(200)).ok())
+            .take(8)
+            .collect();
+    (socket, queued, target)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, TcpListener};
+
+    use super::*;
+
+    const K_TEST_TIMEOUT: Duration = Duration::from_millis(200);
+
+    #[test]
+    fn accepted() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let target = listener.local_addr().unwrap();
+        assert!(matches!(
+            connect(target, K_TEST_TIMEOUT),
+            Connection::Accepted(rtt) if rtt < K_TEST_TIMEOUT
+        ));
+    }
+
+    #[test]
+    fn refused() {
+        let target = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        assert!(matches!(
+            connect(target, K_TEST_TIMEOUT),
+            Connection::Refused(rtt) if rtt < K_TEST_TIMEOUT
+        ));
+    }
+
+    #[test]
+    fn timeouts() {
+        let (_socket, _queued, target) = unanswered();
+        let started = Instant::now();
+        assert_eq!(connect(target, K_TEST_TIMEOUT), Connection::Unanswered);
+        assert!(started.elapsed() >= K_TEST_TIMEOUT);
+    }
+
+    #[test]
+    fn pools() {
+        let paused = Arc::new(AtomicBool::new(true));
+        let (probed, done) = flume::unbounded();
+        let requests = spawn_pool(paused.clone(), move |target| {
+            let _ = probed.send(target);
+        });
+        let target = SocketAddr::from((Ipv4Addr::LOCALHOST, 631));
+        requests.send(target).unwrap();
+        // Waits while paused
+        assert!(done.recv_timeout(2 * K_PAUSE_POLL).is_err());
+        paused.store(false, Ordering::Relaxed);
+        assert_eq!(done.recv_timeout(Duration::from_secs(1)), Ok(target));
+    }
+}
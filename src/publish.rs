@@ -0,0 +1,110 @@
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap_derive::Args;
+use mdns_sd::ServiceInfo;
+use signal_hook::consts::{SIGINT, SIGTERM};
+
+use crate::backend;
+use crate::config::Profile;
+use crate::domain;
+use crate::error::Error;
+
+/// How often the signals are checked for
+const K_POLL: Duration = Duration::from_millis(200);
+/// How long the goodbyes are waited for to go out
+const K_GOODBYE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Args, Debug)]
+pub struct PublishOpts {
+    /// Instance name, e.g. "My Test Server"
+    name: String,
+    /// Service type, e.g. _http._tcp
+    service_type: String,
+    /// Port the service is on
+    port: u16,
+    /// TXT properties, as key=value or a key alone, e.g. path=/
+    txt: Vec<String>,
+    #[arg(long)]
+    /// Hostname to advertise, default: <this host>.local
+    host: Option<String>,
+    #[arg(long = "address", value_name = "ADDRESS")]
+    /// Address to advertise, can be given several times, default: the interfaces' own
+    addresses: Vec<IpAddr>,
+}
+
+/// `host` in `local.`, with the trailing dot.
+fn qualify_host(host: &str) -> String {
+    let host = host.trim_end_matches('.');
+    if host.ends_with(".local") {
+        format!("{host}.")
+    } else {
+        format!("{host}.{}", domain::K_LOCAL_DOMAIN)
+    }
+}
+
+/// Register a service instance with mdns-sd, on the interfaces `profile`
+/// browses on, until interrupted, e.g. to test discovery end to end.
+///
+/// It is unregistered on SIGINT or SIGTERM, which sends goodbyes for
+/// browsers to drop it right away.
+pub fn run(opts: &PublishOpts, profile: &Profile) -> Result<(), Error> {
+    let service_type = domain::qualify(&opts.service_type);
+    if !service_type.ends_with(domain::K_LOCAL_DOMAIN) {
+        return Err(Error::Config(anyhow::anyhow!(
+            "Only services in {} can be published",
+            domain::K_LOCAL_DOMAIN
+        )));
+    }
+    let host = match opts.host.as_deref() {
+        Some(host) => qualify_host(host),
+        None => qualify_host(&dns_lookup::get_hostname().map_err(anyhow::Error::from)?),
+    };
+    let properties: Vec<_> = opts
+        .txt
+        .iter()
+        .map(|property| property.split_once('=').unwrap_or((property, "")))
+        .collect();
+    let info = ServiceInfo::new(
+        &service_type,
+        &opts.name,
+        &host,
+        opts.addresses.as_slice(),
+        opts.port,
+        properties.as_slice(),
+    )
+    .map_err(|e| Error::Config(anyhow::anyhow!("Invalid service: {e}")))?;
+    // Follows the interfaces' addresses as they come and go
+    let info = if opts.addresses.is_empty() {
+        info.enable_addr_auto()
+    } else {
+        info
+    };
+    let fullname = info.get_fullname().to_string();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        signal_hook::flag::register(signal, stop.clone()).map_err(anyhow::Error::from)?;
+    }
+    let mdns = backend::daemon(profile.if_kinds(), profile.ipv6_only).map_err(Error::daemon)?;
+    mdns.register(info)
+        .map_err(|e| Error::daemon(anyhow::anyhow!("Failed to register {fullname}: {e}")))?;
+    tracing::info!("Published {fullname}");
+    println!("Published {fullname} on {host}, port {}", opts.port);
+
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::sleep(K_POLL);
+    }
+
+    match mdns.unregister(&fullname) {
+        Ok(status) => {
+            let _ = status.recv_timeout(K_GOODBYE_TIMEOUT);
+        }
+        Err(e) => tracing::warn!("Failed to unregister {fullname}: {e}"),
+    }
+    let _ = mdns.shutdown();
+    println!("Unpublished {fullname}");
+    Ok(())
+}
@@ -0,0 +1,790 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use flume::{Receiver, Sender, TryRecvError};
+use mdns_sd::{ServiceEvent, ServiceInfo};
+use parking_lot::Mutex;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+use crate::backend::Backend;
+use crate::domain;
+use crate::packet::{self, Record, K_TYPE_A, K_TYPE_AAAA, K_TYPE_PTR, K_TYPE_SRV, K_TYPE_TXT};
+
+/// DNS Push over TLS, RFC 8765, section 6.1
+const K_DEFAULT_PORT: u16 = 853;
+const K_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a read waits before the commands are looked at again
+const K_POLL: Duration = Duration::from_millis(200);
+/// Until the server says otherwise, RFC 8490, section 6.2
+const K_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+/// The shortest keepalive interval a server may ask for
+const K_MIN_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
+/// How often the resolved instances are reported again, see [`Session::run`]
+const K_REANNOUNCE: Duration = Duration::from_secs(3600);
+
+/// DNS Stateful Operations, RFC 8490
+const K_OPCODE_DSO: u16 = 6;
+const K_TLV_KEEPALIVE: u16 = 0x0001;
+const K_TLV_RETRY_DELAY: u16 = 0x0002;
+const K_TLV_SUBSCRIBE: u16 = 0x0040;
+const K_TLV_PUSH: u16 = 0x0041;
+const K_TLV_UNSUBSCRIBE: u16 = 0x0042;
+/// The server doesn't know the TLV, i.e. doesn't do DNS Push
+const K_RCODE_DSOTYPENI: u16 = 11;
+const K_CLASS_IN: u16 = 1;
+const K_TYPE_ANY: u16 = 255;
+/// TTLs of pushed records removing a record, and every record of its type,
+/// RFC 8765, section 6.3.1
+const K_TTL_REMOVE: u32 = 0xffff_ffff;
+const K_TTL_REMOVE_ALL: u32 = 0xffff_fffe;
+
+/// A name, lowercase with a trailing dot, and a record type.
+type Question = (String, u16);
+
+/// `name` split into its labels, e.g. for a hostname.
+fn labels(name: &str) -> Vec<String> {
+    name.trim_end_matches('.')
+        .split('.')
+        .filter(|label| !label.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The name made of `labels`, as records name it.
+fn key(labels: &[String]) -> String {
+    format!("{}.", labels.join(".")).to_lowercase()
+}
+
+/// `host`, `host:port` or `[address]:port` as the host and the port.
+fn split_server(server: &str) -> (String, u16) {
+    if let Ok(addr) = server.parse::<SocketAddr>() {
+        return (addr.ip().to_string(), addr.port());
+    }
+    // A bare IPv6 address ends with what would read as a port
+    if let Ok(addr) = server.parse::<IpAddr>() {
+        return (addr.to_string(), K_DEFAULT_PORT);
+    }
+    if let Some((host, port)) = server.rsplit_once(':') {
+        if let Ok(port) = port.parse() {
+            return (host.trim_matches(['[', ']']).to_string(), port);
+        }
+    }
+    (server.trim_matches(['[', ']']).to_string(), K_DEFAULT_PORT)
+}
+
+/// A DSO message with the `tlvs`, framed for a stream.
+///
+/// A request when `id` isn't 0, unidirectional otherwise.
+fn message(id: u16, tlvs: &[(u16, &[u8])]) -> Vec<u8> {
+    let mut message = Vec::new();
+    message.extend_from_slice(&id.to_be_bytes());
+    message.extend_from_slice(&(K_OPCODE_DSO << 11).to_be_bytes());
+    // No question nor record, the TLVs follow the header
+    message.extend_from_slice(&[0; 8]);
+    for (tlv, data) in tlvs {
+        message.extend_from_slice(&tlv.to_be_bytes());
+        message.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        message.extend_from_slice(data);
+    }
+    let mut framed = (message.len() as u16).to_be_bytes().to_vec();
+    framed.extend(message);
+    framed
+}
+
+/// The TLVs of a DSO message, its type and data.
+fn tlvs(message: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut tlvs = Vec::new();
+    let mut pos = 12;
+    while let (Some(tlv), Some(len)) = (
+        packet::u16_at(message, pos),
+        packet::u16_at(message, pos + 2),
+    ) {
+        let Some(data) = message.get(pos + 4..pos + 4 + len as usize) else {
+            break;
+        };
+        tlvs.push((tlv, data));
+        pos += 4 + len as usize;
+    }
+    tlvs
+}
+
+/// The records of a PUSH TLV, `None` when one is cut short.
+fn pushed(data: &[u8]) -> Option<Vec<Record<'_>>> {
+    let mut records = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let (record, after) = packet::record_at(data, pos)?;
+        pos = after;
+        records.push(record);
+    }
+    Some(records)
+}
+
+/// The keepalive interval of a Keepalive TLV, in milliseconds on the wire.
+fn keepalive_interval(data: &[u8]) -> Option<Duration> {
+    let interval = packet::u32_at(data, 4)?;
+    Some(Duration::from_millis(interval.into()).max(K_MIN_KEEPALIVE_INTERVAL))
+}
+
+/// The port and target of an SRV record.
+fn srv(data: &[u8]) -> Option<(u16, String)> {
+    let port = packet::u16_at(data, 4)?;
+    let (target, _) = packet::read_name(data, 6)?;
+    Some((port, target))
+}
+
+/// The `key=value` entries of a TXT record.
+fn txt(data: &[u8]) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    while let Some(len) = data.get(pos).map(|len| *len as usize) {
+        let Some(entry) = data.get(pos + 1..pos + 1 + len) else {
+            break;
+        };
+        let entry = String::from_utf8_lossy(entry);
+        if !entry.is_empty() {
+            entries.push(match entry.split_once('=') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None => (entry.into_owned(), String::new()),
+            });
+        }
+        pos += 1 + len;
+    }
+    entries
+}
+
+/// What a resolved instance was last reported as.
+#[derive(Debug, Clone, PartialEq)]
+struct Resolved {
+    hostname: String,
+    port: u16,
+    addresses: BTreeSet<IpAddr>,
+    properties: Vec<(String, String)>,
+}
+
+/// A browsed service type, or type enumeration, and what was reported of it.
+struct Browse {
+    query: String,
+    tx: Sender<ServiceEvent>,
+    /// The names subscribed to and their labels, by question
+    wanted: BTreeMap<Question, Vec<String>>,
+    /// Instances, or types when enumerating
+    found: BTreeSet<String>,
+    resolved: HashMap<String, Resolved>,
+}
+
+impl Browse {
+    /// Report what changed since the last time according to `records`,
+    /// and find the records to subscribe to from now on.
+    fn update(&mut self, records: &HashMap<Question, BTreeSet<Vec<u8>>>) {
+        let get = |name: &str, record_type: u16| {
+            records
+                .get(&(name.to_string(), record_type))
+                .into_iter()
+                .flatten()
+        };
+        let query = labels(&self.query);
+        let suffix = format!(".{}", key(&query));
+        let enumeration = domain::is_type_enumeration(&self.query);
+        let mut wanted = BTreeMap::new();
+        wanted.insert((key(&query), K_TYPE_PTR), query.clone());
+
+        let mut found = BTreeSet::new();
+        let mut resolved = HashMap::new();
+        for (target, _) in
+            get(&key(&query), K_TYPE_PTR).filter_map(|data| packet::read_name(data, 0))
+        {
+            if enumeration {
+                found.insert(target);
+                continue;
+            }
+            // The instance name is a single label, dots included
+            let Some(instance) = target
+                .len()
+                .checked_sub(suffix.len())
+                .filter(|end| target.to_lowercase().ends_with(&suffix) && *end > 0)
+                .and_then(|end| target.get(..end))
+                .map(str::to_string)
+            else {
+                continue;
+            };
+            found.insert(format!("{instance}.{}", self.query));
+            let instance_labels: Vec<_> = std::iter::once(instance.clone())
+                .chain(query.iter().cloned())
+                .collect();
+            let name = key(&instance_labels);
+            wanted.insert((name.clone(), K_TYPE_SRV), instance_labels.clone());
+            wanted.insert((name.clone(), K_TYPE_TXT), instance_labels);
+            let Some((port, target)) = get(&name, K_TYPE_SRV).find_map(|data| srv(data)) else {
+                continue;
+            };
+            let host = labels(&target);
+            let host_key = key(&host);
+            wanted.insert((host_key.clone(), K_TYPE_A), host.clone());
+            wanted.insert((host_key.clone(), K_TYPE_AAAA), host);
+            let addresses: BTreeSet<IpAddr> = get(&host_key, K_TYPE_A)
+                .filter_map(|data| <[u8; 4]>::try_from(data.as_slice()).ok())
+                .map(|octets| IpAddr::V4(Ipv4Addr::from(octets)))
+                .chain(
+                    get(&host_key, K_TYPE_AAAA)
+                        .filter_map(|data| <[u8; 16]>::try_from(data.as_slice()).ok())
+                        .map(|octets| IpAddr::V6(Ipv6Addr::from(octets))),
+                )
+                .collect();
+            if addresses.is_empty() {
+                continue;
+            }
+            resolved.insert(
+                instance,
+                Resolved {
+                    hostname: target,
+                    port,
+                    addresses,
+                    properties: get(&name, K_TYPE_TXT)
+                        .next()
+                        .map(|data| txt(data))
+                        .unwrap_or_default(),
+                },
+            );
+        }
+
+        // Nobody listening anymore only means the browse is being stopped
+        for gone in self.found.difference(&found) {
+            let _ = self.tx.send(ServiceEvent::ServiceRemoved(
+                self.query.clone(),
+                gone.clone(),
+            ));
+        }
+        for new in found.difference(&self.found) {
+            let _ = self
+                .tx
+                .send(ServiceEvent::ServiceFound(self.query.clone(), new.clone()));
+        }
+        for (instance, resolution) in resolved.iter() {
+            if self.resolved.get(instance) == Some(resolution) {
+                continue;
+            }
+            let addresses: Vec<_> = resolution.addresses.iter().copied().collect();
+            match ServiceInfo::new(
+                &self.query,
+                instance,
+                &resolution.hostname,
+                addresses.as_slice(),
+                resolution.port,
+                resolution.properties.as_slice(),
+            ) {
+                Ok(info) => {
+                    let _ = self.tx.send(ServiceEvent::ServiceResolved(info));
+                }
+                Err(e) => tracing::warn!("Skipping {instance}.{}: {e}", self.query),
+            }
+        }
+        self.found = found;
+        self.resolved = resolved;
+        self.wanted = wanted;
+    }
+}
+
+/// A subscription and how many browses need it.
+struct Subscription {
+    /// Of the SUBSCRIBE request, which an UNSUBSCRIBE refers to
+    id: u16,
+    users: usize,
+}
+
+enum Command {
+    Browse(String, Sender<ServiceEvent>),
+    StopBrowse(String),
+    Shutdown,
+}
+
+/// A DSO session with a DNS Push server, subscribing to what the browses
+/// need and keeping the pushed records.
+struct Session {
+    server: String,
+    stream: StreamOwned<ClientConnection, TcpStream>,
+    /// Read but not yet a whole message
+    buf: Vec<u8>,
+    next_id: u16,
+    keepalive: Duration,
+    last_sent: Instant,
+    subscriptions: HashMap<Question, Subscription>,
+    records: HashMap<Question, BTreeSet<Vec<u8>>>,
+    browses: Vec<Browse>,
+}
+
+impl Session {
+    fn send(&mut self, id: u16, tlvs: &[(u16, &[u8])]) -> anyhow::Result<()> {
+        self.stream
+            .write_all(&message(id, tlvs))
+            .and_then(|()| self.stream.flush())
+            .with_context(|| format!("Failed to write to {}", self.server))?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    /// A request ID, never 0 which is for unidirectional messages.
+    fn request_id(&mut self) -> u16 {
+        self.next_id = self.next_id.checked_add(1).unwrap_or(1);
+        self.next_id
+    }
+
+    fn subscribe(&mut self, question: &Question, labels: &[String]) -> anyhow::Result<()> {
+        if let Some(subscription) = self.subscriptions.get_mut(question) {
+            subscription.users += 1;
+            return Ok(());
+        }
+        let id = self.request_id();
+        let labels: Vec<_> = labels.iter().map(String::as_str).collect();
+        let mut data = Vec::new();
+        packet::write_name(&mut data, &labels);
+        data.extend_from_slice(&question.1.to_be_bytes());
+        data.extend_from_slice(&K_CLASS_IN.to_be_bytes());
+        self.send(id, &[(K_TLV_SUBSCRIBE, &data)])?;
+        tracing::debug!(
+            "Subscribed to {} {}",
+            question.0,
+            packet::type_name(question.1)
+        );
+        self.subscriptions
+            .insert(question.clone(), Subscription { id, users: 1 });
+        Ok(())
+    }
+
+    fn unsubscribe(&mut self, question: &Question) -> anyhow::Result<()> {
+        let Some(subscription) = self.subscriptions.get_mut(question) else {
+            return Ok(());
+        };
+        subscription.users -= 1;
+        if subscription.users > 0 {
+            return Ok(());
+        }
+        let id = subscription.id;
+        self.subscriptions.remove(question);
+        self.records.remove(question);
+        self.send(0, &[(K_TLV_UNSUBSCRIBE, &id.to_be_bytes())])
+    }
+
+    /// Let every browse report what changed, and subscribe to what they
+    /// need now, and only that.
+    fn refresh(&mut self) -> anyhow::Result<()> {
+        let mut browses = std::mem::take(&mut self.browses);
+        let result = browses.iter_mut().try_for_each(|browse| {
+            // A browse finds more to subscribe to as the records come in,
+            // e.g. the addresses once the SRV record is known
+            let before = std::mem::take(&mut browse.wanted);
+            browse.update(&self.records);
+            for (question, labels) in browse.wanted.iter() {
+                if !before.contains_key(question) {
+                    self.subscribe(question, labels)?;
+                }
+            }
+            for question in before.keys() {
+                if !browse.wanted.contains_key(question) {
+                    self.unsubscribe(question)?;
+                }
+            }
+            anyhow::Ok(())
+        });
+        self.browses = browses;
+        result
+    }
+
+    fn stop_browse(&mut self, query: &str) -> anyhow::Result<()> {
+        let Some(index) = self.browses.iter().position(|browse| browse.query == query) else {
+            return Ok(());
+        };
+        let browse = self.browses.remove(index);
+        for question in browse.wanted.keys() {
+            self.unsubscribe(question)?;
+        }
+        let _ = browse.tx.send(ServiceEvent::SearchStopped(browse.query));
+        Ok(())
+    }
+
+    /// Store the records of a PUSH TLV, the subscribed ones only.
+    fn apply(&mut self, data: &[u8]) {
+        let Some(records) = pushed(data) else {
+            tracing::warn!("Skipping a malformed push from {}", self.server);
+            return;
+        };
+        for (name, record_type, ttl, data) in records {
+            let question = (name.to_lowercase(), record_type);
+            match ttl {
+                K_TTL_REMOVE => {
+                    if let Some(records) = self.records.get_mut(&question) {
+                        records.remove(data);
+                    }
+                }
+                K_TTL_REMOVE_ALL if record_type == K_TYPE_ANY => {
+                    self.records.retain(|(name, _), _| *name != question.0);
+                }
+                K_TTL_REMOVE_ALL => {
+                    self.records.remove(&question);
+                }
+                _ if self.subscriptions.contains_key(&question) => {
+                    self.records
+                        .entry(question)
+                        .or_default()
+                        .insert(data.to_vec());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn handle(&mut self, message: &[u8]) -> anyhow::Result<()> {
+        let (Some(id), Some(flags)) = (packet::u16_at(message, 0), packet::u16_at(message, 2))
+        else {
+            return Ok(());
+        };
+        if (flags >> 11) & 0xf != K_OPCODE_DSO {
+            return Ok(());
+        }
+        let rcode = flags & 0xf;
+        if flags & 0x8000 != 0 {
+            if rcode == K_RCODE_DSOTYPENI {
+                anyhow::bail!("{} doesn't support DNS Push", self.server);
+            }
+            if rcode != 0 {
+                let question = self
+                    .subscriptions
+                    .iter()
+                    .find(|(_, subscription)| subscription.id == id)
+                    .map(|(question, _)| question);
+                match question {
+                    Some((name, record_type)) => tracing::warn!(
+                        "{} refused the subscription to {name} {}: RCODE {rcode}",
+                        self.server,
+                        packet::type_name(*record_type)
+                    ),
+                    None => tracing::warn!("{} refused a request: RCODE {rcode}", self.server),
+                }
+            }
+        }
+        let mut pushed = false;
+        for (tlv, data) in tlvs(message) {
+            match tlv {
+                K_TLV_PUSH => {
+                    self.apply(data);
+                    pushed = true;
+                }
+                K_TLV_KEEPALIVE => {
+                    if let Some(interval) = keepalive_interval(data) {
+                        self.keepalive = interval;
+                    }
+                }
+                K_TLV_RETRY_DELAY => anyhow::bail!("{} ended the session", self.server),
+                _ => {}
+            }
+        }
+        if pushed {
+            self.refresh()?;
+        }
+        Ok(())
+    }
+
+    /// Subscribe as the commands come, until shut down.
+    ///
+    /// Pushed records don't expire while subscribed to, but the model
+    /// expires instances from their TTL: the resolved instances are
+    /// reported again every hour, like a responder would refresh them.
+    fn run(mut self, commands: Receiver<Command>) -> anyhow::Result<()> {
+        let mut reannounced = Instant::now();
+        let mut chunk = [0; 4096];
+        loop {
+            loop {
+                match commands.try_recv() {
+                    Ok(Command::Browse(query, tx)) => {
+                        self.stop_browse(&query)?;
+                        let _ = tx.send(ServiceEvent::SearchStarted(query.clone()));
+                        self.browses.push(Browse {
+                            query,
+                            tx,
+                            wanted: BTreeMap::new(),
+                            found: BTreeSet::new(),
+                            resolved: HashMap::new(),
+                        });
+                        self.refresh()?;
+                    }
+                    Ok(Command::StopBrowse(query)) => self.stop_browse(&query)?,
+                    Ok(Command::Shutdown) | Err(TryRecvError::Disconnected) => {
+                        self.stream.conn.send_close_notify();
+                        let _ = self.stream.flush();
+                        return Ok(());
+                    }
+                    Err(TryRecvError::Empty) => break,
+                }
+            }
+            if self.last_sent.elapsed() >= self.keepalive {
+                let id = self.request_id();
+                let mut data = Vec::new();
+                // Inactivity timeout, then keepalive interval, in milliseconds
+                data.extend_from_slice(&(K_KEEPALIVE_INTERVAL.as_millis() as u32).to_be_bytes());
+                data.extend_from_slice(&(self.keepalive.as_millis() as u32).to_be_bytes());
+                self.send(id, &[(K_TLV_KEEPALIVE, &data)])?;
+            }
+            if reannounced.elapsed() >= K_REANNOUNCE {
+                for browse in self.browses.iter_mut() {
+                    browse.resolved.clear();
+                }
+                self.refresh()?;
+                reannounced = Instant::now();
+            }
+
+            match self.stream.read(&mut chunk) {
+                Ok(0) => anyhow::bail!("{} closed the connection", self.server),
+                Ok(read) => self.buf.extend_from_slice(&chunk[..read]),
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to read from {}", self.server))
+                }
+            }
+            while let Some(len) = packet::u16_at(&self.buf, 0).map(usize::from) {
+                if self.buf.len() < 2 + len {
+                    break;
+                }
+                let message: Vec<_> = self.buf.drain(..2 + len).skip(2).collect();
+                self.handle(&message)?;
+            }
+        }
+    }
+}
+
+/// Backend subscribing to a wide-area domain through a DNS Push server
+/// (RFC 8765), which tells about every change as it happens rather than
+/// being polled.
+///
+/// Experimental. A single TLS session carries the subscriptions of every
+/// browse, and is not reestablished once lost. Browse domains are only
+/// enumerated over mDNS, which the server doesn't relay.
+pub struct Push {
+    commands: Sender<Command>,
+    /// Kept open even once the session is over, or the worker would be
+    /// woken by the closed channels over and over
+    channels: Mutex<HashMap<String, Sender<ServiceEvent>>>,
+}
+
+impl Push {
+    /// Connect to `server`, as `host` or `host:port`, over TLS.
+    pub fn connect(server: &str) -> anyhow::Result<Self> {
+        let (host, port) = split_server(server);
+        let addr = (host.as_str(), port)
+            .to_socket_addrs()
+            .with_context(|| format!("Failed to resolve {host}"))?
+            .next()
+            .with_context(|| format!("{host} has no address"))?;
+        let tcp = TcpStream::connect_timeout(&addr, K_CONNECT_TIMEOUT)
+            .with_context(|| format!("Failed to connect to {server}"))?;
+        tcp.set_read_timeout(Some(K_CONNECT_TIMEOUT))?;
+
+        let mut roots = RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let config =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()?
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+        let name = ServerName::try_from(host.clone())
+            .with_context(|| format!("Invalid server name {host}"))?;
+        let mut stream = StreamOwned::new(ClientConnection::new(Arc::new(config), name)?, tcp);
+        // Handshake right away, so that browsing fails early with a wrong server
+        while stream.conn.is_handshaking() {
+            stream
+                .conn
+                .complete_io(&mut stream.sock)
+                .with_context(|| format!("Failed to handshake with {server}"))?;
+        }
+        stream.sock.set_read_timeout(Some(K_POLL))?;
+        tracing::info!("Connected to the DNS Push server {server}");
+
+        let session = Session {
+            server: server.to_string(),
+            stream,
+            buf: Vec::new(),
+            next_id: 0,
+            keepalive: K_KEEPALIVE_INTERVAL,
+            last_sent: Instant::now(),
+            subscriptions: HashMap::new(),
+            records: HashMap::new(),
+            browses: Vec::new(),
+        };
+        let (commands, rx) = flume::unbounded();
+        let server = server.to_string();
+        std::thread::spawn(move || {
+            let _span = tracing::span!(tracing::Level::TRACE, "DNS Push session").entered();
+            if let Err(e) = session.run(rx) {
+                tracing::error!("The DNS Push session with {server} is over: {e:#}");
+            }
+        });
+        Ok(Self {
+            commands,
+            channels: Mutex::new(HashMap::new()),
+        })
+    }
+}
+
+impl Backend for Push {
+    fn browse(&self, service_type: &str) -> anyhow::Result<Receiver<ServiceEvent>> {
+        let (tx, rx) = flume::unbounded();
+        if !service_type.ends_with(domain::K_LOCAL_DOMAIN) {
+            self.commands
+                .send(Command::Browse(service_type.to_string(), tx.clone()))
+                .map_err(|_| anyhow::anyhow!("The DNS Push session is over, see the log"))?;
+        }
+        self.channels.lock().insert(service_type.to_string(), tx);
+        Ok(rx)
+    }
+
+    fn stop_browse(&self, service_type: &str) -> anyhow::Result<()> {
+        self.channels.lock().remove(service_type);
+        let _ = self
+            .commands
+            .send(Command::StopBrowse(service_type.to_string()));
+        Ok(())
+    }
+
+    fn shutdown(&self) -> anyhow::Result<()> {
+        self.channels.lock().clear();
+        let _ = self.commands.send(Command::Shutdown);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A server's answer to the session's first Keepalive: an inactivity
+    /// timeout of 15 s and a keepalive interval of 60 s.
+    #[rustfmt::skip]
+    const K_KEEPALIVE: [u8; 24] = [
+        // ID, a DSO response, no question nor record
+        0x12, 0x34, 0xb0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        // Keepalive TLV of 8 bytes
+        0x00, 0x01, 0x00, 0x08, 0x00, 0x00, 0x3a, 0x98, 0x00, 0x00, 0xea, 0x60,
+    ];
+
+    /// A unidirectional PUSH of an IPP printer: its PTR, SRV and TXT records,
+    /// and the removal of another instance's PTR record.
+    const K_PUSH: &[u8] = b"\
+        \x00\x00\x30\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+        \x00\x41\x00\xdc\
+        \x04_ipp\x04_tcp\x04home\x04arpa\x00\x00\x0c\x00\x01\x00\x00\x11\x94\x00\x18\
+        \x02lp\x04_ipp\x04_tcp\x04home\x04arpa\x00\
+        \x02lp\x04_ipp\x04_tcp\x04home\x04arpa\x00\x00\x21\x00\x01\x00\x00\x00\x78\x00\x14\
+        \x00\x00\x00\x00\x02\x77\x02lp\x04home\x04arpa\x00\
+        \x02lp\x04_ipp\x04_tcp\x04home\x04arpa\x00\x00\x10\x00\x01\x00\x00\x11\x94\x00\x15\
+        \x0crp=ipp/print\x07Color=T\
+        \x04_ipp\x04_tcp\x04home\x04arpa\x00\x00\x0c\x00\x01\xff\xff\xff\xff\x00\x19\
+        \x03old\x04_ipp\x04_tcp\x04home\x04arpa\x00";
+
+    #[test]
+    fn keepalives() {
+        let tlvs = tlvs(&K_KEEPALIVE);
+        assert_eq!(tlvs, [(K_TLV_KEEPALIVE, &K_KEEPALIVE[16..])]);
+        assert_eq!(keepalive_interval(tlvs[0].1), Some(Duration::from_secs(60)));
+        // Too frequent keepalives are spaced out
+        assert_eq!(
+            keepalive_interval(&[0x00, 0x00, 0x3a, 0x98, 0x00, 0x00, 0x03, 0xe8]),
+            Some(K_MIN_KEEPALIVE_INTERVAL)
+        );
+        assert_eq!(keepalive_interval(&[0x00, 0x00, 0x3a, 0x98]), None);
+    }
+
+    #[test]
+    fn messages_are_read_back() {
+        let framed = message(7, &[(K_TLV_KEEPALIVE, &K_KEEPALIVE[16..])]);
+        assert_eq!(packet::u16_at(&framed, 0), Some(framed.len() as u16 - 2));
+        let message = &framed[2..];
+        assert_eq!(packet::u16_at(message, 0), Some(7));
+        assert_eq!(packet::u16_at(message, 2), Some(K_OPCODE_DSO << 11));
+        assert_eq!(tlvs(message), [(K_TLV_KEEPALIVE, &K_KEEPALIVE[16..])]);
+    }
+
+    #[test]
+    fn pushes() {
+        let tlvs = tlvs(K_PUSH);
+        assert_eq!(tlvs.len(), 1);
+        assert_eq!(tlvs[0].0, K_TLV_PUSH);
+        let records = pushed(tlvs[0].1).unwrap();
+        let summary: Vec<_> = records
+            .iter()
+            .map(|(name, record_type, ttl, _)| (name.as_str(), *record_type, *ttl))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("_ipp._tcp.home.arpa.", K_TYPE_PTR, 4500),
+                ("lp._ipp._tcp.home.arpa.", K_TYPE_SRV, 120),
+                ("lp._ipp._tcp.home.arpa.", K_TYPE_TXT, 4500),
+                ("_ipp._tcp.home.arpa.", K_TYPE_PTR, K_TTL_REMOVE),
+            ]
+        );
+        assert_eq!(
+            packet::read_name(records[0].3, 0),
+            Some(("lp._ipp._tcp.home.arpa.".to_string(), 24))
+        );
+        assert_eq!(srv(records[1].3), Some((631, "lp.home.arpa.".to_string())));
+        assert_eq!(
+            txt(records[2].3),
+            [
+                ("rp".to_string(), "ipp/print".to_string()),
+                ("Color".to_string(), "T".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_tlvs() {
+        // The TLV is dropped rather than read past its end
+        assert!(tlvs(&K_PUSH[..K_PUSH.len() - 1]).is_empty());
+        assert!(tlvs(&K_KEEPALIVE[..14]).is_empty());
+        assert!(tlvs(&K_KEEPALIVE[..12]).is_empty());
+        // A record cut short spoils the whole push
+        let data = &K_PUSH[16..];
+        assert_eq!(pushed(&data[..data.len() - 1]), None);
+        assert_eq!(pushed(&data[..60]), None);
+        assert_eq!(pushed(&[]), Some(Vec::new()));
+        assert_eq!(srv(&[0x00, 0x00, 0x00, 0x00, 0x02]), None);
+    }
+
+    #[test]
+    fn truncated_txt_records() {
+        assert!(txt(b"\x0crp=ipp").is_empty());
+        assert_eq!(
+            txt(b"\x07Color=T\x0crp=ipp"),
+            [("Color".to_string(), "T".to_string())]
+        );
+        // Empty strings and keys without a value
+        assert_eq!(
+            txt(b"\x00\x06duplex"),
+            [("duplex".to_string(), String::new())]
+        );
+    }
+
+    #[test]
+    fn servers() {
+        let server = |host: &str, port| (host.to_string(), port);
+        assert_eq!(
+            split_server("dns.example.com"),
+            server("dns.example.com", 853)
+        );
+        assert_eq!(
+            split_server("dns.example.com:5353"),
+            server("dns.example.com", 5353)
+        );
+        assert_eq!(split_server("192.0.2.1:853"), server("192.0.2.1", 853));
+        assert_eq!(
+            split_server("[2001:db8::1]:8853"),
+            server("2001:db8::1", 8853)
+        );
+        assert_eq!(split_server("[2001:db8::1]"), server("2001:db8::1", 853));
+        assert_eq!(split_server("2001:db8::1"), server("2001:db8::1", 853));
+    }
+}
@@ -0,0 +1,202 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+use regex::Regex;
+
+use crate::colors::theme;
+use crate::domain;
+use crate::widget::DiscoveryWidget;
+
+/// Templates offered when the config doesn't list any.
+pub const K_DEFAULT_TEMPLATES: [&str; 2] = ["_{service}._tcp.local.", "_{service}._udp.local."];
+
+/// Names of the `{variables}` in `template`, in order of appearance.
+fn variables(template: &str) -> Vec<String> {
+    let regex = Regex::new(r"\{(\w+)\}").expect("The variable regex is valid");
+    let mut variables: Vec<String> = Vec::new();
+    for captures in regex.captures_iter(template) {
+        if !variables.iter().any(|variable| *variable == captures[1]) {
+            variables.push(captures[1].to_string());
+        }
+    }
+    variables
+}
+
+/// A template whose variables are being prompted for.
+#[derive(Debug)]
+struct Filling {
+    template: String,
+    variables: Vec<String>,
+    values: Vec<String>,
+    input: String,
+}
+
+impl Filling {
+    fn query(&self) -> String {
+        self.variables
+            .iter()
+            .zip(self.values.iter())
+            .fold(self.template.clone(), |query, (variable, value)| {
+                query.replace(&format!("{{{variable}}}"), value)
+            })
+    }
+}
+
+/// Modal picker of query templates and recently used queries.
+///
+/// Picking a template prompts for each of its `{variables}` in turn. Like
+/// [`crate::confirm::Confirm`], the owner takes the [`QueryPicker::answer`]
+/// once the picker is done.
+#[derive(Debug)]
+pub struct QueryPicker {
+    /// Entries and whether they are templates
+    entries: Vec<(String, bool)>,
+    selected: usize,
+    filling: Option<Box<Filling>>,
+    answer: Option<Option<String>>,
+}
+
+impl QueryPicker {
+    pub fn new<'a>(
+        templates: impl IntoIterator<Item = &'a String>,
+        recent: impl IntoIterator<Item = &'a String>,
+    ) -> Self {
+        Self {
+            entries: templates
+                .into_iter()
+                .map(|template| (template.clone(), true))
+                .chain(recent.into_iter().map(|query| (query.clone(), false)))
+                .collect(),
+            selected: 0,
+            filling: None,
+            answer: None,
+        }
+    }
+
+    /// The fully qualified query picked.
+    ///
+    /// Returns `Err(self)` while still picking.
+    pub fn answer(self) -> Result<Option<String>, Self> {
+        match self.answer {
+            Some(answer) => Ok(answer),
+            None => Err(self),
+        }
+    }
+
+    fn pick(&mut self) {
+        let Some((entry, _)) = self.entries.get(self.selected) else {
+            return;
+        };
+        let variables = variables(entry);
+        if variables.is_empty() {
+            self.answer = Some(Some(domain::qualify(entry)));
+        } else {
+            self.filling = Some(Box::new(Filling {
+                template: entry.clone(),
+                variables,
+                values: Vec::new(),
+                input: String::new(),
+            }));
+        }
+    }
+}
+
+impl DiscoveryWidget for QueryPicker {
+    fn title(&self) -> String {
+        "Query".to_string()
+    }
+
+    fn controls(&self) -> String {
+        match self.filling {
+            Some(_) => "Type the value, ↵ to continue, Esc to go back".to_string(),
+            None => "Use ↓↑ to choose, ↵ to browse the query, Esc to cancel".to_string(),
+        }
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        if let Some(filling) = self.filling.as_mut() {
+            match key_event.code {
+                KeyCode::Char(c) => filling.input.push(c),
+                KeyCode::Backspace => {
+                    filling.input.pop();
+                }
+                KeyCode::Enter if !filling.input.is_empty() => {
+                    filling.values.push(std::mem::take(&mut filling.input));
+                    if filling.values.len() == filling.variables.len() {
+                        self.answer = Some(Some(domain::qualify(&filling.query())));
+                    }
+                }
+                KeyCode::Esc => self.filling = None,
+                _ => {}
+            }
+            return;
+        }
+
+        match key_event.code {
+            KeyCode::Down if !self.entries.is_empty() => {
+                self.selected = (self.selected + 1) % self.entries.len()
+            }
+            KeyCode::Up if !self.entries.is_empty() => {
+                self.selected = (self.selected + self.entries.len() - 1) % self.entries.len()
+            }
+            KeyCode::Enter => self.pick(),
+            KeyCode::Esc => self.answer = Some(None),
+            _ => {}
+        }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(theme().selected_fg).bold())
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .padding(Padding::horizontal(1))
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        let inner_area = block.inner(area);
+        block.render(area, buf);
+
+        if let Some(filling) = self.filling.as_ref() {
+            let mut lines = vec![Line::from(filling.template.clone()).bold(), Line::default()];
+            for (index, variable) in filling.variables.iter().enumerate() {
+                let value = match filling.values.get(index) {
+                    Some(value) => Span::raw(value.clone()),
+                    None if index == filling.values.len() => {
+                        Span::raw(format!("{}▏", filling.input)).fg(theme().selected_fg)
+                    }
+                    None => Span::raw(""),
+                };
+                lines.push(Line::from(vec![
+                    Span::styled(format!("{variable}: "), Style::new().bold().light_cyan()),
+                    value,
+                ]));
+            }
+            Paragraph::new(lines).render(inner_area, buf);
+            return;
+        }
+
+        let items: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(entry, template)| {
+                if *template {
+                    ListItem::new(Line::from(vec![
+                        Span::raw(entry.clone()),
+                        " template".dark_gray(),
+                    ]))
+                } else {
+                    ListItem::new(entry.clone())
+                }
+            })
+            .collect();
+        let list = List::new(items)
+            .highlight_style(Style::new().bold().reversed().fg(theme().selected_fg));
+        StatefulWidget::render(
+            list,
+            inner_area,
+            buf,
+            &mut ListState::default().with_selected(Some(self.selected)),
+        );
+    }
+}
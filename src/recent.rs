@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+const K_MAX_RECENT: usize = 10;
+
+/// Recently used entries, e.g. queries, most recent first.
+///
+/// Stored as a plain list, one entry per line, so that they survive restarts.
+/// The default one is only kept in memory.
+#[derive(Debug, Default)]
+pub struct Recent {
+    path: Option<PathBuf>,
+    entries: Vec<String>,
+}
+
+impl Recent {
+    /// Load the entries stored in `file_name` under
+    /// `$XDG_DATA_HOME/discovery-rs` or the platform equivalent, starting
+    /// afresh if there are none yet.
+    pub fn load(file_name: &str) -> Self {
+        let path = dirs::data_dir().map(|dir| dir.join("discovery-rs").join(file_name));
+        let entries = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|contents| {
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { path, entries }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter()
+    }
+
+    /// Move `entry` to the top and store the list.
+    pub fn remember(&mut self, entry: &str) -> anyhow::Result<()> {
+        self.entries.retain(|recent| recent != entry);
+        self.entries.insert(0, entry.to_string());
+        self.entries.truncate(K_MAX_RECENT);
+        let Some(path) = self.path.as_ref() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(path, self.entries.join("\n") + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
@@ -0,0 +1,159 @@
+use std::io::Write;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use clap_derive::Args;
+use flume::RecvTimeoutError;
+use mdns_sd::{IfKind, ServiceEvent, ServiceInfo};
+
+use crate::backend::{self, BackendKind};
+use crate::domain;
+use crate::error::Error;
+use crate::schema::{self, Host, Instance};
+use crate::units;
+use crate::whois;
+use crate::zone::{self, Zones};
+
+#[derive(Args, Debug)]
+pub struct ResolveOpts {
+    /// Instance fullname, e.g. "My NAS._ssh._tcp.local.", or hostname, e.g. nas.local
+    name: String,
+    #[arg(long, action)]
+    /// Print the whole record as JSON rather than an address
+    json: bool,
+    #[arg(long, action)]
+    /// Print every address, one per line, rather than the preferred one
+    all: bool,
+    #[arg(long, value_parser = units::duration, default_value = "3s")]
+    /// How long to wait for an answer, e.g. 3s or 1m
+    timeout: Duration,
+}
+
+/// Service type of the instance `fullname`, or `None` for a hostname.
+///
+/// The instance label may contain dots, so the type is found from the
+/// protocol label, e.g. `_tcp`, backwards.
+fn service_type(fullname: &str) -> Option<String> {
+    let protocol = ["._tcp", "._udp"]
+        .iter()
+        .filter_map(|protocol| fullname.find(protocol))
+        .min()?;
+    let start = fullname[..protocol].rfind("._")? + 1;
+    Some(domain::qualify(&fullname[start..]))
+}
+
+/// Usable addresses first: IPv4, then global and link-local IPv6, and
+/// self-assigned IPv4 last.
+fn preference(addr: &IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(v4) if v4.is_link_local() => 3,
+        IpAddr::V4(_) => 0,
+        IpAddr::V6(v6) if zone::link_local(v6) => 2,
+        IpAddr::V6(_) => 1,
+    }
+}
+
+fn unresolved(name: &str, timeout: Duration) -> Error {
+    Error::Other(anyhow::anyhow!(
+        "{name} didn't resolve within {}",
+        humantime::format_duration(timeout)
+    ))
+}
+
+/// Browse the type of `fullname` until the instance resolves.
+fn resolve_instance(
+    fullname: &str,
+    service_type: &str,
+    kind: BackendKind,
+    interfaces: Vec<IfKind>,
+    ipv6_only: bool,
+    timeout: Duration,
+) -> Result<Option<ServiceInfo>, Error> {
+    let backend = backend::start(kind, interfaces, ipv6_only).map_err(Error::daemon)?;
+    let events = backend.browse(service_type).map_err(Error::daemon)?;
+    let deadline = Instant::now() + timeout;
+    let resolved = loop {
+        match events.recv_deadline(deadline) {
+            Ok(ServiceEvent::ServiceResolved(info))
+                if info.get_fullname().eq_ignore_ascii_case(fullname) =>
+            {
+                break Some(info)
+            }
+            Ok(_) => {}
+            Err(RecvTimeoutError::Timeout) => break None,
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(Error::Daemon(anyhow::anyhow!("Browsing has stopped early")))
+            }
+        }
+    };
+    let _ = backend.stop_browse(service_type);
+    backend.shutdown().map_err(Error::daemon)?;
+    Ok(resolved)
+}
+
+/// Resolve a single instance or hostname without the TUI and print it.
+///
+/// Meant for shell one-liners, e.g. `ssh $(discovery-rs resolve nas.local)`:
+/// only the preferred address is printed unless asked otherwise, and nothing
+/// at all when the name doesn't resolve within the timeout, which fails.
+pub fn run(
+    opts: &ResolveOpts,
+    kind: BackendKind,
+    interfaces: Vec<IfKind>,
+    ipv6_only: bool,
+) -> Result<(), Error> {
+    let name = domain::qualify(opts.name.trim());
+    let (json, mut addresses) = match service_type(&name) {
+        Some(service_type) => {
+            let Some(info) = resolve_instance(
+                &name,
+                &service_type,
+                kind,
+                interfaces,
+                ipv6_only,
+                opts.timeout,
+            )?
+            else {
+                return Err(unresolved(&name, opts.timeout));
+            };
+            let addresses: Vec<_> = info.get_addresses().iter().copied().collect();
+            (schema::to_json(&Instance::from(&info)), addresses)
+        }
+        None => {
+            // Bare names are looked up in local.
+            let name = if name.matches('.').count() < 2 {
+                format!("{name}{}", domain::K_LOCAL_DOMAIN)
+            } else {
+                name
+            };
+            let addresses =
+                whois::addresses(&name, ipv6_only, opts.timeout).map_err(Error::daemon)?;
+            if addresses.is_empty() {
+                return Err(unresolved(&name, opts.timeout));
+            }
+            let zones = Zones::local();
+            let host = Host {
+                hostname: name,
+                addresses: addresses.iter().map(|addr| zones.display(*addr)).collect(),
+            };
+            (schema::to_json(&host), addresses)
+        }
+    };
+
+    let output = if opts.json {
+        json.map_err(|e| Error::Export(e.into()))?
+    } else {
+        addresses.sort_by_key(preference);
+        let zones = Zones::local();
+        let addresses: Vec<_> = addresses.iter().map(|addr| zones.display(*addr)).collect();
+        if opts.all {
+            addresses.join("\n")
+        } else {
+            addresses.first().cloned().unwrap_or_default()
+        }
+    };
+    writeln!(std::io::stdout(), "{output}")
+        .context("Failed to write the result")
+        .map_err(Error::Export)
+}
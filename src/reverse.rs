@@ -0,0 +1,88 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use flume::Sender;
+use parking_lot::Mutex;
+
+/// How often paused lookups check whether to resume.
+const K_PAUSE_POLL: Duration = Duration::from_millis(250);
+
+/// Outcome of a reverse (PTR) lookup.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lookup {
+    Pending,
+    Found(String),
+    /// The address has no name
+    Missing,
+}
+
+/// Reverse names of the resolved addresses, looked up in the background.
+///
+/// Lookups go through the system resolver, and so through nss-mdns where it
+/// is set up, which may take seconds. They are run one at a time on their
+/// own thread and cached for the rest of the session. They wait while
+/// `paused`.
+#[derive(Debug, Clone, Default)]
+pub struct ReverseNames {
+    names: Arc<Mutex<HashMap<IpAddr, Lookup>>>,
+    /// `None` for a cache nothing is looked up for
+    requests: Option<Sender<IpAddr>>,
+}
+
+impl ReverseNames {
+    pub fn spawn(paused: Arc<AtomicBool>) -> Self {
+        let names: Arc<Mutex<HashMap<IpAddr, Lookup>>> = Default::default();
+        let (requests, pending) = flume::unbounded::<IpAddr>();
+        {
+            let names = names.clone();
+            std::thread::spawn(move || {
+                for addr in pending.iter() {
+                    while paused.load(Ordering::Relaxed) {
+                        std::thread::sleep(K_PAUSE_POLL);
+                    }
+                    let lookup = match dns_lookup::lookup_addr(&addr) {
+                        // The resolver echoes the address when it has no name
+                        Ok(name) if name != addr.to_string() => Lookup::Found(name),
+                        _ => Lookup::Missing,
+                    };
+                    tracing::debug!("Reverse lookup of {addr}: {lookup:?}");
+                    names.lock().insert(addr, lookup);
+                }
+            });
+        }
+        Self {
+            names,
+            requests: Some(requests),
+        }
+    }
+
+    /// Look `addr` up, unless it has been already.
+    pub fn request(&self, addr: IpAddr) {
+        let Some(requests) = self.requests.as_ref() else {
+            return;
+        };
+        if let Entry::Vacant(entry) = self.names.lock().entry(addr) {
+            entry.insert(Lookup::Pending);
+            let _ = requests.send(addr);
+        }
+    }
+
+    pub fn get(&self, addr: &IpAddr) -> Lookup {
+        self.names
+            .lock()
+            .get(addr)
+            .cloned()
+            .unwrap_or(Lookup::Pending)
+    }
+}
+
+/// Whether `name` and `hostname` are the same, regardless of case and the
+/// trailing dot.
+pub fn same_host(name: &str, hostname: &str) -> bool {
+    name.trim_end_matches('.')
+        .eq_ignore_ascii_case(hostname.trim_end_matches('.'))
+}
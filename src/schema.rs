@@ -0,0 +1,361 @@
+use std::collections::BTreeMap;
+
+use mdns_sd::ServiceInfo;
+use serde::{Deserialize, Serialize};
+
+use crate::zone::Zones;
+
+/// Version of the JSON documents written, e.g. by `export` and `census`.
+///
+/// Bumped whenever a field is renamed, removed or changes meaning. Adding
+/// fields doesn't bump it, consumers are expected to ignore unknown ones.
+pub const K_SCHEMA_VERSION: u32 = 1;
+
+/// Top level of every document, `body` stamped with the schema version.
+///
+/// Serialized as JSON by [`to_json`] and [`to_json_line`], and as is by the
+/// other formats, e.g. the TOML export.
+#[derive(Debug, Serialize)]
+pub struct Document<'a, T> {
+    schema_version: u32,
+    #[serde(flatten)]
+    body: &'a T,
+}
+
+impl<'a, T: Serialize> Document<'a, T> {
+    pub fn new(body: &'a T) -> Self {
+        Self {
+            schema_version: K_SCHEMA_VERSION,
+            body,
+        }
+    }
+}
+
+/// Serialize `body` as a pretty printed, versioned JSON document.
+pub fn to_json<T: Serialize>(body: &T) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&Document::new(body))
+}
+
+/// Serialize `body` as a single line, versioned JSON document, e.g. for NDJSON.
+pub fn to_json_line<T: Serialize>(body: &T) -> serde_json::Result<String> {
+    serde_json::to_string(&Document::new(body))
+}
+
+/// A resolved instance.
+#[derive(Debug, Serialize)]
+pub struct Instance {
+    pub fullname: String,
+    pub hostname: String,
+    pub port: u16,
+    /// TTL of the address records, in seconds
+    pub host_ttl: u32,
+    /// TTL of the SRV, TXT and PTR records, in seconds
+    pub other_ttl: u32,
+    /// Link-local IPv6 addresses carry their zone, e.g. `fe80::1%eth0`
+    pub addresses: Vec<String>,
+    pub properties: BTreeMap<String, String>,
+    /// Values of the computed columns of the config, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub computed: BTreeMap<String, String>,
+}
+
+impl From<&ServiceInfo> for Instance {
+    fn from(info: &ServiceInfo) -> Self {
+        let mut addresses: Vec<_> = info.get_addresses().iter().copied().collect();
+        addresses.sort();
+        let zones = Zones::local();
+        let addresses = addresses
+            .into_iter()
+            .map(|addr| zones.display(addr))
+            .collect();
+        Self {
+            fullname: info.get_fullname().to_string(),
+            hostname: info.get_hostname().to_string(),
+            port: info.get_port(),
+            host_ttl: info.get_host_ttl(),
+            other_ttl: info.get_other_ttl(),
+            addresses,
+            properties: info
+                .get_properties()
+                .iter()
+                .map(|property| (property.key().to_string(), property.val_str().to_string()))
+                .collect(),
+            computed: BTreeMap::new(),
+        }
+    }
+}
+
+/// A hostname resolved on its own, e.g. by `resolve`.
+#[derive(Debug, Serialize)]
+pub struct Host {
+    pub hostname: String,
+    /// In the order they were received, zones included like [`Instance`]'s
+    pub addresses: Vec<String>,
+}
+
+/// The discovery state at a point in time, e.g. of an export.
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+    /// Unix timestamp of the snapshot
+    pub exported: u64,
+    /// Instances by service type
+    pub types: BTreeMap<String, Vec<Instance>>,
+    /// Browse domains, `local.` and the wide-area ones discovered
+    pub domains: Vec<String>,
+    /// Every host resolved, by hostname
+    pub hosts: BTreeMap<String, HostState>,
+}
+
+/// Index of an export split per service type, listing the file of every
+/// type.
+#[derive(Debug, Serialize)]
+pub struct ExportIndex {
+    /// Unix timestamp of the snapshot
+    pub exported: u64,
+    pub domains: Vec<String>,
+    pub types: Vec<IndexEntry>,
+}
+
+/// A service type of an [`ExportIndex`].
+#[derive(Debug, Serialize)]
+pub struct IndexEntry {
+    pub service_type: String,
+    /// Relative to the index
+    pub file: String,
+    pub instances: usize,
+}
+
+/// A host as known at the time of a [`Snapshot`].
+#[derive(Debug, Clone, Serialize)]
+pub struct HostState {
+    /// Hardware model advertised through `_device-info._tcp`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Unix timestamps of the first and last time it was resolved
+    pub first_seen: u64,
+    pub last_seen: u64,
+}
+
+/// Consolidated result of a census run.
+#[derive(Debug, Serialize)]
+pub struct Census {
+    /// Unix timestamp of the start of the run
+    pub started: u64,
+    pub duration_ms: u64,
+    pub types: Vec<TypeCensus>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TypeCensus {
+    pub service_type: String,
+    pub instances: Vec<Instance>,
+    /// Why browsing the type failed, the instances are incomplete then
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Statistics of a session, e.g. written by `--summary`.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    /// Unix timestamp of the start of the session
+    pub started: u64,
+    pub duration_ms: u64,
+    pub service_types: usize,
+    /// Distinct instances resolved
+    pub instances: usize,
+    /// mDNS events received
+    pub events: u64,
+    /// Most events received within a second
+    pub peak_events_per_second: u64,
+    /// Local interfaces the resolved addresses are on
+    pub interfaces: Vec<String>,
+}
+
+/// An entry of the event log, one per line of an NDJSON event log.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Unix timestamp in milliseconds
+    pub time: u64,
+    /// `found`, `resolved`, `updated` or `removed`
+    pub event: String,
+    /// Why an instance was removed, `goodbye` or `expired`, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub service_type: String,
+    /// Instance fullname, or the service type found by a type enumeration
+    pub name: String,
+    /// TXT entries changed by an update
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<TxtChange>,
+    /// Host the event was seen from, when streamed with `--stream-to`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probe: Option<String>,
+}
+
+/// A TXT entry changed by a re-resolve, added or removed when `old` or `new` is missing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TxtChange {
+    pub key: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl std::fmt::Display for TxtChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} → {}",
+            self.key,
+            self.old.as_deref().unwrap_or("∅"),
+            self.new.as_deref().unwrap_or("∅")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            exported: 1_792_152_000,
+            types: BTreeMap::from([(
+                "_ipp._tcp.local.".to_string(),
+                vec![Instance {
+                    fullname: "Office Printer._ipp._tcp.local.".to_string(),
+                    hostname: "printer.local.".to_string(),
+                    port: 631,
+                    host_ttl: 120,
+                    other_ttl: 4500,
+                    addresses: vec!["192.168.2.10".to_string(), "fe80::1%eth0".to_string()],
+                    properties: BTreeMap::from([("rp".to_string(), "ipp/print".to_string())]),
+                    computed: BTreeMap::new(),
+                }],
+            )]),
+            domains: vec!["local.".to_string()],
+            hosts: BTreeMap::from([(
+                "printer.local.".to_string(),
+                HostState {
+                    model: Some("LaserJet".to_string()),
+                    first_seen: 1_792_151_000,
+                    last_seen: 1_792_151_900,
+                },
+            )]),
+        }
+    }
+
+    #[test]
+    fn snapshot_document() {
+        assert_eq!(
+            to_json(&snapshot()).unwrap(),
+            r#"{
+  "schema_version": 1,
+  "exported": 1792152000,
+  "types": {
+    "_ipp._tcp.local.": [
+      {
+        "fullname": "Office Printer._ipp._tcp.local.",
+        "hostname": "printer.local.",
+        "port": 631,
+        "host_ttl": 120,
+        "other_ttl": 4500,
+        "addresses": [
+          "192.168.2.10",
+          "fe80::1%eth0"
+        ],
+        "properties": {
+          "rp": "ipp/print"
+        }
+      }
+    ]
+  },
+  "domains": [
+    "local."
+  ],
+  "hosts": {
+    "printer.local.": {
+      "model": "LaserJet",
+      "first_seen": 1792151000,
+      "last_seen": 1792151900
+    }
+  }
+}"#
+        );
+    }
+
+    #[test]
+    fn computed_columns_are_listed_when_configured() {
+        let mut snapshot = snapshot();
+        let instance = &mut snapshot.types.get_mut("_ipp._tcp.local.").unwrap()[0];
+        instance
+            .computed
+            .insert("floor".to_string(), "2".to_string());
+        let json: serde_json::Value = serde_json::from_str(&to_json(instance).unwrap()).unwrap();
+        assert_eq!(json["schema_version"], 1);
+        assert_eq!(json["computed"], serde_json::json!({"floor": "2"}));
+    }
+
+    #[test]
+    fn log_entry_lines() {
+        let removed = LogEntry {
+            time: 1_792_152_000_123,
+            event: "removed".to_string(),
+            reason: Some("goodbye".to_string()),
+            service_type: "_ipp._tcp.local.".to_string(),
+            name: "Office Printer._ipp._tcp.local.".to_string(),
+            changes: Vec::new(),
+            probe: None,
+        };
+        assert_eq!(
+            to_json_line(&removed).unwrap(),
+            r#"{"schema_version":1,"time":1792152000123,"event":"removed","reason":"goodbye","service_type":"_ipp._tcp.local.","name":"Office Printer._ipp._tcp.local."}"#
+        );
+
+        let updated = LogEntry {
+            event: "updated".to_string(),
+            reason: None,
+            changes: vec![TxtChange {
+                key: "rp".to_string(),
+                old: Some("ipp/print".to_string()),
+                new: None,
+            }],
+            probe: Some("probe-1".to_string()),
+            ..removed
+        };
+        assert_eq!(
+            to_json_line(&updated).unwrap(),
+            r#"{"schema_version":1,"time":1792152000123,"event":"updated","service_type":"_ipp._tcp.local.","name":"Office Printer._ipp._tcp.local.","changes":[{"key":"rp","old":"ipp/print","new":null}],"probe":"probe-1"}"#
+        );
+    }
+
+    #[test]
+    fn toml_document() {
+        let toml = toml::to_string_pretty(&Document::new(&snapshot())).unwrap();
+        assert_eq!(
+            toml,
+            r#"schema_version = 1
+exported = 1792152000
+domains = ["local."]
+
+[[types."_ipp._tcp.local."]]
+fullname = "Office Printer._ipp._tcp.local."
+hostname = "printer.local."
+port = 631
+host_ttl = 120
+other_ttl = 4500
+addresses = [
+    "192.168.2.10",
+    "fe80::1%eth0",
+]
+
+[types."_ipp._tcp.local.".properties]
+rp = "ipp/print"
+
+[hosts."printer.local."]
+model = "LaserJet"
+first_seen = 1792151000
+last_seen = 1792151900
+"#
+        );
+    }
+}
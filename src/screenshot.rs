@@ -0,0 +1,234 @@
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use ratatui::buffer::{Buffer, Cell};
+use ratatui::style::{Color, Modifier};
+
+const K_CELL_WIDTH: f32 = 8.4;
+const K_CELL_HEIGHT: f32 = 17.0;
+const K_FONT_SIZE: f32 = 14.0;
+const K_DEFAULT_FG: (u8, u8, u8) = (0xe5, 0xe5, 0xe5);
+const K_DEFAULT_BG: (u8, u8, u8) = (0x00, 0x00, 0x00);
+
+/// Save `buffer` as both an ANSI text file and an SVG image in `dir`.
+///
+/// Returns the paths of the two files.
+pub fn save(buffer: &Buffer, dir: &Path) -> anyhow::Result<(PathBuf, PathBuf)> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let ansi_path = dir.join(format!("discovery-rs-{stamp}.ans"));
+    let svg_path = dir.join(format!("discovery-rs-{stamp}.svg"));
+    std::fs::write(&ansi_path, to_ansi(buffer))
+        .with_context(|| format!("Failed to write {}", ansi_path.display()))?;
+    std::fs::write(&svg_path, to_svg(buffer))
+        .with_context(|| format!("Failed to write {}", svg_path.display()))?;
+    Ok((ansi_path, svg_path))
+}
+
+/// Rows of cells of `buffer`.
+fn rows(buffer: &Buffer) -> impl Iterator<Item = &[Cell]> {
+    buffer.content.chunks(buffer.area.width.max(1) as usize)
+}
+
+/// Render `buffer` as text with ANSI escape sequences, as `cat` would show it.
+pub fn to_ansi(buffer: &Buffer) -> String {
+    let mut ansi = String::new();
+    for row in rows(buffer) {
+        let mut current = None;
+        for cell in row {
+            let style = (cell.fg, cell.bg, cell.modifier);
+            if current != Some(style) {
+                ansi.push_str("\x1b[0");
+                for (modifier, code) in [
+                    (Modifier::BOLD, "1"),
+                    (Modifier::DIM, "2"),
+                    (Modifier::ITALIC, "3"),
+                    (Modifier::UNDERLINED, "4"),
+                    (Modifier::REVERSED, "7"),
+                    (Modifier::CROSSED_OUT, "9"),
+                ] {
+                    if cell.modifier.contains(modifier) {
+                        let _ = write!(ansi, ";{code}");
+                    }
+                }
+                sgr_color(&mut ansi, cell.fg, 30);
+                sgr_color(&mut ansi, cell.bg, 40);
+                ansi.push('m');
+                current = Some(style);
+            }
+            ansi.push_str(cell.symbol());
+        }
+        ansi.push_str("\x1b[0m\n");
+    }
+    ansi
+}
+
+/// Append the SGR parameters of `color`, `base` being 30 for the
+/// foreground and 40 for the background.
+fn sgr_color(ansi: &mut String, color: Color, base: u8) {
+    let _ = match color {
+        Color::Reset => Ok(()),
+        Color::Rgb(r, g, b) => write!(ansi, ";{};2;{r};{g};{b}", base + 8),
+        Color::Indexed(index) => write!(ansi, ";{};5;{index}", base + 8),
+        named => {
+            let index = named_index(named);
+            if index < 8 {
+                write!(ansi, ";{}", base + index)
+            } else {
+                // Bright colors live at 90 and 100
+                write!(ansi, ";{}", base + 60 + index - 8)
+            }
+        }
+    };
+}
+
+/// Index of a named color in the 16 colors palette.
+fn named_index(color: Color) -> u8 {
+    match color {
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::Gray => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        _ => 15,
+    }
+}
+
+/// RGB value of `color`, using xterm's palette for the indexed ones.
+fn rgb(color: Color, default: (u8, u8, u8)) -> (u8, u8, u8) {
+    const K_PALETTE: [(u8, u8, u8); 16] = [
+        (0x00, 0x00, 0x00),
+        (0xcd, 0x00, 0x00),
+        (0x00, 0xcd, 0x00),
+        (0xcd, 0xcd, 0x00),
+        (0x00, 0x00, 0xee),
+        (0xcd, 0x00, 0xcd),
+        (0x00, 0xcd, 0xcd),
+        (0xe5, 0xe5, 0xe5),
+        (0x7f, 0x7f, 0x7f),
+        (0xff, 0x00, 0x00),
+        (0x00, 0xff, 0x00),
+        (0xff, 0xff, 0x00),
+        (0x5c, 0x5c, 0xff),
+        (0xff, 0x00, 0xff),
+        (0x00, 0xff, 0xff),
+        (0xff, 0xff, 0xff),
+    ];
+    match color {
+        Color::Reset => default,
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(index) if index < 16 => K_PALETTE[index as usize],
+        Color::Indexed(index) if index < 232 => {
+            // 6x6x6 color cube
+            let level = |value: u8| if value == 0 { 0 } else { 55 + value * 40 };
+            let index = index - 16;
+            (level(index / 36), level(index / 6 % 6), level(index % 6))
+        }
+        Color::Indexed(index) => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+        named => K_PALETTE[named_index(named) as usize],
+    }
+}
+
+fn hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Render `buffer` as an SVG image of a terminal.
+///
+/// Every run of cells sharing a style becomes one background rectangle and
+/// one text element, which keeps the images small enough for documentation.
+pub fn to_svg(buffer: &Buffer) -> String {
+    let width = buffer.area.width as f32 * K_CELL_WIDTH;
+    let height = buffer.area.height as f32 * K_CELL_HEIGHT;
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        hex(K_DEFAULT_BG)
+    );
+    let _ = writeln!(
+        svg,
+        r#"<g font-family="monospace" font-size="{K_FONT_SIZE}" xml:space="preserve">"#
+    );
+    for (y, row) in rows(buffer).enumerate() {
+        let mut x = 0;
+        while x < row.len() {
+            let first = &row[x];
+            let run = row[x..]
+                .iter()
+                .take_while(|cell| {
+                    (cell.fg, cell.bg, cell.modifier) == (first.fg, first.bg, first.modifier)
+                })
+                .count();
+            let text: String = row[x..x + run].iter().map(Cell::symbol).collect();
+
+            let (mut fg, mut bg) = (rgb(first.fg, K_DEFAULT_FG), rgb(first.bg, K_DEFAULT_BG));
+            if first.modifier.contains(Modifier::REVERSED) {
+                std::mem::swap(&mut fg, &mut bg);
+            }
+            let left = x as f32 * K_CELL_WIDTH;
+            let top = y as f32 * K_CELL_HEIGHT;
+            if bg != K_DEFAULT_BG {
+                let _ = writeln!(
+                    svg,
+                    r#"<rect x="{left}" y="{top}" width="{}" height="{K_CELL_HEIGHT}" fill="{}"/>"#,
+                    run as f32 * K_CELL_WIDTH,
+                    hex(bg)
+                );
+            }
+            if !text.trim().is_empty() {
+                let mut attributes = String::new();
+                if first.modifier.contains(Modifier::BOLD) {
+                    attributes.push_str(r#" font-weight="bold""#);
+                }
+                if first.modifier.contains(Modifier::ITALIC) {
+                    attributes.push_str(r#" font-style="italic""#);
+                }
+                if first.modifier.contains(Modifier::UNDERLINED) {
+                    attributes.push_str(r#" text-decoration="underline""#);
+                }
+                if first.modifier.contains(Modifier::DIM) {
+                    attributes.push_str(r#" opacity="0.6""#);
+                }
+                let _ = writeln!(
+                    svg,
+                    r#"<text x="{left}" y="{}" fill="{}" textLength="{}"{attributes}>{}</text>"#,
+                    top + K_CELL_HEIGHT * 0.8,
+                    hex(fg),
+                    run as f32 * K_CELL_WIDTH,
+                    escape(&text)
+                );
+            }
+            x += run;
+        }
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
@@ -0,0 +1,212 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+use mdns_sd::ServiceInfo;
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+
+/// Upper bound on the operations a single hook may run, so that a runaway
+/// script can't stall the model.
+const K_MAX_OPERATIONS: u64 = 100_000;
+
+/// User scripts reacting to discovery events.
+///
+/// Every `*.rhai` file in the scripts directory may define any of these hooks:
+///
+/// ```rhai
+/// fn on_service_found(service_type, fullname) {}
+/// fn on_resolved(instance) {
+///     if instance.properties.ty == "Office Printer" {
+///         run(`notify-send "${instance.hostname} is back"`);
+///         return #{ name: "Printer", labels: ["office"] };
+///     }
+/// }
+/// fn on_removed(service_type, fullname) {}
+/// ```
+///
+/// `instance` carries the `fullname`, `hostname`, `service_type`, `port`,
+/// `addresses` and `properties` of the resolved instance. Scripts can call
+/// `run(command)` to spawn a shell command and `log(message)` to write to the
+/// trace log.
+pub struct Scripts {
+    engine: Engine,
+    scripts: Vec<(PathBuf, AST)>,
+}
+
+/// How the scripts want a resolved instance to be shown.
+#[derive(Debug, Default)]
+pub struct Reaction {
+    /// Shown in place of the hostname
+    pub name: Option<String>,
+    pub labels: Vec<String>,
+}
+
+impl std::fmt::Debug for Scripts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.scripts.iter().map(|(path, _)| path))
+            .finish()
+    }
+}
+
+impl Default for Scripts {
+    fn default() -> Self {
+        Self {
+            engine: Self::engine(),
+            scripts: Vec::new(),
+        }
+    }
+}
+
+impl Scripts {
+    /// `scripts` next to the config file.
+    pub fn dir(config_path: &Path) -> PathBuf {
+        config_path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join("scripts")
+    }
+
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        engine.set_max_operations(K_MAX_OPERATIONS);
+        engine.on_print(|message| tracing::info!("Script: {message}"));
+        engine.register_fn("log", |message: &str| tracing::info!("Script: {message}"));
+        engine.register_fn("run", |command: &str| {
+            tracing::info!("Script runs: {command}");
+            match Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+            {
+                // Reap the command without blocking the hook
+                Ok(mut child) => {
+                    std::thread::spawn(move || child.wait());
+                }
+                Err(e) => tracing::warn!("Failed to run {command}: {e}"),
+            }
+        });
+        engine
+    }
+
+    /// Compile every `*.rhai` file in `dir`, in file name order.
+    ///
+    /// A missing directory simply means there are no scripts.
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let mut scripts = Self::default();
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(scripts),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", dir.display())),
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "rhai")
+            })
+            .collect();
+        paths.sort();
+        for path in paths {
+            let ast = scripts
+                .engine
+                .compile_file(path.clone())
+                .with_context(|| format!("Failed to compile {}", path.display()))?;
+            tracing::info!("Loaded script {}", path.display());
+            scripts.scripts.push((path, ast));
+        }
+        Ok(scripts)
+    }
+
+    /// Call `hook` in every script defining it, returning what each one returned.
+    fn call(&self, hook: &str, args: impl Fn() -> Vec<Dynamic>) -> Vec<Dynamic> {
+        self.scripts
+            .iter()
+            .filter(|(_, ast)| ast.iter_functions().any(|function| function.name == hook))
+            .filter_map(|(path, ast)| {
+                match self
+                    .engine
+                    .call_fn::<Dynamic>(&mut Scope::new(), ast, hook, args())
+                {
+                    Ok(result) => Some(result),
+                    Err(e) => {
+                        tracing::warn!("{hook} in {} failed: {e}", path.display());
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    pub fn on_service_found(&self, service_type: &str, fullname: &str) {
+        self.call("on_service_found", || {
+            vec![service_type.into(), fullname.into()]
+        });
+    }
+
+    /// Let the scripts rename and label a resolved instance.
+    ///
+    /// When several scripts answer, the last name wins and the labels add up.
+    pub fn on_resolved(&self, info: &ServiceInfo) -> Reaction {
+        let mut reaction = Reaction::default();
+        if self.scripts.is_empty() {
+            return reaction;
+        }
+        let instance = instance(info);
+        for result in self.call("on_resolved", || vec![instance.clone().into()]) {
+            let Some(result) = result.try_cast::<Map>() else {
+                continue;
+            };
+            if let Some(name) = result
+                .get("name")
+                .and_then(|name| name.clone().into_string().ok())
+            {
+                reaction.name = Some(name);
+            }
+            if let Some(labels) = result
+                .get("labels")
+                .and_then(|labels| labels.clone().try_cast::<Array>())
+            {
+                reaction.labels.extend(
+                    labels
+                        .into_iter()
+                        .filter_map(|label| label.into_string().ok()),
+                );
+            }
+        }
+        reaction
+    }
+
+    pub fn on_removed(&self, service_type: &str, fullname: &str) {
+        self.call("on_removed", || vec![service_type.into(), fullname.into()]);
+    }
+}
+
+/// The script's view of a resolved instance.
+fn instance(info: &ServiceInfo) -> Map {
+    let mut addresses: Vec<_> = info.get_addresses().iter().copied().collect();
+    addresses.sort();
+    let properties: Map = info
+        .get_properties()
+        .iter()
+        .map(|property| (property.key().into(), property.val_str().into()))
+        .collect();
+    Map::from_iter([
+        ("fullname".into(), info.get_fullname().into()),
+        ("hostname".into(), info.get_hostname().into()),
+        ("service_type".into(), info.get_type().into()),
+        ("port".into(), Dynamic::from_int(info.get_port().into())),
+        (
+            "addresses".into(),
+            addresses
+                .iter()
+                .map(|addr| Dynamic::from(addr.to_string()))
+                .collect::<Array>()
+                .into(),
+        ),
+        ("properties".into(), properties.into()),
+    ])
+}
@@ -1,28 +1,71 @@
-use crate::colors::{HEADER_BG, NORMAL_ROW_COLOR, SEARCH_STYLE_BORDER, TEXT_COLOR};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::colors::theme;
+use crate::filter::Filter;
 use crate::widget::DiscoveryWidget;
 use crossterm::event::{KeyCode, KeyEvent};
+use parking_lot::Mutex;
 use ratatui::buffer::Buffer;
 use ratatui::layout::Rect;
 use ratatui::prelude::{Alignment, Constraint, Layout, Line, Stylize, Widget};
 use ratatui::style::{Color, Style};
 use ratatui::text::Span;
 use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
-use regex::Regex;
 
-#[derive(Debug, Default)]
+/// How long typing has to pause for the search to be checked.
+const K_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Clones share the checks, which are done while rendering any of them.
+#[derive(Debug, Clone, Default)]
 pub struct Search {
     search: Option<String>,
+    /// When the search was last edited, until it is checked
+    edited: Arc<Mutex<Option<Instant>>>,
+    /// What is wrong with the search as of the last check
+    error: Arc<Mutex<Option<String>>>,
 }
 
 impl Search {
-    pub fn compile_regex(&self) -> anyhow::Result<Option<Regex>> {
+    pub fn compile_filter(&self) -> anyhow::Result<Option<Filter>> {
         if let Some(search) = self.search.as_ref() {
-            let regex = Regex::new(search)?;
-            Ok(Some(regex))
+            let filter = Filter::parse(search)?;
+            Ok(Some(filter))
         } else {
             Ok(None)
         }
     }
+
+    /// Start from `search`, e.g. one applied by a previous run.
+    pub fn set(&mut self, search: &str) {
+        self.search = Some(search.to_string()).filter(|search| !search.is_empty());
+    }
+
+    /// Show `error` below the search until it is edited into a valid one.
+    pub fn set_error(&self, error: &anyhow::Error) {
+        // Regex errors point at the problem over several lines, the last one
+        // tells what it is
+        let error = error.to_string();
+        let message = error.lines().last().unwrap_or_default();
+        let message = message.strip_prefix("error: ").unwrap_or(message);
+        *self.error.lock() = Some(format!("Invalid search: {message}"));
+        *self.edited.lock() = None;
+    }
+
+    /// Check the search once typing paused, rather than on every key.
+    fn validate(&self) {
+        {
+            let mut edited = self.edited.lock();
+            if !edited.is_some_and(|edited| edited.elapsed() >= K_DEBOUNCE) {
+                return;
+            }
+            *edited = None;
+        }
+        match self.compile_filter() {
+            Ok(_) => *self.error.lock() = None,
+            Err(e) => self.set_error(&e),
+        }
+    }
 }
 
 impl DiscoveryWidget for Search {
@@ -31,7 +74,7 @@ impl DiscoveryWidget for Search {
     }
 
     fn controls(&self) -> String {
-        "Use ↵ to apply. Esc to exit".to_string()
+        "Use ↵ to apply. Esc to exit. Combine with !, && and ||".to_string()
     }
 
     fn process_key_event(&mut self, key_event: &KeyEvent) {
@@ -56,6 +99,7 @@ impl DiscoveryWidget for Search {
         {
             self.search = None;
         }
+        *self.edited.lock() = Some(Instant::now());
     }
 
     fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool)
@@ -64,12 +108,12 @@ impl DiscoveryWidget for Search {
     {
         let block = Block::new()
             .borders(Borders::ALL)
-            .border_style(Style::new().fg(SEARCH_STYLE_BORDER).bold())
+            .border_style(Style::new().fg(theme().search_border).bold())
             .title_alignment(Alignment::Center)
             .title(self.title())
             .title_style(Style::new().bold())
-            .fg(TEXT_COLOR)
-            .bg(HEADER_BG);
+            .fg(theme().text)
+            .bg(theme().header_bg);
         let inner_area = block.inner(area);
         block.render(area, buf);
 
@@ -78,8 +122,8 @@ impl DiscoveryWidget for Search {
                 .areas(inner_area);
         let block = Block::new()
             .borders(Borders::NONE)
-            .fg(TEXT_COLOR)
-            .bg(NORMAL_ROW_COLOR);
+            .fg(theme().text)
+            .bg(theme().normal_row);
         let input = Paragraph::new(Line::from(vec![
             Span::styled(" /", Style::default().fg(Color::DarkGray)),
             Span::from(self.search.as_deref().unwrap_or("")),
@@ -88,9 +132,17 @@ impl DiscoveryWidget for Search {
 
         Widget::render(input, search_area, buf);
 
-        Paragraph::new(self.controls())
-            .centered()
-            .wrap(Wrap::default())
-            .render(footer_area, buf);
+        self.validate();
+        match self.error.lock().clone().as_deref() {
+            Some(error) => Paragraph::new(error)
+                .centered()
+                .wrap(Wrap::default())
+                .fg(theme().error)
+                .render(footer_area, buf),
+            None => Paragraph::new(self.controls())
+                .centered()
+                .wrap(Wrap::default())
+                .render(footer_area, buf),
+        }
     }
 }
@@ -0,0 +1,112 @@
+use std::time::SystemTime;
+
+use ratatui::prelude::*;
+use serde::Deserialize;
+
+use crate::colors::theme;
+use crate::domain;
+use crate::host;
+use crate::label::Label;
+use crate::list::ListEntry;
+use crate::servicedb;
+
+/// Extra column of the Services pane, configured per profile.
+///
+/// ```toml
+/// [profiles.home]
+/// columns = ["instances", "first-seen", "protocol", "label"]
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Column {
+    /// Number of resolved instances
+    Instances,
+    /// How long ago the type was first discovered
+    FirstSeen,
+    /// Multicast DNS or unicast DNS-SD, depending on the browse domain
+    Protocol,
+    /// Labels of the resolved instances
+    Label,
+}
+
+/// A discovered service type, as listed in the Services pane.
+#[derive(Debug, Clone)]
+pub struct Service {
+    pub service_type: String,
+    pub first_seen: SystemTime,
+    /// Resolved instances, kept up to date by the model
+    pub instances: usize,
+    /// Labels of the resolved instances, without duplicates
+    pub labels: Vec<Label>,
+    pub columns: Vec<Column>,
+}
+
+impl Service {
+    pub fn new(service_type: String, columns: Vec<Column>, first_seen: SystemTime) -> Self {
+        Self {
+            service_type,
+            first_seen,
+            instances: 0,
+            labels: Vec::new(),
+            columns,
+        }
+    }
+
+    /// Protocol the type was discovered with.
+    pub fn protocol(&self) -> &'static str {
+        if self.service_type.ends_with(domain::K_LOCAL_DOMAIN) {
+            "mDNS"
+        } else {
+            "DNS-SD"
+        }
+    }
+}
+
+impl PartialEq for Service {
+    fn eq(&self, other: &Self) -> bool {
+        self.service_type == other.service_type
+    }
+}
+
+impl ListEntry for Service {
+    /// Fixed width columns come first, in the configured order, so that they
+    /// line up. Labels follow the type.
+    fn entry(&self) -> Line<'_> {
+        let mut spans = Vec::new();
+        for column in self.columns.iter() {
+            let text = match column {
+                Column::Instances => format!("{:>3}", self.instances),
+                Column::FirstSeen => format!("{:>8}", host::ago(self.first_seen)),
+                Column::Protocol => format!("{:<6}", self.protocol()),
+                Column::Label => continue,
+            };
+            spans.push(Span::raw(text).dark_gray());
+            spans.push(Span::raw(" "));
+        }
+        spans.push(Span::styled(self.service_type.clone(), theme().text));
+        if let Some(name) = servicedb::name(&self.service_type) {
+            spans.push(Span::raw(format!(" ({name})")).dark_gray());
+        }
+        if self.columns.contains(&Column::Label) {
+            spans.extend(self.labels.iter().map(|label| {
+                Span::styled(
+                    format!(" [{}]", label.name),
+                    Style::new().fg(label.color.unwrap_or(Color::LightCyan)),
+                )
+            }));
+        }
+        Line::from(spans)
+    }
+
+    fn id(&self) -> String {
+        self.service_type.clone()
+    }
+
+    /// The type and its friendly name, so that either can be searched for.
+    fn search_text(&self) -> String {
+        match servicedb::name(&self.service_type) {
+            Some(name) => format!("{} {name}", self.service_type),
+            None => self.service_type.clone(),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::domain;
+
+/// Friendly names of well-known DNS-SD service types.
+///
+/// Mostly from the IANA registry and the vendors' own documentation, for
+/// the types commonly found on home and office networks.
+const K_SERVICES: [(&str, &str); 72] = [
+    ("_adisk._tcp", "Time Machine disk"),
+    ("_afpovertcp._tcp", "Apple file sharing (AFP)"),
+    ("_airdrop._tcp", "AirDrop"),
+    ("_airplay._tcp", "AirPlay"),
+    ("_airport._tcp", "AirPort base station"),
+    ("_amzn-wplay._tcp", "Amazon Fire TV"),
+    ("_androidtvremote2._tcp", "Android TV remote"),
+    ("_apple-midi._udp", "Network MIDI"),
+    ("_apple-mobdev2._tcp", "Apple device sync"),
+    ("_companion-link._tcp", "Apple companion link"),
+    ("_daap._tcp", "iTunes sharing (DAAP)"),
+    ("_device-info._tcp", "Device information"),
+    ("_distcc._tcp", "distcc"),
+    ("_dosvc._tcp", "Windows Delivery Optimization"),
+    ("_dpap._tcp", "iPhoto sharing (DPAP)"),
+    ("_elg._tcp", "Elgato light"),
+    ("_eppc._tcp", "Remote Apple Events"),
+    ("_esphomelib._tcp", "ESPHome"),
+    ("_ftp._tcp", "FTP"),
+    ("_googlecast._tcp", "Chromecast"),
+    ("_googlezone._tcp", "Google Cast group"),
+    ("_hap._tcp", "HomeKit accessory"),
+    ("_hap._udp", "HomeKit accessory (Thread)"),
+    ("_home-assistant._tcp", "Home Assistant"),
+    ("_homekit._tcp", "HomeKit"),
+    ("_http._tcp", "Web server"),
+    ("_https._tcp", "Secure web server"),
+    ("_hue._tcp", "Philips Hue bridge"),
+    ("_ipp._tcp", "Internet Printing"),
+    ("_ipps._tcp", "Secure Internet Printing"),
+    ("_kdeconnect._udp", "KDE Connect"),
+    ("_matter._tcp", "Matter"),
+    ("_matterc._udp", "Matter commissioning"),
+    ("_mediaremotetv._tcp", "Apple TV remote"),
+    ("_meshcop._udp", "Thread border router"),
+    ("_mqtt._tcp", "MQTT broker"),
+    ("_mysql._tcp", "MySQL"),
+    ("_nfs._tcp", "NFS"),
+    ("_nut._tcp", "Network UPS Tools"),
+    ("_nvstream._tcp", "NVIDIA GameStream"),
+    ("_octoprint._tcp", "OctoPrint"),
+    ("_pdl-datastream._tcp", "Raw printing (port 9100)"),
+    ("_plexmediasvr._tcp", "Plex Media Server"),
+    ("_postgresql._tcp", "PostgreSQL"),
+    ("_printer._tcp", "LPD printing"),
+    ("_privet._tcp", "Google Cloud Print"),
+    ("_ptp._tcp", "Picture Transfer Protocol"),
+    ("_qdiscover._tcp", "QNAP NAS"),
+    ("_raop._tcp", "AirPlay audio (RAOP)"),
+    ("_rdlink._tcp", "Apple remote display link"),
+    ("_remotepairing._tcp", "Apple remote pairing"),
+    ("_rfb._tcp", "Screen sharing (VNC)"),
+    ("_rsync._tcp", "rsync"),
+    ("_rtsp._tcp", "RTSP stream"),
+    ("_scanner._tcp", "Scanner"),
+    ("_sftp-ssh._tcp", "SFTP"),
+    ("_sip._udp", "SIP"),
+    ("_sleep-proxy._udp", "Bonjour Sleep Proxy"),
+    ("_smb._tcp", "Windows file sharing (SMB)"),
+    ("_sonos._tcp", "Sonos"),
+    ("_spotify-connect._tcp", "Spotify Connect"),
+    ("_ssh._tcp", "SSH"),
+    ("_teamviewer._tcp", "TeamViewer"),
+    ("_telnet._tcp", "Telnet"),
+    ("_touch-able._tcp", "Apple Remote"),
+    ("_trel._udp", "Thread radio link"),
+    ("_uscan._tcp", "eSCL scanner"),
+    ("_uscans._tcp", "Secure eSCL scanner"),
+    ("_webdav._tcp", "WebDAV"),
+    ("_webdavs._tcp", "Secure WebDAV"),
+    ("_workstation._tcp", "Workstation"),
+    ("_wsd._udp", "WS-Discovery endpoint"),
+];
+
+/// The friendly name of `service_type`, e.g. "Internet Printing" for
+/// `_ipp._tcp.local.`, regardless of its subtype and domain.
+pub fn name(service_type: &str) -> Option<&'static str> {
+    let service_type = domain::base_type(service_type);
+    K_SERVICES
+        .iter()
+        .find(|(known, _)| {
+            service_type
+                .strip_prefix(known)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        })
+        .map(|(_, name)| *name)
+}
@@ -0,0 +1,152 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write as _;
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use mdns_sd::ServiceInfo;
+use parking_lot::Mutex;
+
+use crate::clock::Clock;
+use crate::host;
+use crate::schema::{self, Summary};
+
+/// Events are counted per window this long for the peak rate.
+const K_RATE_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+struct Counts {
+    started: Instant,
+    started_at: SystemTime,
+    service_types: HashSet<String>,
+    instances: HashSet<String>,
+    /// Every address resolved, to tell the interfaces used at the end
+    addresses: BTreeSet<IpAddr>,
+    events: u64,
+    /// Start of the current rate window and the events within it
+    window: (Instant, u64),
+    peak: u64,
+}
+
+/// Statistics of a whole session, summarized on exit.
+///
+/// Cloning is cheap and yields a handle to the same statistics.
+#[derive(Debug, Clone)]
+pub struct Session {
+    counts: Arc<Mutex<Counts>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Session {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        let now = clock.now();
+        Self {
+            counts: Arc::new(Mutex::new(Counts {
+                started: now,
+                started_at: clock.system_time(),
+                service_types: HashSet::new(),
+                instances: HashSet::new(),
+                addresses: BTreeSet::new(),
+                events: 0,
+                window: (now, 0),
+                peak: 0,
+            })),
+            clock,
+        }
+    }
+
+    /// Count an mDNS event towards the event rate.
+    pub fn event(&self) {
+        let now = self.clock.now();
+        let mut counts = self.counts.lock();
+        counts.events += 1;
+        if now.saturating_duration_since(counts.window.0) >= K_RATE_WINDOW {
+            counts.window = (now, 0);
+        }
+        counts.window.1 += 1;
+        counts.peak = counts.peak.max(counts.window.1);
+    }
+
+    pub fn service_type(&self, service_type: &str) {
+        self.counts
+            .lock()
+            .service_types
+            .insert(service_type.to_string());
+    }
+
+    pub fn resolved(&self, info: &ServiceInfo) {
+        let mut counts = self.counts.lock();
+        counts.instances.insert(info.get_fullname().to_string());
+        counts
+            .addresses
+            .extend(info.get_addresses().iter().copied());
+    }
+
+    pub fn summary(&self) -> Summary {
+        let counts = self.counts.lock();
+        Summary {
+            started: counts
+                .started_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            duration_ms: self
+                .clock
+                .now()
+                .saturating_duration_since(counts.started)
+                .as_millis() as u64,
+            service_types: counts.service_types.len(),
+            instances: counts.instances.len(),
+            events: counts.events,
+            peak_events_per_second: counts.peak,
+            interfaces: host::by_interface(counts.addresses.clone())
+                .into_keys()
+                // Not on any local network, e.g. when reflected
+                .filter(|interface| interface != "unknown")
+                .collect(),
+        }
+    }
+}
+
+/// The summary as plain text, one statistic per line.
+fn text(summary: &Summary) -> String {
+    let duration = Duration::from_secs(summary.duration_ms / 1000);
+    let interfaces = if summary.interfaces.is_empty() {
+        "-".to_string()
+    } else {
+        summary.interfaces.join(", ")
+    };
+    let mut text = String::new();
+    let _ = writeln!(text, "Session summary");
+    let _ = writeln!(
+        text,
+        "  Duration        {}",
+        humantime::format_duration(duration)
+    );
+    let _ = writeln!(text, "  Service types   {}", summary.service_types);
+    let _ = writeln!(text, "  Instances       {}", summary.instances);
+    let _ = writeln!(
+        text,
+        "  Events          {} (peak {}/s)",
+        summary.events, summary.peak_events_per_second
+    );
+    let _ = writeln!(text, "  Interfaces      {interfaces}");
+    text
+}
+
+/// Write the summary of `session` as JSON to `path` if given, or print it
+/// to `out` otherwise.
+pub fn report(session: &Session, path: Option<&Path>, mut out: impl Write) -> anyhow::Result<()> {
+    let summary = session.summary();
+    match path {
+        Some(path) => {
+            let json = schema::to_json(&summary)?;
+            std::fs::write(path, json + "\n")
+                .with_context(|| format!("Failed to write {}", path.display()))
+        }
+        None => write!(out, "{}", text(&summary)).context("Failed to write the summary"),
+    }
+}
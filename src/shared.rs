@@ -0,0 +1,64 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::info::Info;
+use crate::list::{ListEntry, ListWidget};
+
+/// Addresses resolved by more than one hostname, e.g. hosts behind a NAT,
+/// containers publishing through their host's address, or a
+/// misconfiguration.
+///
+/// Rebuilt by the model as instances come and go. Cloning is cheap and
+/// yields a handle to the same addresses.
+#[derive(Debug, Clone, Default)]
+pub struct SharedAddresses {
+    hostnames: Arc<Mutex<HashMap<IpAddr, BTreeSet<String>>>>,
+}
+
+impl SharedAddresses {
+    /// Find the addresses shared among `instances` again.
+    pub fn update(&self, instances: &HashMap<String, ListWidget<Info>>) {
+        let mut hostnames: HashMap<IpAddr, BTreeSet<String>> = HashMap::new();
+        for info in instances.values().flat_map(|list| list.items()) {
+            for addr in info.addresses() {
+                hostnames.entry(addr).or_default().insert(info.id());
+            }
+        }
+        hostnames.retain(|addr, hostnames| {
+            hostnames.len() > 1 && !addr.is_loopback() && !addr.is_unspecified()
+        });
+        *self.hostnames.lock() = hostnames;
+    }
+
+    /// The other hostnames resolving to `addr`, besides `hostname`.
+    pub fn others(&self, addr: &IpAddr, hostname: &str) -> Vec<String> {
+        self.hostnames
+            .lock()
+            .get(addr)
+            .map(|hostnames| {
+                hostnames
+                    .iter()
+                    .filter(|other| *other != hostname)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The addresses of `addresses` shared with other hostnames than
+    /// `hostname`, and those hostnames.
+    pub fn of(
+        &self,
+        addresses: impl IntoIterator<Item = IpAddr>,
+        hostname: &str,
+    ) -> BTreeMap<IpAddr, Vec<String>> {
+        addresses
+            .into_iter()
+            .map(|addr| (addr, self.others(&addr, hostname)))
+            .filter(|(_, others)| !others.is_empty())
+            .collect()
+    }
+}
@@ -0,0 +1,90 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Context;
+use serde::Serialize;
+
+use crate::model::Model;
+use crate::schema;
+
+/// Clients get this long to send their request.
+const K_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Answer to a request that isn't understood.
+#[derive(Debug, Serialize)]
+struct Failure {
+    error: String,
+}
+
+/// The Unix socket serving the state, removed once dropped.
+#[derive(Debug)]
+pub struct StateSocket {
+    path: PathBuf,
+}
+
+impl Drop for StateSocket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Answer a single `request` about `model`.
+fn answer(request: &str, model: &Model) -> anyhow::Result<String> {
+    Ok(match request {
+        "" | "snapshot" => schema::to_json(&model.snapshot())?,
+        "summary" => schema::to_json(&model.session.summary())?,
+        request => schema::to_json(&Failure {
+            error: format!("Unknown request \"{request}\", one of: snapshot, summary"),
+        })?,
+    })
+}
+
+fn handle(stream: UnixStream, model: &Model) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(K_REQUEST_TIMEOUT))?;
+    let mut request = String::new();
+    BufReader::new(&stream).read_line(&mut request)?;
+    let mut stream = stream;
+    stream.write_all((answer(request.trim(), model)? + "\n").as_bytes())?;
+    Ok(())
+}
+
+/// Serve the state of `model` on a Unix socket at `path`, in the background.
+///
+/// Every connection sends a single request line, `snapshot` (or nothing)
+/// for the snapshot `export` writes, or `summary` for the session summary,
+/// and reads the JSON document back until the socket is closed, e.g. with
+/// `echo snapshot | nc -U <path>`. The socket is only accessible to its
+/// owner. A socket left behind by a previous run is replaced.
+pub fn serve(path: &Path, model: Model) -> anyhow::Result<StateSocket> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            anyhow::bail!("{} exists and isn't a socket", path.display());
+        }
+        if UnixStream::connect(path).is_ok() {
+            anyhow::bail!("{} is being served already", path.display());
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove the stale {}", path.display()))?;
+    }
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("Failed to bind {}", path.display()))?;
+    let socket = StateSocket {
+        path: path.to_path_buf(),
+    };
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to restrict {}", path.display()))?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream
+                .context("Failed to accept a connection")
+                .and_then(|stream| handle(stream, &model));
+            if let Err(e) = result {
+                tracing::warn!("State socket: {e:#}");
+            }
+        }
+    });
+    Ok(socket)
+}
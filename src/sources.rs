@@ -0,0 +1,315 @@
+use std::collections::{BTreeSet, HashMap};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use if_addrs::IfAddr;
+use parking_lot::Mutex;
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::clock::{Clock, SystemClock};
+use crate::packet::{self, K_TYPE_NSEC, K_TYPE_SRV, K_TYPE_TXT};
+use crate::zone::{self, Zones};
+
+const K_MDNS_PORT: u16 = 5353;
+const K_MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const K_MDNS_GROUP_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+
+/// Source addresses of the mDNS responses seen on the link, by instance.
+///
+/// The backends only hand over the records, so the responses are listened
+/// to once more on a socket sharing the mDNS port. Only the SRV and TXT
+/// records, which name the instance, count. Where the port can't be shared
+/// the sources stay unknown.
+///
+/// The TTL of the records is kept along, so that the model can expire the
+/// instances on its own and tell goodbyes apart.
+#[derive(Debug, Clone)]
+pub struct Sources {
+    /// Lowercase instance fullnames and the addresses their records came from
+    sources: Arc<Mutex<HashMap<String, BTreeSet<IpAddr>>>>,
+    /// Lowercase instance fullnames and when their records were last heard
+    heard: Arc<Mutex<HashMap<String, Heard>>>,
+    /// Lowercase names, of instances or hosts, and the record types the
+    /// last NSEC record heard for them lists
+    nsec: Arc<Mutex<HashMap<String, Vec<u16>>>>,
+    /// Whether any socket is listening, the records are all missed otherwise
+    listening: Arc<AtomicBool>,
+    /// Networks of the local interfaces, to tell reflected records apart
+    subnets: Arc<Vec<Subnet>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for Sources {
+    fn default() -> Self {
+        Self {
+            sources: Arc::default(),
+            heard: Arc::default(),
+            nsec: Arc::default(),
+            listening: Arc::default(),
+            subnets: Arc::default(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+/// The last response naming an instance.
+#[derive(Debug, Clone, Copy)]
+pub struct Heard {
+    pub at: Instant,
+    /// Shortest TTL of the instance's records in the response, zero for a
+    /// goodbye
+    pub ttl: Duration,
+}
+
+/// A network a local interface is on.
+#[derive(Debug, Clone)]
+struct Subnet {
+    addr: IpAddr,
+    netmask: IpAddr,
+}
+
+impl Subnet {
+    fn contains(&self, addr: &IpAddr) -> bool {
+        match (self.addr, self.netmask, addr) {
+            (IpAddr::V4(local), IpAddr::V4(netmask), IpAddr::V4(addr)) => {
+                u32::from(local) & u32::from(netmask) == u32::from(*addr) & u32::from(netmask)
+            }
+            (IpAddr::V6(local), IpAddr::V6(netmask), IpAddr::V6(addr)) => {
+                u128::from(local) & u128::from(netmask) == u128::from(*addr) & u128::from(netmask)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// IPv6 link-local addresses share a prefix on every interface, so they
+/// don't tell networks apart.
+fn link_local_v6(addr: &IpAddr) -> bool {
+    matches!(addr, IpAddr::V6(v6) if zone::link_local(v6))
+}
+
+impl Sources {
+    /// Listen for responses in the background, over IPv4 and IPv6, telling
+    /// when they were heard with `clock`.
+    pub fn spawn(clock: Arc<dyn Clock>) -> Self {
+        let subnets = if_addrs::get_if_addrs()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|interface| !interface.is_loopback())
+            .map(|interface| match interface.addr {
+                IfAddr::V4(v4) => Subnet {
+                    addr: v4.ip.into(),
+                    netmask: v4.netmask.into(),
+                },
+                IfAddr::V6(v6) => Subnet {
+                    addr: v6.ip.into(),
+                    netmask: v6.netmask.into(),
+                },
+            })
+            .filter(|subnet| !link_local_v6(&subnet.addr))
+            .collect();
+        let sources = Self {
+            subnets: Arc::new(subnets),
+            clock,
+            ..Self::default()
+        };
+        for socket in [listen_v4(), listen_v6()] {
+            match socket {
+                Ok(socket) => {
+                    sources.listening.store(true, Ordering::Relaxed);
+                    let sources = sources.clone();
+                    std::thread::spawn(move || sources.receive(socket));
+                }
+                Err(e) => tracing::warn!("Source addresses won't be known: {e:#}"),
+            }
+        }
+        sources
+    }
+
+    fn receive(&self, socket: UdpSocket) {
+        let mut buf = [0u8; 9000];
+        loop {
+            let (len, from) = match socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) => {
+                    tracing::warn!("Stopped listening for the source addresses: {e}");
+                    return;
+                }
+            };
+            let Some(records) = packet::records(&buf[..len], None) else {
+                continue;
+            };
+            let now = self.clock.now();
+            let mut nsec = self.nsec.lock();
+            for (name, _, _, data) in records
+                .iter()
+                .filter(|(_, record_type, _, _)| *record_type == K_TYPE_NSEC)
+            {
+                if let Some(types) = packet::nsec_types(data) {
+                    nsec.insert(name.to_lowercase(), types);
+                }
+            }
+            drop(nsec);
+            let mut sources = self.sources.lock();
+            let mut heard = self.heard.lock();
+            for (name, _, ttl, _) in records
+                .iter()
+                .filter(|(_, record_type, _, _)| matches!(*record_type, K_TYPE_SRV | K_TYPE_TXT))
+            {
+                let name = name.to_lowercase();
+                sources.entry(name.clone()).or_default().insert(from.ip());
+                let ttl = Duration::from_secs((*ttl).into());
+                heard
+                    .entry(name)
+                    // The SRV and TXT records of a response may differ
+                    .and_modify(|heard| {
+                        if heard.at == now {
+                            heard.ttl = heard.ttl.min(ttl);
+                        } else {
+                            *heard = Heard { at: now, ttl };
+                        }
+                    })
+                    .or_insert(Heard { at: now, ttl });
+            }
+        }
+    }
+
+    /// Sources told the responses with [`Sources::hear`] rather than
+    /// listening to them, e.g. to test expiries.
+    #[cfg(test)]
+    pub fn simulated(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            listening: Arc::new(AtomicBool::new(true)),
+            clock,
+            ..Self::default()
+        }
+    }
+
+    /// Take the records of the instance `fullname` as heard now, with `ttl`.
+    #[cfg(test)]
+    pub fn hear(&self, fullname: &str, ttl: Duration) {
+        let at = self.clock.now();
+        self.heard
+            .lock()
+            .insert(fullname.to_lowercase(), Heard { at, ttl });
+    }
+
+    /// Whether the responses are listened to at all.
+    pub fn listening(&self) -> bool {
+        self.listening.load(Ordering::Relaxed)
+    }
+
+    /// Which of `types` a responder said `name` doesn't have, through NSEC.
+    ///
+    /// Tells "answered: nothing here" apart from no answer at all, e.g. an
+    /// IPv4-only host denying AAAA records.
+    pub fn denied(&self, name: &str, types: &[u16]) -> Vec<u16> {
+        match self.nsec.lock().get(&name.to_lowercase()) {
+            Some(existing) => types
+                .iter()
+                .copied()
+                .filter(|record_type| !existing.contains(record_type))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// When the records of the instance `fullname` were last heard, if ever.
+    pub fn heard(&self, fullname: &str) -> Option<Heard> {
+        self.heard.lock().get(&fullname.to_lowercase()).copied()
+    }
+
+    /// Where the records of the instance `fullname` came from so far.
+    pub fn get(&self, fullname: &str) -> BTreeSet<IpAddr> {
+        self.sources
+            .lock()
+            .get(&fullname.to_lowercase())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Index of the local network `addr` is on, if any.
+    fn subnet(&self, addr: &IpAddr) -> Option<usize> {
+        if link_local_v6(addr) {
+            return None;
+        }
+        self.subnets.iter().position(|subnet| subnet.contains(addr))
+    }
+
+    /// The reflector the records of the instance `fullname` likely came
+    /// through, given the addresses it advertises.
+    ///
+    /// mDNS reflectors, e.g. Avahi's `enable-reflector`, repeat the records
+    /// of one network to another from their own address. Records count as
+    /// reflected when they came from an address the instance doesn't
+    /// advertise, and either arrived from several local networks or name
+    /// addresses on none of them, i.e. a device on another VLAN.
+    pub fn reflector(&self, fullname: &str, advertised: &[IpAddr]) -> Option<IpAddr> {
+        if self.subnets.is_empty() || advertised.is_empty() {
+            return None;
+        }
+        let sources = self.get(fullname);
+        let relayed = *sources.iter().find(|source| !advertised.contains(source))?;
+        let subnets: BTreeSet<_> = sources
+            .iter()
+            .filter_map(|source| self.subnet(source))
+            .collect();
+        let mut routable = advertised
+            .iter()
+            .filter(|addr| !link_local_v6(addr))
+            .peekable();
+        let off_link =
+            routable.peek().is_some() && routable.all(|addr| self.subnet(addr).is_none());
+        (subnets.len() > 1 || off_link).then_some(relayed)
+    }
+}
+
+/// A socket bound to the mDNS port alongside the backend's.
+fn shared_socket(domain: Domain, addr: SocketAddr) -> anyhow::Result<Socket> {
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    if domain == Domain::IPV6 {
+        socket.set_only_v6(true)?;
+    }
+    socket
+        .bind(&addr.into())
+        .with_context(|| format!("Failed to share {addr}"))?;
+    Ok(socket)
+}
+
+fn listen_v4() -> anyhow::Result<UdpSocket> {
+    let socket = shared_socket(
+        Domain::IPV4,
+        SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), K_MDNS_PORT),
+    )?;
+    let interfaces = if_addrs::get_if_addrs().unwrap_or_default();
+    for interface in interfaces
+        .iter()
+        .filter(|interface| !interface.is_loopback())
+    {
+        if let IfAddr::V4(v4) = &interface.addr {
+            if let Err(e) = socket.join_multicast_v4(&K_MDNS_GROUP, &v4.ip) {
+                tracing::debug!("Failed to join {K_MDNS_GROUP} on {}: {e}", v4.ip);
+            }
+        }
+    }
+    Ok(socket.into())
+}
+
+fn listen_v6() -> anyhow::Result<UdpSocket> {
+    let socket = shared_socket(
+        Domain::IPV6,
+        SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), K_MDNS_PORT),
+    )?;
+    for index in Zones::local().indexes() {
+        if let Err(e) = socket.join_multicast_v6(&K_MDNS_GROUP_V6, index) {
+            tracing::debug!("Failed to join {K_MDNS_GROUP_V6} on interface {index}: {e}");
+        }
+    }
+    Ok(socket.into())
+}
@@ -0,0 +1,157 @@
+use std::io::Write;
+use std::time::Duration;
+
+use clap_derive::{Args, ValueEnum};
+use serde::Serialize;
+
+use crate::backend::BackendKind;
+use crate::browse::Headless;
+use crate::config::Profile;
+use crate::error::Error;
+use crate::events::QueueSettings;
+use crate::model::Model;
+use crate::units;
+
+/// Service types listed in the tooltip, the most populated first.
+const K_TOOLTIP_TYPES: usize = 10;
+
+/// How the summary line is printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// `mDNS: 42 svc / 127 inst / 3 new`, e.g. for polybar
+    #[default]
+    Text,
+    /// JSON object with `text`, `tooltip` and `class`, for a waybar custom module
+    Waybar,
+}
+
+#[derive(Args, Debug)]
+pub struct StatusbarOpts {
+    #[arg(long, value_enum, default_value_t)]
+    /// How to print the summary
+    format: Format,
+    #[arg(long, value_parser = units::nonzero_duration, default_value = "2s")]
+    /// How often the summary is updated, e.g. 2s
+    interval: Duration,
+    #[arg(long, value_parser = units::duration, default_value = "5m")]
+    /// How recently an instance must have been seen first to count as new
+    new_within: Duration,
+}
+
+/// Counts summarized on the line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Summary {
+    services: usize,
+    instances: usize,
+    new: usize,
+    /// Instances of the most populated service types
+    types: Vec<(String, usize)>,
+}
+
+impl Summary {
+    fn of(model: &Model, new_within: Duration) -> Self {
+        let services = model.services().len();
+        let now = model.now();
+        let mut instances = 0;
+        let mut new = 0;
+        let mut types = Vec::new();
+        for (service_type, list) in model.instances().iter() {
+            instances += list.len();
+            new += list
+                .items()
+                .filter(|info| now.saturating_duration_since(info.first_seen) <= new_within)
+                .count();
+            if !list.is_empty() {
+                types.push((service_type.clone(), list.len()));
+            }
+        }
+        types.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        types.truncate(K_TOOLTIP_TYPES);
+        Self {
+            services,
+            instances,
+            new,
+            types,
+        }
+    }
+
+    fn text(&self) -> String {
+        format!(
+            "mDNS: {} svc / {} inst / {} new",
+            self.services, self.instances, self.new
+        )
+    }
+}
+
+/// What a waybar custom module with `"return-type": "json"` reads.
+#[derive(Debug, Serialize)]
+struct Waybar {
+    text: String,
+    tooltip: String,
+    /// `empty`, `new` or `idle`, for styling
+    class: &'static str,
+}
+
+impl From<&Summary> for Waybar {
+    fn from(summary: &Summary) -> Self {
+        let tooltip = if summary.types.is_empty() {
+            "Nothing resolved yet".to_string()
+        } else {
+            summary
+                .types
+                .iter()
+                .map(|(service_type, instances)| format!("{instances}\t{service_type}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        Self {
+            text: summary.text(),
+            tooltip,
+            class: if summary.instances == 0 {
+                "empty"
+            } else if summary.new > 0 {
+                "new"
+            } else {
+                "idle"
+            },
+        }
+    }
+}
+
+/// Browse like `browse` does, printing a single line summary of what has
+/// been found whenever it changes, for a status bar, e.g. waybar or polybar.
+pub fn run(
+    opts: &StatusbarOpts,
+    profile: &Profile,
+    backend: BackendKind,
+    queue_settings: QueueSettings,
+) -> Result<(), Error> {
+    let headless = Headless::start(profile, backend, queue_settings)?;
+
+    let mut stdout = std::io::stdout().lock();
+    let mut printed = None;
+    let result = loop {
+        let summary = Summary::of(&headless.model, opts.new_within);
+        if printed.as_ref() != Some(&summary) {
+            let line = match opts.format {
+                Format::Text => summary.text(),
+                Format::Waybar => match serde_json::to_string(&Waybar::from(&summary)) {
+                    Ok(line) => line,
+                    Err(e) => break Err(anyhow::anyhow!("Failed to serialize the summary: {e}")),
+                },
+            };
+            // The bar went away, e.g. it was reloaded
+            if writeln!(stdout, "{line}")
+                .and_then(|()| stdout.flush())
+                .is_err()
+            {
+                break Ok(());
+            }
+            printed = Some(summary);
+        }
+        std::thread::sleep(opts.interval);
+    };
+
+    headless.stop()?;
+    result.map_err(Error::Other)
+}
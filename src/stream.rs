@@ -0,0 +1,87 @@
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use anyhow::Context;
+
+use crate::eventlog::{Event, EventLog};
+use crate::schema;
+
+/// Events held while the collector can't be reached, newer ones are dropped
+/// beyond this many.
+const K_BACKLOG: usize = 10_000;
+/// First wait before reconnecting, doubled after every failed attempt.
+const K_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const K_BACKOFF_MAX: Duration = Duration::from_secs(60);
+const K_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Stream the events of `log` as NDJSON to the collector at `addr`, e.g.
+/// `collector.lan:9000`, in the background.
+///
+/// Meant for running on several probe hosts feeding a central aggregator, so
+/// every line names the host it was seen from. The connection is kept open
+/// and re-established with a backoff when it drops, the events recorded
+/// meanwhile are sent once it is back.
+pub fn spawn(addr: String, log: &EventLog) {
+    let events = log.subscribe(K_BACKLOG);
+    let probe = dns_lookup::get_hostname().ok();
+    std::thread::spawn(move || {
+        let mut backoff = K_BACKOFF_MIN;
+        // Sent when the connection dropped, retried on the next one
+        let mut pending: Option<Event> = None;
+        loop {
+            let mut stream = match connect(&addr) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    tracing::warn!("{e:#}, retrying in {backoff:?}");
+                    std::thread::sleep(backoff);
+                    backoff = (backoff * 2).min(K_BACKOFF_MAX);
+                    continue;
+                }
+            };
+            tracing::info!("Streaming events to {addr}");
+            backoff = K_BACKOFF_MIN;
+            loop {
+                let event = match pending.take() {
+                    Some(event) => event,
+                    None => match events.recv() {
+                        Ok(event) => event,
+                        // The log is gone, and so is the session
+                        Err(_) => return,
+                    },
+                };
+                let mut entry = event.entry();
+                entry.probe.clone_from(&probe);
+                let line = match schema::to_json_line(&entry) {
+                    Ok(line) => line + "\n",
+                    Err(e) => {
+                        tracing::warn!("Failed to serialize an event: {e}");
+                        continue;
+                    }
+                };
+                if let Err(e) = stream.write_all(line.as_bytes()) {
+                    tracing::warn!("Lost the connection to {addr}: {e}");
+                    pending = Some(event);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+fn connect(addr: &str) -> anyhow::Result<TcpStream> {
+    let addrs = addr
+        .to_socket_addrs()
+        .with_context(|| format!("Failed to resolve {addr}"))?;
+    let mut error = None;
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, K_CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => error = Some(e),
+        }
+    }
+    match error {
+        Some(e) => Err(e).with_context(|| format!("Failed to connect to {addr}")),
+        None => anyhow::bail!("{addr} resolved to no address"),
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use ratatui::{prelude::*, widgets::*};
+
+use crate::colors::theme;
+
+const K_TOAST_DURATION: Duration = Duration::from_secs(4);
+const K_MAX_TOASTS: usize = 4;
+const K_TOAST_WIDTH: u16 = 48;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl Level {
+    fn color(&self) -> Color {
+        match self {
+            Level::Info => theme().selected_fg,
+            Level::Success => theme().success,
+            Level::Warning => theme().search_border,
+            Level::Error => theme().error,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Level::Info => "Info",
+            Level::Success => "Done",
+            Level::Warning => "Warning",
+            Level::Error => "Error",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Toast {
+    level: Level,
+    message: String,
+    created: Instant,
+}
+
+/// Transient notifications shown on top of the UI.
+///
+/// Toasts never take the focus and disappear on their own after a few
+/// seconds, so they can acknowledge user actions without getting in the way.
+#[derive(Debug, Default)]
+pub struct Toasts {
+    toasts: VecDeque<Toast>,
+}
+
+impl Toasts {
+    pub fn push(&mut self, level: Level, message: impl Into<String>) {
+        let message = message.into();
+        tracing::debug!("Toast ({level:?}): {message}");
+        self.toasts.push_back(Toast {
+            level,
+            message,
+            created: Instant::now(),
+        });
+        while self.toasts.len() > K_MAX_TOASTS {
+            self.toasts.pop_front();
+        }
+    }
+
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.push(Level::Info, message);
+    }
+
+    pub fn success(&mut self, message: impl Into<String>) {
+        self.push(Level::Success, message);
+    }
+
+    pub fn warning(&mut self, message: impl Into<String>) {
+        self.push(Level::Warning, message);
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.push(Level::Error, message);
+    }
+
+    /// Drop the toasts that have been shown long enough.
+    pub fn expire(&mut self) {
+        self.toasts
+            .retain(|toast| toast.created.elapsed() < K_TOAST_DURATION);
+    }
+}
+
+impl Widget for &Toasts {
+    /// Stack the toasts in the bottom right corner of `area`.
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let width = K_TOAST_WIDTH.min(area.width);
+        let mut bottom = area.bottom();
+        for toast in self.toasts.iter().rev() {
+            let lines = textwrap::wrap(&toast.message, (width as usize).saturating_sub(4));
+            let height = lines.len() as u16 + 2;
+            if bottom < area.top() + height {
+                break;
+            }
+            bottom -= height;
+            let toast_area = Rect::new(area.right() - width, bottom, width, height);
+
+            Clear.render(toast_area, buf);
+            Paragraph::new(
+                lines
+                    .into_iter()
+                    .map(|line| Line::from(line.into_owned()))
+                    .collect::<Vec<_>>(),
+            )
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .border_style(Style::new().fg(toast.level.color()))
+                    .title(toast.level.title())
+                    .title_style(Style::new().bold().fg(toast.level.color()))
+                    .padding(Padding::horizontal(1))
+                    .bg(theme().header_bg),
+            )
+            .fg(theme().text)
+            .render(toast_area, buf);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+
+use mdns_sd::ServiceInfo;
+use serde::Deserialize;
+
+use crate::domain;
+use crate::schema::TxtChange;
+
+/// Config rule alerting when a TXT key of a re-resolved instance changes.
+///
+/// ```toml
+/// [[txt_alerts]]
+/// key = "sf"
+/// type = "_hap._tcp"
+/// ```
+///
+/// Without a `type`, the key is watched on every service type.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TxtAlert {
+    pub key: String,
+    #[serde(rename = "type")]
+    pub service_type: Option<String>,
+}
+
+impl TxtAlert {
+    fn matches(&self, service_type: &str, change: &TxtChange) -> bool {
+        change.key.eq_ignore_ascii_case(&self.key)
+            && self.service_type.as_deref().map_or(true, |watched| {
+                domain::qualify(watched) == domain::base_type(service_type)
+            })
+    }
+}
+
+fn properties(info: &ServiceInfo) -> BTreeMap<String, String> {
+    info.get_properties()
+        .iter()
+        .map(|property| (property.key().to_string(), property.val_str().to_string()))
+        .collect()
+}
+
+/// TXT entries added, removed or changed from `old` to `new`.
+pub fn changes(old: &ServiceInfo, new: &ServiceInfo) -> Vec<TxtChange> {
+    let old = properties(old);
+    let mut new = properties(new);
+    let mut changes = Vec::new();
+    for (key, old_value) in old {
+        match new.remove(&key) {
+            Some(new_value) if new_value == old_value => {}
+            new_value => changes.push(TxtChange {
+                key,
+                old: Some(old_value),
+                new: new_value,
+            }),
+        }
+    }
+    changes.extend(new.into_iter().map(|(key, new_value)| TxtChange {
+        key,
+        old: None,
+        new: Some(new_value),
+    }));
+    changes
+}
+
+/// The changes of `service_type` that `alerts` watch.
+pub fn alerting<'a>(
+    alerts: &[TxtAlert],
+    service_type: &str,
+    changes: &'a [TxtChange],
+) -> Vec<&'a TxtChange> {
+    changes
+        .iter()
+        .filter(|change| {
+            alerts
+                .iter()
+                .any(|alert| alert.matches(service_type, change))
+        })
+        .collect()
+}
@@ -0,0 +1,64 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::model::Order;
+
+const K_UI_STATE_FILE: &str = "ui_state.json";
+
+/// Where the TUI was left, restored on the next start with the same
+/// profile so that it doesn't take navigating back there.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiState {
+    /// Service type selected in the Services pane
+    pub service: Option<String>,
+    /// Whether the Instances pane, or the Info pane, had the focus
+    pub instances_focused: bool,
+    pub zoomed: bool,
+    pub order: Order,
+    /// Search of the Services pane
+    pub services_search: Option<String>,
+    /// Searches of the Instances panes, by service type
+    pub instances_searches: BTreeMap<String, String>,
+}
+
+/// `$XDG_DATA_HOME/discovery-rs/ui_state.json` or the platform equivalent.
+fn path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("discovery-rs").join(K_UI_STATE_FILE))
+}
+
+/// The states saved so far, by profile.
+fn load_all() -> BTreeMap<String, UiState> {
+    let Some(contents) = path().and_then(|path| std::fs::read_to_string(path).ok()) else {
+        return BTreeMap::new();
+    };
+    serde_json::from_str(&contents).unwrap_or_else(|e| {
+        tracing::warn!("Ignoring the saved UI state: {e}");
+        BTreeMap::new()
+    })
+}
+
+impl UiState {
+    /// The state saved for `profile`, if any.
+    pub fn load(profile: &str) -> Option<Self> {
+        load_all().remove(profile)
+    }
+
+    /// Save the state as the one of `profile`, keeping the other profiles'.
+    pub fn save(&self, profile: &str) -> anyhow::Result<()> {
+        let Some(path) = path() else {
+            return Ok(());
+        };
+        let mut states = load_all();
+        states.insert(profile.to_string(), self.clone());
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(&states)? + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
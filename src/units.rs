@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+/// Parse a command line duration like `90s`, `2m` or `1h 30m`.
+///
+/// Bare numbers are seconds, as flags used to take them. Meant as a clap
+/// `value_parser`, so that errors point at the offending flag.
+pub fn duration(value: &str) -> Result<Duration, String> {
+    if let Ok(secs) = value.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+    humantime::parse_duration(value).map_err(|e| format!("{e}, expected e.g. 90s, 2m or 1h 30m"))
+}
+
+/// Parse a duration like [`duration`], rejecting zero, e.g. for periods
+/// that would spin otherwise.
+pub fn nonzero_duration(value: &str) -> Result<Duration, String> {
+    match duration(value)? {
+        Duration::ZERO => Err("must be longer than 0, e.g. 1s".to_string()),
+        duration => Ok(duration),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(duration("1h 30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(duration("0"), Ok(Duration::ZERO));
+        assert!(duration("soon").is_err());
+    }
+
+    #[test]
+    fn nonzero_durations() {
+        assert_eq!(nonzero_duration("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(nonzero_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert!(nonzero_duration("0").is_err());
+        assert!(nonzero_duration("0s").is_err());
+        assert!(nonzero_duration("soon").is_err());
+    }
+}
@@ -0,0 +1,141 @@
+use flume::Receiver;
+
+#[cfg(feature = "update-check")]
+use crates_io::latest_version;
+
+/// The query of crates.io, left out of builds without the `update-check`
+/// feature along with its HTTP client.
+#[cfg(feature = "update-check")]
+mod crates_io {
+    use std::time::Duration;
+
+    use anyhow::Context;
+    use serde::Deserialize;
+
+    const K_CRATES_IO_URL: &str = "https://crates.io/api/v1/crates/discovery-rs";
+    const K_TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[derive(Deserialize)]
+    struct CrateResponse {
+        #[serde(rename = "crate")]
+        krate: Crate,
+    }
+
+    #[derive(Deserialize)]
+    struct Crate {
+        max_stable_version: String,
+    }
+
+    /// Latest released version on crates.io.
+    pub fn latest_version() -> anyhow::Result<String> {
+        // crates.io rejects requests without a user agent
+        let body = ureq::get(K_CRATES_IO_URL)
+            .set(
+                "User-Agent",
+                concat!("discovery-rs/", env!("CARGO_PKG_VERSION")),
+            )
+            .timeout(K_TIMEOUT)
+            .call()
+            .context("Failed to query crates.io")?
+            .into_string()?;
+        let response: CrateResponse = serde_json::from_str(&body)?;
+        Ok(response.krate.max_stable_version)
+    }
+}
+
+#[cfg(not(feature = "update-check"))]
+fn latest_version() -> anyhow::Result<String> {
+    anyhow::bail!("This build doesn't include the update-check feature")
+}
+
+/// Numeric `major.minor.patch` of `version`, e.g. `1.2.3` or `v1.2.3`,
+/// ignoring any pre-release or build metadata.
+fn parse(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let version = version.split(['-', '+']).next()?;
+    let mut parts = version.split('.').map(str::parse);
+    Some((
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    ))
+}
+
+/// Whether `latest` is a newer version than `current`, never when either
+/// can't be told.
+fn newer(latest: &str, current: &str) -> bool {
+    matches!((parse(latest), parse(current)), (Some(latest), Some(current)) if latest > current)
+}
+
+/// Check for a newer release in the background.
+///
+/// The channel yields the latest version once, and only if it is newer than
+/// the running one. Failures are logged and otherwise ignored, an update
+/// check must never get in the way.
+pub fn check() -> Receiver<String> {
+    let (tx, rx) = flume::bounded(1);
+    std::thread::spawn(move || match latest_version() {
+        Ok(latest) => {
+            tracing::info!("Latest released version: {latest}");
+            if newer(&latest, env!("CARGO_PKG_VERSION")) {
+                let _ = tx.send(latest);
+            }
+        }
+        Err(e) => tracing::warn!("Update check failed: {e:#}"),
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions() {
+        assert_eq!(parse("1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse("v1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse("0.10.0"), Some((0, 10, 0)));
+        assert_eq!(parse("1.2.3-rc.1"), Some((1, 2, 3)));
+        assert_eq!(parse("1.2.3+build.5"), Some((1, 2, 3)));
+        assert_eq!(parse("1.2.3-beta+exp.sha.5114f85"), Some((1, 2, 3)));
+    }
+
+    #[test]
+    fn invalid_versions() {
+        for version in [
+            "1.2",
+            "1",
+            "",
+            "v",
+            "latest",
+            "1.x.3",
+            "1..3",
+            "-1.2.3",
+            "1.2.3beta",
+        ] {
+            assert_eq!(parse(version), None, "{version}");
+        }
+    }
+
+    #[test]
+    fn newer_versions() {
+        assert!(newer("1.2.4", "1.2.3"));
+        assert!(newer("1.3.0", "1.2.9"));
+        assert!(newer("2.0.0", "1.99.99"));
+        // Numerically, not as text
+        assert!(newer("0.10.0", "0.9.0"));
+        assert!(newer("v1.2.4", "1.2.3"));
+    }
+
+    #[test]
+    fn not_newer_versions() {
+        assert!(!newer("1.2.3", "1.2.3"));
+        assert!(!newer("1.2.2", "1.2.3"));
+        assert!(!newer("0.9.0", "0.10.0"));
+        // A pre-release of the running version isn't an update
+        assert!(!newer("1.2.3-rc.1", "1.2.3"));
+        assert!(!newer("garbage", "1.2.3"));
+        assert!(!newer("1.2.4", "garbage"));
+        assert!(!newer("1.3", "1.2.3"));
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::colors::theme;
+use crate::host;
+use crate::model::Model;
+use crate::widget::DiscoveryWidget;
+
+/// Which instances are visible on which local interface.
+///
+/// An instance counts as visible on an interface when one of its addresses
+/// is on that interface's network. Instances missing from some interfaces
+/// are listed first and highlighted, they usually point at IGMP snooping or
+/// a misbehaving mDNS reflector. Like the host card, a snapshot taken when
+/// opened.
+#[derive(Debug)]
+pub struct Visibility {
+    interfaces: Vec<String>,
+    /// Instance fullnames and the interfaces they are visible on
+    instances: Vec<(String, BTreeSet<String>)>,
+    scroll: usize,
+}
+
+impl Visibility {
+    pub fn collect(model: &Model) -> Self {
+        let mut instances: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for list in model.instances().values() {
+            for info in list.items() {
+                instances
+                    .entry(info.info.get_fullname().to_string())
+                    .or_default()
+                    .extend(
+                        host::by_interface(info.addresses().into_iter().collect())
+                            .into_keys()
+                            // Not on any local network, e.g. when reflected
+                            .filter(|interface| interface != "unknown"),
+                    );
+            }
+        }
+        let interfaces: BTreeSet<_> = instances.values().flatten().cloned().collect();
+        let mut instances: Vec<_> = instances.into_iter().collect();
+        // Asymmetric ones first, keeping the names sorted otherwise
+        instances.sort_by_key(|(_, visible)| visible.len() == interfaces.len());
+        Self {
+            interfaces: interfaces.into_iter().collect(),
+            instances,
+            scroll: 0,
+        }
+    }
+
+    fn asymmetric(&self) -> usize {
+        self.instances
+            .iter()
+            .filter(|(_, visible)| visible.len() < self.interfaces.len())
+            .count()
+    }
+}
+
+impl DiscoveryWidget for Visibility {
+    fn title(&self) -> String {
+        format!(
+            "Visibility by interface, {} of {} asymmetric",
+            self.asymmetric(),
+            self.instances.len()
+        )
+    }
+
+    fn controls(&self) -> String {
+        "Use ↓↑ to scroll, Esc to close".to_string()
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        match key_event.code {
+            KeyCode::Down => {
+                self.scroll = (self.scroll + 1).min(self.instances.len().saturating_sub(1))
+            }
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool) {
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(theme().selected_fg).bold())
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .padding(Padding::horizontal(1))
+            .fg(theme().text)
+            .bg(theme().header_bg);
+
+        if self.interfaces.len() < 2 {
+            Paragraph::new("Instances are only visible on a single interface, browse more of them to compare, e.g. with --interface all")
+                .wrap(Wrap { trim: true })
+                .block(block)
+                .render(area, buf);
+            return;
+        }
+
+        let header = Row::new(
+            std::iter::once(Cell::new("Instance")).chain(
+                self.interfaces
+                    .iter()
+                    .map(|interface| Cell::new(interface.clone())),
+            ),
+        )
+        .bold()
+        .light_cyan();
+        let rows = self
+            .instances
+            .iter()
+            .skip(self.scroll)
+            .map(|(fullname, visible)| {
+                let style = if visible.len() < self.interfaces.len() {
+                    Style::new().fg(theme().warning)
+                } else {
+                    Style::new()
+                };
+                Row::new(std::iter::once(Cell::new(fullname.clone())).chain(
+                    self.interfaces.iter().map(|interface| {
+                        Cell::new(if visible.contains(interface) {
+                            "✓"
+                        } else {
+                            "✗"
+                        })
+                    }),
+                ))
+                .style(style)
+            });
+        let widths = std::iter::once(Constraint::Min(20))
+            .chain(
+                self.interfaces
+                    .iter()
+                    .map(|interface| Constraint::Length(interface.chars().count().max(3) as u16)),
+            )
+            .collect::<Vec<_>>();
+        Widget::render(
+            Table::new(rows, widths)
+                .header(header)
+                .column_spacing(2)
+                .block(block),
+            area,
+            buf,
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+
+use anyhow::Context;
+use if_addrs::IfAddr;
+
+/// The discard port, which wake-on-LAN packets are usually sent to.
+const K_WOL_PORT: u16 = 9;
+
+/// Parse a hardware address written as `aa:bb:cc:dd:ee:ff` or with dashes.
+fn parse_mac(mac: &str) -> anyhow::Result<[u8; 6]> {
+    let bytes = mac
+        .split([':', '-'])
+        .map(|byte| u8::from_str_radix(byte, 16))
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+        .and_then(|bytes| <[u8; 6]>::try_from(bytes).ok());
+    bytes.with_context(|| format!("Invalid MAC address \"{mac}\""))
+}
+
+/// Six `0xff` bytes, then the hardware address 16 times.
+fn magic_packet(mac: [u8; 6]) -> Vec<u8> {
+    let mut packet = vec![0xff; 6];
+    for _ in 0..16 {
+        packet.extend_from_slice(&mac);
+    }
+    packet
+}
+
+/// Send a wake-on-LAN magic packet to the host with the hardware address
+/// `mac`, known by `addresses`.
+///
+/// The packet is broadcast on the local networks the addresses are on, from
+/// the interface on each, or everywhere when none is. Sleeping hosts don't
+/// answer, so nothing tells whether it woke up but its services coming back.
+pub fn send(mac: &str, addresses: &[IpAddr]) -> anyhow::Result<()> {
+    let packet = magic_packet(parse_mac(mac)?);
+    let interfaces = if_addrs::get_if_addrs().unwrap_or_default();
+    let mut targets: Vec<(Ipv4Addr, Ipv4Addr)> = interfaces
+        .iter()
+        .filter_map(|interface| match &interface.addr {
+            IfAddr::V4(local) if !interface.is_loopback() => Some(local),
+            _ => None,
+        })
+        .filter(|local| {
+            let mask = u32::from(local.netmask);
+            addresses.iter().any(|addr| {
+                matches!(addr, IpAddr::V4(addr) if u32::from(*addr) & mask == u32::from(local.ip) & mask)
+            })
+        })
+        .filter_map(|local| Some((local.ip, local.broadcast?)))
+        .collect();
+    if targets.is_empty() {
+        targets.push((Ipv4Addr::UNSPECIFIED, Ipv4Addr::BROADCAST));
+    }
+    let mut sent = false;
+    for (local, broadcast) in targets {
+        let result = UdpSocket::bind(SocketAddr::new(local.into(), 0)).and_then(|socket| {
+            socket.set_broadcast(true)?;
+            socket.send_to(&packet, SocketAddr::new(broadcast.into(), K_WOL_PORT))
+        });
+        match result {
+            Ok(_) => sent = true,
+            Err(e) => tracing::warn!("Failed to broadcast the magic packet to {broadcast}: {e}"),
+        }
+    }
+    anyhow::ensure!(sent, "Failed to send the magic packet to {mac}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const K_MAC: [u8; 6] = [0xb8, 0x27, 0xeb, 0x12, 0x34, 0x56];
+
+    #[test]
+    fn mac_addresses() {
+        assert_eq!(parse_mac("b8:27:eb:12:34:56").unwrap(), K_MAC);
+        assert_eq!(parse_mac("b8-27-eb-12-34-56").unwrap(), K_MAC);
+        assert_eq!(parse_mac("B8:27:EB:12:34:56").unwrap(), K_MAC);
+    }
+
+    #[test]
+    fn invalid_mac_addresses() {
+        let error = |mac| parse_mac(mac).unwrap_err().to_string();
+        assert_eq!(
+            error("b8:27:eb:12:34"),
+            "Invalid MAC address \"b8:27:eb:12:34\""
+        );
+        assert_eq!(
+            error("b8:27:eb:12:34:56:78"),
+            "Invalid MAC address \"b8:27:eb:12:34:56:78\""
+        );
+        assert_eq!(
+            error("gg:27:eb:12:34:56"),
+            "Invalid MAC address \"gg:27:eb:12:34:56\""
+        );
+        assert_eq!(error(""), "Invalid MAC address \"\"");
+    }
+
+    #[test]
+    fn magic_packets() {
+        let packet = magic_packet(K_MAC);
+        assert_eq!(packet.len(), 102);
+        assert_eq!(packet[..6], [0xff; 6]);
+        assert!(packet[6..].chunks(6).all(|mac| mac == K_MAC));
+    }
+}
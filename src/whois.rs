@@ -0,0 +1,309 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use flume::Receiver;
+
+use crate::packet::{self, Record, K_TYPE_A, K_TYPE_AAAA, K_TYPE_NSEC, K_TYPE_SRV, K_TYPE_TXT};
+use crate::zone::Zones;
+
+const K_MDNS_PORT: u16 = 5353;
+const K_MDNS_GROUP: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::new(224, 0, 0, 251)), K_MDNS_PORT);
+const K_MDNS_GROUP_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+/// How long answers are waited for.
+const K_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// An answer to a one-shot query.
+#[derive(Debug, Clone)]
+pub struct Answer {
+    pub from: IpAddr,
+    pub rtt: Duration,
+    /// Types of the records answered for the instance, e.g. `SRV`
+    pub records: Vec<&'static str>,
+    /// Types the responder said the instance doesn't have, through NSEC
+    pub denied: Vec<&'static str>,
+}
+
+/// Query the SRV and TXT records of a single instance, once, in the background.
+///
+/// The query is sent from an ephemeral port, which makes responders answer
+/// by unicast (RFC 6762, section 6.7), straight back to us rather than to the
+/// whole link. Every answer received within [`K_TIMEOUT`] is reported.
+///
+/// The query goes over IPv4 unless `ipv6`, in which case it is sent to the
+/// IPv6 mDNS group on every interface with a link-local address.
+pub fn query(
+    instance: &str,
+    service_type: &str,
+    ipv6: bool,
+) -> Receiver<anyhow::Result<Vec<Answer>>> {
+    let (tx, rx) = flume::bounded(1);
+    let instance = instance.to_string();
+    let service_type = service_type.to_string();
+    std::thread::spawn(move || {
+        let _ = tx.send(run(&instance, &service_type, ipv6));
+    });
+    rx
+}
+
+fn run(instance: &str, service_type: &str, ipv6: bool) -> anyhow::Result<Vec<Answer>> {
+    let fullname = format!("{instance}.{service_type}");
+    // The instance name is a single label, dots included
+    let labels: Vec<_> = std::iter::once(instance)
+        .chain(labels(service_type))
+        .collect();
+    exchange(
+        &labels,
+        &[K_TYPE_SRV, K_TYPE_TXT],
+        ipv6,
+        K_TIMEOUT,
+        |from, rtt, records| answer(&fullname, from, rtt, records),
+    )
+}
+
+/// What a response to the query of the instance `fullname` answered, if
+/// anything: the records it has, or those an NSEC record denies.
+fn answer(fullname: &str, from: IpAddr, rtt: Duration, records: Vec<Record>) -> Option<Answer> {
+    let mut found = Vec::new();
+    let mut existing = None;
+    for (name, record_type, _, data) in records {
+        if !name.eq_ignore_ascii_case(fullname) {
+            continue;
+        }
+        let record = match record_type {
+            K_TYPE_SRV => "SRV",
+            K_TYPE_TXT => "TXT",
+            K_TYPE_NSEC => {
+                existing = packet::nsec_types(data);
+                continue;
+            }
+            _ => continue,
+        };
+        if !found.contains(&record) {
+            found.push(record);
+        }
+    }
+    let denied: Vec<_> = existing
+        .map(|existing| {
+            [(K_TYPE_SRV, "SRV"), (K_TYPE_TXT, "TXT")]
+                .into_iter()
+                .filter(|(record_type, _)| !existing.contains(record_type))
+                .map(|(_, name)| name)
+                .collect()
+        })
+        .unwrap_or_default();
+    if found.is_empty() && denied.is_empty() {
+        return None;
+    }
+    tracing::debug!("{fullname} answered from {from} in {rtt:?}: {found:?}, denied {denied:?}");
+    Some(Answer {
+        from,
+        rtt,
+        records: found,
+        denied,
+    })
+}
+
+/// Query the addresses of `hostname`, e.g. `nas.local.`, waiting up to
+/// `timeout` for the answers.
+///
+/// Like [`query`], over IPv6 only if `ipv6`. The addresses are returned in
+/// the order they were received, without duplicates.
+pub fn addresses(hostname: &str, ipv6: bool, timeout: Duration) -> anyhow::Result<Vec<IpAddr>> {
+    let labels: Vec<_> = labels(hostname).collect();
+    let answers = exchange(
+        &labels,
+        &[K_TYPE_A, K_TYPE_AAAA],
+        ipv6,
+        timeout,
+        |_, _, records| {
+            let addresses: Vec<_> = records
+                .into_iter()
+                .filter(|(name, _, _, _)| name.eq_ignore_ascii_case(hostname))
+                .filter_map(|(_, record_type, _, data)| match record_type {
+                    K_TYPE_A => Some(IpAddr::from(<[u8; 4]>::try_from(data).ok()?)),
+                    K_TYPE_AAAA => Some(IpAddr::from(<[u8; 16]>::try_from(data).ok()?)),
+                    _ => None,
+                })
+                .collect();
+            (!addresses.is_empty()).then_some(addresses)
+        },
+    )?;
+    let mut addresses: Vec<IpAddr> = Vec::new();
+    for addr in answers.into_iter().flatten() {
+        if !addresses.contains(&addr) {
+            addresses.push(addr);
+        }
+    }
+    Ok(addresses)
+}
+
+/// Labels of a domain name, without the root.
+fn labels(name: &str) -> impl Iterator<Item = &str> {
+    name.trim_end_matches('.')
+        .split('.')
+        .filter(|label| !label.is_empty())
+}
+
+/// Send a query for `types` of the name made of `labels`, and hand every
+/// response received within `timeout` to `answer`, along with where it came
+/// from and how long it took.
+///
+/// Returns what `answer` made of the responses, skipping the `None`s.
+fn exchange<T>(
+    labels: &[&str],
+    types: &[u16],
+    ipv6: bool,
+    timeout: Duration,
+    mut answer: impl FnMut(IpAddr, Duration, Vec<Record>) -> Option<T>,
+) -> anyhow::Result<Vec<T>> {
+    let id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos() as u16;
+    let query = packet::query(id, labels, types);
+    let (socket, started) = if ipv6 {
+        let socket = UdpSocket::bind("[::]:0").context("Failed to open a socket")?;
+        let zones = Zones::local().indexes();
+        if zones.is_empty() {
+            anyhow::bail!("No interface has an IPv6 link-local address");
+        }
+        let started = Instant::now();
+        for zone in zones {
+            let group = SocketAddrV6::new(K_MDNS_GROUP_V6, K_MDNS_PORT, 0, zone);
+            if let Err(e) = socket.send_to(&query, group) {
+                tracing::debug!("Failed to send the query to {group}: {e}");
+            }
+        }
+        (socket, started)
+    } else {
+        let socket = UdpSocket::bind("0.0.0.0:0").context("Failed to open a socket")?;
+        let started = Instant::now();
+        socket
+            .send_to(&query, K_MDNS_GROUP)
+            .context("Failed to send the query")?;
+        (socket, started)
+    };
+
+    let mut answers = Vec::new();
+    let mut buf = [0u8; 9000];
+    while let Some(remaining) = timeout.checked_sub(started.elapsed()) {
+        socket.set_read_timeout(Some(remaining.max(Duration::from_millis(1))))?;
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                break
+            }
+            Err(e) => return Err(e).context("Failed to receive the answers"),
+        };
+        let rtt = started.elapsed();
+        if let Some(answered) = packet::records(&buf[..len], Some(id))
+            .and_then(|records| answer(from.ip(), rtt, records))
+        {
+            answers.push(answered);
+        }
+    }
+    Ok(answers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const K_FULLNAME: &str = "Office._ipp._tcp.local.";
+
+    /// A unicast answer with the SRV and TXT records of Office.
+    #[rustfmt::skip]
+    const K_ANSWER: [u8; 87] = [
+        // ID, flags of an authoritative answer, 0 questions and 2 answers
+        0x12, 0x34, 0x84, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00,
+        // 12: Office._ipp._tcp.local. SRV, cache-flush IN, TTL 120
+        6, b'O', b'f', b'f', b'i', b'c', b'e', 4, b'_', b'i', b'p', b'p',
+        4, b'_', b't', b'c', b'p', 5, b'l', b'o', b'c', b'a', b'l', 0,
+        0x00, 0x21, 0x80, 0x01, 0x00, 0x00, 0x00, 0x78, 0x00, 0x10,
+        // 46: priority 0, weight 0, port 631 and printer, then a pointer to local. at 29
+        0x00, 0x00, 0x00, 0x00, 0x02, 0x77,
+        7, b'p', b'r', b'i', b'n', b't', b'e', b'r', 0xc0, 0x1d,
+        // 62: a pointer to 12, TXT, cache-flush IN, TTL 4500
+        0xc0, 0x0c, 0x00, 0x10, 0x80, 0x01, 0x00, 0x00, 0x11, 0x94, 0x00, 0x0d,
+        12, b'r', b'p', b'=', b'i', b'p', b'p', b'/', b'p', b'r', b'i', b'n', b't',
+    ];
+
+    /// A unicast answer denying both records, as Office is going away.
+    #[rustfmt::skip]
+    const K_DENIAL: [u8; 56] = [
+        0x12, 0x34, 0x84, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+        // 12: Office._ipp._tcp.local. NSEC, cache-flush IN, TTL 120
+        6, b'O', b'f', b'f', b'i', b'c', b'e', 4, b'_', b'i', b'p', b'p',
+        4, b'_', b't', b'c', b'p', 5, b'l', b'o', b'c', b'a', b'l', 0,
+        0x00, 0x2f, 0x80, 0x01, 0x00, 0x00, 0x00, 0x78, 0x00, 0x0a,
+        // 46: a pointer to 12 as the next name, and only NSEC in window 0
+        0xc0, 0x0c, 0x00, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+    ];
+
+    /// A unicast answer with an empty TXT record, and an NSEC record saying
+    /// there isn't any SRV record.
+    #[rustfmt::skip]
+    const K_PARTIAL: [u8; 69] = [
+        0x12, 0x34, 0x84, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00,
+        // 12: Office._ipp._tcp.local. TXT, cache-flush IN, TTL 4500
+        6, b'O', b'f', b'f', b'i', b'c', b'e', 4, b'_', b'i', b'p', b'p',
+        4, b'_', b't', b'c', b'p', 5, b'l', b'o', b'c', b'a', b'l', 0,
+        0x00, 0x10, 0x80, 0x01, 0x00, 0x00, 0x11, 0x94, 0x00, 0x01, 0x00,
+        // 47: a pointer to 12, NSEC, cache-flush IN, TTL 120
+        0xc0, 0x0c, 0x00, 0x2f, 0x80, 0x01, 0x00, 0x00, 0x00, 0x78, 0x00, 0x0a,
+        // 59: a pointer to 12 as the next name, TXT and NSEC in window 0
+        0xc0, 0x0c, 0x00, 0x06, 0x00, 0x00, 0x80, 0x00, 0x00, 0x01,
+    ];
+
+    fn answered(response: &[u8], fullname: &str) -> Option<(Vec<&'static str>, Vec<&'static str>)> {
+        let from = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7));
+        let records = packet::records(response, Some(0x1234)).unwrap();
+        let answer = answer(fullname, from, Duration::from_millis(3), records)?;
+        assert_eq!(answer.from, from);
+        Some((answer.records, answer.denied))
+    }
+
+    #[test]
+    fn answers() {
+        assert_eq!(
+            answered(&K_ANSWER, K_FULLNAME),
+            Some((vec!["SRV", "TXT"], vec![]))
+        );
+        // Names are told apart regardless of case
+        assert_eq!(
+            answered(&K_ANSWER, "office._IPP._tcp.local."),
+            Some((vec!["SRV", "TXT"], vec![]))
+        );
+    }
+
+    #[test]
+    fn denials() {
+        assert_eq!(
+            answered(&K_DENIAL, K_FULLNAME),
+            Some((vec![], vec!["SRV", "TXT"]))
+        );
+        assert_eq!(
+            answered(&K_PARTIAL, K_FULLNAME),
+            Some((vec!["TXT"], vec!["SRV"]))
+        );
+    }
+
+    #[test]
+    fn no_answers() {
+        // About another instance
+        assert_eq!(answered(&K_ANSWER, "Kitchen._ipp._tcp.local."), None);
+        assert_eq!(answered(&K_DENIAL, "Kitchen._ipp._tcp.local."), None);
+        // Without any record
+        let empty = [
+            0x12, 0x34, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(answered(&empty, K_FULLNAME), None);
+    }
+}
@@ -0,0 +1,241 @@
+use std::fmt::Write as _;
+use std::io::IsTerminal;
+use std::path::Path;
+
+use anyhow::Context;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::{prelude::*, widgets::*};
+
+use crate::colors::{self, theme, Theme};
+use crate::config::Config;
+use crate::utils::centered_rect;
+use crate::widget::DiscoveryWidget;
+
+/// A question of the wizard and its choices, as labels and config values.
+#[derive(Debug)]
+struct Step {
+    question: &'static str,
+    choices: Vec<(String, String)>,
+    selected: usize,
+}
+
+impl Step {
+    fn value(&self) -> &str {
+        &self.choices[self.selected].1
+    }
+}
+
+fn yes_no(default: bool) -> Vec<(String, String)> {
+    let choices = [
+        ("Yes".to_string(), "true".to_string()),
+        ("No".to_string(), "false".to_string()),
+    ];
+    if default {
+        choices.into()
+    } else {
+        choices.into_iter().rev().collect()
+    }
+}
+
+/// First-run questions, answered into the initial config file.
+///
+/// Only asked when there is no config file yet, so that users who never
+/// pass flags still get to pick the settings that matter most. The theme is
+/// previewed while choosing it.
+#[derive(Debug)]
+pub struct Wizard {
+    steps: Vec<Step>,
+    current: usize,
+    /// Answered, or skipped when `None`
+    done: Option<bool>,
+}
+
+impl Wizard {
+    fn new() -> Self {
+        let mut interfaces = vec![("All interfaces".to_string(), "all".to_string())];
+        for interface in if_addrs::get_if_addrs()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|interface| !interface.is_loopback())
+        {
+            match interfaces
+                .iter_mut()
+                .find(|(_, name)| *name == interface.name)
+            {
+                Some((label, _)) => {
+                    let _ = write!(label, ", {}", interface.ip());
+                }
+                None => interfaces.push((
+                    format!("{} {}", interface.name, interface.ip()),
+                    interface.name.clone(),
+                )),
+            }
+        }
+        Self {
+            steps: vec![
+                Step {
+                    question: "Which interface should services be discovered on?",
+                    choices: interfaces,
+                    selected: 0,
+                },
+                Step {
+                    question: "Which theme do you prefer?",
+                    choices: vec![
+                        ("Dark".to_string(), "default".to_string()),
+                        ("Light".to_string(), "light".to_string()),
+                    ],
+                    selected: 0,
+                },
+                Step {
+                    question: "Remember the recently used queries across restarts?",
+                    choices: yes_no(true),
+                    selected: 0,
+                },
+                Step {
+                    question: "Check for a newer release on startup? It is the only request made to the internet.",
+                    choices: yes_no(false),
+                    selected: 0,
+                },
+            ],
+            current: 0,
+            done: None,
+        }
+    }
+
+    /// The config file the answers make up.
+    fn config(&self) -> String {
+        let [interface, theme, remember_queries, update_check] = &self.steps[..] else {
+            unreachable!("The wizard asks four questions");
+        };
+        let mut config = String::new();
+        let _ = writeln!(
+            config,
+            "# Written by the first-run wizard, every setting is described in the README"
+        );
+        let _ = writeln!(config, "default_profile = \"default\"");
+        let _ = writeln!(config, "remember_queries = {}", remember_queries.value());
+        let _ = writeln!(config, "update_check = {}", update_check.value());
+        let _ = writeln!(config, "\n[profiles.default]");
+        let _ = writeln!(config, "interfaces = [\"{}\"]", interface.value());
+        let _ = writeln!(config, "theme = \"{}\"", theme.value());
+        config
+    }
+
+    fn preview_theme(&self) {
+        if let Some(theme) = Theme::named(self.steps[1].value()) {
+            colors::set_theme(theme);
+        }
+    }
+}
+
+impl DiscoveryWidget for Wizard {
+    fn title(&self) -> String {
+        format!(
+            "Welcome to discovery-rs ({}/{})",
+            self.current + 1,
+            self.steps.len()
+        )
+    }
+
+    fn controls(&self) -> String {
+        "Use ↓↑ to choose, ↵ to answer, Backspace to go back, Esc to skip and keep the defaults"
+            .to_string()
+    }
+
+    fn process_key_event(&mut self, key_event: &KeyEvent) {
+        let step = &mut self.steps[self.current];
+        let len = step.choices.len();
+        match key_event.code {
+            KeyCode::Down => step.selected = (step.selected + 1) % len,
+            KeyCode::Up => step.selected = (step.selected + len - 1) % len,
+            KeyCode::Enter if self.current + 1 == self.steps.len() => self.done = Some(true),
+            KeyCode::Enter => self.current += 1,
+            KeyCode::Backspace | KeyCode::Left => self.current = self.current.saturating_sub(1),
+            KeyCode::Esc => self.done = Some(false),
+            _ => {}
+        }
+        self.preview_theme();
+    }
+
+    fn render(&self, area: Rect, buf: &mut Buffer, _selected: bool) {
+        Block::new().bg(theme().normal_row).render(area, buf);
+        let dialog_area = centered_rect(60, 50, area);
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(Style::new().fg(theme().selected_fg).bold())
+            .title_alignment(Alignment::Center)
+            .title(self.title())
+            .title_style(Style::new().bold())
+            .padding(Padding::horizontal(1))
+            .fg(theme().text)
+            .bg(theme().header_bg);
+        let inner_area = block.inner(dialog_area);
+        Clear.render(dialog_area, buf);
+        block.render(dialog_area, buf);
+
+        let step = &self.steps[self.current];
+        let [question_area, choices_area, controls_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(2),
+        ])
+        .areas(inner_area);
+        Paragraph::new(step.question)
+            .bold()
+            .wrap(Wrap { trim: true })
+            .render(question_area, buf);
+        let items: Vec<_> = step
+            .choices
+            .iter()
+            .map(|(label, _)| ListItem::new(label.clone()))
+            .collect();
+        let mut state = ListState::default().with_selected(Some(step.selected));
+        StatefulWidget::render(
+            List::new(items)
+                .highlight_style(Style::new().bold().reversed().fg(theme().selected_fg))
+                .highlight_symbol(">")
+                .highlight_spacing(HighlightSpacing::Always),
+            choices_area,
+            buf,
+            &mut state,
+        );
+        Paragraph::new(self.controls())
+            .dark_gray()
+            .wrap(Wrap { trim: true })
+            .render(controls_area, buf);
+    }
+}
+
+/// Whether the wizard should run: no config file yet, and someone at the
+/// terminal to answer it.
+pub fn needed() -> bool {
+    std::io::stdin().is_terminal()
+        && std::io::stdout().is_terminal()
+        && Config::default_path().is_some_and(|path| !path.exists())
+}
+
+/// Ask the first-run questions on `terminal` and write the answers to the
+/// config file at `path`.
+///
+/// Skipping writes the defaults, so that the wizard isn't asked again.
+pub fn run(mut terminal: Terminal<impl Backend>, path: &Path) -> anyhow::Result<()> {
+    let mut wizard = Wizard::new();
+    while wizard.done.is_none() {
+        terminal.draw(|frame| wizard.render(frame.size(), frame.buffer_mut(), true))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                wizard.process_key_event(&key);
+            }
+        }
+    }
+    if wizard.done == Some(false) {
+        wizard = Wizard::new();
+        wizard.preview_theme();
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    std::fs::write(path, wizard.config())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
@@ -0,0 +1,521 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use flume::{Receiver, Sender};
+use if_addrs::IfAddr;
+use mdns_sd::{ServiceEvent, ServiceInfo};
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::zone::Zones;
+
+/// Pseudo service type the WS-Discovery endpoints are listed under.
+pub const K_SERVICE_TYPE: &str = "_wsd._udp.local.";
+const K_WSD_PORT: u16 = 3702;
+const K_WSD_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const K_WSD_GROUP_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xc);
+/// How often the endpoints are probed again, which keeps them from expiring
+const K_PROBE_INTERVAL: Duration = Duration::from_secs(300);
+const K_POLL: Duration = Duration::from_millis(500);
+const K_ACTION_PREFIX: &str = "http://schemas.xmlsoap.org/ws/2005/04/discovery/";
+
+/// An endpoint as announced by Hello or found by a Probe.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Endpoint {
+    /// Endpoint reference, usually `urn:uuid:…`
+    address: String,
+    types: Vec<String>,
+    scopes: Vec<String>,
+    xaddrs: Vec<String>,
+}
+
+impl Endpoint {
+    /// Name it is listed under: the ONVIF name scope, or its first type,
+    /// along with where it is.
+    fn instance(&self, host: &str) -> String {
+        let name = self
+            .scopes
+            .iter()
+            .find_map(|scope| scope.strip_prefix("onvif://www.onvif.org/name/"))
+            .map(percent_decode)
+            .or_else(|| {
+                self.types
+                    .first()
+                    .map(|name| name.rsplit(':').next().unwrap_or(name).to_string())
+            })
+            .unwrap_or_else(|| "Endpoint".to_string());
+        format!("{name} ({host})")
+    }
+
+    /// Host and port of the first transport address, or `from`.
+    fn target(&self, from: IpAddr) -> (String, Option<IpAddr>, u16) {
+        let Some(xaddr) = self.xaddrs.first() else {
+            return (from.to_string(), Some(from), 0);
+        };
+        let (scheme, rest) = xaddr.split_once("://").unwrap_or(("http", xaddr));
+        let authority = rest.split('/').next().unwrap_or_default();
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !host.ends_with(':') && port.parse::<u16>().is_ok() => {
+                (host, port.parse().unwrap_or_default())
+            }
+            _ => (authority, if scheme == "https" { 443 } else { 80 }),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let addr = host.parse().ok().or(Some(from));
+        (host.to_string(), addr, port)
+    }
+
+    fn resolved(&self, from: IpAddr) -> anyhow::Result<ServiceInfo> {
+        let (host, addr, port) = self.target(from);
+        let properties = [
+            ("address", self.address.clone()),
+            ("types", self.types.join(" ")),
+            ("scopes", self.scopes.join(" ")),
+            ("xaddrs", self.xaddrs.join(" ")),
+        ];
+        let properties: Vec<_> = properties
+            .iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(key, value)| (*key, value.as_str()))
+            .collect();
+        let addresses: Vec<IpAddr> = addr.into_iter().collect();
+        Ok(ServiceInfo::new(
+            K_SERVICE_TYPE,
+            &self.instance(&host),
+            &format!("{}.", host.trim_end_matches('.')),
+            addresses.as_slice(),
+            port,
+            properties.as_slice(),
+        )?)
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// The contents of every element named `name`, whatever its namespace
+/// prefix, in document order. Nested elements of the same name aren't
+/// expected.
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let end = rest.find('>').unwrap_or(rest.len());
+        let tag = &rest[..end];
+        let qualified = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+        let local = qualified.rsplit(':').next().unwrap_or_default();
+        if local != name || tag.starts_with('/') {
+            continue;
+        }
+        let body = rest.get(end + 1..).unwrap_or_default();
+        if tag.ends_with('/') {
+            found.push("");
+            continue;
+        }
+        let close = format!("</{qualified}>");
+        let Some(len) = body.find(&close) else {
+            break;
+        };
+        found.push(&body[..len]);
+        rest = &body[len + close.len()..];
+    }
+    found
+}
+
+/// The text of the first element named `name` in `xml`, if any.
+fn text(xml: &str, name: &str) -> Option<String> {
+    elements(xml, name)
+        .first()
+        .map(|text| unescape(text.trim()))
+}
+
+/// The whitespace separated list in the first element named `name`.
+fn list(xml: &str, name: &str) -> Vec<String> {
+    text(xml, name)
+        .map(|text| text.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+fn endpoint(xml: &str) -> Option<Endpoint> {
+    let reference = elements(xml, "EndpointReference").into_iter().next()?;
+    Some(Endpoint {
+        address: text(reference, "Address")?,
+        types: list(xml, "Types"),
+        scopes: list(xml, "Scopes"),
+        xaddrs: list(xml, "XAddrs"),
+    })
+}
+
+/// What a WS-Discovery message tells.
+#[derive(Debug, PartialEq, Eq)]
+enum Message {
+    /// Endpoints joining, or answering a Probe
+    Found(Vec<Endpoint>),
+    /// The endpoint reference of an endpoint leaving
+    Bye(String),
+}
+
+fn parse(xml: &str) -> Option<Message> {
+    let action = text(xml, "Action")?;
+    match action.strip_prefix(K_ACTION_PREFIX)? {
+        "Hello" => Some(Message::Found(vec![endpoint(
+            elements(xml, "Hello").first()?,
+        )?])),
+        "ProbeMatches" => Some(Message::Found(
+            elements(xml, "ProbeMatch")
+                .into_iter()
+                .filter_map(endpoint)
+                .collect(),
+        )),
+        "Bye" => Some(Message::Bye(text(
+            elements(xml, "Bye").first()?,
+            "Address",
+        )?)),
+        _ => None,
+    }
+}
+
+fn message_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let id = (nanos << 64) ^ nanos ^ (u128::from(std::process::id()) << 32);
+    format!(
+        "urn:uuid:{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        (id >> 96) as u32,
+        (id >> 80) as u16,
+        (id >> 64) as u16,
+        (id >> 48) as u16,
+        id as u64 & 0xffff_ffff_ffff
+    )
+}
+
+/// A Probe for every endpoint, whatever its type and scope.
+fn probe() -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><soap:Envelope xmlns:soap="http://www.w3.org/2003/05/soap-envelope" xmlns:wsa="http://schemas.xmlsoap.org/ws/2004/08/addressing" xmlns:wsd="http://schemas.xmlsoap.org/ws/2005/04/discovery"><soap:Header><wsa:To>urn:schemas-xmlsoap-org:ws:2005:04:discovery</wsa:To><wsa:Action>{K_ACTION_PREFIX}Probe</wsa:Action><wsa:MessageID>{}</wsa:MessageID></soap:Header><soap:Body><wsd:Probe/></soap:Body></soap:Envelope>"#,
+        message_id()
+    )
+}
+
+/// A socket bound to the WS-Discovery port, joined to its group on every
+/// interface, for the Hello and Bye announcements.
+fn listen(ipv6: bool) -> anyhow::Result<UdpSocket> {
+    let (domain, addr) = if ipv6 {
+        (Domain::IPV6, IpAddr::V6(Ipv6Addr::UNSPECIFIED))
+    } else {
+        (Domain::IPV4, IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+    };
+    let addr = SocketAddr::new(addr, K_WSD_PORT);
+    let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    if ipv6 {
+        socket.set_only_v6(true)?;
+        for index in Zones::local().indexes() {
+            if let Err(e) = socket.join_multicast_v6(&K_WSD_GROUP_V6, index) {
+                tracing::debug!("Failed to join {K_WSD_GROUP_V6} on interface {index}: {e}");
+            }
+        }
+    }
+    socket
+        .bind(&addr.into())
+        .with_context(|| format!("Failed to share {addr}"))?;
+    if !ipv6 {
+        let interfaces = if_addrs::get_if_addrs().unwrap_or_default();
+        for interface in interfaces
+            .iter()
+            .filter(|interface| !interface.is_loopback())
+        {
+            if let IfAddr::V4(v4) = &interface.addr {
+                if let Err(e) = socket.join_multicast_v4(&K_WSD_GROUP, &v4.ip) {
+                    tracing::debug!("Failed to join {K_WSD_GROUP} on {}: {e}", v4.ip);
+                }
+            }
+        }
+    }
+    Ok(socket.into())
+}
+
+/// Send a Probe from `socket` to the group on every interface.
+fn send_probe(socket: &UdpSocket, ipv6: bool) {
+    let probe = probe();
+    let sent = if ipv6 {
+        Zones::local()
+            .indexes()
+            .into_iter()
+            .map(|index| {
+                let group = SocketAddr::V6(std::net::SocketAddrV6::new(
+                    K_WSD_GROUP_V6,
+                    K_WSD_PORT,
+                    0,
+                    index,
+                ));
+                socket.send_to(probe.as_bytes(), group)
+            })
+            .filter(Result::is_ok)
+            .count()
+    } else {
+        usize::from(
+            socket
+                .send_to(probe.as_bytes(), (K_WSD_GROUP, K_WSD_PORT))
+                .is_ok(),
+        )
+    };
+    if sent == 0 {
+        tracing::warn!("Failed to send a WS-Discovery probe");
+    }
+}
+
+/// Turn what `message` tells into mDNS events, minding the endpoints
+/// already `listed` under their instance fullname.
+fn events(
+    message: Message,
+    from: IpAddr,
+    listed: &mut HashMap<String, String>,
+    tx: &Sender<ServiceEvent>,
+) -> anyhow::Result<()> {
+    match message {
+        Message::Found(endpoints) => {
+            for endpoint in endpoints {
+                let info = match endpoint.resolved(from) {
+                    Ok(info) => info,
+                    Err(e) => {
+                        tracing::warn!("Skipping the WS-Discovery endpoint {endpoint:?}: {e}");
+                        continue;
+                    }
+                };
+                let fullname = info.get_fullname().to_string();
+                if listed.get(&endpoint.address) != Some(&fullname) {
+                    if let Some(previous) = listed.insert(endpoint.address, fullname.clone()) {
+                        tx.send(ServiceEvent::ServiceRemoved(
+                            K_SERVICE_TYPE.to_string(),
+                            previous,
+                        ))?;
+                    }
+                    tx.send(ServiceEvent::ServiceFound(
+                        K_SERVICE_TYPE.to_string(),
+                        fullname,
+                    ))?;
+                }
+                tx.send(ServiceEvent::ServiceResolved(info))?;
+            }
+        }
+        Message::Bye(address) => {
+            if let Some(fullname) = listed.remove(&address) {
+                tx.send(ServiceEvent::ServiceRemoved(
+                    K_SERVICE_TYPE.to_string(),
+                    fullname,
+                ))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Discover the WS-Discovery endpoints on the link, e.g. ONVIF cameras and
+/// printers, in the background, as instances of [`K_SERVICE_TYPE`].
+///
+/// Every endpoint is probed for every few minutes, and its Hello and Bye
+/// announcements are listened to. Its endpoint reference, types, scopes and
+/// transport addresses (XAddrs) make up its TXT properties. Stops once the
+/// receiver is dropped.
+pub fn start(ipv6: bool) -> anyhow::Result<Receiver<ServiceEvent>> {
+    let unspecified = if ipv6 {
+        IpAddr::V6(Ipv6Addr::UNSPECIFIED)
+    } else {
+        IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+    };
+    // Probe matches are sent back to the probing socket
+    let prober = UdpSocket::bind(SocketAddr::new(unspecified, 0))
+        .context("Failed to bind the WS-Discovery socket")?;
+    prober.set_read_timeout(Some(K_POLL))?;
+    let announcements = match listen(ipv6) {
+        Ok(socket) => {
+            socket.set_read_timeout(Some(K_POLL))?;
+            Some(socket)
+        }
+        Err(e) => {
+            tracing::warn!("Not listening to WS-Discovery announcements: {e:#}");
+            None
+        }
+    };
+
+    let (tx, rx) = flume::unbounded();
+    std::thread::spawn(move || {
+        let _span = tracing::span!(tracing::Level::TRACE, "WS-Discovery").entered();
+        let _ = tx.send(ServiceEvent::SearchStarted(K_SERVICE_TYPE.to_string()));
+        let mut listed = HashMap::new();
+        let mut probed = None::<Instant>;
+        let mut buf = vec![0; 65536];
+        while !tx.is_disconnected() {
+            if probed.map_or(true, |probed| probed.elapsed() >= K_PROBE_INTERVAL) {
+                send_probe(&prober, ipv6);
+                probed = Some(Instant::now());
+            }
+            for socket in std::iter::once(&prober).chain(announcements.as_ref()) {
+                let Ok((len, from)) = socket.recv_from(&mut buf) else {
+                    continue;
+                };
+                let Some(message) = parse(&String::from_utf8_lossy(&buf[..len])) else {
+                    continue;
+                };
+                tracing::trace!("WS-Discovery from {from}: {message:?}");
+                if events(message, from.ip(), &mut listed, &tx).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A printer answering a Probe.
+    const K_PROBE_MATCHES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<soap:Envelope xmlns:soap="http://www.w3.org/2003/05/soap-envelope" xmlns:wsa="http://schemas.xmlsoap.org/ws/2004/08/addressing" xmlns:wsd="http://schemas.xmlsoap.org/ws/2005/04/discovery" xmlns:wsdp="http://schemas.xmlsoap.org/ws/2006/02/devprof">
+<soap:Header>
+<wsa:To>http://schemas.xmlsoap.org/ws/2004/08/addressing/role/anonymous</wsa:To>
+<wsa:Action>http://schemas.xmlsoap.org/ws/2005/04/discovery/ProbeMatches</wsa:Action>
+<wsa:MessageID>urn:uuid:5b2c5c4e-2f3a-4c4e-9a43-0a1b2c3d4e5f</wsa:MessageID>
+<wsa:RelatesTo>urn:uuid:0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0</wsa:RelatesTo>
+<wsd:AppSequence InstanceId="12" MessageNumber="3"/>
+</soap:Header>
+<soap:Body>
+<wsd:ProbeMatches>
+<wsd:ProbeMatch>
+<wsa:EndpointReference><wsa:Address>urn:uuid:16a65700-007c-1000-bb49-c8d9d2a1b2c3</wsa:Address></wsa:EndpointReference>
+<wsd:Types>wsdp:Device print:PrintDeviceType</wsd:Types>
+<wsd:XAddrs>http://192.168.2.10:3911/</wsd:XAddrs>
+<wsd:MetadataVersion>1</wsd:MetadataVersion>
+</wsd:ProbeMatch>
+</wsd:ProbeMatches>
+</soap:Body>
+</soap:Envelope>"#;
+
+    /// A camera joining, with other namespace prefixes.
+    const K_HELLO: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<SOAP-ENV:Envelope xmlns:SOAP-ENV="http://www.w3.org/2003/05/soap-envelope" xmlns:a="http://schemas.xmlsoap.org/ws/2004/08/addressing" xmlns:d="http://schemas.xmlsoap.org/ws/2005/04/discovery" xmlns:dn="http://www.onvif.org/ver10/network/wsdl">
+<SOAP-ENV:Header><a:Action SOAP-ENV:mustUnderstand="true">http://schemas.xmlsoap.org/ws/2005/04/discovery/Hello</a:Action></SOAP-ENV:Header>
+<SOAP-ENV:Body><d:Hello>
+<a:EndpointReference><a:Address>urn:uuid:a1b2c3d4-0000-1111-2222-333344445555</a:Address></a:EndpointReference>
+<d:Types>dn:NetworkVideoTransmitter</d:Types>
+<d:Scopes>onvif://www.onvif.org/type/video_encoder onvif://www.onvif.org/name/Front%20Door</d:Scopes>
+<d:XAddrs>http://[fd00::30]:8080/onvif/device_service http://192.168.2.30:8080/onvif/device_service</d:XAddrs>
+<d:MetadataVersion>2</d:MetadataVersion>
+</d:Hello></SOAP-ENV:Body>
+</SOAP-ENV:Envelope>"#;
+
+    const K_BYE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope" xmlns:a="http://schemas.xmlsoap.org/ws/2004/08/addressing" xmlns:d="http://schemas.xmlsoap.org/ws/2005/04/discovery">
+<s:Header><a:Action>http://schemas.xmlsoap.org/ws/2005/04/discovery/Bye</a:Action></s:Header>
+<s:Body><d:Bye><a:EndpointReference><a:Address>urn:uuid:a1b2c3d4-0000-1111-2222-333344445555</a:Address></a:EndpointReference></d:Bye></s:Body>
+</s:Envelope>"#;
+
+    #[test]
+    fn elements_whatever_their_prefix() {
+        assert_eq!(
+            elements(K_PROBE_MATCHES, "Address"),
+            ["urn:uuid:16a65700-007c-1000-bb49-c8d9d2a1b2c3"]
+        );
+        assert_eq!(
+            elements(K_HELLO, "Action"),
+            ["http://schemas.xmlsoap.org/ws/2005/04/discovery/Hello"]
+        );
+        assert_eq!(
+            elements("<a>1</a><x:a>2</x:a><b><a>3</a></b>", "a"),
+            ["1", "2", "3"]
+        );
+        // Only the whole local name counts
+        assert_eq!(elements(K_PROBE_MATCHES, "ProbeMatch").len(), 1);
+        assert!(elements(K_PROBE_MATCHES, "Probe").is_empty());
+    }
+
+    #[test]
+    fn empty_and_unclosed_elements() {
+        assert_eq!(elements(K_PROBE_MATCHES, "AppSequence"), [""]);
+        assert_eq!(elements("<wsd:Probe />", "Probe"), [""]);
+        assert_eq!(elements("<a>1</a><a>2", "a"), ["1"]);
+        assert!(elements("no markup", "a").is_empty());
+        assert!(elements("<a", "a").is_empty());
+    }
+
+    #[test]
+    fn probe_matches() {
+        assert_eq!(
+            parse(K_PROBE_MATCHES),
+            Some(Message::Found(vec![Endpoint {
+                address: "urn:uuid:16a65700-007c-1000-bb49-c8d9d2a1b2c3".to_string(),
+                types: vec![
+                    "wsdp:Device".to_string(),
+                    "print:PrintDeviceType".to_string()
+                ],
+                scopes: Vec::new(),
+                xaddrs: vec!["http://192.168.2.10:3911/".to_string()],
+            }]))
+        );
+    }
+
+    #[test]
+    fn hellos() {
+        let Some(Message::Found(endpoints)) = parse(K_HELLO) else {
+            panic!("Not a Hello");
+        };
+        let from = "192.168.2.30".parse().unwrap();
+        assert_eq!(
+            endpoints[0].target(from),
+            ("fd00::30".to_string(), "fd00::30".parse().ok(), 8080)
+        );
+        assert_eq!(endpoints[0].instance("fd00::30"), "Front Door (fd00::30)");
+    }
+
+    #[test]
+    fn byes() {
+        assert_eq!(
+            parse(K_BYE),
+            Some(Message::Bye(
+                "urn:uuid:a1b2c3d4-0000-1111-2222-333344445555".to_string()
+            ))
+        );
+        // Our own Probe, looped back
+        assert_eq!(parse(&probe()), None);
+    }
+}
@@ -0,0 +1,79 @@
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6};
+
+use if_addrs::IfAddr;
+
+/// Whether `addr` is an IPv6 link-local unicast address, fe80::/10.
+pub(crate) fn link_local(addr: &Ipv6Addr) -> bool {
+    addr.segments()[0] & 0xffc0 == 0xfe80
+}
+
+/// Local interfaces with an IPv6 link-local address, the zones such
+/// addresses are only meaningful in.
+///
+/// mDNS doesn't tell which interface an address was received on, so an
+/// address is placed in the zone of the interface holding it when it is a
+/// local one, and in the first zone otherwise, which is only ambiguous on
+/// hosts with several IPv6 interfaces.
+#[derive(Debug, Clone, Default)]
+pub struct Zones {
+    /// Interface names and indexes, along with their link-local address
+    interfaces: Vec<(String, u32, Ipv6Addr)>,
+}
+
+impl Zones {
+    pub fn local() -> Self {
+        Self {
+            interfaces: if_addrs::get_if_addrs()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|interface| !interface.is_loopback())
+                .filter_map(|interface| match interface.addr {
+                    IfAddr::V6(v6) if link_local(&v6.ip) => {
+                        Some((interface.name, interface.index.unwrap_or(0), v6.ip))
+                    }
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Name and index of the zone of `addr`, `None` unless link-local.
+    pub fn of(&self, addr: IpAddr) -> Option<(&str, u32)> {
+        let IpAddr::V6(v6) = addr else {
+            return None;
+        };
+        if !link_local(&v6) {
+            return None;
+        }
+        self.interfaces
+            .iter()
+            .find(|(_, _, local)| *local == v6)
+            .or_else(|| self.interfaces.first())
+            .map(|(name, index, _)| (name.as_str(), *index))
+    }
+
+    /// Indexes of every zone, to reach the whole IPv6 link.
+    pub fn indexes(&self) -> Vec<u32> {
+        let mut indexes: Vec<_> = self.interfaces.iter().map(|(_, index, _)| *index).collect();
+        indexes.dedup();
+        indexes
+    }
+
+    /// `addr` with its zone, if any, e.g. `fe80::1%eth0`.
+    pub fn display(&self, addr: IpAddr) -> String {
+        match self.of(addr) {
+            Some((name, _)) => format!("{addr}%{name}"),
+            None => addr.to_string(),
+        }
+    }
+
+    /// Socket address to connect to `addr`, scoped to its zone if any.
+    pub fn socket_addr(&self, addr: IpAddr, port: u16) -> SocketAddr {
+        match (addr, self.of(addr)) {
+            (IpAddr::V6(v6), Some((_, index))) => {
+                SocketAddr::V6(SocketAddrV6::new(v6, port, 0, index))
+            }
+            _ => SocketAddr::new(addr, port),
+        }
+    }
+}